@@ -1,34 +1,120 @@
+use crate::hack::{parse_program, Address, Comp, Dest, Instruction, Jump, ParseProgramError, MAX_ADDRESS_VALUE};
+
 /// hack機械語をparseした結果を保持する構造体
 #[derive(Debug, PartialEq)]
 pub struct ParseHackResult {
     lines: Vec<Line>,
+    // 定義済みシンボル以外のシンボル(ラベルと変数)とそのアドレス
+    symbols: Vec<Symbol>,
+}
+
+/// プログラム中で定義されたシンボル
+#[derive(Debug, PartialEq)]
+pub enum Symbol {
+    // ラベル名とROM上のアドレス
+    Label(String, u32),
+    // 変数名とRAM上のアドレス
+    Variable(String, u32),
 }
 
 const VARIABLE_ADDRESS_OFFSET: u32 = 16;
 
+#[derive(Debug, PartialEq, Eq)]
+pub enum AssembleError {
+    Parse(ParseProgramError),
+    // ROMの後ろの方のラベルや増えすぎた変数のアドレスが、A命令で指定できる15bitの範囲を超えた
+    AddressOutOfRange { instruction: String, address: u32 },
+}
+
+impl From<ParseProgramError> for AssembleError {
+    fn from(e: ParseProgramError) -> Self {
+        AssembleError::Parse(e)
+    }
+}
+
+impl std::fmt::Display for AssembleError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AssembleError::Parse(e) => write!(f, "{}", e),
+            AssembleError::AddressOutOfRange { instruction, address } => write!(
+                f,
+                "`{}` refers to address {}, but an A-instruction can hold at most {}",
+                instruction, address, MAX_ADDRESS_VALUE
+            ),
+        }
+    }
+}
+
 struct SymbolTable {
     symbol_table: std::collections::HashMap<String, u32>,
     next_variable_address: u32,
+    // 割り当てた順の変数名
+    variables: Vec<String>,
 }
 
 impl ParseHackResult {
-    pub fn new(content: String) -> ParseHackResult {
-        let mut symbol_table = Self::init_symbol_table(content.clone());
+    pub fn new(content: String) -> Result<ParseHackResult, AssembleError> {
+        Self::from_instructions(&parse_program(&content)?)
+    }
+
+    /// テキストを経由せずに命令の列から機械語に変換する(VM translatorから直接使う)
+    pub fn from_instructions(instructions: &[Instruction]) -> Result<ParseHackResult, AssembleError> {
+        let mut symbol_table = Self::init_symbol_table(instructions);
 
         let mut lines = vec![];
-        for line in content.lines() {
-            if let Some(line) = Line::new(line, &mut symbol_table) {
+        for instruction in instructions {
+            if let Some(line) = Line::new(instruction, &mut symbol_table) {
+                if let Line::AInstruction(address) = line {
+                    if address > MAX_ADDRESS_VALUE as u32 {
+                        return Err(AssembleError::AddressOutOfRange {
+                            instruction: instruction.to_string(),
+                            address,
+                        });
+                    }
+                }
                 lines.push(line);
             }
         }
 
-        ParseHackResult { lines }
+        let mut symbols = Self::build_label_map(instructions)
+            .into_iter()
+            .map(|(label, address)| Symbol::Label(label, address))
+            .collect::<Vec<_>>();
+        symbols.sort_by_key(|s| match s {
+            Symbol::Label(label, address) | Symbol::Variable(label, address) => (*address, label.clone()),
+        });
+        for (i, variable) in symbol_table.variables.into_iter().enumerate() {
+            symbols.push(Symbol::Variable(variable, VARIABLE_ADDRESS_OFFSET + i as u32));
+        }
+
+        Ok(ParseHackResult { lines, symbols })
+    }
+
+    pub fn symbols(&self) -> &[Symbol] {
+        &self.symbols
+    }
+
+    /// シンボルファイルの内容を返す。1行に1つ`ROM {アドレス} {ラベル}`または`RAM {アドレス} {変数}`の形式で出力する
+    pub fn to_symbol_file(&self) -> String {
+        self.symbols
+            .iter()
+            .map(|s| match s {
+                Symbol::Label(label, address) => format!("ROM {} {}", address, label),
+                Symbol::Variable(variable, address) => format!("RAM {} {}", address, variable),
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// 機械語の各命令を16bitの値として返す
+    pub fn words(&self) -> Vec<u16> {
+        self.lines.iter().map(|line| line.to_word()).collect()
     }
 
     /// シンボルテーブルを作成する
     /// 1. 定義済みシンボルを登録
-    /// 2. ファイル内容を走査しラベルのアドレスを登録
-    fn init_symbol_table(content: String) -> SymbolTable {
+    /// 2. 命令を走査しラベルのアドレスを登録
+    fn init_symbol_table(instructions: &[Instruction]) -> SymbolTable {
         let predefined_symbols = std::collections::HashMap::from([
             ("R0".to_string(), 0_u32),
             ("R1".to_string(), 1_u32),
@@ -55,41 +141,26 @@ impl ParseHackResult {
             ("KBD".to_string(), 24576_u32),
         ]);
 
-        let mut label_map = Self::build_label_map(content);
+        let mut label_map = Self::build_label_map(instructions);
         label_map.extend(predefined_symbols);
 
         SymbolTable {
             symbol_table: label_map,
             next_variable_address: VARIABLE_ADDRESS_OFFSET,
+            variables: vec![],
         }
     }
 
-    fn build_label_map(content: String) -> std::collections::HashMap<String, u32> {
+    /// ラベルの次の命令のアドレスをラベルのアドレスとして登録する
+    fn build_label_map(instructions: &[Instruction]) -> std::collections::HashMap<String, u32> {
         let mut map = std::collections::HashMap::new();
-        let mut program_counter = None;
-        for line in content.lines() {
-            let trimmed = line.trim();
-            if Line::should_ignore(trimmed) {
-                continue;
-            }
-            if Line::is_a_instruction(trimmed)
-                || Line::is_c_instruction_dest_comp(trimmed)
-                || Line::is_c_instruction_comp_jump(trimmed)
-            {
-                program_counter = match program_counter {
-                    None => Some(0),
-                    Some(c) => Some(c + 1),
-                };
-                continue;
+        let mut program_counter = 0;
+        for instruction in instructions {
+            if instruction.is_machine_instruction() {
+                program_counter += 1;
             }
-
-            let label = trimmed.trim_matches(['(', ')']);
-            if !label.is_empty() {
-                let count = match program_counter {
-                    Some(c) => c + 1,
-                    None => 0,
-                };
-                map.insert(label.to_string(), count);
+            if let Instruction::Label(label) = instruction {
+                map.insert(label.to_string(), program_counter);
             }
         }
 
@@ -101,7 +172,10 @@ impl ParseHackResult {
     /// そのため変数へのメモリ割当をどこまで行ったかを保持しておく必要がある
     fn add_variable_to_symbol_table(symbol_table: &mut SymbolTable, var_name: String) -> u32 {
         let current_variable_address = symbol_table.next_variable_address;
-        symbol_table.symbol_table.insert(var_name, current_variable_address);
+        symbol_table
+            .symbol_table
+            .insert(var_name.clone(), current_variable_address);
+        symbol_table.variables.push(var_name);
         symbol_table.next_variable_address += 1;
 
         current_variable_address
@@ -126,155 +200,60 @@ impl std::fmt::Display for ParseHackResult {
 #[derive(Debug, PartialEq)]
 enum Line {
     AInstruction(u32), // address or 定数
-    CInstruction(CInstruction),
+    CInstruction {
+        dest: Option<Dest>,
+        comp: Comp,
+        jump: Option<Jump>,
+    },
 }
 
 impl Line {
-    fn new(line: &str, symbol_table: &mut SymbolTable) -> Option<Line> {
-        // `//`で始まる場合と空行の場合はNone
-        let trimmed = line.trim();
-        if Self::should_ignore(trimmed) {
-            return None;
-        }
-
-        // A命令のparse
-        if Self::is_a_instruction(trimmed) {
+    // ラベルとコメントの場合はNone
+    fn new(instruction: &Instruction, symbol_table: &mut SymbolTable) -> Option<Line> {
+        match instruction {
             // A命令には以下の3パターンが存在する。
             // - @定数(0~32767の範囲の10進数)
             // - @定義済みシンボル
             // - @変数
-            // MEMO: Symbolの仕様
-            // 文字 数字 _ . $ :からなる。ただし数字から始まることはできない
-            return match trimmed[1..].parse::<u32>() {
-                Ok(c) => Some(Line::AInstruction(c)),
-                Err(_) => {
-                    // u32としてparseできない => symbol
-                    let symbol = &trimmed[1..];
-                    match symbol_table.symbol_table.get(symbol) {
-                        Some(address) => Some(Line::AInstruction(*address)),
-                        None => {
-                            // 未定義の変数をシンボルテーブルに追加する
-                            let address =
-                                ParseHackResult::add_variable_to_symbol_table(symbol_table, symbol.to_string());
-                            Some(Line::AInstruction(address))
-                        }
-                    }
+            Instruction::AInstr(Address::Value(value)) => Some(Line::AInstruction(*value as u32)),
+            Instruction::AInstr(Address::Symbol(symbol)) => match symbol_table.symbol_table.get(symbol) {
+                Some(address) => Some(Line::AInstruction(*address)),
+                None => {
+                    // 未定義の変数をシンボルテーブルに追加する
+                    let address = ParseHackResult::add_variable_to_symbol_table(symbol_table, symbol.to_string());
+                    Some(Line::AInstruction(address))
                 }
-            };
-        };
-
-        // C命令のparse
-        if Self::is_c_instruction_dest_comp(trimmed) {
-            let parts: Vec<&str> = trimmed.split("=").collect();
-            let dest = Some(parts[0].to_string());
-            let comp = parts[1].to_string();
-            return Some(Line::CInstruction(CInstruction { dest, comp, jump: None }));
+            },
+            Instruction::CInstr { dest, comp, jump } => Some(Line::CInstruction {
+                dest: *dest,
+                comp: *comp,
+                jump: *jump,
+            }),
+            Instruction::Label(_) | Instruction::Comment(_) => None,
         }
-        if Self::is_c_instruction_comp_jump(trimmed) {
-            let parts: Vec<&str> = trimmed.split(";").collect();
-            let comp = parts[0].to_string();
-            let jump = Some(parts[1].to_string());
-            return Some(Line::CInstruction(CInstruction { dest: None, comp, jump }));
-        }
-
-        None
-    }
-
-    fn should_ignore(line: &str) -> bool {
-        line.is_empty() || line.starts_with("//")
-    }
-
-    fn is_a_instruction(line: &str) -> bool {
-        line.starts_with("@") && line.len() > 1
-    }
-
-    fn is_c_instruction_dest_comp(line: &str) -> bool {
-        line.contains("=")
-    }
-
-    fn is_c_instruction_comp_jump(line: &str) -> bool {
-        line.contains(";")
     }
-}
 
-impl std::fmt::Display for Line {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let result = match self {
+    fn to_word(&self) -> u16 {
+        match self {
+            // 15bitを超える値は最上位bitが立ってC命令になってしまうので、from_instructionsで弾いておく
             Line::AInstruction(num) => {
-                format!("0{:015b}", num)
+                assert!(*num <= MAX_ADDRESS_VALUE as u32, "A-instruction value {} does not fit in 15 bits", num);
+                *num as u16
             }
-            Line::CInstruction(c_instruction) => {
-                let (a, c) = match c_instruction.comp.as_str() {
-                    "0" => ("0", "101010"),
-                    "1" => ("0", "111111"),
-                    "-1" => ("0", "111010"),
-                    "D" => ("0", "001100"),
-                    "A" => ("0", "110000"),
-                    "M" => ("1", "110000"),
-                    "!D" => ("0", "001101"),
-                    "!A" => ("0", "110001"),
-                    "!M" => ("1", "110001"),
-                    "-D" => ("0", "001111"),
-                    "-A" => ("0", "110011"),
-                    "-M" => ("1", "110011"),
-                    "D+1" => ("0", "011111"),
-                    "A+1" => ("0", "110111"),
-                    "M+1" => ("1", "110111"),
-                    "D-1" => ("0", "001110"),
-                    "A-1" => ("0", "110010"),
-                    "M-1" => ("1", "110010"),
-                    "D+A" => ("0", "000010"),
-                    "D+M" => ("1", "000010"),
-                    "D-A" => ("0", "010011"),
-                    "D-M" => ("1", "010011"),
-                    "A-D" => ("0", "000111"),
-                    "M-D" => ("1", "000111"),
-                    "D&A" => ("0", "000000"),
-                    "D&M" => ("1", "000000"),
-                    "D|A" => ("0", "010101"),
-                    "D|M" => ("1", "010101"),
-                    _ => ("0", "000000"),
-                };
-                let d = match &c_instruction.dest {
-                    Some(dest) => match dest.as_str() {
-                        "M" => "001",
-                        "D" => "010",
-                        "DM" | "MD" => "011",
-                        "A" => "100",
-                        "AM" | "MA" => "101",
-                        "AD" | "DA" => "110",
-                        "ADM" | "AMD" | "DAM" | "DMA" | "MAD" | "MDA" => "111",
-                        _ => "000",
-                    },
-                    _ => "000",
-                };
-                let j = match &c_instruction.jump {
-                    Some(jump) => match jump.as_str() {
-                        "JGT" => "001",
-                        "JEQ" => "010",
-                        "JGE" => "011",
-                        "JLT" => "100",
-                        "JNE" => "101",
-                        "JLE" => "110",
-                        "JMP" => "111",
-                        _ => "000",
-                    },
-                    _ => "000",
-                };
-                format!("111{}{}{}{}", a, c, d, j)
+            Line::CInstruction { dest, comp, jump } => {
+                (0b111 << 13)
+                    | (comp.bits() << 6)
+                    | (dest.map(|d| d.bits()).unwrap_or(0) << 3)
+                    | jump.map(|j| j.bits()).unwrap_or(0)
             }
-        };
-        write!(f, "{}", result)
+        }
     }
 }
 
-/// C命令はdest=comp;jumpの形式で表されるが実際のパターンとしてはdest=comp || comp;jump
-/// 各フィールドは大文字でなければならない
-#[derive(Debug, PartialEq)]
-struct CInstruction {
-    dest: Option<String>,
-    comp: String, // compは必須
-    jump: Option<String>,
+impl std::fmt::Display for Line {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:016b}", self.to_word())
+    }
 }
 
 #[cfg(test)]
@@ -295,16 +274,24 @@ M=D
                 "#
                 .to_string()
             ),
-            ParseHackResult {
+            Ok(ParseHackResult {
                 lines: vec![
                     Line::AInstruction(10),
-                    Line::CInstruction(CInstruction {
-                        dest: Some("M".to_string()),
-                        comp: "D".to_string(),
+                    Line::CInstruction {
+                        dest: Some(Dest::M),
+                        comp: Comp::D,
                         jump: None,
-                    })
+                    }
                 ],
-            }
+                symbols: vec![],
+            })
+        );
+        assert_eq!(
+            ParseHackResult::new("@10\nM=Q".to_string()).map_err(|e| match e {
+                AssembleError::Parse(e) => e.line_number,
+                _ => 0,
+            }),
+            Err(2)
         );
     }
 
@@ -313,26 +300,50 @@ M=D
         let symbol_table = || SymbolTable {
             symbol_table: std::collections::HashMap::new(),
             next_variable_address: VARIABLE_ADDRESS_OFFSET,
+            variables: vec![],
         };
-        assert_eq!(Line::new("", &mut symbol_table()), None);
-        assert_eq!(Line::new("// comment", &mut symbol_table()), None);
-        assert_eq!(Line::new("@12", &mut symbol_table()), Some(Line::AInstruction(12)));
-        assert_eq!(Line::new("@x", &mut symbol_table()), Some(Line::AInstruction(16)));
+        assert_eq!(Line::new(&Instruction::comment("comment"), &mut symbol_table()), None);
+        assert_eq!(Line::new(&Instruction::label("LOOP"), &mut symbol_table()), None);
+        assert_eq!(Line::new(&Instruction::a_value(12), &mut symbol_table()), Some(Line::AInstruction(12)));
+        assert_eq!(Line::new(&Instruction::a_symbol("x"), &mut symbol_table()), Some(Line::AInstruction(16)));
         assert_eq!(
-            Line::new("D=D-M", &mut symbol_table()),
-            Some(Line::CInstruction(CInstruction {
-                dest: Some("D".to_string()),
-                comp: "D-M".to_string(),
+            Line::new(&Instruction::assign(Dest::D, Comp::DMinusM), &mut symbol_table()),
+            Some(Line::CInstruction {
+                dest: Some(Dest::D),
+                comp: Comp::DMinusM,
                 jump: None,
-            }))
+            })
         );
         assert_eq!(
-            Line::new("0;JMP", &mut symbol_table()),
-            Some(Line::CInstruction(CInstruction {
+            Line::new(&Instruction::jump(Comp::Zero, Jump::JMP), &mut symbol_table()),
+            Some(Line::CInstruction {
                 dest: None,
-                comp: "0".to_string(),
-                jump: Some("JMP".to_string()),
-            }))
+                comp: Comp::Zero,
+                jump: Some(Jump::JMP),
+            })
+        );
+    }
+
+    #[test]
+    fn test_line_to_string() {
+        assert_eq!(Line::AInstruction(21).to_string(), "0000000000010101");
+        assert_eq!(
+            Line::CInstruction {
+                dest: Some(Dest::D),
+                comp: Comp::DMinusM,
+                jump: None,
+            }
+            .to_string(),
+            "1111010011010000"
+        );
+        assert_eq!(
+            Line::CInstruction {
+                dest: None,
+                comp: Comp::Zero,
+                jump: Some(Jump::JMP),
+            }
+            .to_string(),
+            "1110101010000111"
         );
     }
 
@@ -340,7 +351,8 @@ M=D
     fn test_build_label_map() {
         assert_eq!(
             ParseHackResult::build_label_map(
-                r#"
+                &parse_program(
+                    r#"
 @10
 M=D
 // comment
@@ -349,9 +361,67 @@ M=D
 (y)
 @R0
                 "#
-                .to_string()
+                )
+                .unwrap()
             ),
             std::collections::HashMap::from([("x".to_string(), 2), ("y".to_string(), 3)])
         );
     }
+
+    #[test]
+    fn test_from_instructions() {
+        // テキストを経由しても命令列から直接変換しても同じ機械語になる
+        let instructions = vec![
+            Instruction::a_symbol("i"),
+            Instruction::assign(Dest::M, Comp::One),
+            Instruction::label("LOOP"),
+            Instruction::a_symbol("LOOP"),
+            Instruction::jump(Comp::Zero, Jump::JMP),
+        ];
+        let result = ParseHackResult::from_instructions(&instructions).unwrap();
+        assert_eq!(Ok(&result), ParseHackResult::new(crate::hack::to_text(&instructions)).as_ref());
+        assert_eq!(result.words(), vec![16, 0b1110111111001000, 2, 0b1110101010000111]);
+
+        // 32768番地以降のラベルはA命令で指定できない
+        let mut instructions = vec![Instruction::assign(Dest::D, Comp::Zero); 32768];
+        instructions.push(Instruction::label("END"));
+        instructions.push(Instruction::a_symbol("END"));
+        assert_eq!(
+            ParseHackResult::from_instructions(&instructions).err(),
+            Some(AssembleError::AddressOutOfRange {
+                instruction: "@END".to_string(),
+                address: 32768,
+            })
+        );
+    }
+
+    #[test]
+    fn test_symbols() {
+        let result = ParseHackResult::new(
+            r#"
+@i
+M=1
+(LOOP)
+@j
+D=M
+@LOOP
+D;JGT
+(END)
+@END
+0;JMP
+            "#
+            .to_string(),
+        )
+        .unwrap();
+        assert_eq!(
+            result.symbols(),
+            &[
+                Symbol::Label("LOOP".to_string(), 2),
+                Symbol::Label("END".to_string(), 6),
+                Symbol::Variable("i".to_string(), 16),
+                Symbol::Variable("j".to_string(), 17),
+            ]
+        );
+        assert_eq!(result.to_symbol_file(), "ROM 2 LOOP\nROM 6 END\nRAM 16 i\nRAM 17 j");
+    }
 }
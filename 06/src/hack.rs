@@ -0,0 +1,529 @@
+/// Hackアセンブリの命令
+/// テキスト形式(.asmファイルの1行)との相互変換はDisplay, FromStrで行う。
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Instruction {
+    // @value または @symbol
+    AInstr(Address),
+    // dest=comp;jump (destとjumpは省略できる)
+    CInstr {
+        dest: Option<Dest>,
+        comp: Comp,
+        jump: Option<Jump>,
+    },
+    // (label) ROM上の命令は生成しない
+    Label(String),
+    // `// comment` ROM上の命令は生成しない
+    Comment(String),
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Address {
+    // 0~32767の定数
+    Value(u16),
+    // 定義済みシンボル、ラベル、変数
+    Symbol(String),
+}
+
+// A命令で指定できる定数の最大値(15bit)
+pub const MAX_ADDRESS_VALUE: u16 = 32767;
+
+impl Instruction {
+    // 15bitを超える値は最上位bitが立ってC命令として解釈されてしまうので受け付けない
+    pub fn a_value(value: u16) -> Self {
+        assert!(value <= MAX_ADDRESS_VALUE, "A-instruction value {} does not fit in 15 bits", value);
+        Self::AInstr(Address::Value(value))
+    }
+
+    pub fn a_symbol(symbol: impl Into<String>) -> Self {
+        Self::AInstr(Address::Symbol(symbol.into()))
+    }
+
+    // dest=comp
+    pub fn assign(dest: Dest, comp: Comp) -> Self {
+        Self::CInstr {
+            dest: Some(dest),
+            comp,
+            jump: None,
+        }
+    }
+
+    // comp;jump
+    pub fn jump(comp: Comp, jump: Jump) -> Self {
+        Self::CInstr {
+            dest: None,
+            comp,
+            jump: Some(jump),
+        }
+    }
+
+    pub fn label(label: impl Into<String>) -> Self {
+        Self::Label(label.into())
+    }
+
+    pub fn comment(comment: impl Into<String>) -> Self {
+        Self::Comment(comment.into())
+    }
+
+    // ROM上に1ワードの命令として配置されるかどうか(ラベルとコメントは配置されない)
+    pub fn is_machine_instruction(&self) -> bool {
+        matches!(self, Self::AInstr(_) | Self::CInstr { .. })
+    }
+}
+
+impl std::fmt::Display for Instruction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::AInstr(Address::Value(value)) => write!(f, "@{}", value),
+            Self::AInstr(Address::Symbol(symbol)) => write!(f, "@{}", symbol),
+            Self::CInstr { dest, comp, jump } => {
+                if let Some(dest) = dest {
+                    write!(f, "{}=", dest)?;
+                }
+                write!(f, "{}", comp)?;
+                if let Some(jump) = jump {
+                    write!(f, ";{}", jump)?;
+                }
+                Ok(())
+            }
+            Self::Label(label) => write!(f, "({})", label),
+            Self::Comment(comment) => write!(f, "// {}", comment),
+        }
+    }
+}
+
+impl std::str::FromStr for Instruction {
+    type Err = ParseInstructionError;
+
+    // 1行分の命令をparseする。前後の空白は無視する
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let trimmed = line.trim();
+        let err = || ParseInstructionError(trimmed.to_string());
+
+        if let Some(comment) = trimmed.strip_prefix("//") {
+            return Ok(Self::Comment(comment.trim().to_string()));
+        }
+        // 行末のコメントは取り除く
+        let instruction = trimmed.split("//").next().unwrap_or_default().trim();
+
+        if let Some(address) = instruction.strip_prefix('@') {
+            if address.is_empty() {
+                return Err(err());
+            }
+            // MEMO: Symbolの仕様
+            // 文字 数字 _ . $ :からなる。ただし数字から始まることはできない
+            if address.starts_with(|c: char| c.is_ascii_digit()) {
+                return match address.parse::<u16>() {
+                    Ok(value) if value <= MAX_ADDRESS_VALUE => Ok(Self::a_value(value)),
+                    _ => Err(err()),
+                };
+            }
+            if !is_valid_symbol(address) {
+                return Err(err());
+            }
+            return Ok(Self::a_symbol(address));
+        }
+
+        if let Some(label) = instruction.strip_prefix('(').and_then(|l| l.strip_suffix(')')) {
+            if label.starts_with(|c: char| c.is_ascii_digit()) || !is_valid_symbol(label) {
+                return Err(err());
+            }
+            return Ok(Self::label(label));
+        }
+
+        // C命令はdest=comp;jumpの形式で表されるが実際のパターンとしてはdest=comp || comp;jump
+        let (dest, rest) = match instruction.split_once('=') {
+            Some((dest, rest)) => (Some(dest.parse::<Dest>().map_err(|_| err())?), rest),
+            None => (None, instruction),
+        };
+        let (comp, jump) = match rest.split_once(';') {
+            Some((comp, jump)) => (comp, Some(jump.parse::<Jump>().map_err(|_| err())?)),
+            None => (rest, None),
+        };
+        // destもjumpもない行は命令として意味がない
+        if dest.is_none() && jump.is_none() {
+            return Err(err());
+        }
+        let comp = comp.parse::<Comp>().map_err(|_| err())?;
+        Ok(Self::CInstr { dest, comp, jump })
+    }
+}
+
+fn is_valid_symbol(symbol: &str) -> bool {
+    !symbol.is_empty()
+        && symbol
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '$' | ':'))
+}
+
+/// .asmファイルの内容をparseする。空行は無視し、コメントはInstruction::Commentとして残す
+pub fn parse_program(content: &str) -> Result<Vec<Instruction>, ParseProgramError> {
+    content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| {
+            line.parse::<Instruction>().map_err(|error| ParseProgramError {
+                line_number: i + 1,
+                error,
+            })
+        })
+        .collect()
+}
+
+/// 命令の列を.asmファイルの形式に変換する
+pub fn to_text(instructions: &[Instruction]) -> String {
+    instructions
+        .iter()
+        .map(|i| i.to_string())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct ParseInstructionError(pub String);
+
+impl std::fmt::Display for ParseInstructionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid instruction `{}`", self.0)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct ParseProgramError {
+    // 1始まりの行番号
+    pub line_number: usize,
+    pub error: ParseInstructionError,
+}
+
+impl std::fmt::Display for ParseProgramError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}: {}", self.line_number, self.error)
+    }
+}
+
+/// C命令のcomp部分(P89 図4-5 に定義されている28種類)
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Comp {
+    Zero,
+    One,
+    MinusOne,
+    D,
+    A,
+    M,
+    NotD,
+    NotA,
+    NotM,
+    NegD,
+    NegA,
+    NegM,
+    DPlusOne,
+    APlusOne,
+    MPlusOne,
+    DMinusOne,
+    AMinusOne,
+    MMinusOne,
+    DPlusA,
+    DPlusM,
+    DMinusA,
+    DMinusM,
+    AMinusD,
+    MMinusD,
+    DAndA,
+    DAndM,
+    DOrA,
+    DOrM,
+}
+
+impl Comp {
+    const ALL: [Comp; 28] = [
+        Comp::Zero,
+        Comp::One,
+        Comp::MinusOne,
+        Comp::D,
+        Comp::A,
+        Comp::M,
+        Comp::NotD,
+        Comp::NotA,
+        Comp::NotM,
+        Comp::NegD,
+        Comp::NegA,
+        Comp::NegM,
+        Comp::DPlusOne,
+        Comp::APlusOne,
+        Comp::MPlusOne,
+        Comp::DMinusOne,
+        Comp::AMinusOne,
+        Comp::MMinusOne,
+        Comp::DPlusA,
+        Comp::DPlusM,
+        Comp::DMinusA,
+        Comp::DMinusM,
+        Comp::AMinusD,
+        Comp::MMinusD,
+        Comp::DAndA,
+        Comp::DAndM,
+        Comp::DOrA,
+        Comp::DOrM,
+    ];
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            Comp::Zero => "0",
+            Comp::One => "1",
+            Comp::MinusOne => "-1",
+            Comp::D => "D",
+            Comp::A => "A",
+            Comp::M => "M",
+            Comp::NotD => "!D",
+            Comp::NotA => "!A",
+            Comp::NotM => "!M",
+            Comp::NegD => "-D",
+            Comp::NegA => "-A",
+            Comp::NegM => "-M",
+            Comp::DPlusOne => "D+1",
+            Comp::APlusOne => "A+1",
+            Comp::MPlusOne => "M+1",
+            Comp::DMinusOne => "D-1",
+            Comp::AMinusOne => "A-1",
+            Comp::MMinusOne => "M-1",
+            Comp::DPlusA => "D+A",
+            Comp::DPlusM => "D+M",
+            Comp::DMinusA => "D-A",
+            Comp::DMinusM => "D-M",
+            Comp::AMinusD => "A-D",
+            Comp::MMinusD => "M-D",
+            Comp::DAndA => "D&A",
+            Comp::DAndM => "D&M",
+            Comp::DOrA => "D|A",
+            Comp::DOrM => "D|M",
+        }
+    }
+
+    // a(1bit) + c1~c6(6bit)
+    pub fn bits(&self) -> u16 {
+        let (a, c) = match self {
+            Comp::Zero => (0, 0b101010),
+            Comp::One => (0, 0b111111),
+            Comp::MinusOne => (0, 0b111010),
+            Comp::D => (0, 0b001100),
+            Comp::A => (0, 0b110000),
+            Comp::M => (1, 0b110000),
+            Comp::NotD => (0, 0b001101),
+            Comp::NotA => (0, 0b110001),
+            Comp::NotM => (1, 0b110001),
+            Comp::NegD => (0, 0b001111),
+            Comp::NegA => (0, 0b110011),
+            Comp::NegM => (1, 0b110011),
+            Comp::DPlusOne => (0, 0b011111),
+            Comp::APlusOne => (0, 0b110111),
+            Comp::MPlusOne => (1, 0b110111),
+            Comp::DMinusOne => (0, 0b001110),
+            Comp::AMinusOne => (0, 0b110010),
+            Comp::MMinusOne => (1, 0b110010),
+            Comp::DPlusA => (0, 0b000010),
+            Comp::DPlusM => (1, 0b000010),
+            Comp::DMinusA => (0, 0b010011),
+            Comp::DMinusM => (1, 0b010011),
+            Comp::AMinusD => (0, 0b000111),
+            Comp::MMinusD => (1, 0b000111),
+            Comp::DAndA => (0, 0b000000),
+            Comp::DAndM => (1, 0b000000),
+            Comp::DOrA => (0, 0b010101),
+            Comp::DOrM => (1, 0b010101),
+        };
+        (a << 6) | c
+    }
+}
+
+impl std::fmt::Display for Comp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl std::str::FromStr for Comp {
+    type Err = ParseInstructionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let find = |s: &str| Comp::ALL.into_iter().find(|c| c.as_str() == s);
+        // `M+D`, `A&D`のように可換な演算のオペランドが逆順に書かれている場合も受け付ける
+        let swapped = || {
+            let operator = s.find(['+', '&', '|'])?;
+            find(&format!("{}{}{}", &s[operator + 1..], &s[operator..operator + 1], &s[..operator]))
+        };
+        find(s)
+            .or_else(swapped)
+            .ok_or_else(|| ParseInstructionError(s.to_string()))
+    }
+}
+
+/// C命令のdest部分
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Dest {
+    M,
+    D,
+    MD,
+    A,
+    AM,
+    AD,
+    AMD,
+}
+
+impl Dest {
+    // d1(A) d2(D) d3(M)
+    pub fn bits(&self) -> u16 {
+        match self {
+            Dest::M => 0b001,
+            Dest::D => 0b010,
+            Dest::MD => 0b011,
+            Dest::A => 0b100,
+            Dest::AM => 0b101,
+            Dest::AD => 0b110,
+            Dest::AMD => 0b111,
+        }
+    }
+}
+
+impl std::fmt::Display for Dest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl std::str::FromStr for Dest {
+    type Err = ParseInstructionError;
+
+    // `DM`や`MDA`のように順序が異なる書き方も受け付ける
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || ParseInstructionError(s.to_string());
+        let mut bits = 0;
+        for c in s.chars() {
+            let bit = match c {
+                'A' => 0b100,
+                'D' => 0b010,
+                'M' => 0b001,
+                _ => return Err(err()),
+            };
+            if bits & bit != 0 {
+                return Err(err());
+            }
+            bits |= bit;
+        }
+        [Dest::M, Dest::D, Dest::MD, Dest::A, Dest::AM, Dest::AD, Dest::AMD]
+            .into_iter()
+            .find(|d| d.bits() == bits)
+            .ok_or_else(err)
+    }
+}
+
+/// C命令のjump部分
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Jump {
+    JGT,
+    JEQ,
+    JGE,
+    JLT,
+    JNE,
+    JLE,
+    JMP,
+}
+
+impl Jump {
+    // j1(out < 0) j2(out = 0) j3(out > 0)
+    pub fn bits(&self) -> u16 {
+        match self {
+            Jump::JGT => 0b001,
+            Jump::JEQ => 0b010,
+            Jump::JGE => 0b011,
+            Jump::JLT => 0b100,
+            Jump::JNE => 0b101,
+            Jump::JLE => 0b110,
+            Jump::JMP => 0b111,
+        }
+    }
+}
+
+impl std::fmt::Display for Jump {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl std::str::FromStr for Jump {
+    type Err = ParseInstructionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        [
+            Jump::JGT,
+            Jump::JEQ,
+            Jump::JGE,
+            Jump::JLT,
+            Jump::JNE,
+            Jump::JLE,
+            Jump::JMP,
+        ]
+        .into_iter()
+        .find(|j| j.to_string() == s)
+        .ok_or_else(|| ParseInstructionError(s.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_instruction_from_str() {
+        assert_eq!("@12".parse(), Ok(Instruction::a_value(12)));
+        assert_eq!("  @Main.f$ret.0 ".parse(), Ok(Instruction::a_symbol("Main.f$ret.0")));
+        assert_eq!("(LOOP)".parse(), Ok(Instruction::label("LOOP")));
+        assert_eq!("// comment".parse(), Ok(Instruction::comment("comment")));
+        assert_eq!("D=D-M".parse(), Ok(Instruction::assign(Dest::D, Comp::DMinusM)));
+        assert_eq!("0;JMP".parse(), Ok(Instruction::jump(Comp::Zero, Jump::JMP)));
+        assert_eq!(
+            "DM=M+D;JNE // inline comment".parse(),
+            Ok(Instruction::CInstr {
+                dest: Some(Dest::MD),
+                comp: Comp::DPlusM,
+                jump: Some(Jump::JNE),
+            })
+        );
+
+        for invalid in ["@", "@32768", "@1x", "(1x)", "D", "D=D-", "X=D", "MM=D", "D;JXX"] {
+            assert_eq!(invalid.parse::<Instruction>(), Err(ParseInstructionError(invalid.to_string())), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_instruction_to_string() {
+        // Displayで出力したテキストをparseすると元の命令に戻る
+        let mut instructions = vec![
+            Instruction::a_value(0),
+            Instruction::a_value(MAX_ADDRESS_VALUE),
+            Instruction::a_symbol("SP"),
+            Instruction::label("Sys.init"),
+            Instruction::comment("Push(Constant(7))"),
+            Instruction::jump(Comp::D, Jump::JGT),
+        ];
+        for comp in Comp::ALL {
+            instructions.push(Instruction::assign(Dest::AMD, comp));
+        }
+        let text = to_text(&instructions);
+        assert_eq!(text.lines().take(3).collect::<Vec<_>>(), vec!["@0", "@32767", "@SP"]);
+        assert_eq!(parse_program(&text), Ok(instructions));
+        // 15bitを超える値はC命令と区別できなくなるので作れない
+        assert!(std::panic::catch_unwind(|| Instruction::a_value(MAX_ADDRESS_VALUE + 1)).is_err());
+    }
+
+    #[test]
+    fn test_parse_program() {
+        assert_eq!(
+            parse_program("@1\n\nD=A\nfoo\n"),
+            Err(ParseProgramError {
+                line_number: 4,
+                error: ParseInstructionError("foo".to_string()),
+            })
+        );
+    }
+}
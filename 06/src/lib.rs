@@ -0,0 +1,4 @@
+mod assembler;
+pub mod hack;
+
+pub use assembler::{AssembleError, ParseHackResult, Symbol};
@@ -2,8 +2,6 @@ use std::env;
 use std::fs::read_to_string;
 use std::path::PathBuf;
 
-mod assembler;
-
 fn main() {
     let command_line_args: Vec<String> = env::args().collect();
     if command_line_args.len() != 2 {
@@ -21,14 +19,21 @@ fn main() {
         }
     };
 
-    let binary = assemble(source_file_path);
-    let _ = std::fs::write(output_file_path, &binary);
+    match assemble(source_file_path) {
+        Ok(binary) => {
+            let _ = std::fs::write(output_file_path, &binary);
+        }
+        Err(e) => {
+            eprintln!("error: {}", e);
+            std::process::exit(1);
+        }
+    }
 }
 
 // 任意のpathを渡せるようにしておくとUTが書きやすいので切り出しておく
-fn assemble(path: PathBuf) -> String {
+fn assemble(path: PathBuf) -> Result<String, assembler::AssembleError> {
     let content = read_to_string(path.clone()).expect("Failed to read file");
-    assembler::ParseHackResult::new(content).to_string()
+    Ok(assembler::ParseHackResult::new(content)?.to_string())
 }
 
 #[cfg(test)]
@@ -39,32 +44,32 @@ mod test {
     #[test]
     fn test_assemble() {
         assert_eq!(
-            format!("{}\n", assemble(PathBuf::from("test_data/add/Add.asm"))),
+            format!("{}\n", assemble(PathBuf::from("test_data/add/Add.asm")).unwrap()),
             read_to_string("test_data/add/Add.hack").unwrap()
         );
         assert_eq!(
-            format!("{}\n", assemble(PathBuf::from("test_data/max/Max.asm"))),
+            format!("{}\n", assemble(PathBuf::from("test_data/max/Max.asm")).unwrap()),
             read_to_string("test_data/max/Max.hack").unwrap()
         );
         assert_eq!(
-            format!("{}\n", assemble(PathBuf::from("test_data/max/MaxL.asm"))),
+            format!("{}\n", assemble(PathBuf::from("test_data/max/MaxL.asm")).unwrap()),
             read_to_string("test_data/max/MaxL.hack").unwrap()
         );
         // NOTE: ↑の3ファイルだけ末尾改行が入ってない or ↓だけ末尾改行が入っちゃってる
         assert_eq!(
-            assemble(PathBuf::from("test_data/pong/Pong.asm")),
+            assemble(PathBuf::from("test_data/pong/Pong.asm")).unwrap(),
             read_to_string("test_data/pong/Pong.hack").unwrap()
         );
         assert_eq!(
-            assemble(PathBuf::from("test_data/pong/PongL.asm")),
+            assemble(PathBuf::from("test_data/pong/PongL.asm")).unwrap(),
             read_to_string("test_data/pong/PongL.hack").unwrap()
         );
         assert_eq!(
-            assemble(PathBuf::from("test_data/rect/RectL.asm")),
+            assemble(PathBuf::from("test_data/rect/RectL.asm")).unwrap(),
             read_to_string("test_data/rect/RectL.hack").unwrap()
         );
         assert_eq!(
-            assemble(PathBuf::from("test_data/rect/Rect.asm")),
+            assemble(PathBuf::from("test_data/rect/Rect.asm")).unwrap(),
             read_to_string("test_data/rect/Rect.hack").unwrap()
         );
     }
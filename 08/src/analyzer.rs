@@ -0,0 +1,340 @@
+use crate::cfg::{function_ranges, BasicBlock, ControlFlowGraph};
+use crate::translator::{ArithmeticCommand, Command, VMProgram};
+
+/// VMコマンドを実行せずに検査して見つかった問題
+#[derive(PartialEq, Eq, Debug)]
+pub struct Diagnostic {
+    pub file_name: String,
+    pub line_number: usize,
+    pub function_name: String,
+    pub kind: DiagnosticKind,
+}
+
+#[derive(PartialEq, Eq, Debug)]
+pub enum DiagnosticKind {
+    // 空のスタックからpopしている(pop, 算術コマンド, if-goto, callの引数)
+    StackUnderflow,
+    // 戻り値をpushせずにreturnしている
+    ReturnWithEmptyStack,
+    // ラベルに合流する経路によってスタックの深さが異なる
+    DepthMismatch { label: String, depths: (usize, usize) },
+    // 関数内に定義されていないラベルへのジャンプ
+    UndefinedLabel(String),
+    // returnせずに関数の末尾に到達する
+    MissingReturn,
+    // 呼び出し元が渡している引数の数(最小値)以上のargumentにアクセスしている
+    ArgumentOutOfRange { index: u32, args_length: u32 },
+}
+
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.vm:{}: in {}: ", self.file_name, self.line_number, self.function_name)?;
+        match &self.kind {
+            DiagnosticKind::StackUnderflow => write!(f, "pop from an empty stack"),
+            DiagnosticKind::ReturnWithEmptyStack => write!(f, "return with an empty stack"),
+            DiagnosticKind::DepthMismatch {
+                label,
+                depths: (first, second),
+            } => write!(f, "stack depth at label `{}` differs between paths ({} and {})", label, first, second),
+            DiagnosticKind::UndefinedLabel(label) => write!(f, "label `{}` is not defined in this function", label),
+            DiagnosticKind::MissingReturn => write!(f, "control reaches the end of the function without return"),
+            DiagnosticKind::ArgumentOutOfRange { index, args_length } => {
+                write!(f, "`argument {}` is accessed but some call sites pass only {} argument(s)", index, args_length)
+            }
+        }
+    }
+}
+
+/// 全ファイルの関数を検査する
+/// 関数の外のコマンド(07のテストのようにRAMの初期値をテストスクリプトで設定するもの)はスタックの初期状態がわからないので検査しない
+pub fn analyze(programs: &[VMProgram]) -> Vec<Diagnostic> {
+    // key: 関数名, value: 呼び出し元が渡している引数の数の最小値
+    let mut args_lengths = std::collections::HashMap::new();
+    for p in programs {
+        for c in &p.commands {
+            if let Command::Call(function_name, args_length) = c {
+                let min = args_lengths.entry(function_name.as_str()).or_insert(*args_length);
+                *min = (*min).min(*args_length);
+            }
+        }
+    }
+
+    let mut diagnostics = vec![];
+    for p in programs {
+        for (start, end) in function_ranges(&p.commands) {
+            let Command::Function(function_name, _) = &p.commands[start] else {
+                unreachable!()
+            };
+            let mut function = FunctionAnalyzer {
+                program: p,
+                function_name,
+                diagnostics: vec![],
+            };
+            function.check_stack_depth(start, end);
+            if let Some(args_length) = args_lengths.get(function_name.as_str()) {
+                function.check_arguments(start + 1, end, *args_length);
+            }
+            diagnostics.extend(function.diagnostics);
+        }
+    }
+    diagnostics
+}
+
+struct FunctionAnalyzer<'a> {
+    program: &'a VMProgram,
+    function_name: &'a str,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl FunctionAnalyzer<'_> {
+    // functionコマンドの位置から関数の末尾(end)までの各基本ブロックについて、先頭でのスタックの深さを求める
+    fn check_stack_depth(&mut self, function_start: usize, end: usize) {
+        let cfg = ControlFlowGraph::new(&self.program.commands, function_start + 1, end);
+        for (i, label) in &cfg.undefined_labels {
+            self.report(*i, DiagnosticKind::UndefinedLabel(label.clone()));
+        }
+        if cfg.blocks.is_empty() {
+            self.report(function_start, DiagnosticKind::MissingReturn);
+            return;
+        }
+
+        // 各ブロックの先頭でのスタックの深さ(到達しない場合はNone)
+        let mut entry_depths = vec![None; cfg.blocks.len()];
+        entry_depths[0] = Some(0);
+        let mut worklist = vec![0];
+        let mut reported_mismatches = std::collections::HashSet::new();
+        while let Some(block_index) = worklist.pop() {
+            let block = &cfg.blocks[block_index];
+            let depth = self.simulate_block(block, entry_depths[block_index].unwrap_or(0));
+            for successor in &block.successors {
+                let successor = *successor;
+                if successor == cfg.blocks.len() {
+                    self.report(block.end - 1, DiagnosticKind::MissingReturn);
+                    continue;
+                }
+                match entry_depths[successor] {
+                    None => {
+                        entry_depths[successor] = Some(depth);
+                        worklist.push(successor);
+                    }
+                    // 同じラベルでの不一致は1回だけ報告する
+                    Some(entry_depth) if entry_depth != depth && reported_mismatches.insert(successor) => {
+                        let label = match &self.program.commands[cfg.blocks[successor].start] {
+                            Command::Label(label) => label.clone(),
+                            _ => String::new(),
+                        };
+                        self.report(
+                            block.end - 1,
+                            DiagnosticKind::DepthMismatch {
+                                label,
+                                depths: (entry_depth, depth),
+                            },
+                        );
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    // ブロック末尾でのスタックの深さを返す
+    fn simulate_block(&mut self, block: &BasicBlock, entry_depth: usize) -> usize {
+        let mut depth = entry_depth;
+        for i in block.start..block.end {
+            let (pops, pushes) = match &self.program.commands[i] {
+                Command::Push(_) => (0, 1),
+                Command::Pop(_) => (1, 0),
+                Command::Arithmetic(
+                    ArithmeticCommand::Neg | ArithmeticCommand::Not | ArithmeticCommand::Shl | ArithmeticCommand::Shr,
+                ) => (1, 1),
+                Command::Arithmetic(_) => (2, 1),
+                Command::Call(_, args_length) => (*args_length as usize, 1),
+                Command::IfGoTo(_) => (1, 0),
+                Command::Dup => (1, 2),
+                Command::Swap => (2, 2),
+                Command::PushThatIndirect => (1, 1),
+                Command::PopThatIndirect => (2, 0),
+                Command::Label(_)
+                | Command::GoTo(_)
+                | Command::Function(_, _)
+                | Command::Return
+                | Command::Inc(_)
+                | Command::Dec(_) => (0, 0),
+            };
+            if depth < pops {
+                self.report(i, DiagnosticKind::StackUnderflow);
+                depth = 0;
+            } else {
+                depth -= pops;
+            }
+            depth += pushes;
+
+            if let Command::Return = &self.program.commands[i] {
+                if depth == 0 {
+                    self.report(i, DiagnosticKind::ReturnWithEmptyStack);
+                }
+            }
+        }
+        depth
+    }
+
+    // argumentセグメントへのアクセスが呼び出し元から渡される引数の範囲内か検査する
+    fn check_arguments(&mut self, start: usize, end: usize, args_length: u32) {
+        for i in start..end {
+            if let Command::Push(segment) | Command::Pop(segment) | Command::Inc(segment) | Command::Dec(segment) =
+                &self.program.commands[i]
+            {
+                if let crate::translator::Segment::Argument(index) = segment {
+                    if args_length <= *index {
+                        self.report(
+                            i,
+                            DiagnosticKind::ArgumentOutOfRange {
+                                index: *index,
+                                args_length,
+                            },
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    fn report(&mut self, command_index: usize, kind: DiagnosticKind) {
+        self.diagnostics.push(Diagnostic {
+            file_name: self.program.file_name.clone(),
+            line_number: self.program.line_number(command_index),
+            function_name: self.function_name.to_string(),
+            kind,
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn kinds(content: &str) -> Vec<(usize, DiagnosticKind)> {
+        analyze(&[VMProgram::new("Main".to_string(), content.to_string()).unwrap()])
+            .into_iter()
+            .map(|d| (d.line_number, d.kind))
+            .collect()
+    }
+
+    #[test]
+    fn test_analyze_valid_programs() {
+        for dir in [
+            "test_data/FunctionCalls/FibonacciElement",
+            "test_data/FunctionCalls/NestedCall",
+            "test_data/FunctionCalls/SimpleFunction",
+            "test_data/FunctionCalls/StaticsTest",
+        ] {
+            let programs = std::fs::read_dir(dir)
+                .unwrap()
+                .map(|e| e.unwrap().path())
+                .filter(|p| p.extension().is_some_and(|ext| ext == "vm"))
+                .map(|p| {
+                    let file_name = p.file_stem().unwrap().to_string_lossy().to_string();
+                    VMProgram::new(file_name, std::fs::read_to_string(p).unwrap()).unwrap()
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(analyze(&programs), vec![], "{}", dir);
+        }
+    }
+
+    #[test]
+    fn test_analyze_stack_depth() {
+        assert_eq!(
+            kinds("function Main.f 0\npop temp 0\npush constant 1\nreturn"),
+            vec![(2, DiagnosticKind::StackUnderflow)]
+        );
+        assert_eq!(
+            kinds("function Main.f 0\npush constant 1\npop temp 0\nreturn"),
+            vec![(4, DiagnosticKind::ReturnWithEmptyStack)]
+        );
+        // if-gotoで分岐した先で片方だけpushしてから合流する
+        assert_eq!(
+            kinds(
+                r#"
+function Main.f 0
+push argument 0
+if-goto SKIP
+push constant 1
+label SKIP
+push constant 2
+return
+"#
+            ),
+            vec![(
+                5,
+                DiagnosticKind::DepthMismatch {
+                    label: "SKIP".to_string(),
+                    depths: (0, 1),
+                }
+            )]
+        );
+        // ループで深さが変わらなければ問題ない
+        assert_eq!(
+            kinds(
+                r#"
+function Main.f 1
+label LOOP
+push local 0
+push constant 1
+add
+pop local 0
+push local 0
+if-goto LOOP
+push local 0
+return
+"#
+            ),
+            vec![]
+        );
+        assert_eq!(
+            kinds("function Main.f 0\ngoto NOWHERE\nfunction Main.g 0\npush constant 0"),
+            vec![
+                (2, DiagnosticKind::UndefinedLabel("NOWHERE".to_string())),
+                (4, DiagnosticKind::MissingReturn),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_analyze_arguments() {
+        let diagnostics = analyze(&[VMProgram::new(
+            "Main".to_string(),
+            r#"
+function Main.main 0
+push constant 1
+call Main.f 1
+push constant 1
+push constant 2
+call Main.f 2
+add
+return
+function Main.f 0
+push argument 1
+return
+"#
+            .to_string(),
+        )
+        .unwrap()]);
+        assert_eq!(
+            diagnostics,
+            vec![Diagnostic {
+                file_name: "Main".to_string(),
+                line_number: 11,
+                function_name: "Main.f".to_string(),
+                kind: DiagnosticKind::ArgumentOutOfRange {
+                    index: 1,
+                    args_length: 1,
+                },
+            }]
+        );
+        assert_eq!(
+            diagnostics[0].to_string(),
+            "Main.vm:11: in Main.f: `argument 1` is accessed but some call sites pass only 1 argument(s)"
+        );
+    }
+}
@@ -0,0 +1,100 @@
+use crate::translator::{Fragment, SourceMapEntry, VMProgram};
+use assembler::hack::{parse_program, to_text};
+use sha2::{Digest, Sha256};
+use std::path::PathBuf;
+
+// 変換結果が変わる修正をしたときは値を上げて古いキャッシュを使わないようにする
+const CACHE_VERSION: u32 = 1;
+
+/// ファイルごとの変換結果のキャッシュ
+/// `{dir}/{キー}.json`に保存する。キーはファイル名と各行の内容(行番号を含む)、変換の設定から計算したハッシュ
+/// キャッシュの読み書きに失敗した場合は変換し直すだけなのでエラーにはしない
+pub struct FragmentCache {
+    dir: PathBuf,
+}
+
+// キャッシュファイルの形式。命令はアセンブリのテキストとして保存する
+#[derive(serde::Serialize, serde::Deserialize)]
+struct CachedFragment {
+    assembly: String,
+    source_map: Vec<SourceMapEntry>,
+}
+
+impl FragmentCache {
+    pub fn new(dir: PathBuf) -> std::io::Result<Self> {
+        std::fs::create_dir_all(&dir)?;
+        Ok(Self { dir })
+    }
+
+    pub fn get(&self, program: &VMProgram, annotated: bool) -> Option<Fragment> {
+        let content = std::fs::read_to_string(self.path(program, annotated)).ok()?;
+        let cached = serde_json::from_str::<CachedFragment>(&content).ok()?;
+        Some(Fragment {
+            instructions: parse_program(&cached.assembly).ok()?,
+            source_map: cached.source_map,
+        })
+    }
+
+    pub fn put(&self, program: &VMProgram, annotated: bool, fragment: &Fragment) {
+        let cached = CachedFragment {
+            assembly: to_text(&fragment.instructions),
+            source_map: fragment.source_map.clone(),
+        };
+        // 書き込み途中のファイルを読まないように一時ファイルに書いてからrenameする
+        let path = self.path(program, annotated);
+        let temporary_path = path.with_extension(format!("{}.tmp", std::process::id()));
+        if std::fs::write(&temporary_path, serde_json::to_string(&cached).unwrap()).is_ok() {
+            let _ = std::fs::rename(&temporary_path, &path);
+        }
+    }
+
+    fn path(&self, program: &VMProgram, annotated: bool) -> PathBuf {
+        self.dir.join(format!("{}.json", key(program, annotated)))
+    }
+}
+
+fn key(program: &VMProgram, annotated: bool) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(format!("{}\n{}\n{}\n{}\n", CACHE_VERSION, env!("CARGO_PKG_VERSION"), annotated, program.file_name));
+    for i in 0..program.commands.len() {
+        hasher.update(format!("{} {}\n", program.line_number(i), program.source_text(i)));
+    }
+    hasher.finalize().iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use uuid::Uuid;
+
+    #[test]
+    fn test_fragment_cache() {
+        let dir = std::env::temp_dir().join(Uuid::new_v4().to_string());
+        let cache = FragmentCache::new(dir.clone()).unwrap();
+        let content = "function Main.main 0\n// comment\npush constant 7\nreturn\n";
+        let mut program = VMProgram::new("Main".to_string(), content.to_string()).unwrap();
+
+        assert_eq!(cache.get(&program, false), None);
+        let fragment = program.translate(false);
+        cache.put(&program, false, &fragment);
+        assert_eq!(cache.get(&program, false), Some(fragment));
+        // 設定が違う場合は別のキャッシュになる
+        assert_eq!(cache.get(&program, true), None);
+
+        // 内容やファイル名が変わった場合は使わない
+        let changed = VMProgram::new("Main".to_string(), content.replace('7', "8")).unwrap();
+        assert_eq!(cache.get(&changed, false), None);
+        let renamed = VMProgram::new("Other".to_string(), content.to_string()).unwrap();
+        assert_eq!(cache.get(&renamed, false), None);
+        // 行番号が変わるとソースマップが変わるので使わない
+        let moved = VMProgram::new("Main".to_string(), format!("\n{}", content)).unwrap();
+        assert_eq!(cache.get(&moved, false), None);
+
+        // 壊れたキャッシュは無視する
+        std::fs::write(cache.path(&program, false), "{").unwrap();
+        assert_eq!(cache.get(&program, false), None);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
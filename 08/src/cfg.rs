@@ -0,0 +1,139 @@
+use crate::translator::Command;
+
+/// 1つの関数の基本ブロックと、ブロック間の制御フロー
+pub struct ControlFlowGraph {
+    pub blocks: Vec<BasicBlock>,
+    // 関数内に定義されていないラベルへのジャンプ(コマンドの位置, ラベル名)
+    pub undefined_labels: Vec<(usize, String)>,
+}
+
+/// commands[start..end]が分岐なしで実行される
+pub struct BasicBlock {
+    pub start: usize,
+    pub end: usize,
+    // 後続ブロックの位置。blocks.len()は関数の末尾を越えて次の関数(またはファイルの末尾)に到達することを表す
+    pub successors: Vec<usize>,
+}
+
+impl ControlFlowGraph {
+    // 関数の本体commands[start..end]を基本ブロックに分割する
+    pub fn new(commands: &[Command], start: usize, end: usize) -> Self {
+        // ラベルの位置と、goto, if-goto, returnの次の位置から新しいブロックが始まる
+        let mut leaders = std::collections::BTreeSet::from([start]);
+        for (i, c) in commands.iter().enumerate().take(end).skip(start) {
+            match c {
+                Command::Label(_) => {
+                    leaders.insert(i);
+                }
+                Command::GoTo(_) | Command::IfGoTo(_) | Command::Return => {
+                    leaders.insert(i + 1);
+                }
+                _ => {}
+            }
+        }
+        leaders.retain(|i| *i < end);
+        let leaders = leaders.into_iter().collect::<Vec<_>>();
+
+        // key: ラベル名, value: そのラベルから始まるブロックの位置
+        let labels = leaders
+            .iter()
+            .enumerate()
+            .filter_map(|(i, leader)| match &commands[*leader] {
+                Command::Label(label) => Some((label.as_str(), i)),
+                _ => None,
+            })
+            .collect::<std::collections::HashMap<_, _>>();
+
+        let mut undefined_labels = vec![];
+        let mut blocks = vec![];
+        for (i, leader) in leaders.iter().enumerate() {
+            let block_end = leaders.get(i + 1).copied().unwrap_or(end);
+            let last = block_end - 1;
+            let successors = match &commands[last] {
+                Command::Return => vec![],
+                Command::GoTo(label) | Command::IfGoTo(label) => {
+                    let target = labels.get(label.as_str()).copied();
+                    if target.is_none() {
+                        undefined_labels.push((last, label.clone()));
+                    }
+                    match (&commands[last], target) {
+                        (Command::GoTo(_), Some(target)) => vec![target],
+                        (Command::GoTo(_), None) => vec![],
+                        (_, Some(target)) => vec![target, i + 1],
+                        (_, None) => vec![i + 1],
+                    }
+                }
+                _ => vec![i + 1],
+            };
+            blocks.push(BasicBlock {
+                start: *leader,
+                end: block_end,
+                successors,
+            });
+        }
+
+        Self {
+            blocks,
+            undefined_labels,
+        }
+    }
+}
+
+// 各関数のfunctionコマンドの位置と、次の関数の開始位置(またはファイルの末尾)の組を返す
+pub fn function_ranges(commands: &[Command]) -> Vec<(usize, usize)> {
+    let starts = commands
+        .iter()
+        .enumerate()
+        .filter(|(_, c)| matches!(c, Command::Function(_, _)))
+        .map(|(i, _)| i)
+        .collect::<Vec<_>>();
+    starts
+        .iter()
+        .enumerate()
+        .map(|(i, start)| (*start, starts.get(i + 1).copied().unwrap_or(commands.len())))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::translator::VMProgram;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_control_flow_graph() {
+        let program = VMProgram::new(
+            "Main".to_string(),
+            r#"
+function Main.f 0
+push argument 0
+if-goto ELSE
+push constant 1
+goto END
+label ELSE
+push constant 2
+label END
+return
+function Main.g 0
+goto MISSING
+"#
+            .to_string(),
+        )
+        .unwrap();
+        assert_eq!(function_ranges(&program.commands), vec![(0, 9), (9, 11)]);
+
+        let cfg = ControlFlowGraph::new(&program.commands, 1, 9);
+        assert_eq!(
+            cfg.blocks
+                .iter()
+                .map(|b| (b.start, b.end, b.successors.clone()))
+                .collect::<Vec<_>>(),
+            vec![(1, 3, vec![2, 1]), (3, 5, vec![3]), (5, 7, vec![3]), (7, 9, vec![]),]
+        );
+        assert_eq!(cfg.undefined_labels, vec![]);
+
+        let cfg = ControlFlowGraph::new(&program.commands, 10, 11);
+        assert_eq!(cfg.blocks[0].successors, Vec::<usize>::new());
+        assert_eq!(cfg.undefined_labels, vec![(10, "MISSING".to_string())]);
+    }
+}
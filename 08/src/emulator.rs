@@ -0,0 +1,855 @@
+use crate::native_os::{NativeOs, NativeResult, INIT_FUNCTIONS};
+use crate::translator::{
+    scoped_label_name, ArithmeticCommand, Bootstrap, Command, FunctionTable, Segment, TranslateError, VMProgram,
+};
+
+// RAMのサイズ(Hackコンピュータのデータメモリは16bit x 32K)
+pub(crate) const RAM_SIZE: usize = 32768;
+// スクリーンにマップされたメモリのベースアドレス(512x256ピクセル、1ワード16ピクセル)
+pub const SCREEN: usize = 16384;
+const SCREEN_SIZE: usize = 8192;
+// キーボードにマップされたメモリのアドレス
+pub const KBD: usize = 24576;
+// staticセグメントのベースアドレス
+const STATIC_BASE: usize = 16;
+
+const SP: usize = 0;
+const LCL: usize = 1;
+const ARG: usize = 2;
+const THIS: usize = 3;
+const THAT: usize = 4;
+const TEMP_BASE: usize = 5;
+
+/// .vmプログラムを機械語に変換せずに直接実行するVMエミュレータ
+/// メモリのレイアウト(SP, LCL, ARG, THIS, THAT, temp, static, スタックフレームの構造)は
+/// translatorが出力するアセンブリと同じにしているので、.cmpファイルのRAMの値をそのまま比較できる。
+pub struct VMEmulator {
+    ram: Vec<i16>,
+    instructions: Vec<Instruction>,
+    // key: `{関数名}${ラベル名}`, value: ラベルの位置
+    labels: std::collections::HashMap<String, usize>,
+    // key: 関数名, value: functionコマンドの位置
+    functions: std::collections::HashMap<String, usize>,
+    // key: (ファイル名, index), value: RAM上のアドレス
+    statics: std::collections::HashMap<(String, u32), usize>,
+    // 次に実行する命令の位置。instructions.len()以上になったら停止する
+    pc: usize,
+    steps: u64,
+    // Someの場合はOSの関数を.vmのコードではなくRustの実装で実行する
+    native_os: Option<NativeOs>,
+}
+
+// 実行する命令と、staticセグメントやラベルの解決に必要な情報
+struct Instruction {
+    command: Command,
+    file_name: String,
+    function_name: String,
+}
+
+#[derive(PartialEq, Eq, Debug)]
+pub enum StopReason {
+    // プログラムの末尾に到達した、またはエントリポイントの関数からreturnした
+    Halted,
+    // `label END; goto END`のような自分自身へのジャンプで停止している
+    InfiniteLoop,
+    // 指定したステップ数を実行しても停止しなかった
+    StepLimit,
+}
+
+#[derive(PartialEq, Eq, Debug)]
+pub enum RuntimeError {
+    // `pointer 2`, `temp 8`, `pop constant 0`のように存在しない場所へのアクセス
+    InvalidSegment(String),
+    // RAMの範囲外へのアクセス
+    AddressOutOfRange(i32),
+    // 定義されていないラベルへのジャンプ
+    UndefinedLabel(String),
+    // Sys.errorが呼び出された(エラーコード)
+    SysError(i16),
+    // Keyboard.readChar等で読み出すキー入力が残っていない
+    KeyboardInputExhausted,
+    // 拡張命令のdivで0除算をした
+    DivisionByZero,
+}
+
+impl std::fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RuntimeError::InvalidSegment(command) => write!(f, "invalid segment access: {}", command),
+            RuntimeError::AddressOutOfRange(address) => write!(f, "address {} is out of RAM", address),
+            RuntimeError::UndefinedLabel(label) => write!(f, "label `{}` is not defined", label),
+            RuntimeError::SysError(code) => write!(f, "Sys.error was called with error code {}", code),
+            RuntimeError::KeyboardInputExhausted => write!(f, "no keyboard input left to read"),
+            RuntimeError::DivisionByZero => write!(f, "division by zero"),
+        }
+    }
+}
+
+impl VMEmulator {
+    // bootstrapがNoneの場合は先頭の命令から実行する(07のテストのようにRAMの初期値はset_ramで設定する)
+    // native_osがtrueの場合、.vmファイルに定義されていないOSの関数をRustの実装で実行する
+    pub fn new(programs: &[VMProgram], bootstrap: Option<Bootstrap>, native_os: bool) -> Result<Self, TranslateError> {
+        let function_table = FunctionTable::new(programs)?;
+        let is_native = |function_name: &str| {
+            native_os && NativeOs::is_used_for(function_name, |f| function_table.get(f).is_some())
+        };
+        for p in programs {
+            function_table.check_calls(p, is_native)?;
+        }
+
+        let mut instructions = vec![];
+        let mut labels = std::collections::HashMap::new();
+        let mut functions = std::collections::HashMap::new();
+        let mut statics = std::collections::HashMap::new();
+        for p in programs {
+            let mut function_name = String::new();
+            for command in &p.commands {
+                match command {
+                    Command::Function(name, _) => {
+                        function_name = name.clone();
+                        functions.insert(name.clone(), instructions.len());
+                    }
+                    Command::Label(label_name) => {
+                        labels.insert(scoped_label_name(&function_name, label_name), instructions.len());
+                    }
+                    // staticは出現順にアドレスを割り当てる(アセンブラが変数を割り当てる順序と同じ)
+                    Command::Push(Segment::Static(index))
+                    | Command::Pop(Segment::Static(index))
+                    | Command::Inc(Segment::Static(index))
+                    | Command::Dec(Segment::Static(index)) => {
+                        let next_address = STATIC_BASE + statics.len();
+                        statics.entry((p.file_name.clone(), *index)).or_insert(next_address);
+                    }
+                    _ => {}
+                }
+                instructions.push(Instruction {
+                    command: command.clone(),
+                    file_name: p.file_name.clone(),
+                    function_name: function_name.clone(),
+                });
+            }
+        }
+
+        let mut emulator = Self {
+            ram: vec![0; RAM_SIZE],
+            instructions,
+            labels,
+            functions,
+            statics,
+            pc: 0,
+            steps: 0,
+            native_os: if native_os { Some(NativeOs::new()) } else { None },
+        };
+
+        if let Some(bootstrap) = bootstrap {
+            if function_table.get(&bootstrap.entry_point).is_none() && !is_native(&bootstrap.entry_point) {
+                return Err(TranslateError::EntryPointNotFound(bootstrap.entry_point));
+            }
+            emulator.ram[SP] = bootstrap.stack_base as i16;
+            // エントリポイントを呼び出す命令をプログラムの末尾に追加してそこから実行する
+            // エントリポイントからreturnするとその次(命令の範囲外)に戻るので停止する
+            emulator.pc = emulator.instructions.len();
+            let commands = if bootstrap.entry_point == "Sys.init" && is_native("Sys.init") {
+                // ネイティブのSys.initの代わりに各クラスの初期化関数とMain.mainを順に呼び出す。
+                // .vmファイルで定義したクラスの初期化関数(12のMath.initなど)も呼び出すため
+                INIT_FUNCTIONS
+                    .iter()
+                    .filter(|f| is_native(f) || function_table.get(f).is_some())
+                    .flat_map(|f| [Command::Call(f.to_string(), 0), Command::Pop(Segment::Temp(0))])
+                    .chain([Command::Call("Main.main".to_string(), 0)])
+                    .collect()
+            } else {
+                vec![Command::Call(bootstrap.entry_point, 0)]
+            };
+            emulator
+                .instructions
+                .extend(commands.into_iter().map(|command| Instruction {
+                    command,
+                    file_name: String::new(),
+                    function_name: "Bootstrap".to_string(),
+                }));
+        }
+        // リターンアドレスは最大でinstructions.len()になる
+        if emulator.instructions.len() > i16::MAX as usize {
+            return Err(TranslateError::TooManyCommands(emulator.instructions.len()));
+        }
+
+        Ok(emulator)
+    }
+
+    pub fn ram(&self, address: usize) -> i16 {
+        self.ram[address]
+    }
+
+    pub fn set_ram(&mut self, address: usize, value: i16) {
+        self.ram[address] = value;
+    }
+
+    // スクリーンにマップされたメモリ(先頭行の左端から16ピクセルずつ)
+    pub fn screen(&self) -> &[i16] {
+        &self.ram[SCREEN..SCREEN + SCREEN_SIZE]
+    }
+
+    // 押されているキーのコードを設定する(0は何も押されていない状態)
+    pub fn set_key(&mut self, key: i16) {
+        self.ram[KBD] = key;
+    }
+
+    pub fn native_os(&self) -> Option<&NativeOs> {
+        self.native_os.as_ref()
+    }
+
+    pub fn native_os_mut(&mut self) -> Option<&mut NativeOs> {
+        self.native_os.as_mut()
+    }
+
+    // これまでに実行した命令数
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    // 停止するかmax_steps命令実行するまで実行する
+    pub fn run(&mut self, max_steps: u64) -> Result<StopReason, RuntimeError> {
+        for _ in 0..max_steps {
+            if let Some(reason) = self.step()? {
+                return Ok(reason);
+            }
+        }
+        Ok(StopReason::StepLimit)
+    }
+
+    // 1命令実行する。停止した場合は停止理由を返す
+    pub fn step(&mut self) -> Result<Option<StopReason>, RuntimeError> {
+        let Some(instruction) = self.instructions.get(self.pc) else {
+            return Ok(Some(StopReason::Halted));
+        };
+        let command = instruction.command.clone();
+        self.steps += 1;
+        let mut next_pc = self.pc + 1;
+
+        match &command {
+            Command::Arithmetic(ArithmeticCommand::Neg) => {
+                let x = self.pop()?;
+                self.push(x.wrapping_neg())?;
+            }
+            Command::Arithmetic(ArithmeticCommand::Not) => {
+                let x = self.pop()?;
+                self.push(!x)?;
+            }
+            Command::Arithmetic(ArithmeticCommand::Shl) => {
+                let x = self.pop()?;
+                self.push(x.wrapping_shl(1))?;
+            }
+            Command::Arithmetic(ArithmeticCommand::Shr) => {
+                let x = self.pop()?;
+                self.push(x >> 1)?;
+            }
+            Command::Arithmetic(ArithmeticCommand::Div) => {
+                let y = self.pop()?;
+                let x = self.pop()?;
+                if y == 0 {
+                    return Err(RuntimeError::DivisionByZero);
+                }
+                self.push(x.wrapping_div(y))?;
+            }
+            Command::Arithmetic(arithmetic) => {
+                let y = self.pop()?;
+                let x = self.pop()?;
+                let result = match arithmetic {
+                    ArithmeticCommand::Add => x.wrapping_add(y),
+                    ArithmeticCommand::Sub => x.wrapping_sub(y),
+                    ArithmeticCommand::Eq => to_vm_bool(x == y),
+                    ArithmeticCommand::Gt => to_vm_bool(x > y),
+                    ArithmeticCommand::Lt => to_vm_bool(x < y),
+                    ArithmeticCommand::And => x & y,
+                    ArithmeticCommand::Or => x | y,
+                    ArithmeticCommand::Mul => x.wrapping_mul(y),
+                    ArithmeticCommand::Neg
+                    | ArithmeticCommand::Not
+                    | ArithmeticCommand::Div
+                    | ArithmeticCommand::Shl
+                    | ArithmeticCommand::Shr => unreachable!(),
+                };
+                self.push(result)?;
+            }
+            Command::Push(Segment::Constant(value)) => self.push(*value as i16)?,
+            Command::Push(segment) => {
+                let address = self.segment_address(segment, &command)?;
+                self.push(self.ram[address])?;
+            }
+            Command::Pop(segment) => {
+                let address = self.segment_address(segment, &command)?;
+                let value = self.pop()?;
+                self.ram[address] = value;
+            }
+            Command::Label(_) => {}
+            Command::GoTo(label_name) => {
+                let target = self.label_address(label_name)?;
+                // 直前のラベルに戻るだけのgotoはプログラムの終了を表す無限ループとみなす
+                if target + 1 == self.pc {
+                    return Ok(Some(StopReason::InfiniteLoop));
+                }
+                next_pc = target;
+            }
+            Command::IfGoTo(label_name) => {
+                if self.pop()? != 0 {
+                    next_pc = self.label_address(label_name)?;
+                }
+            }
+            Command::Call(function_name, args_length) => {
+                return self.call(function_name, *args_length, next_pc);
+            }
+            Command::Function(_, vars_length) => {
+                for _ in 0..*vars_length {
+                    self.push(0)?;
+                }
+            }
+            Command::Return => {
+                let frame = self.ram[LCL] as i32;
+                let return_address = self.ram[self.address(frame - 5)?];
+                let value = self.pop()?;
+                let arg = self.address(self.ram[ARG] as i32)?;
+                self.ram[arg] = value;
+                self.ram[SP] = self.ram[ARG].wrapping_add(1);
+                self.ram[THAT] = self.ram[self.address(frame - 1)?];
+                self.ram[THIS] = self.ram[self.address(frame - 2)?];
+                self.ram[ARG] = self.ram[self.address(frame - 3)?];
+                self.ram[LCL] = self.ram[self.address(frame - 4)?];
+                // 07のテストのようにリターンアドレスが命令の範囲外の場合は停止する
+                next_pc = usize::try_from(return_address).unwrap_or(usize::MAX);
+            }
+            Command::Dup => {
+                let x = self.pop()?;
+                self.push(x)?;
+                self.push(x)?;
+            }
+            Command::Swap => {
+                let y = self.pop()?;
+                let x = self.pop()?;
+                self.push(y)?;
+                self.push(x)?;
+            }
+            Command::Inc(segment) | Command::Dec(segment) => {
+                let address = self.segment_address(segment, &command)?;
+                let delta = if let Command::Inc(_) = command { 1 } else { -1 };
+                self.ram[address] = self.ram[address].wrapping_add(delta);
+            }
+            Command::PushThatIndirect => {
+                let address = self.pop()?;
+                self.ram[THAT] = address;
+                let value = self.ram[self.address(address as i32)?];
+                self.push(value)?;
+            }
+            Command::PopThatIndirect => {
+                let value = self.pop()?;
+                let address = self.pop()?;
+                self.ram[THAT] = address;
+                let address = self.address(address as i32)?;
+                self.ram[address] = value;
+            }
+        }
+
+        self.pc = next_pc;
+        Ok(None)
+    }
+
+    // 呼び出し元のフレームを保存して関数の先頭にジャンプする
+    // ネイティブ実装の関数は引数をpopして戻り値をpushし、呼び出し元の次の命令に進む
+    fn call(
+        &mut self,
+        function_name: &str,
+        args_length: u32,
+        return_address: usize,
+    ) -> Result<Option<StopReason>, RuntimeError> {
+        if self.native_os.is_some() && NativeOs::is_used_for(function_name, |f| self.functions.contains_key(f)) {
+            let mut args = (0..args_length).map(|_| self.pop()).collect::<Result<Vec<_>, _>>()?;
+            args.reverse();
+            let native_os = self.native_os.as_mut().unwrap();
+            match native_os.call(function_name, &args, &mut self.ram)? {
+                NativeResult::Return(value) => {
+                    self.push(value)?;
+                    self.pc = return_address;
+                }
+                NativeResult::TailCall(next_function_name) => return self.call(&next_function_name, 0, return_address),
+                NativeResult::Halt => return Ok(Some(StopReason::Halted)),
+            }
+            return Ok(None);
+        }
+
+        let sp = self.ram[SP] as i32;
+        let frame = [
+            return_address as i16,
+            self.ram[LCL],
+            self.ram[ARG],
+            self.ram[THIS],
+            self.ram[THAT],
+        ];
+        // 再帰が深すぎるとフレームがRAMからはみ出すので、各スロットのアドレスを確認してから書き込む
+        for (offset, value) in frame.into_iter().enumerate() {
+            let address = self.address(sp + offset as i32)?;
+            self.ram[address] = value;
+        }
+        // 新しいSPも次にpushする位置としてRAMの範囲内でなければならない
+        let new_sp = self.address(sp + 5)?;
+        let arg = self.address(sp - args_length as i32)?;
+        self.ram[SP] = new_sp as i16;
+        self.ram[ARG] = arg as i16;
+        self.ram[LCL] = new_sp as i16;
+        // .vmで定義された関数を呼び出すことはnewの時点で確認済み(Sys.initから呼び出すMain.mainを除く)
+        self.pc = *self
+            .functions
+            .get(function_name)
+            .ok_or_else(|| RuntimeError::UndefinedLabel(function_name.to_string()))?;
+        Ok(None)
+    }
+
+    fn push(&mut self, value: i16) -> Result<(), RuntimeError> {
+        let sp = self.address(self.ram[SP] as i32)?;
+        // pushしたあとのSPもRAMの範囲内でなければi16で表せない
+        let new_sp = self.address(sp as i32 + 1)?;
+        self.ram[sp] = value;
+        self.ram[SP] = new_sp as i16;
+        Ok(())
+    }
+
+    fn pop(&mut self) -> Result<i16, RuntimeError> {
+        let sp = self.address(self.ram[SP] as i32 - 1)?;
+        self.ram[SP] -= 1;
+        Ok(self.ram[sp])
+    }
+
+    // Segmentの実アドレスを返す
+    fn segment_address(&self, segment: &Segment, command: &Command) -> Result<usize, RuntimeError> {
+        let invalid_segment = || RuntimeError::InvalidSegment(format!("{:?}", command));
+        match segment {
+            Segment::Argument(index) => self.address(self.ram[ARG] as i32 + *index as i32),
+            Segment::Local(index) => self.address(self.ram[LCL] as i32 + *index as i32),
+            Segment::This(index) => self.address(self.ram[THIS] as i32 + *index as i32),
+            Segment::That(index) => self.address(self.ram[THAT] as i32 + *index as i32),
+            Segment::Pointer(index) if *index <= 1 => Ok(THIS + *index as usize),
+            Segment::Temp(index) if *index <= 7 => Ok(TEMP_BASE + *index as usize),
+            Segment::Static(index) => {
+                let file_name = self.instructions[self.pc].file_name.clone();
+                self.statics
+                    .get(&(file_name, *index))
+                    .copied()
+                    .ok_or_else(invalid_segment)
+            }
+            _ => Err(invalid_segment()),
+        }
+    }
+
+    fn label_address(&self, label_name: &str) -> Result<usize, RuntimeError> {
+        let scoped_label_name = scoped_label_name(&self.instructions[self.pc].function_name, label_name);
+        self.labels
+            .get(&scoped_label_name)
+            .copied()
+            .ok_or(RuntimeError::UndefinedLabel(scoped_label_name))
+    }
+
+    fn address(&self, address: i32) -> Result<usize, RuntimeError> {
+        if (0..RAM_SIZE as i32).contains(&address) {
+            Ok(address as usize)
+        } else {
+            Err(RuntimeError::AddressOutOfRange(address))
+        }
+    }
+}
+
+// VMではtrueを-1(全ビット1), falseを0で表す
+fn to_vm_bool(b: bool) -> i16 {
+    if b {
+        -1
+    } else {
+        0
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn load(dir: &str) -> Vec<VMProgram> {
+        let mut paths = std::fs::read_dir(dir)
+            .unwrap()
+            .map(|e| e.unwrap().path())
+            .filter(|p| p.extension().is_some_and(|ext| ext == "vm"))
+            .collect::<Vec<_>>();
+        paths.sort();
+        paths
+            .into_iter()
+            .map(|p| {
+                let file_name = p.file_stem().unwrap().to_string_lossy().to_string();
+                VMProgram::new(file_name, std::fs::read_to_string(p).unwrap()).unwrap()
+            })
+            .collect()
+    }
+
+    // テスト用のHack CPU。ENDラベル(終了用の無限ループ)に到達するまで実行する
+    fn run_hack(instructions: &[assembler::hack::Instruction], ram: &mut [i16]) {
+        let hack = assembler::ParseHackResult::from_instructions(instructions).unwrap();
+        let end = hack
+            .symbols()
+            .iter()
+            .find_map(|s| match s {
+                assembler::Symbol::Label(label, address) if label == "END" => Some(*address as usize),
+                _ => None,
+            })
+            .unwrap();
+        let words = hack.words();
+        let (mut a, mut d, mut pc) = (0i16, 0i16, 0usize);
+        while pc != end {
+            let word = words[pc];
+            pc += 1;
+            if word & 0x8000 == 0 {
+                a = word as i16;
+                continue;
+            }
+            let bit = |n: u16| word & (1 << n) != 0;
+            let (mut x, mut y) = (d, if bit(12) { ram[a as usize] } else { a });
+            if bit(11) {
+                x = 0;
+            }
+            if bit(10) {
+                x = !x;
+            }
+            if bit(9) {
+                y = 0;
+            }
+            if bit(8) {
+                y = !y;
+            }
+            let mut out = if bit(7) { x.wrapping_add(y) } else { x & y };
+            if bit(6) {
+                out = !out;
+            }
+            if bit(3) {
+                ram[a as usize] = out;
+            }
+            let address = a;
+            if bit(5) {
+                a = out;
+            }
+            if bit(4) {
+                d = out;
+            }
+            if (bit(2) && out < 0) || (bit(1) && out == 0) || (bit(0) && out > 0) {
+                pc = address as usize;
+            }
+        }
+    }
+
+    #[test]
+    fn test_extended_commands_match_assembly() {
+        let push = |value: i16| match value {
+            i16::MIN => "push constant 32767\nneg\npush constant 1\nsub\n".to_string(),
+            v if v < 0 => format!("push constant {}\nneg\n", -v),
+            v => format!("push constant {}\n", v),
+        };
+        let values = [0, 1, -1, 2, 7, -7, 100, -100, 181, 12345, -2, 32767, -32768];
+        // 結果をRAM[3000]から順にpop that *で格納する
+        let mut content = String::new();
+        let mut results_length = 0;
+        let mut store = |content: &mut String, compute: String| {
+            content.push_str(&format!("push constant {}\n{}pop that *\n", 3000 + results_length, compute));
+            results_length += 1;
+        };
+        for x in values {
+            store(&mut content, format!("{}shl\n", push(x)));
+            store(&mut content, format!("{}shr\n", push(x)));
+            for y in values {
+                store(&mut content, format!("{}{}mul\n", push(x), push(y)));
+                if y != 0 {
+                    store(&mut content, format!("{}{}div\n", push(x), push(y)));
+                }
+            }
+        }
+        // swapの結果が格納される位置(shl, shrと、y=0を除くdivとmulの後)
+        let swap_result_address = 3000 + values.len() * 2 + values.len() * (values.len() * 2 - 1);
+        store(&mut content, "push constant 5\npush constant 9\nswap\nsub\n".to_string());
+        store(&mut content, "push constant 6\ndup\nadd\n".to_string());
+        store(&mut content, "inc local 0\ninc local 0\ndec local 1\npush local 0\npush local 1\nsub\n".to_string());
+        store(&mut content, "inc static 0\ninc static 0\npush static 0\n".to_string());
+        store(&mut content, format!("push constant {}\npush that *\n", swap_result_address));
+
+        let init = |ram: &mut [i16]| {
+            ram[SP] = 256;
+            ram[LCL] = 300;
+        };
+
+        let programs = vec![VMProgram::new("Main".to_string(), content.clone()).unwrap()];
+        let mut emulator = VMEmulator::new(&programs, None, false).unwrap();
+        init(&mut emulator.ram);
+        assert_eq!(emulator.run(100_000), Ok(StopReason::Halted));
+
+        let (instructions, _) =
+            VMProgram::combine(vec![VMProgram::new("Main".to_string(), content).unwrap()], None, false, None).unwrap();
+        let mut ram = vec![0; RAM_SIZE];
+        init(&mut ram);
+        run_hack(&instructions, &mut ram);
+
+        let results = 3000..3000 + results_length;
+        assert_eq!(ram[results.clone()], emulator.ram[results.clone()]);
+        assert_eq!(ram[SP], emulator.ram[SP]);
+        // 最後の5つの結果
+        assert_eq!(emulator.ram[results][results_length - 5..], [4, 12, 3, 2, 4]);
+    }
+
+    #[test]
+    fn test_division_by_zero() {
+        let programs =
+            vec![VMProgram::new("Main".to_string(), "push constant 1\npush constant 0\ndiv\n".to_string()).unwrap()];
+        let mut emulator = VMEmulator::new(&programs, None, false).unwrap();
+        emulator.set_ram(SP, 256);
+        assert_eq!(emulator.run(100), Err(RuntimeError::DivisionByZero));
+    }
+
+    #[test]
+    fn test_stack_overflow() {
+        // 終わらない再帰はスタックがRAMからはみ出したところでエラーにする
+        let programs =
+            vec![
+                VMProgram::new("Sys".to_string(), "function Sys.init 0\ncall Sys.init 0\nreturn\n".to_string())
+                    .unwrap(),
+            ];
+        let mut emulator = VMEmulator::new(&programs, Some(Bootstrap::default()), false).unwrap();
+        assert_eq!(emulator.run(100000), Err(RuntimeError::AddressOutOfRange(32768)));
+
+        // RAMの末尾までpushしたらそれ以上はpushできない
+        let programs =
+            vec![VMProgram::new("Main".to_string(), "push constant 1\npush constant 2\n".to_string()).unwrap()];
+        let mut emulator = VMEmulator::new(&programs, None, false).unwrap();
+        emulator.set_ram(SP, 32766);
+        assert_eq!(emulator.run(100), Err(RuntimeError::AddressOutOfRange(32768)));
+        assert_eq!((emulator.ram(SP), emulator.ram(32766)), (32767, 1));
+    }
+
+    #[test]
+    fn test_too_many_commands() {
+        // リターンアドレスがi16に収まらないプログラムは読み込まない
+        let programs = vec![VMProgram::new(
+            "Sys".to_string(),
+            format!("function Sys.init 0\nlabel L\n{}return\n", "push constant 0\npop temp 0\n".repeat(16382)),
+        )
+        .unwrap()];
+        assert_eq!(
+            VMEmulator::new(&programs, Some(Bootstrap::default()), false).err(),
+            Some(TranslateError::TooManyCommands(32768))
+        );
+        assert!(VMEmulator::new(&programs, None, false).is_ok());
+    }
+
+    #[test]
+    fn test_run_program_flow() {
+        // test_data/ProgramFlow/BasicLoop/BasicLoop.tst と同じ初期値で実行して.cmpと比較する
+        let mut emulator = VMEmulator::new(&load("test_data/ProgramFlow/BasicLoop"), None, false).unwrap();
+        emulator.set_ram(0, 256);
+        emulator.set_ram(1, 300);
+        emulator.set_ram(2, 400);
+        emulator.set_ram(400, 3);
+        assert_eq!(emulator.run(10000), Ok(StopReason::Halted));
+        assert_eq!((emulator.ram(0), emulator.ram(256)), (257, 6));
+
+        let mut emulator = VMEmulator::new(&load("test_data/ProgramFlow/FibonacciSeries"), None, false).unwrap();
+        emulator.set_ram(0, 256);
+        emulator.set_ram(1, 300);
+        emulator.set_ram(2, 400);
+        emulator.set_ram(400, 6);
+        emulator.set_ram(401, 3000);
+        assert_eq!(emulator.run(10000), Ok(StopReason::Halted));
+        assert_eq!((3000..3006).map(|a| emulator.ram(a)).collect::<Vec<_>>(), vec![0, 1, 1, 2, 3, 5]);
+    }
+
+    #[test]
+    fn test_run_function_calls() {
+        let mut emulator = VMEmulator::new(&load("test_data/FunctionCalls/SimpleFunction"), None, false).unwrap();
+        for (address, value) in [
+            (0, 317),
+            (1, 317),
+            (2, 310),
+            (3, 3000),
+            (4, 4000),
+            (310, 1234),
+            (311, 37),
+            (312, 1000),
+            (313, 305),
+            (314, 300),
+            (315, 3010),
+            (316, 4010),
+        ] {
+            emulator.set_ram(address, value);
+        }
+        assert_eq!(emulator.run(10000), Ok(StopReason::Halted));
+        assert_eq!([0, 1, 2, 3, 4, 310].map(|a| emulator.ram(a)), [311, 305, 300, 3010, 4010, 1196]);
+
+        let mut emulator =
+            VMEmulator::new(&load("test_data/FunctionCalls/FibonacciElement"), Some(Bootstrap::default()), false)
+                .unwrap();
+        assert_eq!(emulator.run(10000), Ok(StopReason::InfiniteLoop));
+        assert_eq!((emulator.ram(0), emulator.ram(261)), (262, 3));
+
+        let mut emulator =
+            VMEmulator::new(&load("test_data/FunctionCalls/StaticsTest"), Some(Bootstrap::default()), false).unwrap();
+        assert_eq!(emulator.run(10000), Ok(StopReason::InfiniteLoop));
+        assert_eq!([0, 261, 262].map(|a| emulator.ram(a)), [263, -2, 8]);
+    }
+
+    #[test]
+    fn test_memory_mapped_io() {
+        // キーボードの値をスクリーンの先頭に書き込む
+        let programs = vec![VMProgram::new(
+            "Main".to_string(),
+            r#"
+function Main.main 0
+push constant 24576
+pop pointer 1
+push that 0
+push constant 16384
+pop pointer 1
+pop that 0
+push constant 0
+return
+            "#
+            .to_string(),
+        )
+        .unwrap()];
+        let bootstrap = Bootstrap {
+            stack_base: 256,
+            entry_point: "Main.main".to_string(),
+        };
+        let mut emulator = VMEmulator::new(&programs, Some(bootstrap), false).unwrap();
+        emulator.set_key(75);
+        assert_eq!(emulator.run(100), Ok(StopReason::Halted));
+        assert_eq!(emulator.screen()[0], 75);
+        assert_eq!(emulator.ram(SCREEN), 75);
+    }
+
+    #[test]
+    fn test_native_os() {
+        // 11のコンパイラが出力するようなコードでOSの関数を呼び出す
+        let programs = vec![VMProgram::new(
+            "Main".to_string(),
+            r#"
+function Main.main 1
+push constant 2
+call String.new 1
+push constant 72
+call String.appendChar 2
+push constant 105
+call String.appendChar 2
+pop local 0
+push local 0
+call Output.printString 1
+pop temp 0
+push constant 6
+push constant 7
+call Math.multiply 2
+call Output.printInt 1
+pop temp 0
+push constant 0
+return
+            "#
+            .to_string(),
+        )
+        .unwrap()];
+        // Sys.initの代わりにOSの初期化関数とMain.mainを呼び出し、Main.mainからreturnすると停止する
+        let mut emulator = VMEmulator::new(&programs, Some(Bootstrap::default()), true).unwrap();
+        assert_eq!(emulator.run(1000), Ok(StopReason::Halted));
+        assert_eq!(emulator.native_os().unwrap().printed_text(), "Hi42");
+        assert_eq!([2048, 2049, 2050, 2051].map(|a| emulator.ram(a)), [2, 2, 72, 105]);
+
+        // ネイティブ実装を使わない場合はOSの関数が定義されていないのでエラーになる
+        assert_eq!(
+            VMEmulator::new(&programs, Some(Bootstrap::default()), false).err(),
+            Some(TranslateError::UndefinedFunction {
+                function_name: "String.new".to_string(),
+                file_name: "Main".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn test_native_os_matches_vm_implementation() {
+        // .vmで定義した関数はネイティブ実装より優先されるので、Math.multiplyだけを差し替えて結果を比較する
+        let main = r#"
+function Main.main 0
+push constant 123
+push constant 45
+neg
+call Math.multiply 2
+return
+        "#;
+        // 足し算を繰り返すだけの素朴な実装(xは正の数のみ)
+        let math = r#"
+function Math.multiply 1
+label LOOP
+push argument 0
+push constant 0
+eq
+if-goto END
+push local 0
+push argument 1
+add
+pop local 0
+push argument 0
+push constant 1
+sub
+pop argument 0
+goto LOOP
+label END
+push local 0
+return
+        "#;
+        let program = |name: &str, source: &str| VMProgram::new(name.to_string(), source.to_string()).unwrap();
+        let results = [
+            vec![program("Main", main)],
+            vec![program("Main", main), program("Math", math)],
+        ]
+        .map(|programs| {
+            let mut emulator = VMEmulator::new(&programs, Some(Bootstrap::default()), true).unwrap();
+            assert_eq!(emulator.run(10000), Ok(StopReason::Halted));
+            (emulator.ram(256), emulator.steps())
+        });
+        assert_eq!([results[0].0, results[1].0], [-5535, -5535]);
+        // .vmの実装はループするのでネイティブ実装より多くの命令を実行する
+        assert!(results[0].1 < 20 && results[1].1 > 1000);
+
+        // .vmで定義したMath.initも、ネイティブ実装の初期化関数と一緒にSys.initの代わりに呼び出される
+        let init = "function Math.init 0\npush constant 1000\npop static 0\npush constant 0\nreturn";
+        let mut emulator =
+            VMEmulator::new(&[program("Main", main), program("Math", init)], Some(Bootstrap::default()), true).unwrap();
+        assert_eq!(emulator.run(10000), Ok(StopReason::Halted));
+        assert_eq!(emulator.ram(STATIC_BASE), 1000);
+
+        // Memory.allocを差し替えるとネイティブ実装のヒープは使えないので、String.newも.vmで定義する必要がある
+        let string = "function Main.main 0\npush constant 1\ncall String.new 1\nreturn";
+        let memory = "function Memory.alloc 0\npush constant 0\nreturn";
+        assert_eq!(
+            VMEmulator::new(&[program("Main", string), program("Memory", memory)], Some(Bootstrap::default()), true)
+                .err(),
+            Some(TranslateError::UndefinedFunction {
+                function_name: "String.new".to_string(),
+                file_name: "Main".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn test_native_os_error() {
+        let programs = vec![VMProgram::new(
+            "Main".to_string(),
+            r#"
+function Main.main 0
+push constant 1
+push constant 0
+call Math.divide 2
+return
+            "#
+            .to_string(),
+        )
+        .unwrap()];
+        let mut emulator = VMEmulator::new(&programs, Some(Bootstrap::default()), true).unwrap();
+        assert_eq!(emulator.run(1000), Err(RuntimeError::SysError(3)));
+    }
+}
@@ -0,0 +1,281 @@
+use crate::cfg::{function_ranges, ControlFlowGraph};
+use crate::translator::{Command, VMProgram};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// 出力するグラフの種類
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum GraphKind {
+    // 関数ごとの制御フローグラフ
+    ControlFlow,
+    // プログラム全体のコールグラフ
+    Call,
+}
+
+impl GraphKind {
+    pub fn new(s: &str) -> Option<Self> {
+        match s {
+            "cfg" => Some(Self::ControlFlow),
+            "calls" => Some(Self::Call),
+            _ => None,
+        }
+    }
+}
+
+/// GraphvizのDOT形式でグラフを出力する
+pub fn to_dot(programs: &[VMProgram], kind: GraphKind) -> String {
+    match kind {
+        GraphKind::ControlFlow => control_flow_graphs(programs),
+        GraphKind::Call => call_graph(programs),
+    }
+}
+
+// 関数ごとにclusterを分けて、基本ブロックをノード、goto/if-goto/フォールスルーをエッジとして出力する
+fn control_flow_graphs(programs: &[VMProgram]) -> String {
+    let mut lines = vec![
+        "digraph cfg {".to_string(),
+        "  node [shape=box, fontname=monospace];".to_string(),
+    ];
+    for p in programs {
+        for (start, end) in function_ranges(&p.commands) {
+            let Command::Function(function_name, _) = &p.commands[start] else {
+                unreachable!()
+            };
+            let cfg = ControlFlowGraph::new(&p.commands, start + 1, end);
+            let node = |i: usize| quote(&format!("{}:{}", function_name, i));
+
+            lines.push(format!("  subgraph {} {{", quote(&format!("cluster_{}", function_name))));
+            lines.push(format!("    label={};", quote(function_name)));
+            // エントリーノード(functionコマンド)
+            lines.push(format!(
+                "    {} [label={}, shape=ellipse];",
+                quote(&format!("{}:entry", function_name)),
+                quote(&format!("{}.vm:{}: {}", p.file_name, p.line_number(start), p.source_text(start)))
+            ));
+            if !cfg.blocks.is_empty() {
+                lines.push(format!("    {} -> {};", quote(&format!("{}:entry", function_name)), node(0)));
+            }
+
+            for (i, block) in cfg.blocks.iter().enumerate() {
+                // 左寄せにするため各行の末尾に\lを付ける
+                let label = (block.start..block.end)
+                    .map(|c| format!("{}: {}\\l", p.line_number(c), escape(p.source_text(c))))
+                    .collect::<String>();
+                lines.push(format!("    {} [label=\"{}\"];", node(i), label));
+            }
+            for (i, block) in cfg.blocks.iter().enumerate() {
+                let last = &p.commands[block.end - 1];
+                for successor in &block.successors {
+                    // if-gotoの分岐先(1つ目の後続)にだけラベルを付ける
+                    let attributes = match last {
+                        Command::IfGoTo(_) if *successor != i + 1 => " [label=\"true\"]",
+                        Command::IfGoTo(_) => " [label=\"false\"]",
+                        _ => "",
+                    };
+                    if *successor == cfg.blocks.len() {
+                        // returnせずに関数の末尾を越えるもの
+                        let exit = quote(&format!("{}:fallthrough", function_name));
+                        lines.push(format!("    {} [label=\"fall through\", shape=plaintext];", exit));
+                        lines.push(format!("    {} -> {} [style=dashed];", node(i), exit));
+                    } else {
+                        lines.push(format!("    {} -> {}{};", node(i), node(*successor), attributes));
+                    }
+                }
+            }
+            for (i, label) in &cfg.undefined_labels {
+                lines.push(format!("    // {}.vm:{}: undefined label {}", p.file_name, p.line_number(*i), label));
+            }
+            lines.push("  }".to_string());
+        }
+    }
+    lines.push("}".to_string());
+    lines.join("\n") + "\n"
+}
+
+// 関数をノード、callをエッジとして出力する。エッジのラベルは呼び出し箇所の数
+// 再帰(自分自身に戻ってくる呼び出し)に含まれるエッジは赤で表示する
+fn call_graph(programs: &[VMProgram]) -> String {
+    // key: (呼び出し元, 呼び出し先), value: 呼び出し箇所の数
+    let mut calls = BTreeMap::<(String, String), usize>::new();
+    let mut functions = BTreeSet::new();
+    for p in programs {
+        // 関数の外のcallは`{ファイル名}.vm`から呼び出したものとして扱う
+        let mut caller = format!("{}.vm", p.file_name);
+        for c in &p.commands {
+            match c {
+                Command::Function(function_name, _) => {
+                    caller = function_name.clone();
+                    functions.insert(function_name.clone());
+                }
+                Command::Call(callee, _) => {
+                    functions.insert(caller.clone());
+                    functions.insert(callee.clone());
+                    *calls.entry((caller.clone(), callee.clone())).or_insert(0) += 1;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    let defined = programs
+        .iter()
+        .flat_map(|p| p.commands.iter())
+        .filter_map(|c| match c {
+            Command::Function(function_name, _) => Some(function_name.as_str()),
+            _ => None,
+        })
+        .collect::<BTreeSet<_>>();
+
+    let mut lines = vec!["digraph calls {".to_string(), "  node [shape=box];".to_string()];
+    for f in &functions {
+        // 定義が見つからない関数(OSの関数をリンクしていない場合など)は点線で表示する
+        if !defined.contains(f.as_str()) && !f.ends_with(".vm") {
+            lines.push(format!("  {} [style=dashed];", quote(f)));
+        } else {
+            lines.push(format!("  {};", quote(f)));
+        }
+    }
+    for ((caller, callee), count) in &calls {
+        let recursive = reaches(&calls, callee, caller);
+        lines.push(format!(
+            "  {} -> {} [label=\"{}\"{}];",
+            quote(caller),
+            quote(callee),
+            count,
+            if recursive { ", color=red" } else { "" }
+        ));
+    }
+    lines.push("}".to_string());
+    lines.join("\n") + "\n"
+}
+
+// fromからtoへ呼び出しをたどって到達できるかどうか
+fn reaches(calls: &BTreeMap<(String, String), usize>, from: &str, to: &str) -> bool {
+    let mut visited = BTreeSet::new();
+    let mut stack = vec![from];
+    while let Some(f) = stack.pop() {
+        if f == to {
+            return true;
+        }
+        if !visited.insert(f) {
+            continue;
+        }
+        stack.extend(
+            calls
+                .keys()
+                .filter(|(caller, _)| caller == f)
+                .map(|(_, callee)| callee.as_str()),
+        );
+    }
+    false
+}
+
+fn quote(s: &str) -> String {
+    format!("\"{}\"", escape(s))
+}
+
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_control_flow_graphs() {
+        let program = VMProgram::new(
+            "Main".to_string(),
+            r#"
+function Main.abs 0
+push argument 0
+push constant 0
+lt
+if-goto NEG
+push argument 0
+return
+label NEG
+push argument 0
+neg
+return
+"#
+            .to_string(),
+        )
+        .unwrap();
+        assert_eq!(
+            to_dot(&[program], GraphKind::ControlFlow),
+            r#"digraph cfg {
+  node [shape=box, fontname=monospace];
+  subgraph "cluster_Main.abs" {
+    label="Main.abs";
+    "Main.abs:entry" [label="Main.vm:2: function Main.abs 0", shape=ellipse];
+    "Main.abs:entry" -> "Main.abs:0";
+    "Main.abs:0" [label="3: push argument 0\l4: push constant 0\l5: lt\l6: if-goto NEG\l"];
+    "Main.abs:1" [label="7: push argument 0\l8: return\l"];
+    "Main.abs:2" [label="9: label NEG\l10: push argument 0\l11: neg\l12: return\l"];
+    "Main.abs:0" -> "Main.abs:2" [label="true"];
+    "Main.abs:0" -> "Main.abs:1" [label="false"];
+  }
+}
+"#
+        );
+    }
+
+    #[test]
+    fn test_call_graph() {
+        let programs = vec![
+            VMProgram::new(
+                "Main".to_string(),
+                r#"
+function Main.main 0
+push constant 10
+call Main.fib 1
+push constant 3
+call Math.divide 1
+call Output.printInt 1
+return
+function Main.fib 0
+push argument 0
+push constant 1
+sub
+call Main.fib 1
+push argument 0
+push constant 2
+sub
+call Main.fib 1
+add
+return
+"#
+                .to_string(),
+            )
+            .unwrap(),
+            VMProgram::new(
+                "Math".to_string(),
+                r#"
+function Math.divide 0
+push argument 0
+call Math.divide 1
+return
+"#
+                .to_string(),
+            )
+            .unwrap(),
+        ];
+        assert_eq!(
+            to_dot(&programs, GraphKind::Call),
+            r#"digraph calls {
+  node [shape=box];
+  "Main.fib";
+  "Main.main";
+  "Math.divide";
+  "Output.printInt" [style=dashed];
+  "Main.fib" -> "Main.fib" [label="2", color=red];
+  "Main.main" -> "Main.fib" [label="1"];
+  "Main.main" -> "Math.divide" [label="1"];
+  "Main.main" -> "Output.printInt" [label="1"];
+  "Math.divide" -> "Math.divide" [label="1", color=red];
+}
+"#
+        );
+    }
+}
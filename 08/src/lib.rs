@@ -0,0 +1,7 @@
+pub mod analyzer;
+pub mod cache;
+mod cfg;
+pub mod emulator;
+pub mod graph;
+pub mod native_os;
+pub mod translator;
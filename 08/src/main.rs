@@ -1,31 +1,270 @@
+use assembler::hack::MAX_ADDRESS_VALUE;
 use std::path::{Path, PathBuf};
-use translator::VMProgram;
+use vm_translator::cache::FragmentCache;
+use vm_translator::emulator::VMEmulator;
+use vm_translator::graph::GraphKind;
+use vm_translator::translator::{self, Bootstrap, VMProgram};
+use vm_translator::{analyzer, graph};
 
-mod translator;
+const USAGE: &str = "Usage: vm_translator [--no-bootstrap] [--extended] [--source-map] [--annotate | --hack [--symbols]] [--cache <dir>] [--stack-base <n>] [--entry <function>] [--run [--max-steps <n>] [--native-os] | --check | --graph <cfg|calls>] <filepath>";
+// --runで--max-stepsを指定しなかった場合に実行する最大ステップ数
+const DEFAULT_MAX_STEPS: u64 = 10_000_000;
 
 fn main() {
     let command_line_args: Vec<String> = std::env::args().collect();
-    if command_line_args.len() != 2 {
-        println!("Usage: vm_translator <filepath>");
-        return;
+    let options = match Options::new(&command_line_args[1..]) {
+        Some(options) => options,
+        None => {
+            println!("{}", USAGE);
+            return;
+        }
+    };
+    let (target_files, output_file_path) = get_target_files(&options.source_file_path).unwrap();
+
+    // 読み込みとparseはファイルごとに並列に行う(結果はtarget_filesの順序のまま)
+    let vm_files = translator::parallel_map(target_files, |target| {
+        let file_name_without_ext = target.file_stem().unwrap().to_string_lossy().to_string();
+        parse(target, file_name_without_ext)
+    })
+    .into_iter()
+    .collect::<Result<Vec<_>, _>>()
+    .unwrap_or_else(|e| exit_with_error(e));
+    if !options.extended {
+        for p in &vm_files {
+            p.check_standard_commands().unwrap_or_else(|e| exit_with_error(e));
+        }
     }
-    let source_file_path = std::path::PathBuf::from(&command_line_args[1]);
-    let (target_files, output_file_path) = get_target_files(&source_file_path).unwrap();
-
-    let vm_files = target_files
-        .into_iter()
-        .map(|target| {
-            let file_name_without_ext = target.file_stem().unwrap().to_string_lossy().to_string();
-            parse(target, file_name_without_ext)
-        })
-        .collect();
 
-    let combined_assembly = VMProgram::combine_and_assemble(vm_files);
-    let _ = std::fs::write(output_file_path, combined_assembly);
+    match options.mode {
+        Mode::Translate {
+            source_map,
+            annotated,
+            cache_dir,
+        } => {
+            let cache = cache_dir.map(|dir| FragmentCache::new(dir).unwrap_or_else(|e| exit_with_error(e)));
+            match VMProgram::combine_and_assemble_with_source_map(
+                vm_files,
+                options.bootstrap,
+                annotated,
+                cache.as_ref(),
+            ) {
+                Ok((combined_assembly, map)) => {
+                    if source_map {
+                        std::fs::write(output_file_path.with_extension("map.json"), map.to_json())
+                            .unwrap_or_else(|e| exit_with_error(e));
+                    }
+                    std::fs::write(output_file_path, combined_assembly).unwrap_or_else(|e| exit_with_error(e));
+                }
+                Err(e) => exit_with_error(e),
+            }
+        }
+        Mode::Assemble {
+            source_map,
+            symbols,
+            cache_dir,
+        } => {
+            let cache = cache_dir.map(|dir| FragmentCache::new(dir).unwrap_or_else(|e| exit_with_error(e)));
+            if let Err(e) =
+                write_hack(vm_files, options.bootstrap, &output_file_path, source_map, symbols, cache.as_ref())
+            {
+                exit_with_error(e);
+            }
+        }
+        Mode::Check => {
+            let diagnostics = analyzer::analyze(&vm_files);
+            for d in &diagnostics {
+                eprintln!("{}", d);
+            }
+            if !diagnostics.is_empty() {
+                std::process::exit(1);
+            }
+        }
+        Mode::Graph(kind) => print!("{}", graph::to_dot(&vm_files, kind)),
+        Mode::Run { max_steps, native_os } => {
+            let mut emulator =
+                VMEmulator::new(&vm_files, options.bootstrap, native_os).unwrap_or_else(|e| exit_with_error(e));
+            let result = emulator.run(max_steps);
+            // エラーで停止した場合もそれまでに出力された文字列は表示する
+            if let Some(native_os) = emulator.native_os() {
+                println!("{}", native_os.printed_text());
+            }
+            let reason = result.unwrap_or_else(|e| exit_with_error(e));
+            println!("{:?} after {} steps", reason, emulator.steps());
+            println!(
+                "SP={} LCL={} ARG={} THIS={} THAT={}",
+                emulator.ram(0),
+                emulator.ram(1),
+                emulator.ram(2),
+                emulator.ram(3),
+                emulator.ram(4)
+            );
+        }
+    }
+}
+
+// .asmファイルを経由せずに.hackファイルに変換する
+// output_file_pathの拡張子を.hackに変えたファイルに出力し、symbolsがtrueの場合は.symファイルにシンボルを出力する
+fn write_hack(
+    vm_files: Vec<VMProgram>,
+    bootstrap: Option<Bootstrap>,
+    output_file_path: &Path,
+    source_map: bool,
+    symbols: bool,
+    cache: Option<&FragmentCache>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let (instructions, map) = VMProgram::combine(vm_files, bootstrap, false, cache)?;
+    let words = instructions.iter().filter(|i| i.is_machine_instruction()).count();
+    if words > translator::ROM_SIZE {
+        return Err(translator::TranslateError::RomOverflow(words).into());
+    }
+    let binary =
+        assembler::ParseHackResult::from_instructions(&instructions).map_err(translator::TranslateError::Assemble)?;
+    std::fs::write(output_file_path.with_extension("hack"), binary.to_string())?;
+    if symbols {
+        std::fs::write(output_file_path.with_extension("sym"), binary.to_symbol_file())?;
+    }
+    if source_map {
+        std::fs::write(output_file_path.with_extension("map.json"), map.to_json())?;
+    }
+    Ok(())
+}
+
+fn exit_with_error(e: impl std::fmt::Display) -> ! {
+    eprintln!("error: {}", e);
+    std::process::exit(1);
+}
+
+/// コマンドライン引数をparseした結果
+#[derive(PartialEq, Eq, Debug)]
+struct Options {
+    source_file_path: PathBuf,
+    // Noneの場合はブートストラップコードを出力しない
+    bootstrap: Option<Bootstrap>,
+    // trueの場合はmul, div等の拡張命令を受け付ける
+    extended: bool,
+    mode: Mode,
+}
+
+#[derive(PartialEq, Eq, Debug)]
+enum Mode {
+    // .asmファイルに変換する
+    // source_mapがtrueの場合は`{出力ファイル名}.map.json`にソースマップを出力する
+    // annotatedがtrueの場合は各コマンドの先頭に元の.vmファイルの行をコメントとして出力する
+    // cache_dirを指定した場合はファイルごとの変換結果をキャッシュし、内容が変わっていないファイルは変換しない
+    Translate {
+        source_map: bool,
+        annotated: bool,
+        cache_dir: Option<PathBuf>,
+    },
+    // .asmファイルを経由せずに.hackファイルに変換する
+    // symbolsがtrueの場合は`{出力ファイル名}.sym`にラベルと変数のアドレスを出力する
+    Assemble {
+        source_map: bool,
+        symbols: bool,
+        cache_dir: Option<PathBuf>,
+    },
+    // VMエミュレータで実行する(native_osがtrueの場合はOSの関数をRustの実装で実行する)
+    Run {
+        max_steps: u64,
+        native_os: bool,
+    },
+    // スタックの深さや引数の数を静的に検査する
+    Check,
+    // 制御フローグラフまたはコールグラフをDOT形式で標準出力に出力する
+    Graph(GraphKind),
+}
+
+impl Options {
+    // 引数が不正な場合はNoneを返す
+    fn new(args: &[String]) -> Option<Self> {
+        let mut source_file_path = None;
+        let mut no_bootstrap = false;
+        let mut extended = false;
+        let mut bootstrap = Bootstrap::default();
+        let mut run = false;
+        let mut max_steps = DEFAULT_MAX_STEPS;
+        let mut native_os = false;
+        let mut source_map = false;
+        let mut annotated = false;
+        let mut hack = false;
+        let mut symbols = false;
+        let mut check = false;
+        let mut graph = None;
+        let mut cache_dir = None;
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--no-bootstrap" => no_bootstrap = true,
+                "--extended" => extended = true,
+                "--stack-base" => {
+                    // A命令で指定できる範囲(15bit)のアドレスのみ受け付ける
+                    bootstrap.stack_base = args
+                        .next()?
+                        .parse::<u16>()
+                        .ok()
+                        .filter(|base| *base <= MAX_ADDRESS_VALUE)?
+                }
+                "--entry" => bootstrap.entry_point = args.next()?.to_string(),
+                "--run" => run = true,
+                "--max-steps" => max_steps = args.next()?.parse::<u64>().ok()?,
+                "--native-os" => native_os = true,
+                "--source-map" => source_map = true,
+                "--annotate" => annotated = true,
+                "--hack" => hack = true,
+                "--symbols" => symbols = true,
+                "--check" => check = true,
+                "--graph" => graph = Some(GraphKind::new(args.next()?)?),
+                "--cache" => cache_dir = Some(PathBuf::from(args.next()?)),
+                _ if arg.starts_with("--") => return None,
+                _ => {
+                    // 入力パスは1つだけ指定できる
+                    if source_file_path.is_some() {
+                        return None;
+                    }
+                    source_file_path = Some(PathBuf::from(arg));
+                }
+            }
+        }
+
+        // --annotateは.asmファイルにしか、--symbolsは.hackファイルにしか意味がない
+        if (hack && annotated) || (symbols && !hack) || (check && run) || (graph.is_some() && (check || run)) {
+            return None;
+        }
+        // キャッシュは変換結果を出力する場合にしか使わない
+        if cache_dir.is_some() && (check || run || graph.is_some()) {
+            return None;
+        }
+
+        Some(Self {
+            source_file_path: source_file_path?,
+            bootstrap: if no_bootstrap { None } else { Some(bootstrap) },
+            extended,
+            mode: if let Some(kind) = graph {
+                Mode::Graph(kind)
+            } else if check {
+                Mode::Check
+            } else if run {
+                Mode::Run { max_steps, native_os }
+            } else if hack {
+                Mode::Assemble {
+                    source_map,
+                    symbols,
+                    cache_dir,
+                }
+            } else {
+                Mode::Translate {
+                    source_map,
+                    annotated,
+                    cache_dir,
+                }
+            },
+        })
+    }
 }
 
 // 任意のpathを渡せるようにしておくとUTが書きやすいので切り出しておく
-fn parse(path: PathBuf, file_name: String) -> translator::VMProgram {
+fn parse(path: PathBuf, file_name: String) -> Result<translator::VMProgram, translator::TranslateError> {
     let content = std::fs::read_to_string(path.clone()).expect("Failed to read file");
     translator::VMProgram::new(file_name, content)
 }
@@ -33,7 +272,7 @@ fn parse(path: PathBuf, file_name: String) -> translator::VMProgram {
 fn get_target_files(input_path: &Path) -> Option<(Vec<PathBuf>, PathBuf)> {
     // inputがファイルだったら.vmかどうか判定して(target_files, output_file_path)を返す
     if input_path.is_file() {
-        if input_path.extension().unwrap() != "vm" {
+        if input_path.extension().is_none_or(|e| e != "vm") {
             return None;
         }
         return Some((vec![input_path.to_path_buf()], input_path.with_extension("asm")));
@@ -46,11 +285,14 @@ fn get_target_files(input_path: &Path) -> Option<(Vec<PathBuf>, PathBuf)> {
     let mut result = vec![];
     for e in std::fs::read_dir(input_path).unwrap() {
         let e_path = e.unwrap().path();
-        if e_path.extension().unwrap() == "vm" {
+        // --cacheのディレクトリなどのサブディレクトリは対象にしない
+        if e_path.is_file() && e_path.extension().is_some_and(|e| e == "vm") {
             result.push(e_path);
         }
     }
 
+    // read_dirの順序はファイルシステムに依存するので、出力が毎回同じになるようにソートする
+    result.sort();
     Some((result, input_path.join(input_path.file_name().unwrap()).with_extension("asm")))
 }
 
@@ -103,16 +345,251 @@ mod test {
             std::fs::File::create(&child2).unwrap();
             let other = parent_dir.join("README.md");
             std::fs::File::create(&other).unwrap();
+            std::fs::create_dir(parent_dir.join("cache")).unwrap();
+            std::fs::create_dir(parent_dir.join("Nested.vm")).unwrap();
 
+            // ファイル名順に並ぶ
             let result = get_target_files(&parent_dir.clone()).unwrap();
-            assert_eq!(result.0.len(), 3);
-            assert_eq!(result.0.contains(&main), true);
-            assert_eq!(result.0.contains(&child1), true);
-            assert_eq!(result.0.contains(&child2), true);
+            assert_eq!(result.0, vec![child1, child2, main]);
             assert_eq!(result.0.contains(&other), false);
             assert_eq!(result.1, parent_dir.join("Foo.asm"));
         }
 
         let _ = std::fs::remove_dir_all(&test_root_dir);
     }
+
+    #[test]
+    fn test_options_new() {
+        let args = |args: &[&str]| args.iter().map(|a| a.to_string()).collect::<Vec<String>>();
+
+        // 何も指定しなければSys.initを呼び出すブートストラップコードを出力する
+        assert_eq!(
+            Options::new(&args(&["Foo"])),
+            Some(Options {
+                source_file_path: PathBuf::from("Foo"),
+                bootstrap: Some(Bootstrap::default()),
+                extended: false,
+                mode: Mode::Translate {
+                    source_map: false,
+                    annotated: false,
+                    cache_dir: None,
+                },
+            })
+        );
+        assert_eq!(
+            Options::new(&args(&["--no-bootstrap", "Foo.vm"])),
+            Some(Options {
+                source_file_path: PathBuf::from("Foo.vm"),
+                bootstrap: None,
+                extended: false,
+                mode: Mode::Translate {
+                    source_map: false,
+                    annotated: false,
+                    cache_dir: None,
+                },
+            })
+        );
+        assert_eq!(
+            Options::new(&args(&["--stack-base", "300", "--entry", "Main.main", "Foo"])),
+            Some(Options {
+                source_file_path: PathBuf::from("Foo"),
+                bootstrap: Some(Bootstrap {
+                    stack_base: 300,
+                    entry_point: "Main.main".to_string(),
+                }),
+                extended: false,
+                mode: Mode::Translate {
+                    source_map: false,
+                    annotated: false,
+                    cache_dir: None,
+                },
+            })
+        );
+        assert_eq!(
+            Options::new(&args(&["--source-map", "--annotate", "Foo"])),
+            Some(Options {
+                source_file_path: PathBuf::from("Foo"),
+                bootstrap: Some(Bootstrap::default()),
+                extended: false,
+                mode: Mode::Translate {
+                    source_map: true,
+                    annotated: true,
+                    cache_dir: None,
+                },
+            })
+        );
+        assert_eq!(
+            Options::new(&args(&["--hack", "--symbols", "Foo"])),
+            Some(Options {
+                source_file_path: PathBuf::from("Foo"),
+                bootstrap: Some(Bootstrap::default()),
+                extended: false,
+                mode: Mode::Assemble {
+                    source_map: false,
+                    symbols: true,
+                    cache_dir: None,
+                },
+            })
+        );
+        assert_eq!(
+            Options::new(&args(&["--check", "Foo"])),
+            Some(Options {
+                source_file_path: PathBuf::from("Foo"),
+                bootstrap: Some(Bootstrap::default()),
+                extended: false,
+                mode: Mode::Check,
+            })
+        );
+        assert_eq!(
+            Options::new(&args(&["--run", "Foo"])),
+            Some(Options {
+                source_file_path: PathBuf::from("Foo"),
+                bootstrap: Some(Bootstrap::default()),
+                extended: false,
+                mode: Mode::Run {
+                    max_steps: DEFAULT_MAX_STEPS,
+                    native_os: false,
+                },
+            })
+        );
+        assert_eq!(
+            Options::new(&args(&["--run", "--max-steps", "100", "--native-os", "Foo"])),
+            Some(Options {
+                source_file_path: PathBuf::from("Foo"),
+                bootstrap: Some(Bootstrap::default()),
+                extended: false,
+                mode: Mode::Run {
+                    max_steps: 100,
+                    native_os: true,
+                },
+            })
+        );
+
+        // 不正な引数
+        assert_eq!(Options::new(&args(&[])), None);
+        assert_eq!(Options::new(&args(&["Foo", "Bar"])), None);
+        assert_eq!(Options::new(&args(&["--stack-base", "foo", "Foo"])), None);
+        assert_eq!(Options::new(&args(&["--stack-base", "32768", "Foo"])), None);
+        assert_eq!(Options::new(&args(&["--entry"])), None);
+        assert_eq!(Options::new(&args(&["--unknown", "Foo"])), None);
+        assert_eq!(Options::new(&args(&["--symbols", "Foo"])), None);
+        assert_eq!(Options::new(&args(&["--check", "--run", "Foo"])), None);
+        assert_eq!(
+            Options::new(&args(&["--graph", "calls", "Foo"])),
+            Some(Options {
+                source_file_path: PathBuf::from("Foo"),
+                bootstrap: Some(Bootstrap::default()),
+                extended: false,
+                mode: Mode::Graph(GraphKind::Call),
+            })
+        );
+        assert_eq!(Options::new(&args(&["--graph", "ast", "Foo"])), None);
+        assert_eq!(
+            Options::new(&args(&["--hack", "--cache", ".cache", "Foo"])),
+            Some(Options {
+                source_file_path: PathBuf::from("Foo"),
+                bootstrap: Some(Bootstrap::default()),
+                extended: false,
+                mode: Mode::Assemble {
+                    source_map: false,
+                    symbols: false,
+                    cache_dir: Some(PathBuf::from(".cache")),
+                },
+            })
+        );
+        assert_eq!(Options::new(&args(&["--cache", ".cache", "--run", "Foo"])), None);
+        assert_eq!(
+            Options::new(&args(&["--extended", "--run", "Foo"])),
+            Some(Options {
+                source_file_path: PathBuf::from("Foo"),
+                bootstrap: Some(Bootstrap::default()),
+                extended: true,
+                mode: Mode::Run {
+                    max_steps: DEFAULT_MAX_STEPS,
+                    native_os: false,
+                },
+            })
+        );
+        assert_eq!(Options::new(&args(&["--graph", "cfg", "--check", "Foo"])), None);
+        assert_eq!(Options::new(&args(&["--hack", "--annotate", "Foo"])), None);
+        assert_eq!(Options::new(&args(&["--run", "--max-steps", "-1", "Foo"])), None);
+    }
+
+    #[test]
+    fn test_translate_with_cache() {
+        let cache_dir = std::env::temp_dir().join(format!("nand2tetris08-{}", Uuid::new_v4()));
+        let cache = FragmentCache::new(cache_dir.clone()).unwrap();
+
+        let (target_files, _) = get_target_files(Path::new("test_data/FunctionCalls/StaticsTest")).unwrap();
+        let vm_files = || {
+            target_files
+                .iter()
+                .map(|t| parse(t.clone(), t.file_stem().unwrap().to_string_lossy().to_string()).unwrap())
+                .collect::<Vec<_>>()
+        };
+        let translate = |cache: Option<&FragmentCache>| {
+            VMProgram::combine_and_assemble_with_source_map(vm_files(), Some(Bootstrap::default()), true, cache)
+                .unwrap()
+        };
+
+        // キャッシュがない場合、キャッシュを作る場合、キャッシュを使う場合で出力が一致する
+        let expected = translate(None);
+        assert_eq!(translate(Some(&cache)), expected);
+        assert_eq!(std::fs::read_dir(&cache_dir).unwrap().count(), target_files.len());
+        assert_eq!(translate(Some(&cache)), expected);
+        assert_eq!(std::fs::read_dir(&cache_dir).unwrap().count(), target_files.len());
+
+        let _ = std::fs::remove_dir_all(&cache_dir);
+    }
+
+    #[test]
+    fn test_write_hack() {
+        let test_target_dir = std::env::temp_dir().join(format!("nand2tetris08-{}", Uuid::new_v4()));
+        std::fs::create_dir(&test_target_dir).unwrap();
+        let output_file_path = test_target_dir.join("FibonacciElement.asm");
+
+        let (target_files, _) = get_target_files(Path::new("test_data/FunctionCalls/FibonacciElement")).unwrap();
+        let vm_files = || {
+            target_files
+                .iter()
+                .map(|t| parse(t.clone(), t.file_stem().unwrap().to_string_lossy().to_string()).unwrap())
+                .collect::<Vec<_>>()
+        };
+        write_hack(vm_files(), Some(Bootstrap::default()), &output_file_path, false, true, None).unwrap();
+
+        // .asmファイルを出力してからアセンブルした結果と一致する
+        let assembly = VMProgram::combine_and_assemble(vm_files(), Some(Bootstrap::default())).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(test_target_dir.join("FibonacciElement.hack")).unwrap(),
+            assembler::ParseHackResult::new(assembly).unwrap().to_string()
+        );
+        let symbol_file = std::fs::read_to_string(test_target_dir.join("FibonacciElement.sym")).unwrap();
+        assert_eq!(
+            symbol_file
+                .lines()
+                .any(|l| l.starts_with("ROM ") && l.ends_with(" Sys.init")),
+            true
+        );
+        assert_eq!(symbol_file.lines().any(|l| l.starts_with("RAM 16 ")), false);
+        assert_eq!(test_target_dir.join("FibonacciElement.asm").exists(), false);
+
+        // 32KワードのROMに収まらないプログラムは出力しない
+        let program = VMProgram::new(
+            "Main".to_string(),
+            format!("function Main.main 0\n{}return\n", "push constant 0\npop temp 0\n".repeat(2300)),
+        )
+        .unwrap();
+        let large_file_path = test_target_dir.join("Large.asm");
+        let error = write_hack(vec![program], None, &large_file_path, false, false, None).unwrap_err();
+        assert!(error
+            .to_string()
+            .ends_with("words of ROM, but the Hack computer has only 32768"));
+        assert_eq!(test_target_dir.join("Large.hack").exists(), false);
+
+        // 書き込みに失敗したらエラーを返す
+        let missing_dir_path = test_target_dir.join("missing").join("FibonacciElement.asm");
+        assert!(write_hack(vm_files(), Some(Bootstrap::default()), &missing_dir_path, false, false, None).is_err());
+
+        let _ = std::fs::remove_dir_all(&test_target_dir);
+    }
 }
@@ -0,0 +1,842 @@
+use crate::emulator::{RuntimeError, KBD, RAM_SIZE, SCREEN};
+
+// ヒープ領域(Memory.allocで割り当てる範囲)
+const HEAP_BASE: usize = 2048;
+const HEAP_END: usize = 16384;
+
+// スクリーンの大きさ(ピクセル)と1行あたりのワード数
+const SCREEN_WIDTH: i32 = 512;
+const SCREEN_HEIGHT: i32 = 256;
+const WORDS_PER_ROW: usize = 32;
+
+// Outputの文字の配置(23行64列、1文字あたり8x11ピクセル)
+const OUTPUT_ROWS: usize = 23;
+const OUTPUT_COLS: usize = 64;
+const CHAR_HEIGHT: usize = 11;
+
+// Stringで使う特殊文字
+const NEW_LINE: i16 = 128;
+const BACK_SPACE: i16 = 129;
+const DOUBLE_QUOTE: i16 = 34;
+
+/// ネイティブ実装しているOSの関数
+const FUNCTIONS: [&str; 49] = [
+    "Math.init",
+    "Math.abs",
+    "Math.multiply",
+    "Math.divide",
+    "Math.min",
+    "Math.max",
+    "Math.sqrt",
+    "Memory.init",
+    "Memory.peek",
+    "Memory.poke",
+    "Memory.alloc",
+    "Memory.deAlloc",
+    "Array.new",
+    "Array.dispose",
+    "String.new",
+    "String.dispose",
+    "String.length",
+    "String.charAt",
+    "String.setCharAt",
+    "String.appendChar",
+    "String.eraseLastChar",
+    "String.intValue",
+    "String.setInt",
+    "String.newLine",
+    "String.backSpace",
+    "String.doubleQuote",
+    "Output.init",
+    "Output.moveCursor",
+    "Output.printChar",
+    "Output.printString",
+    "Output.printInt",
+    "Output.println",
+    "Output.backSpace",
+    "Screen.init",
+    "Screen.clearScreen",
+    "Screen.setColor",
+    "Screen.drawPixel",
+    "Screen.drawLine",
+    "Screen.drawRectangle",
+    "Screen.drawCircle",
+    "Keyboard.init",
+    "Keyboard.keyPressed",
+    "Keyboard.readChar",
+    "Keyboard.readLine",
+    "Keyboard.readInt",
+    "Sys.init",
+    "Sys.halt",
+    "Sys.error",
+    "Sys.wait",
+];
+
+/// ネイティブ実装が他のOS関数の内部表現に依存している関数
+/// キーの関数が.vmファイルで定義されている場合、ネイティブ実装のヒープや文字列とは互換性がないので値の関数もネイティブ実装を使わない
+const DEPENDENCIES: [(&str, &[&str]); 2] = [
+    (
+        "Memory.alloc",
+        &[
+            "Memory.init",
+            "Memory.deAlloc",
+            "Array.new",
+            "Array.dispose",
+            "String.new",
+            "String.dispose",
+            "Keyboard.readLine",
+        ],
+    ),
+    (
+        "String.new",
+        &[
+            "String.dispose",
+            "String.length",
+            "String.charAt",
+            "String.setCharAt",
+            "String.appendChar",
+            "String.eraseLastChar",
+            "String.intValue",
+            "String.setInt",
+            "Output.printString",
+            "Keyboard.readLine",
+            "Keyboard.readInt",
+        ],
+    ),
+];
+
+/// Sys.initが呼び出す各クラスの初期化関数(本家のSys.initと同じ順序)
+pub const INIT_FUNCTIONS: [&str; 5] = [
+    "Memory.init",
+    "Math.init",
+    "Screen.init",
+    "Output.init",
+    "Keyboard.init",
+];
+
+/// ネイティブ実装したOSの関数を呼び出した結果
+#[derive(PartialEq, Eq, Debug)]
+pub enum NativeResult {
+    // 戻り値をスタックにpushして呼び出し元に戻る(voidの関数は0を返す)
+    Return(i16),
+    // 指定した関数を呼び出し、その戻り値をそのまま呼び出し元に返す(Sys.initからMain.mainを呼び出す場合)
+    TailCall(String),
+    // プログラムを停止する(Sys.halt)
+    Halt,
+}
+
+/// Java製のVMエミュレータの組み込みOSに相当する、Rustで実装したJack OS
+/// エラー時は本家のOSと同じエラーコードで`RuntimeError::SysError`を返す。
+pub struct NativeOs {
+    // 空きブロックの一覧 key: ベースアドレス, value: サイズ
+    free_blocks: std::collections::BTreeMap<usize, usize>,
+    // 割り当て済みブロックの一覧 key: ベースアドレス, value: サイズ
+    allocated_blocks: std::collections::HashMap<usize, usize>,
+    cursor_row: usize,
+    cursor_col: usize,
+    // trueなら黒で描画する
+    color: bool,
+    // Keyboard.readChar等で読み出すキー入力
+    keyboard_input: std::collections::VecDeque<i16>,
+    // Outputで出力された文字列(スクリーンのビットマップを読まずに出力を確認するため)
+    printed_text: String,
+}
+
+impl NativeOs {
+    pub fn new() -> Self {
+        Self {
+            free_blocks: std::collections::BTreeMap::from([(HEAP_BASE, HEAP_END - HEAP_BASE)]),
+            allocated_blocks: std::collections::HashMap::new(),
+            cursor_row: 0,
+            cursor_col: 0,
+            color: true,
+            keyboard_input: std::collections::VecDeque::new(),
+            printed_text: String::new(),
+        }
+    }
+
+    pub fn implements(function_name: &str) -> bool {
+        FUNCTIONS.contains(&function_name)
+    }
+
+    // ネイティブ実装で実行する関数ならtrueを返す(is_definedは.vmファイルに定義されている関数ならtrueを返す)
+    // .vmファイルで定義された関数はネイティブ実装より優先する。
+    // これにより、12のOSの一部のクラスや関数だけを自作の実装に差し替えて実行できる
+    pub fn is_used_for(function_name: &str, is_defined: impl Fn(&str) -> bool) -> bool {
+        Self::implements(function_name)
+            && !is_defined(function_name)
+            && !DEPENDENCIES
+                .iter()
+                .any(|(dependency, dependents)| dependents.contains(&function_name) && is_defined(dependency))
+    }
+
+    pub fn printed_text(&self) -> &str {
+        &self.printed_text
+    }
+
+    // Keyboard.readChar等で読み出されるキー入力を追加する
+    pub fn type_keys(&mut self, keys: &[i16]) {
+        self.keyboard_input.extend(keys);
+    }
+
+    pub fn call(&mut self, function_name: &str, args: &[i16], ram: &mut [i16]) -> Result<NativeResult, RuntimeError> {
+        let arg = |i: usize| args.get(i).copied().unwrap_or(0);
+        let value = match function_name {
+            "Math.init" => 0,
+            "Math.abs" => arg(0).wrapping_abs(),
+            "Math.multiply" => arg(0).wrapping_mul(arg(1)),
+            "Math.divide" => {
+                if arg(1) == 0 {
+                    return Err(RuntimeError::SysError(3));
+                }
+                arg(0).wrapping_div(arg(1))
+            }
+            "Math.min" => arg(0).min(arg(1)),
+            "Math.max" => arg(0).max(arg(1)),
+            "Math.sqrt" => {
+                if arg(0) < 0 {
+                    return Err(RuntimeError::SysError(4));
+                }
+                (arg(0) as f64).sqrt() as i16
+            }
+
+            "Memory.init" => {
+                *self = Self {
+                    keyboard_input: std::mem::take(&mut self.keyboard_input),
+                    ..Self::new()
+                };
+                0
+            }
+            "Memory.peek" => ram[address(arg(0) as i32)?],
+            "Memory.poke" => {
+                ram[address(arg(0) as i32)?] = arg(1);
+                0
+            }
+            "Memory.alloc" => {
+                if arg(0) <= 0 {
+                    return Err(RuntimeError::SysError(5));
+                }
+                self.alloc(arg(0) as usize)?
+            }
+            "Memory.deAlloc" | "Array.dispose" | "String.dispose" => {
+                self.de_alloc(arg(0));
+                0
+            }
+
+            "Array.new" => {
+                if arg(0) <= 0 {
+                    return Err(RuntimeError::SysError(2));
+                }
+                self.alloc(arg(0) as usize)?
+            }
+
+            "String.new" => {
+                if arg(0) < 0 {
+                    return Err(RuntimeError::SysError(14));
+                }
+                self.new_string(arg(0) as usize, ram)?
+            }
+            // Stringのフィールドは[最大長, 長さ, 文字...]の順に並んでいる。
+            // 壊れたポインタでもホストが落ちないように、thisから求めたアドレスは全てaddressで範囲を確認する
+            "String.length" => ram[address(arg(0) as i32 + 1)?],
+            "String.charAt" => {
+                let (this, j) = (arg(0) as i32, arg(1));
+                if j < 0 || ram[address(this + 1)?] <= j {
+                    return Err(RuntimeError::SysError(15));
+                }
+                ram[address(this + 2 + j as i32)?]
+            }
+            "String.setCharAt" => {
+                let (this, j) = (arg(0) as i32, arg(1));
+                if j < 0 || ram[address(this + 1)?] <= j {
+                    return Err(RuntimeError::SysError(16));
+                }
+                ram[address(this + 2 + j as i32)?] = arg(2);
+                0
+            }
+            "String.appendChar" => {
+                let this = arg(0) as i32;
+                let (max_length, length) = (ram[address(this)?], ram[address(this + 1)?]);
+                if max_length <= length {
+                    return Err(RuntimeError::SysError(17));
+                }
+                ram[address(this + 2 + length as i32)?] = arg(1);
+                ram[address(this + 1)?] = length + 1;
+                arg(0)
+            }
+            "String.eraseLastChar" => {
+                let length = address(arg(0) as i32 + 1)?;
+                if ram[length] == 0 {
+                    return Err(RuntimeError::SysError(18));
+                }
+                ram[length] -= 1;
+                0
+            }
+            "String.intValue" => int_value(&read_string(arg(0), ram)?),
+            "String.setInt" => {
+                let this = arg(0) as i32;
+                let chars = arg(1).to_string().chars().map(|c| c as i16).collect::<Vec<_>>();
+                if (ram[address(this)?] as usize) < chars.len() {
+                    return Err(RuntimeError::SysError(19));
+                }
+                ram[address(this + 1)?] = chars.len() as i16;
+                for (i, c) in chars.into_iter().enumerate() {
+                    ram[address(this + 2 + i as i32)?] = c;
+                }
+                0
+            }
+            "String.newLine" => NEW_LINE,
+            "String.backSpace" => BACK_SPACE,
+            "String.doubleQuote" => DOUBLE_QUOTE,
+
+            "Output.init" => {
+                self.cursor_row = 0;
+                self.cursor_col = 0;
+                0
+            }
+            "Output.moveCursor" => {
+                let (i, j) = (arg(0), arg(1));
+                if !(0..OUTPUT_ROWS as i16).contains(&i) || !(0..OUTPUT_COLS as i16).contains(&j) {
+                    return Err(RuntimeError::SysError(20));
+                }
+                self.cursor_row = i as usize;
+                self.cursor_col = j as usize;
+                self.draw_char(' ' as i16, ram);
+                0
+            }
+            "Output.printChar" => {
+                self.print_char(arg(0), ram);
+                0
+            }
+            "Output.printString" => {
+                for c in read_string(arg(0), ram)? {
+                    self.print_char(c, ram);
+                }
+                0
+            }
+            "Output.printInt" => {
+                for c in arg(0).to_string().chars() {
+                    self.print_char(c as i16, ram);
+                }
+                0
+            }
+            "Output.println" => {
+                self.print_char(NEW_LINE, ram);
+                0
+            }
+            "Output.backSpace" => {
+                self.print_char(BACK_SPACE, ram);
+                0
+            }
+
+            "Screen.init" => {
+                self.color = true;
+                0
+            }
+            "Screen.clearScreen" => {
+                ram[SCREEN..KBD].fill(0);
+                0
+            }
+            "Screen.setColor" => {
+                self.color = arg(0) != 0;
+                0
+            }
+            "Screen.drawPixel" => {
+                let (x, y) = (arg(0) as i32, arg(1) as i32);
+                if !on_screen(x, y) {
+                    return Err(RuntimeError::SysError(7));
+                }
+                self.draw_pixel(x, y, ram);
+                0
+            }
+            "Screen.drawLine" => {
+                let (x1, y1, x2, y2) = (arg(0) as i32, arg(1) as i32, arg(2) as i32, arg(3) as i32);
+                if !on_screen(x1, y1) || !on_screen(x2, y2) {
+                    return Err(RuntimeError::SysError(8));
+                }
+                self.draw_line(x1, y1, x2, y2, ram);
+                0
+            }
+            "Screen.drawRectangle" => {
+                let (x1, y1, x2, y2) = (arg(0) as i32, arg(1) as i32, arg(2) as i32, arg(3) as i32);
+                if x1 > x2 || y1 > y2 || !on_screen(x1, y1) || !on_screen(x2, y2) {
+                    return Err(RuntimeError::SysError(9));
+                }
+                for y in y1..=y2 {
+                    self.draw_line(x1, y, x2, y, ram);
+                }
+                0
+            }
+            "Screen.drawCircle" => {
+                let (x, y, r) = (arg(0) as i32, arg(1) as i32, arg(2) as i32);
+                if !on_screen(x, y) {
+                    return Err(RuntimeError::SysError(12));
+                }
+                if !(0..=181).contains(&r) {
+                    return Err(RuntimeError::SysError(13));
+                }
+                for dy in -r..=r {
+                    let dx = ((r * r - dy * dy) as f64).sqrt() as i32;
+                    // 画面外にはみ出す部分は描画しない
+                    for px in (x - dx).max(0)..=(x + dx).min(SCREEN_WIDTH - 1) {
+                        if on_screen(px, y + dy) {
+                            self.draw_pixel(px, y + dy, ram);
+                        }
+                    }
+                }
+                0
+            }
+
+            "Keyboard.init" => 0,
+            "Keyboard.keyPressed" => ram[KBD],
+            "Keyboard.readChar" => {
+                let c = self.read_key()?;
+                self.print_char(c, ram);
+                c
+            }
+            "Keyboard.readLine" => {
+                let line = self.read_line(arg(0), ram)?;
+                self.new_string_from(&line, ram)?
+            }
+            "Keyboard.readInt" => int_value(&self.read_line(arg(0), ram)?),
+
+            "Sys.init" => {
+                self.call("Memory.init", &[], ram)?;
+                return Ok(NativeResult::TailCall("Main.main".to_string()));
+            }
+            "Sys.halt" => return Ok(NativeResult::Halt),
+            "Sys.error" => {
+                self.printed_text += &format!("ERR{}", arg(0));
+                return Err(RuntimeError::SysError(arg(0)));
+            }
+            "Sys.wait" => {
+                if arg(0) < 0 {
+                    return Err(RuntimeError::SysError(1));
+                }
+                0
+            }
+            _ => unreachable!("{} is not implemented in the native OS", function_name),
+        };
+
+        Ok(NativeResult::Return(value))
+    }
+
+    // first-fitでヒープからsizeワード割り当てる
+    fn alloc(&mut self, size: usize) -> Result<i16, RuntimeError> {
+        let (base, block_size) = self
+            .free_blocks
+            .iter()
+            .find(|(_, block_size)| size <= **block_size)
+            .map(|(base, block_size)| (*base, *block_size))
+            .ok_or(RuntimeError::SysError(6))?;
+        self.free_blocks.remove(&base);
+        if size < block_size {
+            self.free_blocks.insert(base + size, block_size - size);
+        }
+        self.allocated_blocks.insert(base, size);
+        Ok(base as i16)
+    }
+
+    // 割り当て済みのブロックを解放し、隣接する空きブロックと結合する
+    fn de_alloc(&mut self, base: i16) {
+        let Some(mut size) = self.allocated_blocks.remove(&(base as usize)) else {
+            return;
+        };
+        let mut base = base as usize;
+        if let Some(next_size) = self.free_blocks.remove(&(base + size)) {
+            size += next_size;
+        }
+        if let Some((&prev_base, &prev_size)) = self.free_blocks.range(..base).next_back() {
+            if prev_base + prev_size == base {
+                self.free_blocks.remove(&prev_base);
+                base = prev_base;
+                size += prev_size;
+            }
+        }
+        self.free_blocks.insert(base, size);
+    }
+
+    // Stringは[最大長, 長さ, 文字...]というレイアウトでヒープに割り当てる
+    fn new_string(&mut self, max_length: usize, ram: &mut [i16]) -> Result<i16, RuntimeError> {
+        let this = self.alloc(max_length + 2)?;
+        ram[this as usize] = max_length as i16;
+        ram[this as usize + 1] = 0;
+        Ok(this)
+    }
+
+    fn new_string_from(&mut self, chars: &[i16], ram: &mut [i16]) -> Result<i16, RuntimeError> {
+        let this = self.new_string(chars.len(), ram)?;
+        ram[this as usize + 1] = chars.len() as i16;
+        ram[this as usize + 2..this as usize + 2 + chars.len()].copy_from_slice(chars);
+        Ok(this)
+    }
+
+    fn read_key(&mut self) -> Result<i16, RuntimeError> {
+        self.keyboard_input
+            .pop_front()
+            .ok_or(RuntimeError::KeyboardInputExhausted)
+    }
+
+    // messageを表示してから改行までの入力を読み出す(バックスペースで直前の文字を消す)
+    fn read_line(&mut self, message: i16, ram: &mut [i16]) -> Result<Vec<i16>, RuntimeError> {
+        for c in read_string(message, ram)? {
+            self.print_char(c, ram);
+        }
+        let mut line = vec![];
+        loop {
+            match self.read_key()? {
+                NEW_LINE => {
+                    self.print_char(NEW_LINE, ram);
+                    return Ok(line);
+                }
+                BACK_SPACE => {
+                    if line.pop().is_some() {
+                        self.print_char(BACK_SPACE, ram);
+                    }
+                }
+                c => {
+                    line.push(c);
+                    self.print_char(c, ram);
+                }
+            }
+        }
+    }
+
+    fn print_char(&mut self, c: i16, ram: &mut [i16]) {
+        match c {
+            NEW_LINE => {
+                self.printed_text.push('\n');
+                self.cursor_col = 0;
+                self.cursor_row = (self.cursor_row + 1) % OUTPUT_ROWS;
+            }
+            BACK_SPACE => {
+                self.printed_text.pop();
+                if 0 < self.cursor_col {
+                    self.cursor_col -= 1;
+                } else if 0 < self.cursor_row {
+                    self.cursor_row -= 1;
+                    self.cursor_col = OUTPUT_COLS - 1;
+                }
+                self.draw_char(' ' as i16, ram);
+            }
+            _ => {
+                self.printed_text.push(char::from_u32(c as u32).unwrap_or('?'));
+                self.draw_char(c, ram);
+                self.cursor_col += 1;
+                if self.cursor_col == OUTPUT_COLS {
+                    self.cursor_col = 0;
+                    self.cursor_row = (self.cursor_row + 1) % OUTPUT_ROWS;
+                }
+            }
+        }
+    }
+
+    // カーソル位置に文字を描画する。1ワードに2文字分(下位8bitが偶数列、上位8bitが奇数列)が入る
+    fn draw_char(&self, c: i16, ram: &mut [i16]) {
+        let char_map = char_map(c);
+        for (i, row) in char_map.iter().enumerate() {
+            let address = SCREEN + (self.cursor_row * CHAR_HEIGHT + i) * WORDS_PER_ROW + self.cursor_col / 2;
+            let word = ram[address] as u16;
+            ram[address] = if self.cursor_col.is_multiple_of(2) {
+                (word & 0xFF00) | *row as u16
+            } else {
+                (word & 0x00FF) | ((*row as u16) << 8)
+            } as i16;
+        }
+    }
+
+    fn draw_pixel(&self, x: i32, y: i32, ram: &mut [i16]) {
+        let address = SCREEN + y as usize * WORDS_PER_ROW + x as usize / 16;
+        let mask = (1_u16 << (x % 16)) as i16;
+        if self.color {
+            ram[address] |= mask;
+        } else {
+            ram[address] &= !mask;
+        }
+    }
+
+    // Bresenhamのアルゴリズムで(x1, y1)から(x2, y2)まで描画する
+    fn draw_line(&self, x1: i32, y1: i32, x2: i32, y2: i32, ram: &mut [i16]) {
+        let (dx, dy) = ((x2 - x1).abs(), -(y2 - y1).abs());
+        let (sx, sy) = ((x2 - x1).signum(), (y2 - y1).signum());
+        let (mut x, mut y, mut err) = (x1, y1, dx + dy);
+        loop {
+            self.draw_pixel(x, y, ram);
+            if x == x2 && y == y2 {
+                return;
+            }
+            if dy <= 2 * err {
+                err += dy;
+                x += sx;
+            }
+            if 2 * err <= dx {
+                err += dx;
+                y += sy;
+            }
+        }
+    }
+}
+
+impl Default for NativeOs {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn address(value: i32) -> Result<usize, RuntimeError> {
+    if (0..RAM_SIZE as i32).contains(&value) {
+        Ok(value as usize)
+    } else {
+        Err(RuntimeError::AddressOutOfRange(value))
+    }
+}
+
+fn on_screen(x: i32, y: i32) -> bool {
+    (0..SCREEN_WIDTH).contains(&x) && (0..SCREEN_HEIGHT).contains(&y)
+}
+
+// Stringオブジェクトの文字列を読み出す
+fn read_string(this: i16, ram: &[i16]) -> Result<Vec<i16>, RuntimeError> {
+    let this = this as i32;
+    let length = ram[address(this + 1)?].max(0) as i32;
+    (0..length).map(|i| Ok(ram[address(this + 2 + i)?])).collect()
+}
+
+// 先頭の`-`と数字の並びを整数として読む(数字以外が現れたらそこで終了する)
+fn int_value(chars: &[i16]) -> i16 {
+    let (sign, digits) = match chars.first() {
+        Some(c) if *c == '-' as i16 => (-1, &chars[1..]),
+        _ => (1, chars),
+    };
+    let mut value: i16 = 0;
+    for c in digits.iter().take_while(|c| ('0' as i16..='9' as i16).contains(c)) {
+        value = value.wrapping_mul(10).wrapping_add(c - '0' as i16);
+    }
+    value.wrapping_mul(sign)
+}
+
+// 文字のビットマップを返す。表示できない文字は黒い四角で表示する
+fn char_map(c: i16) -> [i16; CHAR_HEIGHT] {
+    if (32..=126).contains(&c) {
+        CHAR_MAPS[c as usize - 32]
+    } else {
+        [63, 63, 63, 63, 63, 63, 63, 63, 63, 0, 0]
+    }
+}
+
+// 12/Output.jackのinitMapと同じフォント(文字コード32~126)
+const CHAR_MAPS: [[i16; CHAR_HEIGHT]; 95] = [
+    [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0],           // (space)
+    [12, 30, 30, 30, 12, 12, 0, 12, 12, 0, 0],   // !
+    [54, 54, 20, 0, 0, 0, 0, 0, 0, 0, 0],        // "
+    [0, 18, 18, 63, 18, 18, 63, 18, 18, 0, 0],   // #
+    [12, 30, 51, 3, 30, 48, 51, 30, 12, 12, 0],  // $
+    [0, 0, 35, 51, 24, 12, 6, 51, 49, 0, 0],     // %
+    [12, 30, 30, 12, 54, 27, 27, 27, 54, 0, 0],  // &
+    [12, 12, 6, 0, 0, 0, 0, 0, 0, 0, 0],         // '
+    [24, 12, 6, 6, 6, 6, 6, 12, 24, 0, 0],       // (
+    [6, 12, 24, 24, 24, 24, 24, 12, 6, 0, 0],    // )
+    [0, 0, 0, 51, 30, 63, 30, 51, 0, 0, 0],      // *
+    [0, 0, 0, 12, 12, 63, 12, 12, 0, 0, 0],      // +
+    [0, 0, 0, 0, 0, 0, 0, 12, 12, 6, 0],         // ,
+    [0, 0, 0, 0, 0, 63, 0, 0, 0, 0, 0],          // -
+    [0, 0, 0, 0, 0, 0, 0, 12, 12, 0, 0],         // .
+    [0, 0, 32, 48, 24, 12, 6, 3, 1, 0, 0],       // /
+    [12, 30, 51, 51, 51, 51, 51, 30, 12, 0, 0],  // 0
+    [12, 14, 15, 12, 12, 12, 12, 12, 63, 0, 0],  // 1
+    [30, 51, 48, 24, 12, 6, 3, 51, 63, 0, 0],    // 2
+    [30, 51, 48, 48, 28, 48, 48, 51, 30, 0, 0],  // 3
+    [16, 24, 28, 26, 25, 63, 24, 24, 60, 0, 0],  // 4
+    [63, 3, 3, 31, 48, 48, 48, 51, 30, 0, 0],    // 5
+    [28, 6, 3, 3, 31, 51, 51, 51, 30, 0, 0],     // 6
+    [63, 49, 48, 48, 24, 12, 12, 12, 12, 0, 0],  // 7
+    [30, 51, 51, 51, 30, 51, 51, 51, 30, 0, 0],  // 8
+    [30, 51, 51, 51, 62, 48, 48, 24, 14, 0, 0],  // 9
+    [0, 0, 12, 12, 0, 0, 12, 12, 0, 0, 0],       // :
+    [0, 0, 12, 12, 0, 0, 12, 12, 6, 0, 0],       // ;
+    [0, 0, 24, 12, 6, 3, 6, 12, 24, 0, 0],       // <
+    [0, 0, 0, 63, 0, 0, 63, 0, 0, 0, 0],         // =
+    [0, 0, 3, 6, 12, 24, 12, 6, 3, 0, 0],        // >
+    [30, 51, 51, 24, 12, 12, 0, 12, 12, 0, 0],   // ?
+    [30, 51, 51, 59, 59, 59, 27, 3, 30, 0, 0],   // @
+    [12, 30, 51, 51, 63, 51, 51, 51, 51, 0, 0],  // A
+    [31, 51, 51, 51, 31, 51, 51, 51, 31, 0, 0],  // B
+    [28, 54, 35, 3, 3, 3, 35, 54, 28, 0, 0],     // C
+    [15, 27, 51, 51, 51, 51, 51, 27, 15, 0, 0],  // D
+    [63, 51, 35, 11, 15, 11, 35, 51, 63, 0, 0],  // E
+    [63, 51, 35, 11, 15, 11, 3, 3, 3, 0, 0],     // F
+    [28, 54, 35, 3, 59, 51, 51, 54, 44, 0, 0],   // G
+    [51, 51, 51, 51, 63, 51, 51, 51, 51, 0, 0],  // H
+    [30, 12, 12, 12, 12, 12, 12, 12, 30, 0, 0],  // I
+    [60, 24, 24, 24, 24, 24, 27, 27, 14, 0, 0],  // J
+    [51, 51, 51, 27, 15, 27, 51, 51, 51, 0, 0],  // K
+    [3, 3, 3, 3, 3, 3, 35, 51, 63, 0, 0],        // L
+    [33, 51, 63, 63, 51, 51, 51, 51, 51, 0, 0],  // M
+    [51, 51, 55, 55, 63, 59, 59, 51, 51, 0, 0],  // N
+    [30, 51, 51, 51, 51, 51, 51, 51, 30, 0, 0],  // O
+    [31, 51, 51, 51, 31, 3, 3, 3, 3, 0, 0],      // P
+    [30, 51, 51, 51, 51, 51, 63, 59, 30, 48, 0], // Q
+    [31, 51, 51, 51, 31, 27, 51, 51, 51, 0, 0],  // R
+    [30, 51, 51, 6, 28, 48, 51, 51, 30, 0, 0],   // S
+    [63, 63, 45, 12, 12, 12, 12, 12, 30, 0, 0],  // T
+    [51, 51, 51, 51, 51, 51, 51, 51, 30, 0, 0],  // U
+    [51, 51, 51, 51, 51, 30, 30, 12, 12, 0, 0],  // V
+    [51, 51, 51, 51, 51, 63, 63, 63, 18, 0, 0],  // W
+    [51, 51, 30, 30, 12, 30, 30, 51, 51, 0, 0],  // X
+    [51, 51, 51, 51, 30, 12, 12, 12, 30, 0, 0],  // Y
+    [63, 51, 49, 24, 12, 6, 35, 51, 63, 0, 0],   // Z
+    [30, 6, 6, 6, 6, 6, 6, 6, 30, 0, 0],         // [
+    [0, 0, 1, 3, 6, 12, 24, 48, 32, 0, 0],       // \
+    [30, 24, 24, 24, 24, 24, 24, 24, 30, 0, 0],  // ]
+    [8, 28, 54, 0, 0, 0, 0, 0, 0, 0, 0],         // ^
+    [0, 0, 0, 0, 0, 0, 0, 0, 0, 63, 0],          // _
+    [6, 12, 24, 0, 0, 0, 0, 0, 0, 0, 0],         // `
+    [0, 0, 0, 14, 24, 30, 27, 27, 54, 0, 0],     // a
+    [3, 3, 3, 15, 27, 51, 51, 51, 30, 0, 0],     // b
+    [0, 0, 0, 30, 51, 3, 3, 51, 30, 0, 0],       // c
+    [48, 48, 48, 60, 54, 51, 51, 51, 30, 0, 0],  // d
+    [0, 0, 0, 30, 51, 63, 3, 51, 30, 0, 0],      // e
+    [28, 54, 38, 6, 15, 6, 6, 6, 15, 0, 0],      // f
+    [0, 0, 30, 51, 51, 51, 62, 48, 51, 30, 0],   // g
+    [3, 3, 3, 27, 55, 51, 51, 51, 51, 0, 0],     // h
+    [12, 12, 0, 14, 12, 12, 12, 12, 30, 0, 0],   // i
+    [48, 48, 0, 56, 48, 48, 48, 48, 51, 30, 0],  // j
+    [3, 3, 3, 51, 27, 15, 15, 27, 51, 0, 0],     // k
+    [14, 12, 12, 12, 12, 12, 12, 12, 30, 0, 0],  // l
+    [0, 0, 0, 29, 63, 43, 43, 43, 43, 0, 0],     // m
+    [0, 0, 0, 29, 51, 51, 51, 51, 51, 0, 0],     // n
+    [0, 0, 0, 30, 51, 51, 51, 51, 30, 0, 0],     // o
+    [0, 0, 0, 30, 51, 51, 51, 31, 3, 3, 0],      // p
+    [0, 0, 0, 30, 51, 51, 51, 62, 48, 48, 0],    // q
+    [0, 0, 0, 29, 55, 51, 3, 3, 7, 0, 0],        // r
+    [0, 0, 0, 30, 51, 6, 24, 51, 30, 0, 0],      // s
+    [4, 6, 6, 15, 6, 6, 6, 54, 28, 0, 0],        // t
+    [0, 0, 0, 27, 27, 27, 27, 27, 54, 0, 0],     // u
+    [0, 0, 0, 51, 51, 51, 51, 30, 12, 0, 0],     // v
+    [0, 0, 0, 51, 51, 51, 63, 63, 18, 0, 0],     // w
+    [0, 0, 0, 51, 30, 12, 12, 30, 51, 0, 0],     // x
+    [0, 0, 0, 51, 51, 51, 62, 48, 24, 15, 0],    // y
+    [0, 0, 0, 63, 27, 12, 6, 51, 63, 0, 0],      // z
+    [56, 12, 12, 12, 7, 12, 12, 12, 56, 0, 0],   // {
+    [12, 12, 12, 12, 12, 12, 12, 12, 12, 0, 0],  // |
+    [7, 12, 12, 12, 56, 12, 12, 12, 7, 0, 0],    // }
+    [38, 45, 25, 0, 0, 0, 0, 0, 0, 0, 0],        // ~
+];
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn call(os: &mut NativeOs, function_name: &str, args: &[i16], ram: &mut [i16]) -> i16 {
+        match os.call(function_name, args, ram) {
+            Ok(NativeResult::Return(value)) => value,
+            result => panic!("{} returned {:?}", function_name, result),
+        }
+    }
+
+    #[test]
+    fn test_math() {
+        let mut os = NativeOs::new();
+        let mut ram = vec![0; 32768];
+        assert_eq!(call(&mut os, "Math.multiply", &[-7, 6], &mut ram), -42);
+        assert_eq!(call(&mut os, "Math.divide", &[-7, 2], &mut ram), -3);
+        assert_eq!(call(&mut os, "Math.sqrt", &[99], &mut ram), 9);
+        assert_eq!(call(&mut os, "Math.abs", &[-5], &mut ram), 5);
+        assert_eq!(call(&mut os, "Math.min", &[3, -5], &mut ram), -5);
+        assert_eq!(call(&mut os, "Math.max", &[3, -5], &mut ram), 3);
+        assert_eq!(os.call("Math.divide", &[1, 0], &mut ram), Err(RuntimeError::SysError(3)));
+        assert_eq!(os.call("Math.sqrt", &[-1], &mut ram), Err(RuntimeError::SysError(4)));
+    }
+
+    #[test]
+    fn test_memory() {
+        let mut os = NativeOs::new();
+        let mut ram = vec![0; 32768];
+        let a = call(&mut os, "Memory.alloc", &[10], &mut ram);
+        let b = call(&mut os, "Memory.alloc", &[5], &mut ram);
+        assert_eq!((a, b), (2048, 2058));
+        // 解放した領域は再利用される
+        call(&mut os, "Memory.deAlloc", &[a], &mut ram);
+        assert_eq!(call(&mut os, "Memory.alloc", &[3], &mut ram), 2048);
+        // 隣接する空き領域は結合される
+        call(&mut os, "Memory.deAlloc", &[2048], &mut ram);
+        call(&mut os, "Memory.deAlloc", &[b], &mut ram);
+        assert_eq!(os.free_blocks, std::collections::BTreeMap::from([(HEAP_BASE, HEAP_END - HEAP_BASE)]));
+        assert_eq!(os.call("Memory.alloc", &[0], &mut ram), Err(RuntimeError::SysError(5)));
+        assert_eq!(os.call("Memory.alloc", &[20000], &mut ram), Err(RuntimeError::SysError(6)));
+
+        call(&mut os, "Memory.poke", &[8000, 17], &mut ram);
+        assert_eq!(call(&mut os, "Memory.peek", &[8000], &mut ram), 17);
+    }
+
+    #[test]
+    fn test_string() {
+        let mut os = NativeOs::new();
+        let mut ram = vec![0; 32768];
+        let s = call(&mut os, "String.new", &[6], &mut ram);
+        for c in "-123".chars() {
+            assert_eq!(call(&mut os, "String.appendChar", &[s, c as i16], &mut ram), s);
+        }
+        assert_eq!(call(&mut os, "String.length", &[s], &mut ram), 4);
+        assert_eq!(call(&mut os, "String.charAt", &[s, 1], &mut ram), '1' as i16);
+        assert_eq!(call(&mut os, "String.intValue", &[s], &mut ram), -123);
+        call(&mut os, "String.eraseLastChar", &[s], &mut ram);
+        assert_eq!(call(&mut os, "String.intValue", &[s], &mut ram), -12);
+        call(&mut os, "String.setInt", &[s, 4567], &mut ram);
+        assert_eq!(read_string(s, &ram), Ok(vec![52, 53, 54, 55]));
+        assert_eq!(os.call("String.charAt", &[s, 4], &mut ram), Err(RuntimeError::SysError(15)));
+        let short = call(&mut os, "String.new", &[2], &mut ram);
+        assert_eq!(os.call("String.setInt", &[short, 123], &mut ram), Err(RuntimeError::SysError(19)));
+
+        // RAMの末尾を指す壊れたポインタでもホストは落ちずにエラーを返す
+        ram[32767] = 5;
+        let broken = 32767;
+        assert_eq!(os.call("String.length", &[broken], &mut ram), Err(RuntimeError::AddressOutOfRange(32768)));
+        assert_eq!(os.call("String.appendChar", &[broken, 65], &mut ram), Err(RuntimeError::AddressOutOfRange(32768)));
+        assert_eq!(os.call("String.eraseLastChar", &[broken], &mut ram), Err(RuntimeError::AddressOutOfRange(32768)));
+        ram[32766] = 3;
+        ram[32767] = 3;
+        assert_eq!(os.call("String.charAt", &[32766, 2], &mut ram), Err(RuntimeError::AddressOutOfRange(32770)));
+        assert_eq!(os.call("String.setCharAt", &[32766, 0, 65], &mut ram), Err(RuntimeError::AddressOutOfRange(32768)));
+        assert_eq!(read_string(32766, &ram), Err(RuntimeError::AddressOutOfRange(32768)));
+    }
+
+    #[test]
+    fn test_output_and_keyboard() {
+        let mut os = NativeOs::new();
+        let mut ram = vec![0; 32768];
+        call(&mut os, "Output.printInt", &[-42], &mut ram);
+        call(&mut os, "Output.println", &[], &mut ram);
+        call(&mut os, "Output.printChar", &['A' as i16], &mut ram);
+        assert_eq!(os.printed_text(), "-42\nA");
+        // 2行目の先頭(偶数列なので下位8bit)に`A`が描画されている
+        assert_eq!(
+            (0..CHAR_HEIGHT)
+                .map(|i| ram[SCREEN + (CHAR_HEIGHT + i) * WORDS_PER_ROW])
+                .collect::<Vec<_>>(),
+            char_map('A' as i16).to_vec()
+        );
+
+        let message = os.new_string_from(&[63], &mut ram).unwrap();
+        os.type_keys(&['1' as i16, '2' as i16, BACK_SPACE, '5' as i16, NEW_LINE]);
+        assert_eq!(call(&mut os, "Keyboard.readInt", &[message], &mut ram), 15);
+        assert_eq!(os.printed_text(), "-42\nA?15\n");
+        assert_eq!(os.call("Keyboard.readChar", &[], &mut ram), Err(RuntimeError::KeyboardInputExhausted));
+    }
+
+    #[test]
+    fn test_screen() {
+        let mut os = NativeOs::new();
+        let mut ram = vec![0; 32768];
+        call(&mut os, "Screen.drawLine", &[0, 0, 17, 0], &mut ram);
+        assert_eq!((ram[SCREEN], ram[SCREEN + 1]), (-1, 0b11));
+        call(&mut os, "Screen.setColor", &[0], &mut ram);
+        call(&mut os, "Screen.drawPixel", &[1, 0], &mut ram);
+        assert_eq!(ram[SCREEN], -3);
+        call(&mut os, "Screen.setColor", &[-1], &mut ram);
+        call(&mut os, "Screen.drawRectangle", &[16, 10, 31, 12], &mut ram);
+        assert_eq!((9..14).map(|y| ram[SCREEN + y * WORDS_PER_ROW + 1]).collect::<Vec<_>>(), vec![0, -1, -1, -1, 0]);
+        assert_eq!(os.call("Screen.drawPixel", &[512, 0], &mut ram), Err(RuntimeError::SysError(7)));
+    }
+}
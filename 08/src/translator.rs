@@ -1,11 +1,16 @@
+use crate::cache::FragmentCache;
+use assembler::hack::{to_text, Comp, Dest, Instruction, Jump, MAX_ADDRESS_VALUE};
+
 /// VMProgramは.vmファイルの内容を保持する構造体
 #[derive(PartialEq, Eq, Debug)]
 pub struct VMProgram {
-    commands: Vec<Command>,
+    pub(crate) commands: Vec<Command>,
+    // commandsと同じ順序で、各コマンドが書かれていた行
+    source_lines: Vec<SourceLine>,
     // 処理ごとにラベルを一意にしたいケースにsuffixとして利用する値
     label_id: u32,
     // staticセグメントを機械語に変換する際に必要。`Foo.vm`で`static i`への参照があったとき`Foo.i`というシンボルを生成する。
-    file_name: String,
+    pub(crate) file_name: String,
     // returnアドレスのsuffix
     // Xxx.vmの中のfoo関数の中で任意の関数を呼び出したとき、`Xxx.foo$ret.{return_address_id}`
     return_address_id: u32,
@@ -14,10 +19,11 @@ pub struct VMProgram {
 }
 
 impl VMProgram {
-    // .vmファイルをparseする
-    pub fn new(file_name: String, content: String) -> Self {
+    // .vmファイルをparseする。解釈できない行があればその行を示すエラーを返す
+    pub fn new(file_name: String, content: String) -> Result<Self, TranslateError> {
         let mut commands = vec![];
-        for line in content.lines() {
+        let mut source_lines = vec![];
+        for (i, line) in content.lines().enumerate() {
             let trimmed = line.trim();
             if trimmed.is_empty() || trimmed.starts_with("//") {
                 continue;
@@ -36,6 +42,8 @@ impl VMProgram {
                 "and" => Some(Command::Arithmetic(ArithmeticCommand::And)),
                 "or" => Some(Command::Arithmetic(ArithmeticCommand::Or)),
                 "not" => Some(Command::Arithmetic(ArithmeticCommand::Not)),
+                "push" if terms.get(1..) == Some(&["that", "*"]) => Some(Command::PushThatIndirect),
+                "pop" if terms.get(1..) == Some(&["that", "*"]) => Some(Command::PopThatIndirect),
                 "push" => terms.get(1).zip(terms.get(2)).and_then(|(first_arg, second_arg)| {
                     second_arg
                         .parse::<u32>()
@@ -66,49 +74,224 @@ impl VMProgram {
                         .map(|len| Command::Call(function_name.to_string(), len))
                 }),
                 "return" => Some(Command::Return),
-                _ => todo!("{} is invalid command", *terms.first().unwrap()),
+                // 拡張命令
+                "mul" => Some(Command::Arithmetic(ArithmeticCommand::Mul)),
+                "div" => Some(Command::Arithmetic(ArithmeticCommand::Div)),
+                "shl" => Some(Command::Arithmetic(ArithmeticCommand::Shl)),
+                "shr" => Some(Command::Arithmetic(ArithmeticCommand::Shr)),
+                "dup" => Some(Command::Dup),
+                "swap" => Some(Command::Swap),
+                "inc" | "dec" => terms.get(1).zip(terms.get(2)).and_then(|(first_arg, second_arg)| {
+                    second_arg
+                        .parse::<u32>()
+                        .ok()
+                        .and_then(|index| Segment::new(first_arg, index))
+                        // constantセグメントは書き換えられない
+                        .filter(|segment| !matches!(segment, Segment::Constant(_)))
+                        .map(|segment| match *terms.first().unwrap() {
+                            "inc" => Command::Inc(segment),
+                            _ => Command::Dec(segment),
+                        })
+                }),
+                _ => None,
             };
-            if let Some(command) = command {
-                commands.push(command);
-            }
+            // 読み飛ばすと以降のスタックの深さがずれるので、`push constant 40000`のような行もエラーにする
+            let Some(command) = command else {
+                return Err(TranslateError::InvalidCommand {
+                    file_name,
+                    line_number: i + 1,
+                    command: trimmed.to_string(),
+                });
+            };
+            commands.push(command);
+            source_lines.push(SourceLine {
+                line_number: i + 1,
+                text: trimmed.to_string(),
+            });
         }
 
-        Self {
+        Ok(Self {
             commands,
+            source_lines,
             label_id: 0,
             file_name,
             return_address_id: 0,
             current_function_name: String::new(),
-        }
+        })
     }
 
-    pub fn combine_and_assemble(programs: Vec<VMProgram>) -> String {
-        let mut result: Vec<String>;
+    pub fn combine_and_assemble(
+        programs: Vec<VMProgram>,
+        bootstrap: Option<Bootstrap>,
+    ) -> Result<String, TranslateError> {
+        Self::combine_and_assemble_with_source_map(programs, bootstrap, false, None).map(|(assembly, _)| assembly)
+    }
 
-        let init_stack_pointer = [vec!["// init", "@256", "D=A", "@SP", "M=D"]]
-            .concat()
-            .iter()
-            .map(|c| c.to_string())
-            .collect();
-        // TODO: to_commandsの第1引数、本当はSys.initが定義されているファイル名を取る必要がある
-        let (call_init, _, _, _) = Command::Call("Sys.init".to_string(), 0).to_commands("Sys", 0, 0, "Init");
-        result = [init_stack_pointer, call_init].concat();
-
-        for mut p in programs {
-            result = [result, p.to_machine_language()].concat()
+    // アセンブリと、各命令がどのVMコマンドから生成されたかを表すソースマップを返す
+    // annotatedがtrueの場合、各コマンドの先頭のコメントを元の.vmファイルの行にする
+    pub fn combine_and_assemble_with_source_map(
+        programs: Vec<VMProgram>,
+        bootstrap: Option<Bootstrap>,
+        annotated: bool,
+        cache: Option<&FragmentCache>,
+    ) -> Result<(String, SourceMap), TranslateError> {
+        let (instructions, source_map) = Self::combine(programs, bootstrap, annotated, cache)?;
+        Ok((to_text(&instructions), source_map))
+    }
+
+    // 全ファイルを変換して1つの命令列にまとめる
+    // 各ファイルは独立に(並列に)変換し、渡された順序で連結する。cacheがある場合は内容が変わっていないファイルの変換結果を再利用する
+    pub fn combine(
+        programs: Vec<VMProgram>,
+        bootstrap: Option<Bootstrap>,
+        annotated: bool,
+        cache: Option<&FragmentCache>,
+    ) -> Result<(Vec<Instruction>, SourceMap), TranslateError> {
+        let function_table = FunctionTable::new(&programs)?;
+        for p in &programs {
+            function_table.check_calls(p, |_| false)?;
         }
-        let shutdown_loop = ["// end", "(END)", "@END", "0;JMP"]
+
+        let mut result: Vec<Instruction> = vec![];
+
+        if let Some(bootstrap) = bootstrap {
+            // エントリポイントが定義されているファイルを探す。見つからなければエラーにする。
+            let defined_file_name = function_table
+                .get(&bootstrap.entry_point)
+                .cloned()
+                .ok_or_else(|| TranslateError::EntryPointNotFound(bootstrap.entry_point.clone()))?;
+
+            let init_stack_pointer = vec![
+                Instruction::comment("init"),
+                Instruction::a_value(bootstrap.stack_base),
+                Instruction::assign(Dest::D, Comp::A),
+                Instruction::a_symbol("SP"),
+                Instruction::assign(Dest::M, Comp::D),
+            ];
+            let (call_init, _, _, _) =
+                Command::Call(bootstrap.entry_point, 0).to_commands(&defined_file_name, 0, 0, "Bootstrap");
+            result = [init_stack_pointer, call_init].concat();
+        }
+
+        // 使われているランタイムルーチン
+        let runtime_routines = programs
             .iter()
-            .map(|c| c.to_string())
-            .collect(); // 終了用の無限ループ
+            .flat_map(|p| p.commands.iter())
+            .filter_map(|c| match c {
+                Command::Arithmetic(
+                    arithmetic @ (ArithmeticCommand::Mul | ArithmeticCommand::Div | ArithmeticCommand::Shr),
+                ) => Some(RuntimeRoutine::from_arithmetic(arithmetic)),
+                _ => None,
+            })
+            .collect::<std::collections::BTreeSet<_>>();
+
+        let fragments = parallel_map(programs, |mut p| {
+            if let Some(fragment) = cache.and_then(|c| c.get(&p, annotated)) {
+                return fragment;
+            }
+            let fragment = p.translate(annotated);
+            if let Some(c) = cache {
+                c.put(&p, annotated, &fragment);
+            }
+            fragment
+        });
+
+        let mut source_map = SourceMap::default();
+        let mut rom_address = count_instructions(&result);
+        for fragment in fragments {
+            result.push(Instruction::comment("body"));
+            source_map
+                .entries
+                .extend(fragment.source_map.into_iter().map(|entry| SourceMapEntry {
+                    rom_address: rom_address + entry.rom_address,
+                    ..entry
+                }));
+            rom_address += count_instructions(&fragment.instructions);
+            result.extend(fragment.instructions);
+        }
+        // 終了用の無限ループ
+        let shutdown_loop = vec![
+            Instruction::comment("end"),
+            Instruction::label("END"),
+            Instruction::a_symbol("END"),
+            Instruction::jump(Comp::Zero, Jump::JMP),
+        ];
         result = [result, shutdown_loop].concat();
+        for routine in runtime_routines {
+            result.extend(routine.instructions());
+        }
+
+        Ok((result, source_map))
+    }
+
+    // このファイルだけを変換する。ソースマップのROMアドレスはファイルの先頭からの相対アドレスになる
+    pub fn translate(&mut self, annotated: bool) -> Fragment {
+        let mut instructions = vec![];
+        let mut source_map = vec![];
+        for (i, (mut commands, function_name)) in self.translate_commands().into_iter().enumerate() {
+            let source_line = &self.source_lines[i];
+            if annotated {
+                let annotation = Instruction::comment(format!(
+                    "{}.vm:{}: {}",
+                    self.file_name, source_line.line_number, source_line.text
+                ));
+                match commands.first_mut() {
+                    Some(first @ Instruction::Comment(_)) => *first = annotation,
+                    _ => commands.insert(0, annotation),
+                }
+            }
+            source_map.push(SourceMapEntry {
+                rom_address: count_instructions(&instructions),
+                length: count_instructions(&commands),
+                file_name: format!("{}.vm", self.file_name),
+                line_number: source_line.line_number,
+                function_name,
+                command: source_line.text.clone(),
+            });
+            instructions.extend(commands);
+        }
+        Fragment {
+            instructions,
+            source_map,
+        }
+    }
+
+    // index番目のコマンドが書かれていた行番号(1始まり)
+    pub(crate) fn line_number(&self, index: usize) -> usize {
+        self.source_lines[index].line_number
+    }
+
+    // commands[index]の元になった行(前後の空白を除いたもの)を返す
+    pub(crate) fn source_text(&self, index: usize) -> &str {
+        &self.source_lines[index].text
+    }
+
+    // 拡張命令を使っていないことを確認する(拡張命令を有効にしていない場合に使う)
+    pub fn check_standard_commands(&self) -> Result<(), TranslateError> {
+        match self.commands.iter().position(|c| c.is_extended()) {
+            Some(i) => Err(TranslateError::ExtendedCommand {
+                file_name: self.file_name.clone(),
+                line_number: self.line_number(i),
+                command: self.source_text(i).to_string(),
+            }),
+            None => Ok(()),
+        }
+    }
 
-        result.join("\n")
+    // このファイルで定義されている関数名の一覧を返す
+    fn defined_functions(&self) -> Vec<&String> {
+        self.commands
+            .iter()
+            .filter_map(|c| match c {
+                Command::Function(name, _) => Some(name),
+                _ => None,
+            })
+            .collect()
     }
 
-    #[allow(clippy::wrong_self_convention)]
-    pub fn to_machine_language(&mut self) -> Vec<String> {
-        let mut result = vec!["// body".to_string()];
+    // コマンドごとに、変換したアセンブリとそのコマンドが所属する関数名を返す
+    fn translate_commands(&mut self) -> Vec<(Vec<Instruction>, String)> {
+        let mut result = vec![];
         for command in &self.commands.clone() {
             let (commands, should_increment_label_number, should_increment_return_address_id, new_function_name) =
                 command.to_commands(
@@ -117,7 +300,6 @@ impl VMProgram {
                     self.return_address_id,
                     &self.current_function_name,
                 );
-            result.extend(commands);
 
             if should_increment_label_number {
                 self.increment_label_id();
@@ -128,6 +310,7 @@ impl VMProgram {
             if let Some(func_name) = new_function_name {
                 self.update_current_function_name(func_name);
             }
+            result.push((commands, self.current_function_name.clone()));
         }
         result
     }
@@ -145,8 +328,220 @@ impl VMProgram {
     }
 }
 
+// .vmファイルの1行
+#[derive(PartialEq, Eq, Debug, Clone)]
+struct SourceLine {
+    // 1始まりの行番号
+    line_number: usize,
+    // 前後の空白を取り除いた行の内容
+    text: String,
+}
+
+/// 1ファイル分の変換結果
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct Fragment {
+    pub instructions: Vec<Instruction>,
+    // rom_addressはinstructionsの先頭からの相対アドレス
+    pub source_map: Vec<SourceMapEntry>,
+}
+
+// itemsの各要素にfを並列に適用し、結果をitemsと同じ順序で返す
+pub fn parallel_map<T: Send, U: Send>(items: Vec<T>, f: impl Fn(T) -> U + Sync) -> Vec<U> {
+    let f = &f;
+    std::thread::scope(|scope| {
+        let handles = items
+            .into_iter()
+            .map(|item| scope.spawn(move || f(item)))
+            .collect::<Vec<_>>();
+        handles.into_iter().map(|h| h.join().unwrap()).collect()
+    })
+}
+
+/// アセンブリの命令とVMコマンドの対応表
+/// CPUエミュレータでVMコマンド単位でステップ実行したり、関数ごとのROMサイズを集計するために使う。
+#[derive(PartialEq, Eq, Debug, Default, serde::Serialize)]
+pub struct SourceMap {
+    pub entries: Vec<SourceMapEntry>,
+}
+
+/// 1つのVMコマンドから生成された命令の範囲(ROMアドレスがrom_addressからrom_address+length-1までの命令)
+#[derive(PartialEq, Eq, Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct SourceMapEntry {
+    pub rom_address: usize,
+    pub length: usize,
+    pub file_name: String,
+    pub line_number: usize,
+    // コマンドが所属する関数名(関数の外のコマンドは空文字列)
+    pub function_name: String,
+    pub command: String,
+}
+
+impl SourceMap {
+    pub fn to_json(&self) -> String {
+        // Serializeを導出しているだけなので失敗しない
+        serde_json::to_string_pretty(self).unwrap()
+    }
+}
+
+// コメントとラベル定義を除いた、ROMに配置される命令の数を数える
+fn count_instructions(instructions: &[Instruction]) -> usize {
+    instructions.iter().filter(|i| i.is_machine_instruction()).count()
+}
+
+/// ブートストラップコードの設定
+/// 07のテストのようにブートストラップが不要な場合は`combine_and_assemble`に`None`を渡す
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct Bootstrap {
+    // SPの初期値
+    pub stack_base: u16,
+    // 最初に呼び出す関数名(e.g. `Sys.init`, OSなしで動かす場合は`Main.main`など)
+    pub entry_point: String,
+}
+
+impl Default for Bootstrap {
+    fn default() -> Self {
+        Self {
+            stack_base: 256,
+            entry_point: "Sys.init".to_string(),
+        }
+    }
+}
+
+/// プログラム全体(全.vmファイル)の関数テーブル
+/// key: 関数名, value: その関数が定義されているファイル名
+/// 関数名はプログラム全体で一意なので、関数の開始ラベルには関数名をそのまま使う。
+#[derive(PartialEq, Eq, Debug)]
+pub struct FunctionTable(std::collections::HashMap<String, String>);
+
+impl FunctionTable {
+    pub fn new(programs: &[VMProgram]) -> Result<Self, TranslateError> {
+        let mut table = std::collections::HashMap::new();
+        for p in programs {
+            for function_name in p.defined_functions() {
+                if let Some(defined_file_name) = table.insert(function_name.clone(), p.file_name.clone()) {
+                    return Err(TranslateError::DuplicateFunction {
+                        function_name: function_name.clone(),
+                        file_names: (defined_file_name, p.file_name.clone()),
+                    });
+                }
+            }
+        }
+        Ok(Self(table))
+    }
+
+    pub fn get(&self, function_name: &str) -> Option<&String> {
+        self.0.get(function_name)
+    }
+
+    // 呼び出している関数がすべてどこかのファイルで定義されていることを確認する
+    // is_builtinがtrueを返す関数は定義されていなくても呼び出せる(エミュレータのネイティブ実装のOS)
+    pub(crate) fn check_calls(
+        &self,
+        program: &VMProgram,
+        is_builtin: impl Fn(&str) -> bool,
+    ) -> Result<(), TranslateError> {
+        for c in &program.commands {
+            if let Command::Call(function_name, _) = c {
+                if self.get(function_name).is_none() && !is_builtin(function_name) {
+                    return Err(TranslateError::UndefinedFunction {
+                        function_name: function_name.clone(),
+                        file_name: program.file_name.clone(),
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+// Hackコンピュータの命令メモリのワード数
+pub const ROM_SIZE: usize = 32768;
+
+#[derive(PartialEq, Eq, Debug)]
+pub enum TranslateError {
+    // ブートストラップで呼び出す関数がどのファイルにも定義されていない
+    EntryPointNotFound(String),
+    // 同じ名前の関数が複数回定義されている(file_namesは定義されている2つのファイル名)
+    DuplicateFunction {
+        function_name: String,
+        file_names: (String, String),
+    },
+    // どのファイルにも定義されていない関数を呼び出している
+    UndefinedFunction {
+        function_name: String,
+        file_name: String,
+    },
+    // 拡張命令を有効にせずに拡張命令を使っている
+    ExtendedCommand {
+        file_name: String,
+        line_number: usize,
+        command: String,
+    },
+    // 解釈できない行(未知のコマンド、引数の不足、範囲外の定数など)
+    InvalidCommand {
+        file_name: String,
+        line_number: usize,
+        command: String,
+    },
+    // 機械語に変換できなかった(ラベルのアドレスがA命令で指定できる範囲を超えたなど)
+    Assemble(assembler::AssembleError),
+    // Hackコンピュータの32KワードのROMに収まらない(値は必要なワード数)
+    RomOverflow(usize),
+    // エミュレータはリターンアドレスをi16でRAMに保存するので、命令数がi16::MAXを超えるプログラムは実行できない
+    TooManyCommands(usize),
+}
+
+impl std::fmt::Display for TranslateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TranslateError::EntryPointNotFound(function_name) => {
+                write!(f, "entry point `{}` is not defined in any input file", function_name)
+            }
+            TranslateError::DuplicateFunction {
+                function_name,
+                file_names: (first, second),
+            } => {
+                write!(f, "function `{}` is defined more than once (in {}.vm and {}.vm)", function_name, first, second)
+            }
+            TranslateError::UndefinedFunction {
+                function_name,
+                file_name,
+            } => {
+                write!(f, "function `{}` called in {}.vm is not defined in any input file", function_name, file_name)
+            }
+            TranslateError::ExtendedCommand {
+                file_name,
+                line_number,
+                command,
+            } => {
+                write!(
+                    f,
+                    "{}.vm:{}: `{}` is an extended command (pass --extended to enable it)",
+                    file_name, line_number, command
+                )
+            }
+            TranslateError::InvalidCommand {
+                file_name,
+                line_number,
+                command,
+            } => {
+                write!(f, "{}.vm:{}: invalid command `{}`", file_name, line_number, command)
+            }
+            TranslateError::Assemble(e) => write!(f, "{}", e),
+            TranslateError::RomOverflow(words) => {
+                write!(f, "program needs {} words of ROM, but the Hack computer has only {}", words, ROM_SIZE)
+            }
+            TranslateError::TooManyCommands(length) => {
+                write!(f, "program has {} commands, but the emulator can run at most {}", length, i16::MAX)
+            }
+        }
+    }
+}
+
+impl std::error::Error for TranslateError {}
+
 #[derive(PartialEq, Eq, Debug, Clone)]
-enum Command {
+pub(crate) enum Command {
     Arithmetic(ArithmeticCommand),
     Push(Segment),
     Pop(Segment),
@@ -156,9 +551,37 @@ enum Command {
     Call(String, u32),
     Function(String, u32),
     Return,
+    // 以下は拡張命令
+    // スタックの最上位の値を複製する
+    Dup,
+    // スタックの上位2つの値を入れ替える
+    Swap,
+    // セグメントの値を1増やす/減らす
+    Inc(Segment),
+    Dec(Segment),
+    // `push that *`: アドレスをpopしてTHATに設定し、そのアドレスの値をpushする(`pop pointer 1`, `push that 0`と同じ)
+    PushThatIndirect,
+    // `pop that *`: 値とアドレスをpopしてTHATにアドレスを設定し、そのアドレスに値を格納する
+    // (`pop temp 0`, `pop pointer 1`, `push temp 0`, `pop that 0`と同じ)
+    PopThatIndirect,
 }
 
 impl Command {
+    // 標準の命令セットにない拡張命令かどうか
+    pub(crate) fn is_extended(&self) -> bool {
+        matches!(
+            self,
+            Command::Arithmetic(
+                ArithmeticCommand::Mul | ArithmeticCommand::Div | ArithmeticCommand::Shl | ArithmeticCommand::Shr
+            ) | Command::Dup
+                | Command::Swap
+                | Command::Inc(_)
+                | Command::Dec(_)
+                | Command::PushThatIndirect
+                | Command::PopThatIndirect
+        )
+    }
+
     // command, should_increment_label_number, should_increment_return_address_id,
     // new_function_nameを返す
     fn to_commands(
@@ -167,259 +590,202 @@ impl Command {
         label_suffix: u32,
         return_address_id: u32,
         current_function_name: &str,
-    ) -> (Vec<String>, bool, bool, Option<String>) {
+    ) -> (Vec<Instruction>, bool, bool, Option<String>) {
         // RAM[SP]にDを格納する
-        let push_d: Vec<&str> = vec!["@SP", "A=M", "M=D", "@SP", "M=M+1"];
+        let push_d = vec![
+            Instruction::a_symbol("SP"),
+            Instruction::assign(Dest::A, Comp::M),
+            Instruction::assign(Dest::M, Comp::D),
+            Instruction::a_symbol("SP"),
+            Instruction::assign(Dest::M, Comp::MPlusOne),
+        ];
 
         // 1つのオペランドを取る処理の前処理
         // RAM[SP-1]をMに格納する
-        let get_1_operand: Vec<&str> = vec!["@SP", "A=M", "A=A-1"];
+        let get_1_operand = vec![
+            Instruction::a_symbol("SP"),
+            Instruction::assign(Dest::A, Comp::M),
+            Instruction::assign(Dest::A, Comp::AMinusOne),
+        ];
 
         // 2つのオペランドを取る処理の前処理
         // 計算の順序をM+DではなくD+Mにしたいので先にxをDに格納している。
         // P89 図4-5 に定義されている命令セットに厳密に従いたいのでこうしている。
         // (D&Mは定義されているがM&Dは定義されていないのでMの前にDにが来るような順番で統一したい)
-        let get_2_operand: Vec<&str> = [
+        let get_2_operand = [
             // RAM[SP-2]をDに格納
-            vec!["@SP", "A=M", "A=A-1", "A=A-1", "D=M"],
+            vec![
+                Instruction::a_symbol("SP"),
+                Instruction::assign(Dest::A, Comp::M),
+                Instruction::assign(Dest::A, Comp::AMinusOne),
+                Instruction::assign(Dest::A, Comp::AMinusOne),
+                Instruction::assign(Dest::D, Comp::M),
+            ],
             // RAM[SP-1]をMに格納
-            vec!["@SP", "A=M", "A=A-1"],
+            get_1_operand.clone(),
         ]
         .concat();
 
         // 1つのオペランドを取る計算の結果を格納する(計算結果がDに入っていることを期待している)
-        let save_result_1_operand: Vec<&str> = [
+        let save_result_1_operand = [
             // RAM[SP-1]をMに格納
-            vec!["@SP", "A=M", "A=A-1"],
+            get_1_operand.clone(),
             // RAM[SP-1]を0にする
-            vec!["M=0"],
+            vec![Instruction::assign(Dest::M, Comp::Zero)],
             // SPをSP-1する
-            vec!["@SP", "M=M-1"],
+            vec![
+                Instruction::a_symbol("SP"),
+                Instruction::assign(Dest::M, Comp::MMinusOne),
+            ],
             // 結果をpushする
             push_d.clone(),
         ]
         .concat();
 
         // 2つのオペランドを取る計算の結果を格納する(計算結果がDに入っていることを期待している)
-        let save_result_2_operand: Vec<&str> = [
+        let save_result_2_operand = [
             // RAM[SP-2]を0にする
-            vec!["@SP", "A=M", "A=A-1", "A=A-1", "M=0"],
+            vec![
+                Instruction::a_symbol("SP"),
+                Instruction::assign(Dest::A, Comp::M),
+                Instruction::assign(Dest::A, Comp::AMinusOne),
+                Instruction::assign(Dest::A, Comp::AMinusOne),
+                Instruction::assign(Dest::M, Comp::Zero),
+            ],
             // RAM[SP-1]を0にする
-            vec!["@SP", "A=M", "A=A-1", "M=0"],
+            get_1_operand.clone(),
+            vec![Instruction::assign(Dest::M, Comp::Zero)],
             // SPをSP-2する
-            vec!["@SP", "M=M-1", "M=M-1"],
+            vec![
+                Instruction::a_symbol("SP"),
+                Instruction::assign(Dest::M, Comp::MMinusOne),
+                Instruction::assign(Dest::M, Comp::MMinusOne),
+            ],
             // 結果をpushする
             push_d.clone(),
         ]
         .concat();
 
-        // NOTE: 各命令を["@FOO", "M=0"]のように配列の形で書くのが一番シンプルだが、
-        // そうするとコメントが意図しない位置で改行されてしまうのでvectorの配列をconcatしている。
-        match self {
-            Command::Arithmetic(ArithmeticCommand::Add) => {
-                // x: RAM[SP-2], y: RAM[SP-1]としたときのx+yの結果を返す
-                let commands = [
-                    vec![format!("// {:?}", self).as_str()],
-                    get_2_operand,
-                    // add
-                    vec!["D=D+M"],
-                    save_result_2_operand,
-                ]
-                .concat()
-                .iter()
-                .map(|c| c.to_string())
-                .collect();
-                (commands, false, false, None)
-            }
+        let comment = Instruction::comment(format!("{:?}", self));
 
-            Command::Arithmetic(ArithmeticCommand::Sub) => {
-                // x: RAM[SP-2], y: RAM[SP-1]としたときのx-yの結果を返す
+        match self {
+            Command::Arithmetic(
+                arithmetic @ (ArithmeticCommand::Add
+                | ArithmeticCommand::Sub
+                | ArithmeticCommand::And
+                | ArithmeticCommand::Or),
+            ) => {
+                // x: RAM[SP-2], y: RAM[SP-1]としたときのx+y, x-y, x&y, x|yの結果を返す
+                let comp = match arithmetic {
+                    ArithmeticCommand::Add => Comp::DPlusM,
+                    ArithmeticCommand::Sub => Comp::DMinusM,
+                    ArithmeticCommand::And => Comp::DAndM,
+                    _ => Comp::DOrM,
+                };
                 let commands = [
-                    vec![format!("// {:?}", self).as_str()],
+                    vec![comment],
                     get_2_operand,
-                    // sub
-                    vec!["D=D-M"],
+                    vec![Instruction::assign(Dest::D, comp)],
                     save_result_2_operand,
                 ]
-                .concat()
-                .iter()
-                .map(|c| c.to_string())
-                .collect();
+                .concat();
                 (commands, false, false, None)
             }
 
-            Command::Arithmetic(ArithmeticCommand::Neg) => {
-                // x: RAM[SP-1]としたときの-xの結果を返す
+            Command::Arithmetic(arithmetic @ (ArithmeticCommand::Neg | ArithmeticCommand::Not)) => {
+                // x: RAM[SP-1]としたときの-x, !xの結果を返す
+                let comp = match arithmetic {
+                    ArithmeticCommand::Neg => Comp::NegM,
+                    _ => Comp::NotM,
+                };
                 let commands = [
-                    vec![format!("// {:?}", self).as_str()],
+                    vec![comment],
                     get_1_operand,
-                    // neg
-                    vec!["D=-M"],
+                    vec![Instruction::assign(Dest::D, comp)],
                     save_result_1_operand,
                 ]
-                .concat()
-                .iter()
-                .map(|c| c.to_string())
-                .collect();
+                .concat();
                 (commands, false, false, None)
             }
 
-            Command::Arithmetic(ArithmeticCommand::Eq) => {
-                // x: RAM[SP-2], y: RAM[SP-1]としたときのx==yの結果を返す
-                let true_label = format!("TRUE_{:05}", label_suffix);
-                let false_label = format!("FALSE_{:05}", label_suffix);
-                let end_if_label = format!("END_IF_{:05}", label_suffix);
+            Command::Arithmetic(ArithmeticCommand::Shl) => {
+                // x: RAM[SP-1]を1bit左にシフトする(x+xと同じ)
                 let commands = [
-                    vec![format!("// {:?}", self).as_str()],
-                    get_2_operand,
-                    // eqの判定ここから
-                    vec!["D=D-M"], // x-y
-                    // x-y==0ならtrue_labelにジャンプ
-                    vec![format!("@{}", true_label).as_str(), "D;JEQ"],
-                    // x != yの場合
+                    vec![comment],
+                    get_1_operand,
                     vec![
-                        format!("({})", false_label).as_str(),
-                        "D=0",
-                        format!("@{}", end_if_label).as_str(),
-                        "0;JMP",
+                        Instruction::assign(Dest::D, Comp::M),
+                        Instruction::assign(Dest::M, Comp::DPlusM),
                     ],
-                    // x == yの場合
-                    vec![format!("({})", true_label).as_str(), "D=-1"],
-                    // end_if_label
-                    vec![format!("({})", end_if_label).as_str()],
-                    // eqの判定ここまで
-                    save_result_2_operand,
                 ]
-                .concat()
-                .iter()
-                .map(|c| c.to_string())
-                .collect();
-                (commands, true, false, None)
+                .concat();
+                (commands, false, false, None)
             }
 
-            Command::Arithmetic(ArithmeticCommand::Gt) => {
-                // x: RAM[SP-2], y: RAM[SP-1]としたときのx>yの結果を返す
-                let true_label = format!("TRUE_{:05}", label_suffix);
-                let false_label = format!("FALSE_{:05}", label_suffix);
-                let end_if_label = format!("END_IF_{:05}", label_suffix);
-                let commands = [
-                    // x: RAM[SP-2], y: RAM[SP-1]としたときのx>yの結果を返す
-                    vec![format!("// {:?}", self).as_str()],
-                    get_2_operand,
-                    // gtの判定ここから
-                    vec!["D=D-M"], // x-y
-                    // x-y==0ならtrue_labelにジャンプ
-                    vec![format!("@{}", true_label).as_str(), "D;JGT"],
-                    // x != yの場合
-                    vec![
-                        format!("({})", false_label).as_str(),
-                        "D=0",
-                        format!("@{}", end_if_label).as_str(),
-                        "0;JMP",
-                    ],
-                    // x == yの場合
-                    vec![format!("({})", true_label).as_str(), "D=-1"],
-                    // end_if_label
-                    vec![format!("({})", end_if_label).as_str()],
-                    // gtの判定ここまで
-                    save_result_2_operand,
-                ]
-                .concat()
-                .iter()
-                .map(|c| c.to_string())
-                .collect();
+            Command::Arithmetic(
+                arithmetic @ (ArithmeticCommand::Mul | ArithmeticCommand::Div | ArithmeticCommand::Shr),
+            ) => {
+                // Hackの命令では1命令で計算できないのでランタイムルーチンを呼び出す
+                // R15にリターンアドレスを設定してジャンプし、ルーチンはスタック上で計算して戻ってくる
+                let routine = RuntimeRoutine::from_arithmetic(arithmetic);
+                let return_label = format!("{}.VM_RET_{:05}", file_name, label_suffix);
+                let commands = vec![
+                    comment,
+                    Instruction::a_symbol(&return_label),
+                    Instruction::assign(Dest::D, Comp::A),
+                    Instruction::a_symbol("R15"),
+                    Instruction::assign(Dest::M, Comp::D),
+                    Instruction::a_symbol(routine.label()),
+                    Instruction::jump(Comp::Zero, Jump::JMP),
+                    Instruction::label(return_label),
+                ];
                 (commands, true, false, None)
             }
 
-            Command::Arithmetic(ArithmeticCommand::Lt) => {
-                // x: RAM[SP-2], y: RAM[SP-1]としたときのx<yの結果を返す
-                let true_label = format!("TRUE_{:05}", label_suffix);
-                let false_label = format!("FALSE_{:05}", label_suffix);
-                let end_if_label = format!("END_IF_{:05}", label_suffix);
+            Command::Arithmetic(arithmetic) => {
+                // x: RAM[SP-2], y: RAM[SP-1]としたときのx==y, x>y, x<yの結果を返す
+                let jump = match arithmetic {
+                    ArithmeticCommand::Eq => Jump::JEQ,
+                    ArithmeticCommand::Gt => Jump::JGT,
+                    _ => Jump::JLT,
+                };
+                let true_label = format!("{}.TRUE_{:05}", file_name, label_suffix);
+                let false_label = format!("{}.FALSE_{:05}", file_name, label_suffix);
+                let end_if_label = format!("{}.END_IF_{:05}", file_name, label_suffix);
                 let commands = [
-                    vec![format!("// {:?}", self).as_str()],
+                    vec![comment],
                     get_2_operand,
-                    // ltの判定ここから
-                    vec!["D=D-M"], // x-y
-                    // x-y==0ならtrue_labelにジャンプ
-                    vec![format!("@{}", true_label).as_str(), "D;JLT"],
-                    // x != yの場合
+                    // 判定ここから
+                    vec![Instruction::assign(Dest::D, Comp::DMinusM)], // x-y
+                    // x-yが条件を満たすならtrue_labelにジャンプ
+                    vec![Instruction::a_symbol(&true_label), Instruction::jump(Comp::D, jump)],
+                    // 条件を満たさない場合
+                    vec![
+                        Instruction::label(false_label),
+                        Instruction::assign(Dest::D, Comp::Zero),
+                        Instruction::a_symbol(&end_if_label),
+                        Instruction::jump(Comp::Zero, Jump::JMP),
+                    ],
+                    // 条件を満たす場合
                     vec![
-                        format!("({})", false_label).as_str(),
-                        "D=0",
-                        format!("@{}", end_if_label).as_str(),
-                        "0;JMP",
+                        Instruction::label(true_label),
+                        Instruction::assign(Dest::D, Comp::MinusOne),
                     ],
-                    // x == yの場合
-                    vec![format!("({})", true_label).as_str(), "D=-1"],
                     // end_if_label
-                    vec![format!("({})", end_if_label).as_str()],
-                    // ltの判定ここまで
+                    vec![Instruction::label(end_if_label)],
+                    // 判定ここまで
                     save_result_2_operand,
                 ]
-                .concat()
-                .iter()
-                .map(|c| c.to_string())
-                .collect();
+                .concat();
                 (commands, true, false, None)
             }
 
-            Command::Arithmetic(ArithmeticCommand::And) => {
-                // x: RAM[SP-2], y: RAM[SP-1]としたときのx&yの結果を返す
-                let commands = [
-                    vec![format!("// {:?}", self).as_str()],
-                    get_2_operand,
-                    // and
-                    vec!["D=D&M"],
-                    save_result_2_operand,
-                ]
-                .concat()
-                .iter()
-                .map(|c| c.to_string())
-                .collect();
-                (commands, false, false, None)
-            }
-
-            Command::Arithmetic(ArithmeticCommand::Or) => {
-                let commands = [
-                    vec![format!("// {:?}", self).as_str()],
-                    get_2_operand,
-                    // or
-                    vec!["D=D|M"],
-                    save_result_2_operand,
-                ]
-                .concat()
-                .iter()
-                .map(|c| c.to_string())
-                .collect();
-                (commands, false, false, None)
-            }
-
-            Command::Arithmetic(ArithmeticCommand::Not) => {
-                // x: RAM[SP-1]としたときの!xの結果を返す
-                let commands = [
-                    vec![format!("// {:?}", self).as_str()],
-                    get_1_operand,
-                    // not
-                    vec!["D=!M"],
-                    save_result_1_operand,
-                ]
-                .concat()
-                .iter()
-                .map(|c| c.to_string())
-                .collect();
-                (commands, false, false, None)
-            }
-
             Command::Push(segment) => {
                 let commands = [
-                    vec![format!("// {:?}", self)],
-                    segment.clone().get_address_instructions(file_name),
-                    vec![format!("D={}", segment.get_value_register_name()).as_str()]
-                        .into_iter()
-                        .map(|c| c.to_string())
-                        .collect(),
-                    push_d.clone().into_iter().map(|c| c.to_string()).collect(),
+                    vec![comment],
+                    segment.get_address_instructions(file_name),
+                    vec![Instruction::assign(Dest::D, segment.get_value_comp())],
+                    push_d,
                 ]
                 .concat();
                 (commands, false, false, None)
@@ -427,158 +793,568 @@ impl Command {
 
             Command::Pop(segment) => {
                 let commands = [
-                    vec![format!("// {:?}", self)],
-                    vec!["@SP", "A=M-1", "D=M", "M=0"]
-                        .into_iter()
-                        .map(|c| c.to_string())
-                        .collect(), // RAM[SP]の値をDに格納しMを初期化
-                    segment.get_address_instructions(file_name), // Aにpopのdescを設定(ここでDを使うのでRAM[SP]の値が消えてしまうので注意)
-                    vec!["M=D"].into_iter().map(|c| c.to_string()).collect(), // L34
-                    vec!["@SP", "M=M-1"].into_iter().map(|c| c.to_string()).collect(),
+                    vec![comment],
+                    // RAM[SP]の値をDに格納しMを初期化
+                    vec![
+                        Instruction::a_symbol("SP"),
+                        Instruction::assign(Dest::A, Comp::MMinusOne),
+                        Instruction::assign(Dest::D, Comp::M),
+                        Instruction::assign(Dest::M, Comp::Zero),
+                    ],
+                    // Aにpopのdescを設定(ここでDを使うのでRAM[SP]の値が消えてしまうので注意)
+                    segment.get_address_instructions(file_name),
+                    vec![Instruction::assign(Dest::M, Comp::D)],
+                    vec![
+                        Instruction::a_symbol("SP"),
+                        Instruction::assign(Dest::M, Comp::MMinusOne),
+                    ],
                 ]
                 .concat();
                 (commands, false, false, None)
             }
 
             Command::Label(label_name) => {
-                let commands = [vec![format!("// {:?}", self)], vec![format!("({})", label_name)]].concat();
+                let commands = vec![
+                    comment,
+                    Instruction::label(scoped_label_name(current_function_name, label_name)),
+                ];
                 (commands, false, false, None)
             }
             Command::GoTo(label_name) => {
-                let commands = [
-                    vec![format!("// {:?}", self)],
-                    vec![format!("@{}", label_name), "0;JMP".to_string()],
-                ]
-                .concat();
+                let commands = vec![
+                    comment,
+                    Instruction::a_symbol(scoped_label_name(current_function_name, label_name)),
+                    Instruction::jump(Comp::Zero, Jump::JMP),
+                ];
                 (commands, false, false, None)
             }
             Command::IfGoTo(label_name) => {
-                let commands = [
-                    vec![format!("// {:?}", self)],
+                let commands = vec![
+                    comment,
                     // スタックの最上位の値xをpopし、x!=0ならばJUMPする
-                    vec![
-                        "@SP",
-                        "A=M-1",
-                        "D=M",
-                        "M=0",
-                        "@SP",
-                        "M=M-1",
-                        format!("@{}", label_name).as_str(),
-                        "D;JNE",
-                    ]
-                    .into_iter()
-                    .map(|c| c.to_string())
-                    .collect(),
-                ]
-                .concat();
+                    Instruction::a_symbol("SP"),
+                    Instruction::assign(Dest::A, Comp::MMinusOne),
+                    Instruction::assign(Dest::D, Comp::M),
+                    Instruction::assign(Dest::M, Comp::Zero),
+                    Instruction::a_symbol("SP"),
+                    Instruction::assign(Dest::M, Comp::MMinusOne),
+                    Instruction::a_symbol(scoped_label_name(current_function_name, label_name)),
+                    Instruction::jump(Comp::D, Jump::JNE),
+                ];
                 (commands, false, false, None)
             }
             Command::Call(function_name, vars_length) => {
-                let return_address_label = format!(
-                    "{}.{}$ret.{}", // リターンアドレスを宣言し、Dに格納
-                    file_name, current_function_name, return_address_id
-                );
-                // defined_file_nameは本来はVec<VMProgram>を走査して定義されているファイルを特定する必要があるが
-                // ここではfunction
-                // Class1.getのようにファイル名が先頭につくのでそれを利用している。
-                let defined_file_name = function_name.split('.').collect::<Vec<&str>>()[0].to_string();
-                let go_to_address_label = format!(
-                    "{}.{}", // 呼び出し先関数のアドレスを宣言し、Dに格納
-                    defined_file_name, function_name
-                );
+                // リターンアドレスを宣言し、Dに格納
+                // 関数名はプログラム全体で一意なので`{呼び出し元の関数名}$ret.{return_address_id}`で一意になる
+                let return_address_label = format!("{}$ret.{}", current_function_name, return_address_id);
+                // 呼び出し先関数のアドレス。関数の開始ラベルは関数名そのもの(FunctionTable参照)
+                let go_to_address_label = function_name;
+                // RAM[address]の値をpushする
+                let push_register = |address: u16| {
+                    [
+                        vec![Instruction::a_value(address), Instruction::assign(Dest::D, Comp::M)],
+                        push_d.clone(),
+                    ]
+                    .concat()
+                };
                 let commands = [
-                    vec![format!("// {:?}", self).as_str()],
+                    vec![comment],
                     // リターンアドレスを宣言してスタックにpush
-                    vec![format!("@{}", return_address_label).as_str(), "D=A"],
+                    vec![
+                        Instruction::a_symbol(&return_address_label),
+                        Instruction::assign(Dest::D, Comp::A),
+                    ],
                     push_d.clone(),
                     // LCL(RAM[1]: ローカルのベースアドレス)をpushし元のデータを消去
-                    vec!["@1", "D=M"],
-                    push_d.clone(),
+                    push_register(1),
                     // ARG(RAM[2]: argumentのベースアドレス)をpushし元のデータを消去
-                    vec!["@2", "D=M"],
-                    push_d.clone(),
+                    push_register(2),
                     // THIS(RAM[3]: thisのベースアドレス)をpushし元のデータを消去
-                    vec!["@3", "D=M"],
-                    push_d.clone(),
+                    push_register(3),
                     // THAT(RAM[4]: thatのベースアドレス)をpushし元のデータを消去
-                    vec!["@4", "D=M"],
-                    push_d.clone(),
+                    push_register(4),
                     // ARGを`SP-5-nArgs`に変更する
-                    vec!["@SP", "D=M", "@2", "M=D"],
-                    vec!["M=M-1"; (5 + vars_length) as usize],
+                    vec![
+                        Instruction::a_symbol("SP"),
+                        Instruction::assign(Dest::D, Comp::M),
+                        Instruction::a_value(2),
+                        Instruction::assign(Dest::M, Comp::D),
+                    ],
+                    vec![Instruction::assign(Dest::M, Comp::MMinusOne); (5 + vars_length) as usize],
                     // LCLをSPの値に変更する
-                    vec!["@SP", "D=M", "@1", "M=D"],
+                    vec![
+                        Instruction::a_symbol("SP"),
+                        Instruction::assign(Dest::D, Comp::M),
+                        Instruction::a_value(1),
+                        Instruction::assign(Dest::M, Comp::D),
+                    ],
                     // 呼び出される側に制御を移す
-                    vec![format!("@{}", go_to_address_label).as_str(), "0;JMP"],
+                    vec![
+                        Instruction::a_symbol(go_to_address_label),
+                        Instruction::jump(Comp::Zero, Jump::JMP),
+                    ],
                     // リターンアドレスラベルを挿入
-                    vec![format!("({})", return_address_label).as_str()],
+                    vec![Instruction::label(return_address_label)],
                     // MEMO: 呼び出される側のthis, that, pointer, tempを明示的に初期化する必要ってある？
                 ]
-                .concat()
-                .into_iter()
-                .map(|c| c.to_string())
-                .collect();
+                .concat();
                 (commands, false, true, None)
             }
             Command::Function(function_name, vars_length) => {
-                let go_to_address_label = format!(
-                    "{}.{}", // 呼び出し先関数のアドレスを宣言し、Dに格納
-                    file_name, function_name
-                );
+                // 関数の開始ラベル。関数名はプログラム全体で一意なのでファイル名をつける必要はない
+                let go_to_address_label = function_name;
                 let init_local_segment = {
-                    let mut result: Vec<&str> = vec![];
+                    let mut result = vec![];
                     for _ in 0..*vars_length {
-                        result = [result, vec!["@0", "D=A"], push_d.clone()].concat();
+                        result = [
+                            result,
+                            vec![Instruction::a_value(0), Instruction::assign(Dest::D, Comp::A)],
+                            push_d.clone(),
+                        ]
+                        .concat();
                     }
                     result
                 };
                 let commands = [
-                    vec![format!("// {:?}", self).as_str()],
+                    vec![comment],
                     // 開始ラベルを挿入する
-                    vec![format!("({})", go_to_address_label).as_str()],
+                    vec![Instruction::label(go_to_address_label)],
                     // ローカルセグメントを初期化する(必要な数だけ0うめする）
                     init_local_segment,
                 ]
-                .concat()
-                .into_iter()
-                .map(|c| c.to_string())
-                .collect();
+                .concat();
                 (commands, false, false, Some(function_name.to_string()))
             }
             Command::Return => {
+                // RAM[13](frame)からoffsetだけ前のアドレスの値をDに格納する
+                let load_frame = |offset: usize| {
+                    [
+                        vec![Instruction::a_value(13), Instruction::assign(Dest::A, Comp::M)],
+                        vec![Instruction::assign(Dest::A, Comp::AMinusOne); offset],
+                        vec![Instruction::assign(Dest::D, Comp::M)],
+                    ]
+                    .concat()
+                };
                 let commands = [
-                    vec![format!("// {:?}", self).as_str()],
+                    vec![comment],
                     // LCLを一時変数(R13)に保存(以降のコメントではR13のことをframeと呼ぶ)
-                    vec!["@1", "D=M", "@13", "M=D"],
+                    vec![
+                        Instruction::a_value(1),
+                        Instruction::assign(Dest::D, Comp::M),
+                        Instruction::a_value(13),
+                        Instruction::assign(Dest::M, Comp::D),
+                    ],
                     // リターンアドレス*(frame-5)を一時変数(R14)に保存(以降はretAddrと呼ぶ)
-                    [vec!["@13", "A=M"], vec!["A=A-1"; 5], vec!["D=M", "@14", "M=D"]].concat(),
+                    load_frame(5),
+                    vec![Instruction::a_value(14), Instruction::assign(Dest::M, Comp::D)],
                     // 戻り値(スタックの先頭にあるはず)をRAM[ARG]にpopする
-                    vec!["@SP", "A=M-1", "D=M", "@2", "A=M", "M=D"],
+                    vec![
+                        Instruction::a_symbol("SP"),
+                        Instruction::assign(Dest::A, Comp::MMinusOne),
+                        Instruction::assign(Dest::D, Comp::M),
+                        Instruction::a_value(2),
+                        Instruction::assign(Dest::A, Comp::M),
+                        Instruction::assign(Dest::M, Comp::D),
+                    ],
                     // SPをARG+1の位置に設定する
-                    vec!["@2", "D=M+1", "@SP", "M=D"],
+                    vec![
+                        Instruction::a_value(2),
+                        Instruction::assign(Dest::D, Comp::MPlusOne),
+                        Instruction::a_symbol("SP"),
+                        Instruction::assign(Dest::M, Comp::D),
+                    ],
                     // 呼び出し側のTHATを復元する(*(frame-1))
-                    vec!["@13", "A=M-1", "D=M", "@4", "M=D"],
+                    vec![
+                        Instruction::a_value(13),
+                        Instruction::assign(Dest::A, Comp::MMinusOne),
+                        Instruction::assign(Dest::D, Comp::M),
+                        Instruction::a_value(4),
+                        Instruction::assign(Dest::M, Comp::D),
+                    ],
                     // 呼び出し側のTHISを復元する(*(frame-2))
-                    [vec!["@13", "A=M"], vec!["A=A-1"; 2], vec!["D=M", "@3", "M=D"]].concat(),
+                    load_frame(2),
+                    vec![Instruction::a_value(3), Instruction::assign(Dest::M, Comp::D)],
                     // 呼び出し側のARGを復元する
-                    [vec!["@13", "A=M"], vec!["A=A-1"; 3], vec!["D=M", "@2", "M=D"]].concat(),
+                    load_frame(3),
+                    vec![Instruction::a_value(2), Instruction::assign(Dest::M, Comp::D)],
                     // 呼び出し側のLCLを復元する
-                    [vec!["@13", "A=M"], vec!["A=A-1"; 4], vec!["D=M", "@1", "M=D"]].concat(),
+                    load_frame(4),
+                    vec![Instruction::a_value(1), Instruction::assign(Dest::M, Comp::D)],
                     // リターンアドレスに移動する
-                    vec!["@R14", "A=M", "0;JMP"],
+                    vec![
+                        Instruction::a_symbol("R14"),
+                        Instruction::assign(Dest::A, Comp::M),
+                        Instruction::jump(Comp::Zero, Jump::JMP),
+                    ],
                 ]
-                .concat()
-                .into_iter()
-                .map(|c| c.to_string())
-                .collect();
+                .concat();
+                (commands, false, false, None)
+            }
+            Command::Dup => {
+                let commands = [
+                    vec![comment],
+                    get_1_operand,
+                    vec![Instruction::assign(Dest::D, Comp::M)],
+                    push_d,
+                ]
+                .concat();
+                (commands, false, false, None)
+            }
+            Command::Swap => {
+                let commands = vec![
+                    comment,
+                    // RAM[SP-1]をR13に退避する
+                    Instruction::a_symbol("SP"),
+                    Instruction::assign(Dest::A, Comp::MMinusOne),
+                    Instruction::assign(Dest::D, Comp::M),
+                    Instruction::a_value(13),
+                    Instruction::assign(Dest::M, Comp::D),
+                    // RAM[SP-2]をRAM[SP-1]に移す
+                    Instruction::a_symbol("SP"),
+                    Instruction::assign(Dest::A, Comp::MMinusOne),
+                    Instruction::assign(Dest::A, Comp::AMinusOne),
+                    Instruction::assign(Dest::D, Comp::M),
+                    Instruction::assign(Dest::A, Comp::APlusOne),
+                    Instruction::assign(Dest::M, Comp::D),
+                    // 退避した値をRAM[SP-2]に格納する
+                    Instruction::a_value(13),
+                    Instruction::assign(Dest::D, Comp::M),
+                    Instruction::a_symbol("SP"),
+                    Instruction::assign(Dest::A, Comp::MMinusOne),
+                    Instruction::assign(Dest::A, Comp::AMinusOne),
+                    Instruction::assign(Dest::M, Comp::D),
+                ];
+                (commands, false, false, None)
+            }
+            Command::Inc(segment) | Command::Dec(segment) => {
+                let comp = match self {
+                    Command::Inc(_) => Comp::MPlusOne,
+                    _ => Comp::MMinusOne,
+                };
+                let commands = [
+                    vec![comment],
+                    segment.get_address_instructions(file_name),
+                    vec![Instruction::assign(Dest::M, comp)],
+                ]
+                .concat();
+                (commands, false, false, None)
+            }
+            Command::PushThatIndirect => {
+                let commands = [
+                    vec![comment],
+                    // アドレスRAM[SP-1]をTHATに設定する
+                    get_1_operand,
+                    vec![
+                        Instruction::assign(Dest::D, Comp::M),
+                        Instruction::a_value(4),
+                        Instruction::assign(Dest::M, Comp::D),
+                    ],
+                    // RAM[アドレス]の値でRAM[SP-1]を置き換える
+                    vec![
+                        Instruction::assign(Dest::A, Comp::D),
+                        Instruction::assign(Dest::D, Comp::M),
+                        Instruction::a_symbol("SP"),
+                        Instruction::assign(Dest::A, Comp::MMinusOne),
+                        Instruction::assign(Dest::M, Comp::D),
+                    ],
+                ]
+                .concat();
+                (commands, false, false, None)
+            }
+            Command::PopThatIndirect => {
+                let commands = vec![
+                    comment,
+                    // 値をpopしてR13に退避する
+                    Instruction::a_symbol("SP"),
+                    Instruction::assign(Dest::AM, Comp::MMinusOne),
+                    Instruction::assign(Dest::D, Comp::M),
+                    Instruction::a_value(13),
+                    Instruction::assign(Dest::M, Comp::D),
+                    // アドレスをpopしてTHATに設定する
+                    Instruction::a_symbol("SP"),
+                    Instruction::assign(Dest::AM, Comp::MMinusOne),
+                    Instruction::assign(Dest::D, Comp::M),
+                    Instruction::a_value(4),
+                    Instruction::assign(Dest::M, Comp::D),
+                    // RAM[THAT]に値を格納する
+                    Instruction::a_value(13),
+                    Instruction::assign(Dest::D, Comp::M),
+                    Instruction::a_value(4),
+                    Instruction::assign(Dest::A, Comp::M),
+                    Instruction::assign(Dest::M, Comp::D),
+                ];
                 (commands, false, false, None)
             }
         }
     }
 }
 
+/// 拡張命令のうちHackの命令に展開すると長くなるもののランタイムルーチン
+/// 使われているものだけを終了用の無限ループの後ろに1回だけ出力する
+/// 呼び出し側はR15にリターンアドレスを設定してジャンプする。オペランドはスタックから取り出し、結果をスタックに積んで戻る
+#[derive(PartialEq, Eq, Debug, Clone, Copy, PartialOrd, Ord)]
+enum RuntimeRoutine {
+    Mul,
+    Div,
+    Shr,
+}
+
+impl RuntimeRoutine {
+    fn from_arithmetic(arithmetic: &ArithmeticCommand) -> Self {
+        match arithmetic {
+            ArithmeticCommand::Mul => Self::Mul,
+            ArithmeticCommand::Div => Self::Div,
+            ArithmeticCommand::Shr => Self::Shr,
+            _ => unreachable!("{:?} is not implemented by a runtime routine", arithmetic),
+        }
+    }
+
+    fn label(&self) -> &'static str {
+        match self {
+            Self::Mul => "__VM.MUL",
+            Self::Div => "__VM.DIV",
+            Self::Shr => "__VM.SHR",
+        }
+    }
+
+    fn instructions(&self) -> Vec<Instruction> {
+        let instructions = match self {
+            // x*y: yの各bitについて、bitが立っていればxを足し、xを左にシフトする
+            Self::Mul => {
+                vec![
+                    Instruction::label("__VM.MUL"),
+                    Instruction::a_symbol("SP"),
+                    Instruction::assign(Dest::AM, Comp::MMinusOne),
+                    Instruction::assign(Dest::D, Comp::M),
+                    Instruction::a_symbol("__VM.y"),
+                    Instruction::assign(Dest::M, Comp::D),
+                    Instruction::a_symbol("SP"),
+                    Instruction::assign(Dest::A, Comp::MMinusOne),
+                    Instruction::assign(Dest::D, Comp::M),
+                    Instruction::a_symbol("__VM.x"),
+                    Instruction::assign(Dest::M, Comp::D),
+                    Instruction::a_symbol("__VM.result"),
+                    Instruction::assign(Dest::M, Comp::Zero),
+                    Instruction::a_symbol("__VM.bit"),
+                    Instruction::assign(Dest::M, Comp::One),
+                    Instruction::label("__VM.MUL_LOOP"),
+                    Instruction::a_symbol("__VM.bit"),
+                    Instruction::assign(Dest::D, Comp::M),
+                    Instruction::a_symbol("__VM.y"),
+                    Instruction::assign(Dest::D, Comp::DAndM),
+                    Instruction::a_symbol("__VM.MUL_SKIP"),
+                    Instruction::jump(Comp::D, Jump::JEQ),
+                    Instruction::a_symbol("__VM.x"),
+                    Instruction::assign(Dest::D, Comp::M),
+                    Instruction::a_symbol("__VM.result"),
+                    Instruction::assign(Dest::M, Comp::DPlusM),
+                    Instruction::label("__VM.MUL_SKIP"),
+                    Instruction::a_symbol("__VM.x"),
+                    Instruction::assign(Dest::D, Comp::M),
+                    Instruction::assign(Dest::M, Comp::DPlusM),
+                    Instruction::a_symbol("__VM.bit"),
+                    Instruction::assign(Dest::D, Comp::M),
+                    Instruction::assign(Dest::M, Comp::DPlusM),
+                    Instruction::assign(Dest::D, Comp::M),
+                    Instruction::a_symbol("__VM.MUL_LOOP"),
+                    Instruction::jump(Comp::D, Jump::JNE),
+                    Instruction::a_symbol("__VM.result"),
+                    Instruction::assign(Dest::D, Comp::M),
+                    Instruction::a_symbol("SP"),
+                    Instruction::assign(Dest::A, Comp::MMinusOne),
+                    Instruction::assign(Dest::M, Comp::D),
+                    Instruction::a_symbol("R15"),
+                    Instruction::assign(Dest::A, Comp::M),
+                    Instruction::jump(Comp::Zero, Jump::JMP),
+                ]
+            }
+            // x/y(0に向かって切り捨て): 絶対値で筆算をしてから符号をつける。y=0の場合は停止する
+            Self::Div => {
+                vec![
+                    Instruction::label("__VM.DIV"),
+                    Instruction::a_symbol("SP"),
+                    Instruction::assign(Dest::AM, Comp::MMinusOne),
+                    Instruction::assign(Dest::D, Comp::M),
+                    Instruction::a_symbol("__VM.y"),
+                    Instruction::assign(Dest::M, Comp::D),
+                    Instruction::a_symbol("END"),
+                    Instruction::jump(Comp::D, Jump::JEQ),
+                    Instruction::a_symbol("SP"),
+                    Instruction::assign(Dest::A, Comp::MMinusOne),
+                    Instruction::assign(Dest::D, Comp::M),
+                    Instruction::a_symbol("__VM.x"),
+                    Instruction::assign(Dest::M, Comp::D),
+                    Instruction::a_symbol("__VM.neg"),
+                    Instruction::assign(Dest::M, Comp::Zero),
+                    Instruction::a_symbol("__VM.x"),
+                    Instruction::assign(Dest::D, Comp::M),
+                    Instruction::a_symbol("__VM.DIV_X_POSITIVE"),
+                    Instruction::jump(Comp::D, Jump::JGE),
+                    Instruction::a_symbol("__VM.x"),
+                    Instruction::assign(Dest::M, Comp::NegM),
+                    Instruction::a_symbol("__VM.neg"),
+                    Instruction::assign(Dest::M, Comp::NotM),
+                    Instruction::label("__VM.DIV_X_POSITIVE"),
+                    Instruction::a_symbol("__VM.y"),
+                    Instruction::assign(Dest::D, Comp::M),
+                    Instruction::a_symbol("__VM.DIV_Y_POSITIVE"),
+                    Instruction::jump(Comp::D, Jump::JGE),
+                    Instruction::a_symbol("__VM.y"),
+                    Instruction::assign(Dest::M, Comp::NegM),
+                    Instruction::a_symbol("__VM.neg"),
+                    Instruction::assign(Dest::M, Comp::NotM),
+                    Instruction::label("__VM.DIV_Y_POSITIVE"),
+                    Instruction::a_symbol("__VM.result"),
+                    Instruction::assign(Dest::M, Comp::Zero),
+                    Instruction::a_symbol("__VM.y"),
+                    Instruction::assign(Dest::D, Comp::M),
+                    Instruction::a_symbol("__VM.DIV_Y_MIN"),
+                    Instruction::jump(Comp::D, Jump::JLT),
+                    Instruction::a_symbol("__VM.r"),
+                    Instruction::assign(Dest::M, Comp::Zero),
+                    Instruction::a_value(16),
+                    Instruction::assign(Dest::D, Comp::A),
+                    Instruction::a_symbol("__VM.bit"),
+                    Instruction::assign(Dest::M, Comp::D),
+                    Instruction::label("__VM.DIV_LOOP"),
+                    Instruction::a_symbol("__VM.r"),
+                    Instruction::assign(Dest::D, Comp::M),
+                    Instruction::assign(Dest::M, Comp::DPlusM),
+                    Instruction::a_symbol("__VM.x"),
+                    Instruction::assign(Dest::D, Comp::M),
+                    Instruction::a_symbol("__VM.DIV_NO_CARRY"),
+                    Instruction::jump(Comp::D, Jump::JGE),
+                    Instruction::a_symbol("__VM.r"),
+                    Instruction::assign(Dest::M, Comp::MPlusOne),
+                    Instruction::label("__VM.DIV_NO_CARRY"),
+                    Instruction::a_symbol("__VM.x"),
+                    Instruction::assign(Dest::D, Comp::M),
+                    Instruction::assign(Dest::M, Comp::DPlusM),
+                    Instruction::a_symbol("__VM.result"),
+                    Instruction::assign(Dest::D, Comp::M),
+                    Instruction::assign(Dest::M, Comp::DPlusM),
+                    Instruction::a_symbol("__VM.r"),
+                    Instruction::assign(Dest::D, Comp::M),
+                    Instruction::a_symbol("__VM.DIV_SUBTRACT"),
+                    Instruction::jump(Comp::D, Jump::JLT),
+                    Instruction::a_symbol("__VM.y"),
+                    Instruction::assign(Dest::D, Comp::DMinusM),
+                    Instruction::a_symbol("__VM.DIV_NEXT"),
+                    Instruction::jump(Comp::D, Jump::JLT),
+                    Instruction::label("__VM.DIV_SUBTRACT"),
+                    Instruction::a_symbol("__VM.y"),
+                    Instruction::assign(Dest::D, Comp::M),
+                    Instruction::a_symbol("__VM.r"),
+                    Instruction::assign(Dest::M, Comp::MMinusD),
+                    Instruction::a_symbol("__VM.result"),
+                    Instruction::assign(Dest::M, Comp::MPlusOne),
+                    Instruction::label("__VM.DIV_NEXT"),
+                    Instruction::a_symbol("__VM.bit"),
+                    Instruction::assign(Dest::M, Comp::MMinusOne),
+                    Instruction::assign(Dest::D, Comp::M),
+                    Instruction::a_symbol("__VM.DIV_LOOP"),
+                    Instruction::jump(Comp::D, Jump::JGT),
+                    Instruction::label("__VM.DIV_SIGN"),
+                    Instruction::a_symbol("__VM.neg"),
+                    Instruction::assign(Dest::D, Comp::M),
+                    Instruction::a_symbol("__VM.DIV_RETURN"),
+                    Instruction::jump(Comp::D, Jump::JEQ),
+                    Instruction::a_symbol("__VM.result"),
+                    Instruction::assign(Dest::M, Comp::NegM),
+                    Instruction::label("__VM.DIV_RETURN"),
+                    Instruction::a_symbol("__VM.result"),
+                    Instruction::assign(Dest::D, Comp::M),
+                    Instruction::a_symbol("SP"),
+                    Instruction::assign(Dest::A, Comp::MMinusOne),
+                    Instruction::assign(Dest::M, Comp::D),
+                    Instruction::a_symbol("R15"),
+                    Instruction::assign(Dest::A, Comp::M),
+                    Instruction::jump(Comp::Zero, Jump::JMP),
+                    Instruction::label("__VM.DIV_Y_MIN"),
+                    Instruction::a_symbol("__VM.x"),
+                    Instruction::assign(Dest::D, Comp::M),
+                    Instruction::a_symbol("__VM.DIV_SIGN"),
+                    Instruction::jump(Comp::D, Jump::JGE),
+                    Instruction::a_symbol("__VM.result"),
+                    Instruction::assign(Dest::M, Comp::One),
+                    Instruction::a_symbol("__VM.DIV_SIGN"),
+                    Instruction::jump(Comp::Zero, Jump::JMP),
+                ]
+            }
+            // x>>1(算術シフト): bit1から15をそれぞれ1つ下のbitに移し、負数なら最上位bitを立てる
+            Self::Shr => {
+                vec![
+                    Instruction::label("__VM.SHR"),
+                    Instruction::a_symbol("SP"),
+                    Instruction::assign(Dest::A, Comp::MMinusOne),
+                    Instruction::assign(Dest::D, Comp::M),
+                    Instruction::a_symbol("__VM.x"),
+                    Instruction::assign(Dest::M, Comp::D),
+                    Instruction::a_symbol("__VM.result"),
+                    Instruction::assign(Dest::M, Comp::Zero),
+                    Instruction::a_symbol("__VM.bit"),
+                    Instruction::assign(Dest::M, Comp::One),
+                    Instruction::a_value(2),
+                    Instruction::assign(Dest::D, Comp::A),
+                    Instruction::a_symbol("__VM.y"),
+                    Instruction::assign(Dest::M, Comp::D),
+                    Instruction::label("__VM.SHR_LOOP"),
+                    Instruction::a_symbol("__VM.x"),
+                    Instruction::assign(Dest::D, Comp::M),
+                    Instruction::a_symbol("__VM.y"),
+                    Instruction::assign(Dest::D, Comp::DAndM),
+                    Instruction::a_symbol("__VM.SHR_SKIP"),
+                    Instruction::jump(Comp::D, Jump::JEQ),
+                    Instruction::a_symbol("__VM.bit"),
+                    Instruction::assign(Dest::D, Comp::M),
+                    Instruction::a_symbol("__VM.result"),
+                    Instruction::assign(Dest::M, Comp::DOrM),
+                    Instruction::label("__VM.SHR_SKIP"),
+                    Instruction::a_symbol("__VM.bit"),
+                    Instruction::assign(Dest::D, Comp::M),
+                    Instruction::assign(Dest::M, Comp::DPlusM),
+                    Instruction::a_symbol("__VM.y"),
+                    Instruction::assign(Dest::D, Comp::M),
+                    Instruction::assign(Dest::M, Comp::DPlusM),
+                    Instruction::assign(Dest::D, Comp::M),
+                    Instruction::a_symbol("__VM.SHR_LOOP"),
+                    Instruction::jump(Comp::D, Jump::JNE),
+                    Instruction::a_symbol("__VM.x"),
+                    Instruction::assign(Dest::D, Comp::M),
+                    Instruction::a_symbol("__VM.SHR_RETURN"),
+                    Instruction::jump(Comp::D, Jump::JGE),
+                    Instruction::a_value(32767),
+                    Instruction::assign(Dest::D, Comp::NotA),
+                    Instruction::a_symbol("__VM.result"),
+                    Instruction::assign(Dest::M, Comp::DOrM),
+                    Instruction::label("__VM.SHR_RETURN"),
+                    Instruction::a_symbol("__VM.result"),
+                    Instruction::assign(Dest::D, Comp::M),
+                    Instruction::a_symbol("SP"),
+                    Instruction::assign(Dest::A, Comp::MMinusOne),
+                    Instruction::assign(Dest::M, Comp::D),
+                    Instruction::a_symbol("R15"),
+                    Instruction::assign(Dest::A, Comp::M),
+                    Instruction::jump(Comp::Zero, Jump::JMP),
+                ]
+            }
+        };
+        [vec![Instruction::comment(format!("runtime {:?}", self))], instructions].concat()
+    }
+}
+
+// label, goto, if-goto のラベル名は関数内でのみ有効なので`{関数名}${ラベル名}`に変換する
+// 関数の外(07のテストのような関数定義のないファイル)ではそのまま使う
+pub(crate) fn scoped_label_name(current_function_name: &str, label_name: &str) -> String {
+    if current_function_name.is_empty() {
+        label_name.to_string()
+    } else {
+        format!("{}${}", current_function_name, label_name)
+    }
+}
+
 #[derive(PartialEq, Eq, Debug, Clone)]
-enum ArithmeticCommand {
+pub(crate) enum ArithmeticCommand {
     Add,
     Sub,
     Neg,
@@ -588,10 +1364,15 @@ enum ArithmeticCommand {
     And,
     Or,
     Not,
+    // 以下は拡張命令(shl, shrは1bitの算術シフト)
+    Mul,
+    Div,
+    Shl,
+    Shr,
 }
 
 #[derive(PartialEq, Eq, Debug, Clone)]
-enum Segment {
+pub(crate) enum Segment {
     Argument(u32),
     Local(u32),
     Static(u32),
@@ -608,7 +1389,8 @@ impl Segment {
             "argument" => Some(Self::Argument(index)),
             "local" => Some(Self::Local(index)),
             "static" => Some(Self::Static(index)),
-            "constant" => Some(Self::Constant(index)),
+            // A命令で扱える15bitの範囲を超える定数は不正
+            "constant" if index <= MAX_ADDRESS_VALUE as u32 => Some(Self::Constant(index)),
             "this" => Some(Self::This(index)),
             "that" => Some(Self::That(index)),
             "pointer" => Some(Self::Pointer(index)),
@@ -618,45 +1400,36 @@ impl Segment {
     }
 
     // Segmentの実アドレスを返す命令群を返す
-    fn get_address_instructions(&self, file_name: &str) -> Vec<String> {
+    fn get_address_instructions(&self, file_name: &str) -> Vec<Instruction> {
+        // RAM[base]に格納されたベースアドレスからindexだけ進めたアドレスをAに設定する
+        let offset_from = |base: u16, index: u32| {
+            [
+                vec![Instruction::a_value(base), Instruction::assign(Dest::A, Comp::M)],
+                vec![Instruction::assign(Dest::A, Comp::APlusOne); index as usize],
+            ]
+            .concat()
+        };
         match self {
             Self::Argument(index) => {
-                // format!("@{}", index).as_str(), "A=D+A" のようにすれば対象のアドレスを取得できるが意図的にA=A+1の繰り返しで処理している。
+                // `@index`, `A=D+A` のようにすれば対象のアドレスを取得できるが意図的にA=A+1の繰り返しで処理している。
                 // Dレジスタを使ってしまうとpopの処理時にSPの値を記憶しておくことができなくなってしまうため。
                 [
-                    vec![format!("// argument {}", index).as_str(), "@2", "A=M"],
-                    vec!["A=A+1"; *index as usize],
+                    vec![Instruction::comment(format!("argument {}", index))],
+                    offset_from(2, *index),
                 ]
                 .concat()
-                .iter()
-                .map(|c| c.to_string())
-                .collect::<Vec<String>>()
             }
-            Self::Local(index) => [vec!["@1", "A=M"], vec!["A=A+1"; *index as usize]]
-                .concat()
-                .into_iter()
-                .map(|c| c.to_string())
-                .collect::<Vec<String>>(),
-            Self::Static(index) => vec![format!("@{}.{}", file_name, index)],
-            Self::Constant(value) => [format!("@{}", value).as_str()]
-                .iter()
-                .map(|c| c.to_string())
-                .collect::<Vec<String>>(),
-            Self::This(index) => [vec!["@3", "A=M"], vec!["A=A+1"; *index as usize]]
-                .concat()
-                .into_iter()
-                .map(|c| c.to_string())
-                .collect::<Vec<String>>(),
-            Self::That(index) => [vec!["@4", "A=M"], vec!["A=A+1"; *index as usize]]
-                .concat()
-                .into_iter()
-                .map(|c| c.to_string())
-                .collect::<Vec<String>>(),
+            Self::Local(index) => offset_from(1, *index),
+            Self::Static(index) => vec![Instruction::a_symbol(format!("{}.{}", file_name, index))],
+            // Segment::newで15bitに収まる値であることを確認している
+            Self::Constant(value) => vec![Instruction::a_value(*value as u16)],
+            Self::This(index) => offset_from(3, *index),
+            Self::That(index) => offset_from(4, *index),
             Self::Pointer(index) => {
                 if *index == 0 {
-                    vec!["@3".to_string()]
+                    vec![Instruction::a_value(3)]
                 } else if *index == 1 {
-                    vec!["@4".to_string()]
+                    vec![Instruction::a_value(4)]
                 } else {
                     vec![]
                 }
@@ -665,25 +1438,22 @@ impl Segment {
                 if 7 < *index {
                     vec![]
                 } else {
-                    [vec!["@5"], vec!["A=A+1"; *index as usize]]
-                        .concat()
-                        .into_iter()
-                        .map(|c| c.to_string())
-                        .collect::<Vec<String>>()
+                    [
+                        vec![Instruction::a_value(5)],
+                        vec![Instruction::assign(Dest::A, Comp::APlusOne); *index as usize],
+                    ]
+                    .concat()
                 }
             }
         }
-        .iter()
-        .map(|c| c.to_string())
-        .collect()
     }
 
-    // そのセグメントのデータが格納されているレジスタ名を返す(AまたはD)
-    fn get_value_register_name(&self) -> String {
+    // そのセグメントのデータが格納されているレジスタ(AまたはM)を返す
+    fn get_value_comp(&self) -> Comp {
         if let Self::Constant(_) = self {
-            "A".to_string()
+            Comp::A
         } else {
-            "M".to_string()
+            Comp::M
         }
     }
 }
@@ -693,6 +1463,16 @@ mod test {
     use super::*;
     use pretty_assertions::assert_eq;
 
+    fn source_lines(lines: &[(&str, usize)]) -> Vec<SourceLine> {
+        lines
+            .iter()
+            .map(|(text, line_number)| SourceLine {
+                line_number: *line_number,
+                text: text.to_string(),
+            })
+            .collect()
+    }
+
     #[test]
     fn test_vm_program_new() {
         // push, pop, add
@@ -734,7 +1514,8 @@ push temp 6
 add
                 "#
                 .to_string(),
-            ),
+            )
+            .unwrap(),
             VMProgram {
                 commands: vec![
                     Command::Push(Segment::Constant(10)),
@@ -763,6 +1544,33 @@ add
                     Command::Push(Segment::Temp(6)),
                     Command::Arithmetic(ArithmeticCommand::Add),
                 ],
+                source_lines: source_lines(&[
+                    ("push constant 10", 9),
+                    ("pop local 0", 10),
+                    ("push constant 21", 11),
+                    ("push constant 22", 12),
+                    ("pop argument 2", 13),
+                    ("pop argument 1", 14),
+                    ("push constant 36", 15),
+                    ("pop this 6", 16),
+                    ("push constant 42", 17),
+                    ("push constant 45", 18),
+                    ("pop that 5", 19),
+                    ("pop that 2", 20),
+                    ("push constant 510", 21),
+                    ("pop temp 6", 22),
+                    ("push local 0", 23),
+                    ("push that 5", 24),
+                    ("add", 25),
+                    ("push argument 1", 26),
+                    ("sub", 27),
+                    ("push this 6", 28),
+                    ("push this 6", 29),
+                    ("add", 30),
+                    ("sub", 31),
+                    ("push temp 6", 32),
+                    ("add", 33),
+                ]),
                 label_id: 0,
                 file_name: "foo.vm".to_string(),
                 return_address_id: 0,
@@ -783,7 +1591,8 @@ call f_name 2
 return
                 "#
                 .to_string(),
-            ),
+            )
+            .unwrap(),
             VMProgram {
                 commands: vec![
                     Command::Label("LOOP".to_string()),
@@ -793,11 +1602,276 @@ return
                     Command::Call("f_name".to_string(), 2),
                     Command::Return,
                 ],
+                source_lines: source_lines(&[
+                    ("label LOOP", 2),
+                    ("goto LOOP", 3),
+                    ("if-goto LOOP", 4),
+                    ("function f_name 2", 5),
+                    ("call f_name 2", 6),
+                    ("return", 7),
+                ]),
                 label_id: 0,
                 file_name: "foo.vm".to_string(),
                 return_address_id: 0,
                 current_function_name: String::new(),
             }
         );
+
+        // 15bitに収まらない定数や未知のコマンドは読み飛ばさずに行番号付きのエラーにする
+        assert_eq!(
+            VMProgram::new("Main".to_string(), "push constant 1\npush constant 40000\nadd".to_string()).err(),
+            Some(TranslateError::InvalidCommand {
+                file_name: "Main".to_string(),
+                line_number: 2,
+                command: "push constant 40000".to_string(),
+            })
+        );
+        assert_eq!(
+            VMProgram::new("Main".to_string(), "\n  push local\n".to_string())
+                .err()
+                .map(|e| e.to_string()),
+            Some("Main.vm:2: invalid command `push local`".to_string())
+        );
+        assert!(VMProgram::new("Main".to_string(), "pusj constant 1".to_string()).is_err());
+    }
+
+    #[test]
+    fn test_combine_and_assemble_bootstrap() {
+        let programs = || {
+            vec![
+                VMProgram::new("Main".to_string(), "function Main.main 0\npush constant 0\nreturn".to_string())
+                    .unwrap(),
+                VMProgram::new("Sys".to_string(), "function Sys.init 0\ncall Main.main 0\nreturn".to_string()).unwrap(),
+            ]
+        };
+
+        // ブートストラップなし
+        let result = VMProgram::combine_and_assemble(programs(), None).unwrap();
+        assert_eq!(result.contains("// init"), false);
+        assert_eq!(result.starts_with("// body"), true);
+
+        // デフォルトではSPを256に設定してSys.initを呼び出す
+        let result = VMProgram::combine_and_assemble(programs(), Some(Bootstrap::default())).unwrap();
+        assert_eq!(result.starts_with("// init\n@256\nD=A\n@SP\nM=D"), true);
+        assert_eq!(result.contains("@Sys.init\n0;JMP"), true);
+        assert_eq!(result.contains("(Bootstrap$ret.0)"), true);
+
+        // スタックのベースアドレスとエントリポイントを指定
+        let bootstrap = Bootstrap {
+            stack_base: 300,
+            entry_point: "Main.main".to_string(),
+        };
+        let result = VMProgram::combine_and_assemble(programs(), Some(bootstrap)).unwrap();
+        assert_eq!(result.starts_with("// init\n@300\nD=A\n@SP\nM=D"), true);
+        assert_eq!(result.contains("@Main.main\n0;JMP"), true);
+
+        // エントリポイントが定義されていない
+        let bootstrap = Bootstrap {
+            stack_base: 256,
+            entry_point: "Main.run".to_string(),
+        };
+        assert_eq!(
+            VMProgram::combine_and_assemble(programs(), Some(bootstrap)),
+            Err(TranslateError::EntryPointNotFound("Main.run".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_function_table() {
+        // 関数名のprefixとファイル名が一致していなくても定義されているファイルを特定できる
+        let programs = vec![
+            VMProgram::new("Main".to_string(), "function Main.main 0\ncall Util.run 0\nreturn".to_string()).unwrap(),
+            VMProgram::new("Helper".to_string(), "function Util.run 0\npush constant 0\nreturn".to_string()).unwrap(),
+        ];
+        let table = FunctionTable::new(&programs).unwrap();
+        assert_eq!(table.get("Main.main"), Some(&"Main".to_string()));
+        assert_eq!(table.get("Util.run"), Some(&"Helper".to_string()));
+        assert_eq!(table.get("Helper.run"), None);
+
+        let result = VMProgram::combine_and_assemble(programs, None).unwrap();
+        assert_eq!(result.contains("(Util.run)"), true);
+        assert_eq!(result.contains("@Util.run\n0;JMP"), true);
+        assert_eq!(result.contains("(Main.main$ret.0)"), true);
+
+        // 同じ関数が複数のファイルで定義されている
+        let programs = vec![
+            VMProgram::new("Foo".to_string(), "function Foo.f 0\nreturn".to_string()).unwrap(),
+            VMProgram::new("Bar".to_string(), "function Foo.f 0\nreturn".to_string()).unwrap(),
+        ];
+        assert_eq!(
+            FunctionTable::new(&programs),
+            Err(TranslateError::DuplicateFunction {
+                function_name: "Foo.f".to_string(),
+                file_names: ("Foo".to_string(), "Bar".to_string()),
+            })
+        );
+
+        // 定義されていない関数を呼び出している
+        let programs =
+            vec![VMProgram::new("Foo".to_string(), "function Foo.f 0\ncall Bar.g 0\nreturn".to_string()).unwrap()];
+        assert_eq!(
+            VMProgram::combine_and_assemble(programs, None),
+            Err(TranslateError::UndefinedFunction {
+                function_name: "Bar.g".to_string(),
+                file_name: "Foo".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn test_scoped_labels() {
+        // 関数内のラベルは関数名でスコープされるので、別の関数の同名ラベルと衝突しない
+        let programs = vec![
+            VMProgram::new("Foo".to_string(), "function Foo.f 0\nlabel LOOP\ngoto LOOP".to_string()).unwrap(),
+            VMProgram::new("Bar".to_string(), "function Bar.g 0\nlabel LOOP\nif-goto LOOP\neq".to_string()).unwrap(),
+        ];
+        let result = VMProgram::combine_and_assemble(programs, None).unwrap();
+        assert_eq!(result.contains("(Foo.f$LOOP)\n// GoTo(\"LOOP\")\n@Foo.f$LOOP\n0;JMP"), true);
+        assert_eq!(result.contains("(Bar.g$LOOP)"), true);
+        assert_eq!(result.contains("@Bar.g$LOOP\nD;JNE"), true);
+        // eq等で生成するラベルはファイル名でスコープされる
+        assert_eq!(result.contains("(Bar.TRUE_00000)"), true);
+
+        // 関数の外ではラベル名をそのまま使う
+        let programs = vec![VMProgram::new("Foo".to_string(), "label LOOP\ngoto LOOP".to_string()).unwrap()];
+        let result = VMProgram::combine_and_assemble(programs, None).unwrap();
+        assert_eq!(result.contains("(LOOP)\n// GoTo(\"LOOP\")\n@LOOP\n0;JMP"), true);
+    }
+
+    #[test]
+    fn test_extended_commands() {
+        let program = VMProgram::new(
+            "Main".to_string(),
+            r#"
+push constant 6
+push constant 7
+mul
+dup
+shr
+swap
+pop that *
+inc local 2
+dec static 0
+push that *
+"#
+            .to_string(),
+        )
+        .unwrap();
+        assert_eq!(
+            program.commands,
+            vec![
+                Command::Push(Segment::Constant(6)),
+                Command::Push(Segment::Constant(7)),
+                Command::Arithmetic(ArithmeticCommand::Mul),
+                Command::Dup,
+                Command::Arithmetic(ArithmeticCommand::Shr),
+                Command::Swap,
+                Command::PopThatIndirect,
+                Command::Inc(Segment::Local(2)),
+                Command::Dec(Segment::Static(0)),
+                Command::PushThatIndirect,
+            ]
+        );
+        assert_eq!(
+            program.check_standard_commands(),
+            Err(TranslateError::ExtendedCommand {
+                file_name: "Main".to_string(),
+                line_number: 4,
+                command: "mul".to_string(),
+            })
+        );
+        // constantセグメントは書き換えられない
+        assert_eq!(
+            VMProgram::new("Main".to_string(), "inc constant 1".to_string()).err(),
+            Some(TranslateError::InvalidCommand {
+                file_name: "Main".to_string(),
+                line_number: 1,
+                command: "inc constant 1".to_string(),
+            })
+        );
+
+        // 使われているランタイムルーチンだけが1回ずつ出力される
+        let (instructions, _) = VMProgram::combine(vec![program], None, false, None).unwrap();
+        let labels = instructions
+            .iter()
+            .filter_map(|i| match i {
+                Instruction::Label(label) if matches!(label.as_str(), "__VM.MUL" | "__VM.DIV" | "__VM.SHR") => {
+                    Some(label.as_str())
+                }
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(labels, vec!["__VM.MUL", "__VM.SHR"]);
+    }
+
+    #[test]
+    fn test_source_map() {
+        let programs = vec![VMProgram::new(
+            "Main".to_string(),
+            r#"
+function Main.main 0
+    push constant 7   
+label LOOP
+goto LOOP
+            "#
+            .to_string(),
+        )
+        .unwrap()];
+        let bootstrap = Bootstrap {
+            stack_base: 256,
+            entry_point: "Main.main".to_string(),
+        };
+        let (assembly, source_map) =
+            VMProgram::combine_and_assemble_with_source_map(programs, Some(bootstrap), true, None).unwrap();
+
+        // ブートストラップ(SPの初期化4命令 + call)の後にMain.mainが配置される
+        let bootstrap_length = count_instructions(
+            &Command::Call("Main.main".to_string(), 0)
+                .to_commands("Main", 0, 0, "Bootstrap")
+                .0,
+        ) + 4;
+        let entries = source_map
+            .entries
+            .iter()
+            .map(|e| (e.rom_address, e.line_number, e.function_name.as_str(), e.command.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            entries,
+            vec![
+                (bootstrap_length, 2, "Main.main", "function Main.main 0"),
+                (bootstrap_length, 3, "Main.main", "push constant 7"),
+                (bootstrap_length + 7, 4, "Main.main", "label LOOP"),
+                (bootstrap_length + 7, 5, "Main.main", "goto LOOP"),
+            ]
+        );
+        assert_eq!(source_map.entries.iter().map(|e| e.length).collect::<Vec<_>>(), vec![0, 7, 0, 2]);
+        assert_eq!(source_map.entries[1].file_name, "Main.vm");
+
+        // 注釈付きの出力では元の.vmファイルの行がコメントとして出力される
+        assert!(assembly.contains("// Main.vm:3: push constant 7\n@7\n"));
+        assert!(!assembly.contains("// Push(Constant(7))"));
+    }
+
+    #[test]
+    fn test_combine_to_instructions() {
+        let programs = || {
+            vec![VMProgram::new(
+                "Main".to_string(),
+                "function Main.main 1\npush constant 7\npush static 0\nlt\nreturn".to_string(),
+            )
+            .unwrap()]
+        };
+        let bootstrap = Bootstrap {
+            stack_base: 256,
+            entry_point: "Main.main".to_string(),
+        };
+        let (instructions, _) = VMProgram::combine(programs(), Some(bootstrap.clone()), false, None).unwrap();
+        // 命令列をテキストにしたものは.asmの出力と一致する
+        assert_eq!(Ok(to_text(&instructions)), VMProgram::combine_and_assemble(programs(), Some(bootstrap)));
+        // アセンブラはテキストを経由せずに命令列をそのまま機械語に変換できる
+        assert_eq!(
+            assembler::ParseHackResult::from_instructions(&instructions).unwrap(),
+            assembler::ParseHackResult::new(to_text(&instructions)).unwrap()
+        );
     }
 }
@@ -1,156 +1,351 @@
-use std::arch::aarch64::uint8x8_t;
+use crate::compiler::diagnostics::Diagnostic;
 
 #[derive(Debug, PartialEq, Eq)]
 pub struct Tokens {
     pub tokens: Vec<Token>,
-    parsing_token: String,
-    parsing_string_const: bool,
-    current_comment_type: Option<CurrentCommentType>,
+    /// `tokens`と同じ並びで各トークンのソース上の位置を保持する
+    pub spans: Vec<Span>,
+    /// 字句解析のエラー。エラーがあってもトークナイズは最後まで続ける
+    pub errors: Vec<Diagnostic>,
+    /// トークナイズしたときの言語仕様。パーサはこれを見てJack+の構文を受け付けるか決める
+    pub dialect: Dialect,
 }
 
-#[derive(Debug, PartialEq, Eq)]
-enum CurrentCommentType {
-    /// `// comment`形式のコメント(行末まで)
-    Normal,
-    /// `/* multi \n line \n comment */`形式のコメント(1行/複数行両方のパターンがある)
-    /// 厳密にはAPIコメントもあるが、`//* comment */`という形式なのでMultiLineコメントの一種として扱ってしまう
-    MultiLine,
+/// 言語仕様。デフォルトは標準のJack
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum Dialect {
+    #[default]
+    Standard,
+    /// for文, break/continue, else if, 複合代入(`+=`など), 短絡評価の`&&`, `||`,
+    /// 文字定数(`'A'`), 16進数と2進数の整数定数(`0x7FFF`, `0b1010`), クラスの定数(`const`, `enum`)を加えた拡張
+    JackPlus,
+}
+
+/// トークンのソース上の位置。行と列は1始まりで、列は文字単位で数える
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Span {
+    pub line: usize,
+    pub column: usize,
+    pub length: usize,
 }
 
+// Jackの整数定数の上限
+const INTEGER_CONSTANT_MAX: u32 = 32767;
+// Jack+の16進数, 2進数の定数の上限。16bitの値をそのまま書けるようにする
+const RADIX_CONSTANT_MAX: u32 = 0xFFFF;
+
+// Hackの文字セットのうち、印字可能な文字(32..=126)以外でエスケープで書けるキー
+// `\n`: newLine(128), `\b`: backSpace(129)
+const HACK_NEW_LINE: char = '\u{80}';
+const HACK_BACKSPACE: char = '\u{81}';
+
 impl Tokens {
-    // トークナイズする関数
-    // NOTE:
-    // リファクタの余地はありそうだが今は本書の内容の理解に優先的に時間を使いたい。
-    // そのためテストさえ通っていればOKとして今はリファクタ（とその検討）をしない。
     pub fn new(source_code: String) -> Self {
-        let mut tokens = Tokens {
-            tokens: vec![],
-            parsing_token: "".to_string(),
-            parsing_string_const: false,
-            current_comment_type: None,
+        Self::with_dialect(source_code, Dialect::Standard)
+    }
+
+    // Jack+では`for`, `break`, `continue`, `const`, `enum`をキーワードとして、`&&`, `||`を1つの記号として扱う。
+    // 標準のJackではただの識別子と、`&`, `|`の並び
+    pub fn with_dialect(source_code: String, dialect: Dialect) -> Self {
+        let mut lexer = Lexer {
+            chars: source_code.chars().peekable(),
+            line: 1,
+            column: 1,
+            tokens: Tokens {
+                tokens: vec![],
+                spans: vec![],
+                errors: vec![],
+                dialect,
+            },
         };
+        lexer.run();
+        lexer.tokens
+    }
 
-        for (index, char) in source_code.chars().enumerate() {
-            // コメント終端判定
-            match &tokens.current_comment_type {
-                Some(CurrentCommentType::Normal) => {
-                    // Normalコメントは行末にきたら終了する
-                    if Self::is_end_of_line(char) {
-                        tokens.current_comment_type = None;
-                    }
-                    continue;
+    // index番目のトークンの位置を返す。トークン列の終端を指している場合は最後のトークンの直後の位置を返す
+    pub fn span_at(&self, index: usize) -> Span {
+        match (self.spans.get(index), self.spans.last()) {
+            (Some(span), _) => *span,
+            (None, Some(last)) => Span {
+                line: last.line,
+                column: last.column + last.length,
+                length: 1,
+            },
+            (None, None) => Span {
+                line: 1,
+                column: 1,
+                length: 1,
+            },
+        }
+    }
+
+    pub fn to_xml(&self) -> String {
+        let mut result = vec!["<tokens>".to_string()];
+        for t in &self.tokens {
+            result.push(t.to_xml());
+        }
+        result.push("</tokens>".to_string());
+        result.join("\n")
+    }
+}
+
+// ソースを1文字ずつ先読みしながら読み進める字句解析器。
+// 各文字は一度しか読まないので入力の長さに対して線形に動く
+struct Lexer<'a> {
+    chars: std::iter::Peekable<std::str::Chars<'a>>,
+    // 次に読む文字の位置
+    line: usize,
+    column: usize,
+    tokens: Tokens,
+}
+
+impl Lexer<'_> {
+    fn run(&mut self) {
+        while let Some(&c) = self.chars.peek() {
+            let start = (self.line, self.column);
+            match c {
+                _ if c.is_whitespace() => {
+                    self.bump();
                 }
-                Some(CurrentCommentType::MultiLine) => {
-                    if let Some(sym) = Symbol::new(char) {
-                        if matches!(sym, Symbol::Slash) {
-                            if let Some(got) = source_code.chars().nth(index - 1) {
-                                if let Some(Symbol::Asterisk) = Symbol::new(got) {
-                                    // `*/`だったらMultiLineコメント終了
-                                    tokens.current_comment_type = None;
-                                    continue;
-                                }
-                            }
-                        }
+                '/' => {
+                    self.bump();
+                    match self.chars.peek() {
+                        Some('/') => self.line_comment(),
+                        // `/** API comment */`も`/*`から始まるのでここで扱う
+                        Some('*') => self.block_comment(start),
+                        _ => self.push(Token::Sym(Symbol::Slash), start),
                     }
-                    continue;
                 }
-                _ => {}
-            }
-
-            if let Some(c) = Symbol::new(char) {
-                if !tokens.parsing_string_const {
-                    // コメント開始判定
-                    // 上で判定しているのでcurrent_comment_type == Noneなことを前提にできる
-                    if let Symbol::Slash = c {
-                        if let Some(got) = source_code.chars().nth(index + 1) {
-                            if let Some(Symbol::Slash) = Symbol::new(got) {
-                                // `/`が連続していたらNormalコメント開始
-                                tokens.current_comment_type = Some(CurrentCommentType::Normal);
-                                continue;
-                            }
-                        }
-                        if let Some(got) = source_code.chars().nth(index + 1) {
-                            if let Some(Symbol::Asterisk) = Symbol::new(got) {
-                                // `/*`だったらMultiLineコメント開始
-                                tokens.current_comment_type = Some(CurrentCommentType::MultiLine);
-                                continue;
-                            }
+                '"' => self.string_constant(start),
+                '\'' if self.tokens.dialect == Dialect::JackPlus => self.char_constant(start),
+                _ if c.is_ascii_digit() => self.integer_constant(start),
+                _ if c.is_ascii_alphabetic() || c == '_' => self.keyword_or_identifier(start),
+                '&' | '|' if self.tokens.dialect == Dialect::JackPlus => {
+                    self.bump();
+                    let sym = match (c, self.chars.peek()) {
+                        ('&', Some('&')) => Some(Symbol::DoubleAmpersand),
+                        ('|', Some('|')) => Some(Symbol::DoublePipe),
+                        _ => None,
+                    };
+                    match sym {
+                        Some(sym) => {
+                            self.bump();
+                            self.push(Token::Sym(sym), start);
                         }
+                        None => self.push(Token::Sym(Symbol::new(c).unwrap()), start),
                     }
-                    // コメント開始ではなかったのでSymbolとしてpush
-                    if !tokens.parsing_token.is_empty() {
-                        let token = Self::parse_as_keyword_or_identifier(tokens.parsing_token.clone());
-                        tokens.push_token(token);
+                }
+                _ => {
+                    self.bump();
+                    match Symbol::new(c) {
+                        Some(sym) => self.push(Token::Sym(sym), start),
+                        None => self.error(start, 1, format!("unexpected character `{}`", c)),
                     }
-                    tokens.tokens.push(Token::Sym(c));
-                    continue;
                 }
             }
+        }
+    }
 
-            // `"`のハンドリング
-            if char == '"' {
-                // 文字列の始点
-                if !tokens.parsing_string_const {
-                    tokens.toggle_is_string_const();
-                } else {
-                    // 文字列の終端
-                    let token = Token::StringConstant(StringConstant(tokens.parsing_token.clone()));
-                    tokens.push_token(token);
-                    tokens.toggle_is_string_const();
-                }
-                continue;
+    // 1文字読み進めて位置を更新する。CRLFの`\r`は列に数えない
+    fn bump(&mut self) -> Option<char> {
+        let c = self.chars.next()?;
+        match c {
+            '\n' => {
+                self.line += 1;
+                self.column = 1;
             }
+            '\r' => {}
+            _ => self.column += 1,
+        }
+        Some(c)
+    }
 
-            if char.is_whitespace() {
-                if tokens.parsing_string_const {
-                    tokens.parsing_token += &char.to_string();
-                    continue;
-                }
-                if !tokens.parsing_token.is_empty() {
-                    let token = Self::parse_as_keyword_or_identifier(tokens.parsing_token.clone());
-                    tokens.push_token(token);
-                    continue;
-                }
-                continue;
+    fn bump_while(&mut self, predicate: impl Fn(char) -> bool) -> String {
+        let mut result = String::new();
+        while let Some(&c) = self.chars.peek() {
+            if !predicate(c) {
+                break;
             }
-
-            tokens.parsing_token += &char.to_string();
+            result.push(c);
+            self.bump();
         }
+        result
+    }
 
-        tokens
+    fn push(&mut self, token: Token, start: (usize, usize)) {
+        // 複数行にまたがるトークンはないので、開始位置からの列の差を長さにする
+        self.tokens.spans.push(Span {
+            line: start.0,
+            column: start.1,
+            length: self.column - start.1,
+        });
+        self.tokens.tokens.push(token);
     }
 
-    fn toggle_is_string_const(&mut self) {
-        self.parsing_string_const = !self.parsing_string_const;
+    fn error(&mut self, start: (usize, usize), length: usize, message: String) {
+        let span = Span {
+            line: start.0,
+            column: start.1,
+            length,
+        };
+        self.tokens.errors.push(Diagnostic::new(span, message));
     }
 
-    #[allow(dead_code)]
-    pub fn to_xml(&self) -> String {
-        let mut result = vec!["<tokens>".to_string()];
-        for t in &self.tokens {
-            result.push(t.to_xml());
+    // `//`から行末までを読み飛ばす。改行は次の空白として読む
+    fn line_comment(&mut self) {
+        self.bump_while(|c| c != '\n');
+    }
+
+    // `/*`から`*/`までを読み飛ばす。最初の`/`は読み終えている
+    fn block_comment(&mut self, start: (usize, usize)) {
+        self.bump();
+        while let Some(c) = self.bump() {
+            if c == '*' && self.chars.peek() == Some(&'/') {
+                self.bump();
+                return;
+            }
+        }
+        self.error(start, 2, "unterminated comment".to_string());
+    }
+
+    // `"`で囲まれた文字列定数を読む。Jackの文字列は改行を含められない。
+    // `\"`, `\\`, `\n`, `\b`はエスケープとして扱い、それ以外の`\`はそのままの文字として残す。
+    // 値はHackの文字セットに含まれる文字だけにする
+    fn string_constant(&mut self, start: (usize, usize)) {
+        self.bump();
+        let mut value = String::new();
+        loop {
+            match self.chars.peek() {
+                Some('"') => {
+                    self.bump();
+                    self.push(Token::StringConstant(StringConstant(value)), start);
+                    return;
+                }
+                Some('\n' | '\r') | None => {
+                    let length = self.column - start.1;
+                    self.error(start, length, "unterminated string constant".to_string());
+                    return;
+                }
+                Some('\\') => {
+                    self.bump();
+                    let escaped = match self.chars.peek() {
+                        Some(&c @ ('"' | '\\')) => Some(c),
+                        Some('n') => Some(HACK_NEW_LINE),
+                        Some('b') => Some(HACK_BACKSPACE),
+                        _ => None,
+                    };
+                    match escaped {
+                        Some(c) => {
+                            self.bump();
+                            value.push(c);
+                        }
+                        None => value.push('\\'),
+                    }
+                }
+                Some(&c) => {
+                    let position = (self.line, self.column);
+                    self.bump();
+                    if !(' '..='~').contains(&c) {
+                        let message = format!("character {:?} is not in the Hack character set", c);
+                        self.error(position, 1, message);
+                    }
+                    value.push(c);
+                }
+            }
         }
-        result.push("</tokens>".to_string());
-        result.join("\n")
     }
 
-    fn parse_as_keyword_or_identifier(token: String) -> Token {
-        if let Some(k) = Keyword::new(token.clone()) {
-            Token::Key(k)
-        } else if let Ok(num) = token.clone().parse::<u32>() {
-            Token::IntegerConstant(IntegerConstant(num))
-        } else {
-            Token::Identifier(Identifier(token))
+    // `'`で囲まれた文字定数を読む。エスケープは文字列定数と同じだが、`\"`の代わりに`\'`を使う
+    fn char_constant(&mut self, start: (usize, usize)) {
+        self.bump();
+        let value = match self.chars.peek() {
+            Some('\\') => {
+                self.bump();
+                match self.chars.peek() {
+                    Some(&c @ ('\'' | '\\')) => {
+                        self.bump();
+                        Some(c)
+                    }
+                    Some('n') => {
+                        self.bump();
+                        Some(HACK_NEW_LINE)
+                    }
+                    Some('b') => {
+                        self.bump();
+                        Some(HACK_BACKSPACE)
+                    }
+                    _ => None,
+                }
+            }
+            Some('\'' | '\n' | '\r') | None => None,
+            Some(_) => self.bump(),
+        };
+
+        match (value, self.chars.peek()) {
+            (Some(c), Some('\'')) => {
+                self.bump();
+                if (' '..='~').contains(&c) || c == HACK_NEW_LINE || c == HACK_BACKSPACE {
+                    self.push(Token::CharConstant(CharConstant(c)), start);
+                } else {
+                    let message = format!("character {:?} is not in the Hack character set", c);
+                    self.error(start, self.column - start.1, message);
+                }
+            }
+            _ => {
+                // 同じ行の閉じる`'`までを1つの不正な文字定数として読み飛ばす
+                self.bump_while(|c| !matches!(c, '\'' | '\n' | '\r'));
+                if self.chars.peek() == Some(&'\'') {
+                    self.bump();
+                }
+                let length = self.column - start.1;
+                self.error(start, length, "invalid character constant".to_string());
+            }
         }
     }
 
-    fn push_token(&mut self, token: Token) {
-        self.tokens.push(token);
-        self.parsing_token = String::new();
+    // 10進数の整数定数を読む。Jack+では`0x`, `0b`から始まる16進数, 2進数も読む。
+    // 単項の`-`の直後に限り32768を受け付けて、16bitで表せる最小の値`-32768`を書けるようにする
+    fn integer_constant(&mut self, start: (usize, usize)) {
+        let digits = self.bump_while(|c| c.is_ascii_digit());
+        if self.tokens.dialect == Dialect::JackPlus && digits == "0" {
+            if let Some(&prefix @ ('x' | 'b')) = self.chars.peek() {
+                self.bump();
+                let body = self.bump_while(|c| c.is_ascii_alphanumeric() || c == '_');
+                let text = format!("0{}{}", prefix, body);
+                let radix = if prefix == 'x' { 16 } else { 2 };
+                match u32::from_str_radix(&body, radix) {
+                    Ok(n) if n <= RADIX_CONSTANT_MAX => self.push(Token::IntegerConstant(IntegerConstant(n)), start),
+                    Ok(_) => {
+                        let message = format!("integer constant `{}` is out of range (0..=0xFFFF)", text);
+                        self.error(start, text.chars().count(), message);
+                    }
+                    Err(_) => {
+                        let message = format!("invalid integer constant `{}`", text);
+                        self.error(start, text.chars().count(), message);
+                    }
+                }
+                return;
+            }
+        }
+        let after_minus = self.tokens.tokens.last() == Some(&Token::Sym(Symbol::Minus));
+        match digits.parse::<u32>() {
+            Ok(n) if n <= INTEGER_CONSTANT_MAX || (n == INTEGER_CONSTANT_MAX + 1 && after_minus) => {
+                self.push(Token::IntegerConstant(IntegerConstant(n)), start)
+            }
+            _ => {
+                let message = format!("integer constant `{}` is out of range (0..={})", digits, INTEGER_CONSTANT_MAX);
+                self.error(start, digits.chars().count(), message);
+            }
+        }
     }
 
-    fn is_end_of_line(c: char) -> bool {
-        c == '\n' || c == '\r'
+    fn keyword_or_identifier(&mut self, start: (usize, usize)) {
+        let word = self.bump_while(|c| c.is_ascii_alphanumeric() || c == '_');
+        let token = match Keyword::new(word.clone()) {
+            Some(k) if self.tokens.dialect == Dialect::JackPlus || !k.is_jack_plus() => Token::Key(k),
+            _ => Token::Identifier(Identifier(word)),
+        };
+        self.push(token, start);
     }
 }
 
@@ -161,14 +356,39 @@ pub enum Token {
     IntegerConstant(IntegerConstant),
     StringConstant(StringConstant),
     Identifier(Identifier),
+    CharConstant(CharConstant),
 }
 
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct IntegerConstant(pub u32);
 impl IntegerConstant {
+    #[allow(clippy::inherent_to_string)]
+    pub fn to_string(&self) -> Vec<String> {
+        // `push constant`は0..=32767しか書けないので、それより大きい16bitの値はビット反転した値をnotする
+        if self.0 > INTEGER_CONSTANT_MAX {
+            return vec![
+                format!("push constant {}", self.0 ^ RADIX_CONSTANT_MAX),
+                "not".to_string(),
+            ];
+        }
+        vec![format!("push constant {}", self.0)]
+    }
+    pub fn to_xml(&self) -> String {
+        let (open, close) = get_xml_tag("integerConstant".to_string());
+        format!("{} {} {}", open, self.0, close)
+    }
+}
+/// 文字定数(Jack+のみ)。値はHackの文字セットの文字で、エスケープしたキーは128以降の文字になっている
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct CharConstant(pub char);
+impl CharConstant {
     #[allow(clippy::inherent_to_string)]
     pub fn to_string(&self) -> String {
-        format!("push constant {}", self.0)
+        format!("push constant {}", self.0 as u32)
+    }
+    pub fn to_xml(&self) -> String {
+        let (open, close) = get_xml_tag("charConstant".to_string());
+        format!("{} {} {}", open, self.0 as u32, close)
     }
 }
 #[derive(Debug, PartialEq, Eq, Clone)]
@@ -178,21 +398,22 @@ impl StringConstant {
     pub fn to_string(&self) -> Vec<String> {
         // Stringコンストラクタを呼び出してオブジェクトを初期化
         let mut result = vec![];
-        let len = self.0.len();
+        // 字句解析でHackの文字セットの文字だけにしているので、文字数がそのままStringの長さになる
+        let len = self.0.chars().count();
         result = [
             result,
             vec![format!("push constant {}", len), "call String.new 1".to_string()],
         ]
         .concat();
-        // stringを1文字ずつ取り出してASCIIコードに変換してappendCharメソッドを呼び出す
+        // stringを1文字ずつ取り出してHackの文字コードに変換してappendCharメソッドを呼び出す
         for char in self.0.chars() {
-            // charをASCIIコードに変換
-            let ascii_code = char as u8;
+            // 印字可能な文字はASCIIコード、エスケープしたキーは128以降のコードになっている
+            let code = char as u32;
             // appendCharメソッドを使って文字を追加
             result = [
                 result,
                 vec![
-                    format!("push constant {}", ascii_code),
+                    format!("push constant {}", code),
                     "call String.appendChar 2".to_string(),
                 ],
             ]
@@ -202,6 +423,10 @@ impl StringConstant {
         // `call String.appendChar 2`の返り値がレシーバなのでstackのtopにStringインスタンスが残った状態になるため。
         result
     }
+    pub fn to_xml(&self) -> String {
+        let (open, close) = get_xml_tag("stringConstant".to_string());
+        format!("{} {} {}", open, self.0, close)
+    }
 }
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct Identifier(pub String);
@@ -210,10 +435,13 @@ impl Identifier {
     pub fn to_string(&self) -> String {
         self.0.clone()
     }
+    pub fn to_xml(&self) -> String {
+        let (open, close) = get_xml_tag("identifier".to_string());
+        format!("{} {} {}", open, self.0, close)
+    }
 }
 
 impl Token {
-    #[allow(dead_code)]
     fn to_xml(&self) -> String {
         match self {
             Self::Key(v) => {
@@ -224,15 +452,10 @@ impl Token {
                 let tag_name = "symbol";
                 format!("<{}> {} </{}>", tag_name, v.to_string(), tag_name)
             }
-            Self::IntegerConstant(IntegerConstant(v)) => {
-                let tag_name = "integerConstant";
-                format!("<{}> {} </{}>", tag_name, v, tag_name)
-            }
-            Self::StringConstant(StringConstant(v)) => {
-                let tag_name = "stringConstant";
-                format!("<{}> {} </{}>", tag_name, v, tag_name)
-            }
-            Self::Identifier(v) => v.to_string(),
+            Self::IntegerConstant(v) => v.to_xml(),
+            Self::StringConstant(v) => v.to_xml(),
+            Self::Identifier(v) => v.to_xml(),
+            Self::CharConstant(v) => v.to_xml(),
         }
     }
 }
@@ -260,6 +483,12 @@ pub enum Keyword {
     Else,
     While,
     Return,
+    // Jack+のみ
+    For,
+    Break,
+    Continue,
+    Const,
+    Enum,
 }
 
 impl Keyword {
@@ -286,9 +515,18 @@ impl Keyword {
             "else" => Some(Keyword::Else),
             "while" => Some(Keyword::While),
             "return" => Some(Keyword::Return),
+            "for" => Some(Keyword::For),
+            "break" => Some(Keyword::Break),
+            "continue" => Some(Keyword::Continue),
+            "const" => Some(Keyword::Const),
+            "enum" => Some(Keyword::Enum),
             _ => None,
         }
     }
+
+    pub fn is_jack_plus(&self) -> bool {
+        matches!(self, Keyword::For | Keyword::Break | Keyword::Continue | Keyword::Const | Keyword::Enum)
+    }
 }
 
 #[derive(PartialEq, Eq)]
@@ -331,6 +569,10 @@ pub enum Symbol {
     Equal,
     /// ~
     Tilde,
+    /// && (Jack+のみ)
+    DoubleAmpersand,
+    /// || (Jack+のみ)
+    DoublePipe,
 }
 
 impl Symbol {
@@ -381,6 +623,8 @@ impl Symbol {
             Symbol::LessThan => "&lt;".to_string(),
             Symbol::Equal => "=".to_string(),
             Symbol::Tilde => "~".to_string(),
+            Symbol::DoubleAmpersand => "&amp;&amp;".to_string(),
+            Symbol::DoublePipe => "||".to_string(),
         }
     }
 }
@@ -413,40 +657,36 @@ mod test {
                 }
                                 "#
                 .to_string()
-            ),
-            Tokens {
-                tokens: vec![
-                    Token::Key(Keyword::Class),
-                    Token::Identifier(Identifier("Main".to_string())),
-                    Token::Sym(Symbol::LeftBrace),
-                    Token::Key(Keyword::Function),
-                    Token::Key(Keyword::Void),
-                    Token::Identifier(Identifier("main".to_string())),
-                    Token::Sym(Symbol::LeftParen),
-                    Token::Sym(Symbol::RightParen),
-                    Token::Sym(Symbol::LeftBrace),
-                    Token::Key(Keyword::Do),
-                    Token::Identifier(Identifier("Output".to_string())),
-                    Token::Sym(Symbol::Dot),
-                    Token::Identifier(Identifier("printString".to_string())),
-                    Token::Sym(Symbol::LeftParen),
-                    Token::StringConstant(StringConstant("hello. world!".to_string())),
-                    Token::Sym(Symbol::RightParen),
-                    Token::Sym(Symbol::SemiColon),
-                    Token::Key(Keyword::Let),
-                    Token::Identifier(Identifier("x".to_string())),
-                    Token::Sym(Symbol::Equal),
-                    Token::IntegerConstant(IntegerConstant(100)),
-                    Token::Sym(Symbol::SemiColon),
-                    Token::Key(Keyword::Return),
-                    Token::Sym(Symbol::SemiColon),
-                    Token::Sym(Symbol::RightBrace),
-                    Token::Sym(Symbol::RightBrace),
-                ],
-                parsing_token: String::new(),
-                parsing_string_const: false,
-                current_comment_type: None,
-            }
+            )
+            .tokens,
+            vec![
+                Token::Key(Keyword::Class),
+                Token::Identifier(Identifier("Main".to_string())),
+                Token::Sym(Symbol::LeftBrace),
+                Token::Key(Keyword::Function),
+                Token::Key(Keyword::Void),
+                Token::Identifier(Identifier("main".to_string())),
+                Token::Sym(Symbol::LeftParen),
+                Token::Sym(Symbol::RightParen),
+                Token::Sym(Symbol::LeftBrace),
+                Token::Key(Keyword::Do),
+                Token::Identifier(Identifier("Output".to_string())),
+                Token::Sym(Symbol::Dot),
+                Token::Identifier(Identifier("printString".to_string())),
+                Token::Sym(Symbol::LeftParen),
+                Token::StringConstant(StringConstant("hello. world!".to_string())),
+                Token::Sym(Symbol::RightParen),
+                Token::Sym(Symbol::SemiColon),
+                Token::Key(Keyword::Let),
+                Token::Identifier(Identifier("x".to_string())),
+                Token::Sym(Symbol::Equal),
+                Token::IntegerConstant(IntegerConstant(100)),
+                Token::Sym(Symbol::SemiColon),
+                Token::Key(Keyword::Return),
+                Token::Sym(Symbol::SemiColon),
+                Token::Sym(Symbol::RightBrace),
+                Token::Sym(Symbol::RightBrace),
+            ]
         );
 
         // program with comments
@@ -465,35 +705,242 @@ mod test {
                 }
                                 "#
                 .to_string()
-            ),
-            Tokens {
-                tokens: vec![
-                    Token::Key(Keyword::Class),
-                    Token::Identifier(Identifier("Main".to_string())),
-                    Token::Sym(Symbol::LeftBrace),
-                    Token::Key(Keyword::Function),
-                    Token::Key(Keyword::Void),
-                    Token::Identifier(Identifier("main".to_string())),
-                    Token::Sym(Symbol::LeftParen),
-                    Token::Sym(Symbol::RightParen),
-                    Token::Sym(Symbol::LeftBrace),
-                    Token::Key(Keyword::Do),
-                    Token::Identifier(Identifier("Output".to_string())),
-                    Token::Sym(Symbol::Dot),
-                    Token::Identifier(Identifier("printString".to_string())),
-                    Token::Sym(Symbol::LeftParen),
-                    Token::StringConstant(StringConstant("/*hello.*/ world!".to_string())),
-                    Token::Sym(Symbol::RightParen),
-                    Token::Sym(Symbol::SemiColon),
-                    Token::Key(Keyword::Return),
-                    Token::Sym(Symbol::SemiColon),
-                    Token::Sym(Symbol::RightBrace),
-                    Token::Sym(Symbol::RightBrace),
-                ],
-                parsing_token: String::new(),
-                parsing_string_const: false,
-                current_comment_type: None,
-            }
+            )
+            .tokens,
+            vec![
+                Token::Key(Keyword::Class),
+                Token::Identifier(Identifier("Main".to_string())),
+                Token::Sym(Symbol::LeftBrace),
+                Token::Key(Keyword::Function),
+                Token::Key(Keyword::Void),
+                Token::Identifier(Identifier("main".to_string())),
+                Token::Sym(Symbol::LeftParen),
+                Token::Sym(Symbol::RightParen),
+                Token::Sym(Symbol::LeftBrace),
+                Token::Key(Keyword::Do),
+                Token::Identifier(Identifier("Output".to_string())),
+                Token::Sym(Symbol::Dot),
+                Token::Identifier(Identifier("printString".to_string())),
+                Token::Sym(Symbol::LeftParen),
+                Token::StringConstant(StringConstant("/*hello.*/ world!".to_string())),
+                Token::Sym(Symbol::RightParen),
+                Token::Sym(Symbol::SemiColon),
+                Token::Key(Keyword::Return),
+                Token::Sym(Symbol::SemiColon),
+                Token::Sym(Symbol::RightBrace),
+                Token::Sym(Symbol::RightBrace),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tokenizer_spans() {
+        let tokens = Tokens::new(
+            r#"class Main {
+  /* comment */ let s = "a b";
+    return x;
+}"#
+            .to_string(),
+        );
+        let span = |line, column, length| Span { line, column, length };
+        assert_eq!(tokens.spans.len(), tokens.tokens.len());
+        assert_eq!(
+            tokens.spans,
+            vec![
+                span(1, 1, 5),
+                span(1, 7, 4),
+                span(1, 12, 1),
+                span(2, 17, 3),
+                span(2, 21, 1),
+                span(2, 23, 1),
+                span(2, 25, 5),
+                span(2, 30, 1),
+                span(3, 5, 6),
+                span(3, 12, 1),
+                span(3, 13, 1),
+                span(4, 1, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tokenizer_crlf_and_escapes() {
+        let tokens = Tokens::new("let s = \"a\\\"b\\\\c[\\]\";\r\n// comment\r\nreturn 32767;".to_string());
+        assert_eq!(
+            tokens.tokens,
+            vec![
+                Token::Key(Keyword::Let),
+                Token::Identifier(Identifier("s".to_string())),
+                Token::Sym(Symbol::Equal),
+                Token::StringConstant(StringConstant("a\"b\\c[\\]".to_string())),
+                Token::Sym(Symbol::SemiColon),
+                Token::Key(Keyword::Return),
+                Token::IntegerConstant(IntegerConstant(32767)),
+                Token::Sym(Symbol::SemiColon),
+            ]
+        );
+        // 文字列定数の長さはエスケープを含むソース上の長さ。`\r`は列に数えない
+        let span = |line, column, length| Span { line, column, length };
+        assert_eq!(
+            tokens.spans,
+            vec![
+                span(1, 1, 3),
+                span(1, 5, 1),
+                span(1, 7, 1),
+                span(1, 9, 12),
+                span(1, 21, 1),
+                span(3, 1, 6),
+                span(3, 8, 5),
+                span(3, 13, 1),
+            ]
+        );
+        assert_eq!(tokens.errors, vec![]);
+    }
+
+    #[test]
+    fn test_tokenizer_errors() {
+        let tokens = Tokens::new("let x = 32768;\nlet s = \"abc;\nlet y = #;\n/* open".to_string());
+        let messages: Vec<_> = tokens
+            .errors
+            .iter()
+            .map(|e| format!("{}:{}: {}", e.span.line, e.span.column, e.message))
+            .collect();
+        assert_eq!(
+            messages,
+            vec![
+                "1:9: integer constant `32768` is out of range (0..=32767)",
+                "2:9: unterminated string constant",
+                "3:9: unexpected character `#`",
+                "4:1: unterminated comment",
+            ]
+        );
+        // エラーになった部分以外はトークナイズを続ける
+        assert_eq!(
+            tokens.tokens[..4],
+            [
+                Token::Key(Keyword::Let),
+                Token::Identifier(Identifier("x".to_string())),
+                Token::Sym(Symbol::Equal),
+                Token::Sym(Symbol::SemiColon),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tokenizer_hack_charset() {
+        let tokens = Tokens::new("\"a\\nb\\b\" \"x\ty\" \"日本\"".to_string());
+        assert_eq!(tokens.tokens[0], Token::StringConstant(StringConstant("a\u{80}b\u{81}".to_string())));
+        // エスケープしたキーは128以降のコードでappendCharする
+        assert_eq!(
+            StringConstant("a\u{80}".to_string()).to_string(),
+            vec![
+                "push constant 2",
+                "call String.new 1",
+                "push constant 97",
+                "call String.appendChar 2",
+                "push constant 128",
+                "call String.appendChar 2",
+            ]
+        );
+        let messages: Vec<_> = tokens
+            .errors
+            .iter()
+            .map(|e| format!("{}:{}: {}", e.span.line, e.span.column, e.message))
+            .collect();
+        assert_eq!(
+            messages,
+            vec![
+                "1:12: character '\\t' is not in the Hack character set",
+                "1:17: character '日' is not in the Hack character set",
+                "1:18: character '本' is not in the Hack character set",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tokenizer_dialect() {
+        // `for`, `break`, `continue`と`&&`, `||`はJack+でだけキーワード, 記号になる
+        let source = "for break continue while && | ||";
+        assert_eq!(
+            Tokens::new(source.to_string()).tokens,
+            vec![
+                Token::Identifier(Identifier("for".to_string())),
+                Token::Identifier(Identifier("break".to_string())),
+                Token::Identifier(Identifier("continue".to_string())),
+                Token::Key(Keyword::While),
+                Token::Sym(Symbol::Ampersand),
+                Token::Sym(Symbol::Ampersand),
+                Token::Sym(Symbol::Pipe),
+                Token::Sym(Symbol::Pipe),
+                Token::Sym(Symbol::Pipe),
+            ]
+        );
+        assert_eq!(
+            Tokens::with_dialect(source.to_string(), Dialect::JackPlus).tokens,
+            vec![
+                Token::Key(Keyword::For),
+                Token::Key(Keyword::Break),
+                Token::Key(Keyword::Continue),
+                Token::Key(Keyword::While),
+                Token::Sym(Symbol::DoubleAmpersand),
+                Token::Sym(Symbol::Pipe),
+                Token::Sym(Symbol::DoublePipe),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tokenizer_jack_plus_literals() {
+        let tokens = Tokens::with_dialect(
+            r"'A' '\n' '\'' 0x7FFF 0b1010 0x8000 -32768 32768 0x10000 0b102 'ab' 'é'".to_string(),
+            Dialect::JackPlus,
+        );
+        assert_eq!(
+            tokens.tokens,
+            vec![
+                Token::CharConstant(CharConstant('A')),
+                Token::CharConstant(CharConstant('\u{80}')),
+                Token::CharConstant(CharConstant('\'')),
+                Token::IntegerConstant(IntegerConstant(0x7FFF)),
+                Token::IntegerConstant(IntegerConstant(0b1010)),
+                Token::IntegerConstant(IntegerConstant(0x8000)),
+                Token::Sym(Symbol::Minus),
+                Token::IntegerConstant(IntegerConstant(32768)),
+            ]
+        );
+        assert_eq!(
+            tokens
+                .errors
+                .iter()
+                .map(|e| format!("{}:{}: {}", e.span.line, e.span.column, e.message))
+                .collect::<Vec<_>>(),
+            vec![
+                "1:43: integer constant `32768` is out of range (0..=32767)",
+                "1:49: integer constant `0x10000` is out of range (0..=0xFFFF)",
+                "1:57: invalid integer constant `0b102`",
+                "1:63: invalid character constant",
+                "1:68: character 'é' is not in the Hack character set",
+            ]
+        );
+
+        // 16bitの値はpush constantで書けない分をビット反転してnotする
+        assert_eq!(IntegerConstant(0x7FFF).to_string(), vec!["push constant 32767"]);
+        assert_eq!(IntegerConstant(32768).to_string(), vec!["push constant 32767", "not"]);
+        assert_eq!(IntegerConstant(0xFFFF).to_string(), vec!["push constant 0", "not"]);
+
+        // 標準のJackでは文字定数と16進数は書けない
+        let tokens = Tokens::new("'A' 0x10".to_string());
+        assert_eq!(
+            tokens.errors.iter().map(|e| e.message.as_str()).collect::<Vec<_>>(),
+            vec!["unexpected character `'`", "unexpected character `'`"]
+        );
+        assert_eq!(
+            tokens.tokens,
+            vec![
+                Token::Identifier(Identifier("A".to_string())),
+                Token::IntegerConstant(IntegerConstant(0)),
+                Token::Identifier(Identifier("x10".to_string())),
+            ]
         );
     }
 }
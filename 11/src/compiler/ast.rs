@@ -1,8 +1,15 @@
 use crate::analyzer::token;
-use rand::seq::SliceRandom;
+use crate::compiler::diagnostics::Diagnostic;
+
+pub(crate) mod optimize;
+pub(crate) mod precedence;
+pub(crate) mod semantic;
+pub(crate) mod xml;
 
 pub struct Ast {
     class: Class,
+    // 意味解析でエラー位置を求めるためにトークン列も保持しておく
+    tokens: token::Tokens,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -15,8 +22,28 @@ struct SymbolTables {
     // コンパイラフロントエンドで生成したsymbol_tableを参照するために保持している。
     // 関数に{現在処理中のsubroutine_name}を引き回す方法もなくはないが複雑になりそうだったので意図的にこうしている。
     current_subroutine_name: Option<String>,
-    // 一意のラベルを生成するために必要
+    // コンパイル対象のファイル名(拡張子なし)
     file_name: String,
+    // subroutineごとのif文, while文の通し番号。ラベル(IF_FALSE0, WHILE_EXP0など)の一意性を担保するために使う
+    // VMのラベルは関数内でのみ有効なのでsubroutineごとに0から数える
+    // コード生成は&SymbolTablesで行うのでCellで持つ
+    if_label_count: std::cell::Cell<usize>,
+    while_label_count: std::cell::Cell<usize>,
+    // 文字列定数の初期化済み判定のラベル(STRING_READY0など)の通し番号
+    string_label_count: std::cell::Cell<usize>,
+    // `&&`, `||`の短絡評価の分岐のラベル(AND_FALSE0, OR_TRUE0など)の通し番号
+    short_circuit_label_count: std::cell::Cell<usize>,
+    // 処理中のループの(continueの飛び先, breakの飛び先)のラベル。内側のループほど後ろにある
+    loop_labels: std::cell::RefCell<Vec<(String, String)>>,
+    // 処理中のクラス名。自クラスのsubroutine呼び出しの解決に使う
+    current_class_name: Option<String>,
+    // 全クラスのsubroutineの種類。コード生成の直前にAst::to_vmでセットする
+    classes: std::rc::Rc<ClassTable>,
+    // 文字列定数をインターンする場合に、クラス内で現れた文字列定数を出現順に保持する。
+    // n番目の文字列はクラス変数の後ろのstatic変数に格納する。subroutineをまたいで共有するのでRcで持つ
+    interned_strings: Option<std::rc::Rc<std::cell::RefCell<Vec<String>>>>,
+    // コード生成で行う最適化のレベル。CodegenOptions::opt_levelと同じ
+    opt_level: u8,
 }
 impl SymbolTables {
     fn default(file_name: String) -> Self {
@@ -25,6 +52,15 @@ impl SymbolTables {
             subroutine_scopes: std::collections::HashMap::new(),
             current_subroutine_name: None,
             file_name,
+            if_label_count: std::cell::Cell::new(0),
+            while_label_count: std::cell::Cell::new(0),
+            string_label_count: std::cell::Cell::new(0),
+            short_circuit_label_count: std::cell::Cell::new(0),
+            loop_labels: std::cell::RefCell::default(),
+            current_class_name: None,
+            classes: std::rc::Rc::default(),
+            interned_strings: None,
+            opt_level: 0,
         }
     }
     fn determine_next_item_index_class(&self, symbol_type: &ClassSymbolType) -> usize {
@@ -91,22 +127,76 @@ impl SymbolTables {
     fn update_current_subroutine_name(&self, subroutine_name: String) -> Self {
         let mut st = self.clone();
         st.current_subroutine_name = Some(subroutine_name);
+        st.if_label_count.set(0);
+        st.while_label_count.set(0);
+        st.string_label_count.set(0);
+        st.short_circuit_label_count.set(0);
         st
     }
+    // 次のif文の通し番号を返す
+    fn next_if_label_id(&self) -> usize {
+        let id = self.if_label_count.get();
+        self.if_label_count.set(id + 1);
+        id
+    }
+    // 次のwhile文の通し番号を返す
+    fn next_while_label_id(&self) -> usize {
+        let id = self.while_label_count.get();
+        self.while_label_count.set(id + 1);
+        id
+    }
+    // 次の文字列定数の通し番号を返す
+    fn next_string_label_id(&self) -> usize {
+        let id = self.string_label_count.get();
+        self.string_label_count.set(id + 1);
+        id
+    }
+    // 次の短絡評価の通し番号を返す
+    fn next_short_circuit_label_id(&self) -> usize {
+        let id = self.short_circuit_label_count.get();
+        self.short_circuit_label_count.set(id + 1);
+        id
+    }
     fn get(&self, var_name: &str) -> Symbol {
+        match self.find(var_name) {
+            Some(s) => s,
+            None => panic!("{:?} not found in {:?}", var_name, self),
+        }
+    }
+    // subroutineのスコープ, classのスコープの順に変数を探す
+    fn find(&self, var_name: &str) -> Option<Symbol> {
         if let Some(s) = self
             .subroutine_scopes
             .get(&self.current_subroutine_name.clone().unwrap())
             .unwrap()
             .get(var_name)
         {
-            Symbol::Subroutine(s.clone())
-        } else if let Some(s) = self.class_scope.get(var_name) {
-            Symbol::Class(s.clone())
+            Some(Symbol::Subroutine(s.clone()))
         } else {
-            panic!("{:?} not found in {:?}", var_name, self);
+            self.class_scope.get(var_name).map(|s| Symbol::Class(s.clone()))
         }
     }
+    // 定数ならその値を返す。subroutineの中では同名のローカル変数, 引数が優先される
+    fn constant(&self, var_name: &str) -> Option<i16> {
+        let symbol = match self.current_subroutine_name {
+            Some(_) => self.find(var_name),
+            None => self.class_scope.get(var_name).map(|s| Symbol::Class(s.clone())),
+        };
+        match symbol {
+            Some(Symbol::Class(ClassSymbol {
+                symbol_type: ClassSymbolType::Constant(value),
+                ..
+            })) => Some(value),
+            _ => None,
+        }
+    }
+    fn with_codegen_context(&self, classes: &ClassTable, options: &CodegenOptions) -> Self {
+        let mut st = self.clone();
+        st.classes = std::rc::Rc::new(classes.clone());
+        st.interned_strings = options.intern_strings.then(std::rc::Rc::default);
+        st.opt_level = options.opt_level;
+        st
+    }
     #[allow(dead_code)]
     fn debug_class_symbol_table(&self) {
         println!("class_scope:");
@@ -140,6 +230,24 @@ impl SymbolTables {
             .filter(|s| s.symbol_type == ClassSymbolType::Field)
             .count()
     }
+    fn get_static_count(&self) -> usize {
+        self.class_scope
+            .values()
+            .filter(|s| s.symbol_type == ClassSymbolType::Static)
+            .count()
+    }
+    // インターンした文字列定数を格納するstatic変数の番号を返す。初めて現れた文字列には新しい番号を割り当てる
+    fn interned_string_index(&self, value: &str) -> Option<usize> {
+        let mut strings = self.interned_strings.as_ref()?.borrow_mut();
+        let position = match strings.iter().position(|s| s == value) {
+            Some(position) => position,
+            None => {
+                strings.push(value.to_string());
+                strings.len() - 1
+            }
+        };
+        Some(self.get_static_count() + position)
+    }
 }
 
 // symbol_typeをgenericな型として外から受け取るとSubroutineSymbolと構造体定義を共通化できそうにも思えるが
@@ -155,12 +263,15 @@ struct ClassSymbol {
 enum ClassSymbolType {
     Static,
     Field,
+    // Jack+のconst, enumで宣言した定数。メモリには置かず、参照する箇所に値を埋め込む
+    Constant(i16),
 }
 impl ClassSymbolType {
     fn to_segment_name(&self) -> String {
         match self {
             ClassSymbolType::Static => "static",
             ClassSymbolType::Field => "this",
+            ClassSymbolType::Constant(_) => "constant",
         }
         .to_string()
     }
@@ -228,24 +339,100 @@ impl Symbol {
 }
 
 impl Ast {
-    pub fn new(tokens: Vec<token::Token>, file_name: String) -> Self {
-        let class = match tokens.first() {
-            Some(token::Token::Key(token::Keyword::Class)) => match Class::new(&tokens, file_name, 0) {
-                Some(class) => class,
-                _ => panic!("{}", invalid_token(&tokens, 1)),
-            },
-            Some(_) => panic!("{}", invalid_token(&tokens, 0)),
-            None => panic!("{}", invalid_token(&tokens, 0)),
+    // パースエラーがあった場合はソース上の位置に変換したエラーを全て返す
+    // 字句解析のエラーがあった場合は、欠けたトークンによるパースエラーが連鎖するのでそちらだけを返す
+    pub fn new(tokens: token::Tokens, file_name: String) -> Result<Self, Vec<Diagnostic>> {
+        if !tokens.errors.is_empty() {
+            return Err(tokens.errors);
+        }
+        let mut errors = match Class::new(&tokens.tokens, file_name, 0, tokens.dialect) {
+            Ok(class) => {
+                let errors = class.parse_errors();
+                if errors.is_empty() {
+                    return Ok(Self { class, tokens });
+                }
+                errors
+            }
+            Err(e) => vec![e],
         };
 
-        Self { class }
+        errors.sort_by_key(|e| e.index);
+        Err(errors
+            .into_iter()
+            .map(|e| Diagnostic::new(tokens.span_at(e.index), e.message))
+            .collect())
     }
 
-    pub fn to_vm(&self) -> String {
-        self.class.to_string(&self.class.symbol_tables).join("\n")
+    // classesには他のクラスと、このクラス自身のsubroutineが含まれている必要がある
+    pub fn to_vm(&self, classes: &ClassTable, options: &CodegenOptions) -> String {
+        self.class
+            .to_string(&self.class.symbol_tables.with_codegen_context(classes, options))
+            .join("\n")
     }
 }
 
+/// コード生成のオプション
+#[derive(Clone, Copy, Debug, Default)]
+pub struct CodegenOptions {
+    /// 同じ文字列定数はstatic変数に一度だけ生成して使い回す。
+    /// 生成したStringは共有されるので、書き換えたりdisposeしたりするプログラムでは使えない
+    pub intern_strings: bool,
+    /// 最適化のレベル。0は最適化せず、提供されている比較用の出力と同じコードになる。
+    /// 1ではProgram::optimizeで定数式の畳み込みと実行されない節の削除を行い、
+    /// 2ではさらに2のべき乗の掛け算をaddの繰り返しにし、条件分岐の余分なnotを出力しない。
+    /// notを出力しないのは、条件が`~e`の分岐と、else節のあるif文で条件が比較などtrueかfalseにしかならない式の場合だけ。
+    /// else節のないif文やwhile文の`x < n`のような比較は、VMに逆の比較がないので`lt; not; if-goto`のまま残る
+    pub opt_level: u8,
+}
+
+/// 全クラスのsubroutineの種類と列挙型の一覧。
+/// subroutine呼び出しがメソッド呼び出しかどうかはレシーバの見た目では決まらないので、コード生成時にここから解決する。
+/// 他のクラスで宣言された列挙子の値もここから引く
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ClassTable {
+    subroutines: std::collections::HashMap<String, std::collections::HashMap<String, SubroutineDecKind>>,
+    // key: 列挙型の名前, value: 列挙子の並び
+    enums: std::collections::HashMap<String, Vec<String>>,
+}
+impl ClassTable {
+    pub fn new(asts: &[&Ast]) -> Self {
+        let mut classes = std::collections::HashMap::new();
+        let mut enums = std::collections::HashMap::new();
+        for ast in asts {
+            for var_dec in &ast.class.var_dec {
+                if let ClassVarKind::Enum(name) = &var_dec.kind {
+                    let members = var_dec.var_names.iter().map(|v| v.0 .0.clone()).collect();
+                    enums.insert(name.0.clone(), members);
+                }
+            }
+            let subroutines = ast
+                .class
+                .subroutine_dec
+                .iter()
+                .map(|s| (s.subroutine_name.0.clone(), s.kind.clone()))
+                .collect();
+            classes.insert(ast.class.name.0 .0.clone(), subroutines);
+        }
+        Self {
+            subroutines: classes,
+            enums,
+        }
+    }
+    fn kind(&self, class_name: &str, subroutine_name: &str) -> Option<&SubroutineDecKind> {
+        self.subroutines.get(class_name)?.get(subroutine_name)
+    }
+    // 列挙子の値は宣言順の番号になる
+    fn enum_value(&self, enum_name: &str, member: &str) -> Option<i16> {
+        let position = self.enums.get(enum_name)?.iter().position(|m| m == member)?;
+        Some(position as i16)
+    }
+}
+
+// 列挙子をシンボルテーブルに登録する名前。識別子は`.`を含まないので変数や定数の名前とは重ならない
+fn enum_member_name(enum_name: &str, member: &str) -> String {
+    format!("{}.{}", enum_name, member)
+}
+
 // シンボルテーブルの仕様メモ
 // ## 保持したいデータ
 // - name: 変数名
@@ -257,58 +444,103 @@ impl Ast {
 // - class_scopeのシンボルテーブルとsubroutine_scopeのシンボルテーブルを別々に管理する必要がある
 // - subroutineの処理を開始するタイミングでsubroutine_scopeのシンボルテーブルはrefreshされる必要がある
 
+/// ノードのトークン列上の位置。意味解析の診断はここから`Tokens::span_at`でソース上の位置を求める。
+/// 最適化で生成したノードなど位置を持たないものはdefault(先頭のトークン)にする。
+/// ASTの比較では位置を区別しない(同じ構造の式は現れた位置によらず等しい)
+#[derive(Clone, Copy, Debug, Default)]
+struct TokenIndex(usize);
+impl PartialEq for TokenIndex {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+impl Eq for TokenIndex {}
+
 /*
  * プログラムの構造
  */
 #[derive(Debug, PartialEq, Eq)]
 struct Class {
     name: ClassName,
+    // クラス名の位置
+    name_index: TokenIndex,
     var_dec: Vec<ClassVarDec>,
     subroutine_dec: Vec<SubroutineDec>,
     symbol_tables: SymbolTables,
+    // subroutine宣言の途中で回復できなかったパースエラー
+    errors: Vec<ParseError>,
 }
 impl Class {
     // parse結果を返す。ひとまずindexは返さない
-    fn new(tokens: &[token::Token], file_name: String, index: usize) -> Option<Self> {
-        let symbol_tables = SymbolTables::default(file_name);
+    fn new(
+        tokens: &[token::Token],
+        file_name: String,
+        index: usize,
+        dialect: token::Dialect,
+    ) -> Result<Self, ParseError> {
+        let mut symbol_tables = SymbolTables::default(file_name);
         let index = match tokens.get(index) {
             Some(token::Token::Key(token::Keyword::Class)) => index + 1,
-            _ => panic!("{}", invalid_token(tokens, 0)),
+            _ => return expected(index, "`class`"),
         };
-        let (name, index) = ClassName::new(tokens, index);
+        let name_index = TokenIndex(index);
+        let (name, index) = ClassName::new(tokens, index)?;
+        symbol_tables.current_class_name = Some(name.0 .0.clone());
         let index = {
             if let Some(token::Token::Sym(token::Symbol::LeftBrace)) = tokens.get(index) {
                 index + 1
             } else {
-                panic!("{}", invalid_token(tokens, 0))
+                return expected(index, "`{` after class name");
             }
         };
 
-        let (var_dec, index, mut symbol_tables) = ClassVarDec::new(tokens, index, symbol_tables);
+        let (var_dec, index, mut symbol_tables) = ClassVarDec::new(tokens, index, &name, dialect, symbol_tables)?;
         let mut subroutine_dec = vec![];
+        let mut errors = vec![];
         let mut index = index;
-        while let (Some(s), returned_index, returned_symbol_tables) =
-            SubroutineDec::new(tokens, index, &name, symbol_tables.clone())
-        {
-            subroutine_dec.push(s);
-            index = returned_index;
-            symbol_tables = returned_symbol_tables;
+        loop {
+            match SubroutineDec::new(tokens, index, &name, dialect, symbol_tables.clone()) {
+                Ok((Some(s), returned_index, returned_symbol_tables)) => {
+                    subroutine_dec.push(s);
+                    index = returned_index;
+                    symbol_tables = returned_symbol_tables;
+                }
+                Ok((None, _, _)) => break,
+                Err(e) => {
+                    // 次のsubroutine宣言までスキップしてパースを続ける
+                    errors.push(e);
+                    index = skip_subroutine(tokens, index);
+                }
+            }
         }
 
         match tokens.get(index) {
             Some(token::Token::Sym(token::Symbol::RightBrace)) => {}
-            _ => panic!("{}", invalid_token(tokens, index)),
+            // subroutineのエラーをスキップした結果終端に達した場合は、同じ位置のエラーを重ねて報告しない
+            None if !errors.is_empty() => {}
+            _ => return expected(index, "subroutine declaration or `}`"),
         };
 
         // symbol_tables.debug_class_symbol_table();
-        Some(Class {
+        Ok(Class {
             name,
+            name_index,
             var_dec,
             subroutine_dec,
             symbol_tables,
+            errors,
         })
     }
 
+    // subroutine本体の文の中で記録したものも含めて全てのパースエラーを返す
+    fn parse_errors(&self) -> Vec<ParseError> {
+        let mut result = self.errors.clone();
+        for s in &self.subroutine_dec {
+            result = [result, s.body.statements.parse_errors()].concat();
+        }
+        result
+    }
+
     pub fn to_string(&self, symbol_tables: &SymbolTables) -> Vec<String> {
         let mut result = vec![];
         // NOTE: 場合によってはクラス変数の初期化処理が必要かもしれない
@@ -328,11 +560,19 @@ struct ClassVarDec {
     kind: ClassVarKind,
     type_: Type,
     var_names: Vec<VarName>,
+    // constのときはvar_namesと同じ並びで各定数の値の式を持つ。それ以外は空
+    values: Vec<Expression>,
 }
 impl ClassVarDec {
     // parse結果と次のトークンの読み出し位置を返す
     // FIXME: SubroutineDec::newは単数を返すのにこっちはVecを返すのは一貫性がないので直してもいいかもしれない
-    fn new(tokens: &[token::Token], index: usize, mut symbol_tables: SymbolTables) -> (Vec<Self>, usize, SymbolTables) {
+    fn new(
+        tokens: &[token::Token],
+        index: usize,
+        class_name: &ClassName,
+        dialect: token::Dialect,
+        mut symbol_tables: SymbolTables,
+    ) -> Result<(Vec<Self>, usize, SymbolTables), ParseError> {
         let mut class_var_decs = vec![];
         let mut index = index;
         loop {
@@ -345,6 +585,28 @@ impl ClassVarDec {
                     index += 1;
                     ClassVarKind::Field
                 }
+                Some(token::Token::Key(token::Keyword::Const)) => {
+                    index += 1;
+                    ClassVarKind::Const
+                }
+                Some(token::Token::Key(token::Keyword::Enum)) => {
+                    let (class_var_dec, returned_index) = Self::enum_dec(tokens, index)?;
+                    // 列挙子は宣言順に0から番号を振ったintの定数にする。
+                    // `EnumName.MEMBER`で参照するので、別のenumに同名の列挙子があってもぶつからないように列挙型の名前を付けて登録する
+                    let ClassVarKind::Enum(enum_name) = &class_var_dec.kind else {
+                        unreachable!()
+                    };
+                    for (value, var_name) in class_var_dec.var_names.iter().enumerate() {
+                        symbol_tables = symbol_tables.append_class_symbol(
+                            enum_member_name(&enum_name.0, &var_name.0 .0),
+                            Type::Int,
+                            ClassSymbolType::Constant(value as i16),
+                        );
+                    }
+                    class_var_decs.push(class_var_dec);
+                    index = returned_index;
+                    continue;
+                }
                 _ => break,
             };
 
@@ -365,67 +627,108 @@ impl ClassVarDec {
                     index += 1;
                     Type::ClassName(id.0.clone())
                 }
-                _ => panic!("{}", invalid_token(tokens, index)),
+                _ => return expected(index, "type"),
             };
 
-            let var_name = match tokens.get(index) {
-                Some(token::Token::Identifier(id)) => {
-                    index += 1;
-                    VarName(id.clone())
-                }
-                _ => panic!("{}", invalid_token(tokens, index)),
-            };
-
-            // このあとは `, varName`が任意の回数続く。
-            let mut var_names = vec![var_name];
-            while let Some(token::Token::Sym(token::Symbol::Comma)) = tokens.get(index) {
-                index += 1; // , が取得できたのでindexを1進める
-
-                match tokens.get(index) {
+            // `varName`のあとに`, varName`が任意の回数続く。constのときはそれぞれに`= 定数式`が付く
+            let mut var_names = vec![];
+            let mut values = vec![];
+            loop {
+                let var_name = match tokens.get(index) {
                     Some(token::Token::Identifier(id)) => {
                         index += 1;
-                        var_names.push(VarName(id.clone()))
+                        VarName(id.clone(), TokenIndex(index - 1))
+                    }
+                    _ => return expected(index, "variable name"),
+                };
+
+                let symbol_type = match kind {
+                    ClassVarKind::Const => {
+                        index = match tokens.get(index) {
+                            Some(token::Token::Sym(token::Symbol::Equal)) => index + 1,
+                            _ => return expected(index, "`=` after constant name"),
+                        };
+                        let (value, returned_index) = match Expression::new(tokens, index, class_name, dialect)? {
+                            (Some(e), returned_index) => (e, returned_index),
+                            _ => return expected(index, "expression"),
+                        };
+                        // 先に宣言した定数は参照できる
+                        let Some(v) = value.const_value(&symbol_tables) else {
+                            return expected(index, "constant expression");
+                        };
+                        index = returned_index;
+                        values.push(value);
+                        ClassSymbolType::Constant(v)
                     }
-                    _ => panic!("{}", invalid_token(tokens, index)),
+                    _ => kind.clone().into(),
+                };
+                // シンボルテーブルを更新
+                symbol_tables = symbol_tables.append_class_symbol(var_name.0 .0.clone(), type_.clone(), symbol_type);
+                var_names.push(var_name);
+
+                match tokens.get(index) {
+                    Some(token::Token::Sym(token::Symbol::Comma)) => index += 1, // , が取得できたのでindexを1進める
+                    _ => break,
                 }
             }
 
             // 最後にセミコロンがあることをチェック
             match tokens.get(index) {
                 Some(token::Token::Sym(token::Symbol::SemiColon)) => index += 1,
-                _ => panic!("{}", invalid_token(tokens, index)),
+                _ => return expected(index, "`;` after variable declaration"),
             };
 
-            for var_name in &var_names {
-                // シンボルテーブルを更新
-                symbol_tables =
-                    symbol_tables.append_class_symbol(var_name.clone().0 .0, type_.clone(), kind.clone().into());
-            }
-            class_var_decs.push(Self { kind, type_, var_names });
+            class_var_decs.push(Self {
+                kind,
+                type_,
+                var_names,
+                values,
+            });
         }
 
-        (class_var_decs, index, symbol_tables)
+        Ok((class_var_decs, index, symbol_tables))
     }
 
-    fn to_string(&self, symbol_tables: &SymbolTables) -> Vec<String> {
-        todo!("実装は不要だと思うが念の為残している");
-        // let mut result = vec![];
-        // let (open, close) = get_xml_tag("classVarDec".to_string());
-        // result.push(open);
-        // result.push(self.kind.to_string());
-        // result.push(self.type_.to_string());
-        // for (index, n) in self.var_names.iter().enumerate() {
-        //     if index != 0 {
-        //         result.push(to_xml_tag(token::Symbol::Comma));
-        //     }
-        //     result.push(n.to_string(symbol_tables));
-        // }
-        // if !&self.var_names.is_empty() {
-        //     result.push(to_xml_tag(token::Symbol::SemiColon));
-        // }
-        //
-        // result.push(close);
-        // result
+    // `enum Name { A, B, ... }`を読む。indexは`enum`を指している
+    fn enum_dec(tokens: &[token::Token], index: usize) -> Result<(Self, usize), ParseError> {
+        let (name, index) = match tokens.get(index + 1) {
+            Some(token::Token::Identifier(id)) => (id.clone(), index + 2),
+            _ => return expected(index + 1, "enum name"),
+        };
+        let mut index = match tokens.get(index) {
+            Some(token::Token::Sym(token::Symbol::LeftBrace)) => index + 1,
+            _ => return expected(index, "`{` after enum name"),
+        };
+
+        let mut var_names = vec![];
+        loop {
+            match tokens.get(index) {
+                Some(token::Token::Identifier(id)) => {
+                    var_names.push(VarName(id.clone(), TokenIndex(index)));
+                    index += 1;
+                }
+                _ => return expected(index, "enumerator name"),
+            }
+            match tokens.get(index) {
+                Some(token::Token::Sym(token::Symbol::Comma)) => index += 1,
+                _ => break,
+            }
+        }
+
+        let index = match tokens.get(index) {
+            Some(token::Token::Sym(token::Symbol::RightBrace)) => index + 1,
+            _ => return expected(index, "`}` after enumerators"),
+        };
+
+        Ok((
+            Self {
+                kind: ClassVarKind::Enum(name),
+                type_: Type::Int,
+                var_names,
+                values: vec![],
+            },
+            index,
+        ))
     }
 }
 
@@ -433,20 +736,18 @@ impl ClassVarDec {
 enum ClassVarKind {
     Static,
     Field,
+    // 以下はJack+のみ。enumは列挙型の名前を持つ
+    Const,
+    Enum(token::Identifier),
 }
-impl ClassVarKind {
-    #[allow(clippy::inherent_to_string)]
-    fn to_string(&self) -> String {
-        todo!();
-        // let (open, close) = get_xml_tag("keyword".to_string());
-        // format!("{} {} {}", open, format!("{:?}", self).to_lowercase(), close)
-    }
-}
+impl ClassVarKind {}
 impl From<ClassVarKind> for ClassSymbolType {
+    // constとenumは値が決まってから登録するのでここでは扱わない
     fn from(kind: ClassVarKind) -> Self {
         match kind {
             ClassVarKind::Static => ClassSymbolType::Static,
             ClassVarKind::Field => ClassSymbolType::Field,
+            ClassVarKind::Const | ClassVarKind::Enum(_) => unreachable!("定数は値と一緒に登録する"),
         }
     }
 }
@@ -468,21 +769,6 @@ impl Type {
             _ => (None, index),
         }
     }
-    #[allow(clippy::inherent_to_string)]
-    fn to_string(&self) -> String {
-        todo!();
-        // match self {
-        //     Type::ClassName(c) => {
-        //         todo!();
-        //         let (open, close) = get_xml_tag("identifier".to_string());
-        //         format!("{} {} {}", open, c, close)
-        //     }
-        //     _ => {
-        //         let (open, close) = get_xml_tag("keyword".to_string());
-        //         format!("{} {} {}", open, format!("{:?}", self).to_lowercase(), close)
-        //     }
-        // }
-    }
 }
 
 #[derive(Debug, PartialEq, Eq)]
@@ -490,6 +776,8 @@ struct SubroutineDec {
     kind: SubroutineDecKind,
     type_: SubroutineDecType,
     subroutine_name: token::Identifier,
+    // subroutine名の位置
+    name_index: TokenIndex,
     parameter_list: ParameterList,
     body: SubroutineBody,
 }
@@ -498,22 +786,24 @@ impl SubroutineDec {
         tokens: &[token::Token],
         index: usize,
         class_name: &ClassName,
+        dialect: token::Dialect,
         symbol_tables: SymbolTables,
-    ) -> (Option<Self>, usize, SymbolTables) {
+    ) -> Result<(Option<Self>, usize, SymbolTables), ParseError> {
         let (kind, index) = match SubroutineDecKind::new(tokens, index) {
             (Some(k), i) => (k, i),
-            _ => return (None, index, symbol_tables),
+            _ => return Ok((None, index, symbol_tables)),
         };
-        let (type_, index) = SubroutineDecType::new(tokens, index);
+        let (type_, index) = SubroutineDecType::new(tokens, index)?;
+        let name_index = TokenIndex(index);
         let (subroutine_name, index) = match tokens.get(index) {
             Some(token::Token::Identifier(i)) => (i.clone(), index + 1),
-            _ => panic!("{}", invalid_token(tokens, index)),
+            _ => return expected(index, "subroutine name"),
         };
         let symbol_tables = symbol_tables.add_subroutine_symbol_table(subroutine_name.0.clone());
 
         let index = match tokens.get(index) {
             Some(token::Token::Sym(token::Symbol::LeftParen)) => index + 1,
-            _ => panic!("{}", invalid_token(tokens, index)),
+            _ => return expected(index, "`(` after subroutine name"),
         };
 
         // methodだったらThisを追加する
@@ -537,24 +827,25 @@ impl SubroutineDec {
             _ => symbol_tables,
         };
 
-        let (parameter_list, index, symbol_tables) = ParameterList::new(tokens, index, symbol_tables);
+        let (parameter_list, index, symbol_tables) = ParameterList::new(tokens, index, symbol_tables)?;
         let index = match tokens.get(index) {
             Some(token::Token::Sym(token::Symbol::RightParen)) => index + 1,
-            _ => panic!("{}", invalid_token(tokens, index)),
+            _ => return expected(index, "`)` after parameter list"),
         };
-        let (body, index, symbol_tables) = SubroutineBody::new(tokens, index, class_name, symbol_tables);
+        let (body, index, symbol_tables) = SubroutineBody::new(tokens, index, class_name, dialect, symbol_tables)?;
 
-        (
+        Ok((
             Some(Self {
                 kind,
                 type_,
                 subroutine_name,
+                name_index,
                 parameter_list,
                 body,
             }),
             index,
             symbol_tables,
-        )
+        ))
     }
     fn to_string(&self, class_name: &ClassName, symbol_tables: &SymbolTables) -> Vec<String> {
         let mut symbol_tables = symbol_tables.update_current_subroutine_name(self.subroutine_name.0.clone());
@@ -594,7 +885,7 @@ impl SubroutineDec {
     }
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq, Clone)]
 enum SubroutineDecKind {
     Constructor,
     Function,
@@ -609,11 +900,6 @@ impl SubroutineDecKind {
             _ => (None, index),
         }
     }
-    fn to_string(&self) -> String {
-        todo!();
-        // let (open, close) = get_xml_tag("keyword".to_string());
-        // format!("{} {} {}", open, format!("{:?}", self).to_lowercase(), close)
-    }
 }
 
 #[derive(Debug, PartialEq, Eq)]
@@ -622,25 +908,15 @@ enum SubroutineDecType {
     Type_(Type),
 }
 impl SubroutineDecType {
-    fn new(tokens: &[token::Token], index: usize) -> (Self, usize) {
+    fn new(tokens: &[token::Token], index: usize) -> Result<(Self, usize), ParseError> {
         match tokens.get(index) {
-            Some(token::Token::Key(token::Keyword::Void)) => (SubroutineDecType::Void, index + 1),
+            Some(token::Token::Key(token::Keyword::Void)) => Ok((SubroutineDecType::Void, index + 1)),
             _ => match Type::new(tokens, index) {
-                (Some(t), index) => (SubroutineDecType::Type_(t), index),
-                _ => panic!("{}", invalid_token(tokens, index)),
+                (Some(t), index) => Ok((SubroutineDecType::Type_(t), index)),
+                _ => expected(index, "`void` or type"),
             },
         }
     }
-    fn to_string(&self) -> String {
-        todo!();
-        // match self {
-        //     SubroutineDecType::Void => {
-        //         let (open, close) = get_xml_tag("keyword".to_string());
-        //         format!("{} {} {}", open, format!("{:?}", self).to_lowercase(), close)
-        //     }
-        //     SubroutineDecType::Type_(t) => t.to_string(),
-        // }
-    }
 }
 
 #[derive(Debug, PartialEq, Eq)]
@@ -649,7 +925,11 @@ impl ParameterList {
     // パターンメモ
     // ``: 引数なし
     // `type var_name, type var_name, ..., type var_name`: n個の引数
-    fn new(tokens: &[token::Token], index: usize, mut symbol_tables: SymbolTables) -> (Self, usize, SymbolTables) {
+    fn new(
+        tokens: &[token::Token],
+        index: usize,
+        mut symbol_tables: SymbolTables,
+    ) -> Result<(Self, usize, SymbolTables), ParseError> {
         let mut index = index;
         let mut param_list = vec![];
         while let (Some(type_), returned_index) = Type::new(tokens, index) {
@@ -658,9 +938,9 @@ impl ParameterList {
             let var_name = match tokens.get(index) {
                 Some(token::Token::Identifier(i)) => {
                     index += 1;
-                    VarName(token::Identifier(i.clone().0))
+                    VarName(token::Identifier(i.clone().0), TokenIndex(index - 1))
                 }
-                _ => panic!("{}", invalid_token(tokens, index)),
+                _ => return expected(index, "parameter name"),
             };
 
             param_list.push((type_.clone(), var_name.clone()));
@@ -675,23 +955,7 @@ impl ParameterList {
             }
         }
 
-        (Self(param_list), index, symbol_tables)
-    }
-    #[allow(clippy::inherent_to_string)]
-    fn to_string(&self, symbol_tables: &SymbolTables) -> Vec<String> {
-        todo!();
-        // let mut result = vec![];
-        // let (open, close) = get_xml_tag("parameterList".to_string());
-        // result.push(open);
-        // for (index, p) in self.0.iter().enumerate() {
-        //     if index != 0 {
-        //         result.push(to_xml_tag(token::Symbol::Comma));
-        //     }
-        //     result.push(p.0.to_string());
-        //     result.push(p.1.to_string(symbol_tables));
-        // }
-        // result.push(close);
-        // result
+        Ok((Self(param_list), index, symbol_tables))
     }
 }
 
@@ -705,31 +969,32 @@ impl SubroutineBody {
         tokens: &[token::Token],
         index: usize,
         class_name: &ClassName,
+        dialect: token::Dialect,
         mut symbol_tables: SymbolTables,
-    ) -> (Self, usize, SymbolTables) {
+    ) -> Result<(Self, usize, SymbolTables), ParseError> {
         let index = match tokens.get(index) {
             Some(token::Token::Sym(token::Symbol::LeftBrace)) => index + 1,
-            _ => panic!("{}", invalid_token(tokens, index)),
+            _ => return expected(index, "`{` before subroutine body"),
         };
 
         let mut var_dec = vec![];
         let mut index = index;
         while let (Some(got), returned_index, returned_symbol_tables) =
-            VarDec::new(tokens, index, symbol_tables.clone())
+            VarDec::new(tokens, index, symbol_tables.clone())?
         {
             var_dec.push(got);
             index = returned_index;
             symbol_tables = returned_symbol_tables;
         }
 
-        let (statements, index) = Statements::new(tokens, index, class_name);
+        let (statements, index) = Statements::new(tokens, index, class_name, dialect);
 
         let index = match tokens.get(index) {
             Some(token::Token::Sym(token::Symbol::RightBrace)) => index + 1,
-            _ => panic!("{}", invalid_token(tokens, index)),
+            _ => return expected(index, "`}` after subroutine body"),
         };
 
-        (Self { var_dec, statements }, index, symbol_tables)
+        Ok((Self { var_dec, statements }, index, symbol_tables))
     }
     fn to_string(&self, symbol_tables: &SymbolTables) -> Vec<String> {
         let mut result = vec![];
@@ -752,26 +1017,26 @@ impl VarDec {
         tokens: &[token::Token],
         index: usize,
         mut symbol_tables: SymbolTables,
-    ) -> (Option<Self>, usize, SymbolTables) {
+    ) -> Result<(Option<Self>, usize, SymbolTables), ParseError> {
         let mut var_name = vec![];
         let index = match tokens.get(index) {
             Some(token::Token::Key(token::Keyword::Var)) => index + 1,
-            _ => return (None, index, symbol_tables),
+            _ => return Ok((None, index, symbol_tables)),
         };
 
         let (type_, index) = match Type::new(tokens, index) {
             (Some(t), i) => (t, i),
-            _ => panic!("{}", invalid_token(tokens, index)),
+            _ => return expected(index, "type"),
         };
 
         let mut index = match tokens.get(index) {
             Some(token::Token::Identifier(token::Identifier(id))) => {
-                var_name.push(VarName(token::Identifier(id.clone())));
+                var_name.push(VarName(token::Identifier(id.clone()), TokenIndex(index)));
                 symbol_tables =
                     symbol_tables.append_subroutine_symbol(id.clone(), type_.clone(), SubroutineSymbolType::Var);
                 index + 1
             }
-            _ => panic!("{}", invalid_token(tokens, index)),
+            _ => return expected(index, "variable name"),
         };
 
         while let Some(token::Token::Sym(token::Symbol::Comma)) = tokens.get(index) {
@@ -780,9 +1045,9 @@ impl VarDec {
             let var_name_new = match tokens.get(index) {
                 Some(token::Token::Identifier(i)) => {
                     index += 1;
-                    VarName(token::Identifier(i.clone().0))
+                    VarName(token::Identifier(i.clone().0), TokenIndex(index - 1))
                 }
-                _ => panic!("{}", invalid_token(tokens, index)),
+                _ => return expected(index, "variable name"),
             };
 
             var_name.push(var_name_new.clone());
@@ -793,54 +1058,39 @@ impl VarDec {
         // 最後にセミコロンがあることをチェック
         match tokens.get(index) {
             Some(token::Token::Sym(token::Symbol::SemiColon)) => index += 1,
-            _ => panic!("{}", invalid_token(tokens, index)),
+            _ => return expected(index, "`;` after variable declaration"),
         };
 
-        (Some(Self { type_, var_name }), index, symbol_tables)
-    }
-    fn to_string(&self, symbol_tables: &SymbolTables) -> Vec<String> {
-        todo!("実装は不要だと思うが念の為残している");
-        // let mut result = vec![];
-        // let (open, close) = get_xml_tag("varDec".to_string());
-        // result.push(open);
-        // result.push(to_xml_tag(token::Keyword::Var));
-        // result.push(self.type_.to_string());
-        // for (index, n) in self.var_name.iter().enumerate() {
-        //     if index != 0 {
-        //         result.push(to_xml_tag(token::Symbol::Comma));
-        //     }
-        //     result.push(n.to_string(symbol_tables));
-        // }
-        // if !&self.var_name.is_empty() {
-        //     result.push(to_xml_tag(token::Symbol::SemiColon));
-        // }
-        //
-        // result.push(close);
-        // result
+        Ok((Some(Self { type_, var_name }), index, symbol_tables))
     }
 }
 
 #[derive(Debug, PartialEq, Eq, Clone)]
 struct ClassName(token::Identifier);
 impl ClassName {
-    fn new(tokens: &[token::Token], index: usize) -> (Self, usize) {
-        if let token::Token::Identifier(token::Identifier(s)) = tokens.get(index).unwrap() {
-            (ClassName(token::Identifier(s.to_string())), index + 1)
+    fn new(tokens: &[token::Token], index: usize) -> Result<(Self, usize), ParseError> {
+        if let Some(token::Token::Identifier(token::Identifier(s))) = tokens.get(index) {
+            Ok((ClassName(token::Identifier(s.to_string())), index + 1))
         } else {
-            panic!("{}", invalid_token(tokens, index))
+            expected(index, "class name")
         }
     }
 }
 
 #[derive(Debug, PartialEq, Eq, Clone)]
 struct SubroutineName(token::Identifier);
+// 位置は変数を参照している箇所(宣言では宣言している箇所)
 #[derive(Clone, Debug, PartialEq, Eq)]
-struct VarName(token::Identifier);
+struct VarName(token::Identifier, TokenIndex);
 impl VarName {
     #[allow(clippy::inherent_to_string)]
-    fn to_string(&self, symbol_tables: &SymbolTables) -> String {
+    fn to_string(&self, symbol_tables: &SymbolTables) -> Vec<String> {
+        // 定数はメモリに置かずに値をそのままpushする
+        if let Some(value) = symbol_tables.constant(&self.0 .0) {
+            return token::IntegerConstant(value as u16 as u32).to_string();
+        }
         let symbol = symbol_tables.get(&self.0 .0);
-        symbol.push()
+        vec![symbol.push()]
     }
 }
 
@@ -850,27 +1100,52 @@ impl VarName {
 #[derive(Debug, PartialEq, Eq, Clone)]
 struct Statements(Vec<Statement>);
 impl Statements {
-    fn new(tokens: &[token::Token], index: usize, class_name: &ClassName) -> (Self, usize) {
+    // `}`かトークン列の終端まで文を読む。
+    // 文の途中でパースエラーがあった場合はStatement::Errorとして記録し、次の文の先頭までスキップして続ける
+    fn new(tokens: &[token::Token], index: usize, class_name: &ClassName, dialect: token::Dialect) -> (Self, usize) {
         let mut statements = vec![];
         let mut index = index;
-        while let (Some(s), returned_index) = Statement::new(tokens, index, class_name) {
-            statements.push(s);
-            index = returned_index;
+        while !matches!(tokens.get(index), None | Some(token::Token::Sym(token::Symbol::RightBrace))) {
+            match Statement::new(tokens, index, class_name, dialect) {
+                Ok((Some(s), returned_index)) => {
+                    statements.push(s);
+                    index = returned_index;
+                }
+                Ok((None, _)) => {
+                    statements.push(Statement::Error(ParseError {
+                        index,
+                        message: "expected statement or `}`".to_string(),
+                    }));
+                    index = skip_statement(tokens, index);
+                }
+                Err(e) => {
+                    statements.push(Statement::Error(e));
+                    index = skip_statement(tokens, index);
+                }
+            }
         }
 
         (Statements(statements), index)
     }
-}
 
-// labelの一意性を担保するためのsuffixを生成するための関数。
-// randomなので厳密には衝突する可能性があり、一意性を必ずしも担保できているとはいえないが、
-// 実装量をセーブするためにそこは妥協する。
-fn gen_random_6_characters_str() -> String {
-    let mut rng = rand::rng();
-    let mut v: Vec<u8> = (b'a'..=b'z').collect();
-    v.shuffle(&mut rng);
-    v = v[0..6].to_vec();
-    String::from_utf8(v).unwrap()
+    fn parse_errors(&self) -> Vec<ParseError> {
+        let mut result = vec![];
+        for s in &self.0 {
+            match s {
+                Statement::Error(e) => result.push(e.clone()),
+                Statement::If(s) => {
+                    result = [result, s.positive_case_body.parse_errors()].concat();
+                    if let Some(negative_case_body) = &s.negative_case_body {
+                        result = [result, negative_case_body.parse_errors()].concat();
+                    }
+                }
+                Statement::While(s) => result = [result, s.body.parse_errors()].concat(),
+                Statement::For(s) => result = [result, s.body.parse_errors()].concat(),
+                _ => {}
+            }
+        }
+        result
+    }
 }
 
 #[derive(Debug, PartialEq, Eq, Clone)]
@@ -880,32 +1155,54 @@ enum Statement {
     While(WhileStatement),
     Do(DoStatement),
     Return(ReturnStatement),
+    // 以下はJack+のみ
+    For(ForStatement),
+    // 位置は`break`, `continue`の位置
+    Break(TokenIndex),
+    Continue(TokenIndex),
+    // パースできなかった文。Ast::newでエラーとして報告されるのでコード生成には使われない
+    Error(ParseError),
 }
 impl Statement {
-    fn new(tokens: &[token::Token], index: usize, class_name: &ClassName) -> (Option<Self>, usize) {
-        match tokens.get(index) {
+    fn new(
+        tokens: &[token::Token],
+        index: usize,
+        class_name: &ClassName,
+        dialect: token::Dialect,
+    ) -> Result<(Option<Self>, usize), ParseError> {
+        Ok(match tokens.get(index) {
             Some(token::Token::Key(token::Keyword::Let)) => {
-                let (l, i) = LetStatement::new(tokens, index, class_name);
+                let (l, i) = LetStatement::new(tokens, index, class_name, dialect)?;
                 (Some(Self::Let(l)), i)
             }
             Some(token::Token::Key(token::Keyword::If)) => {
-                let (l, i) = IfStatement::new(tokens, index, class_name);
+                let (l, i) = IfStatement::new(tokens, index, class_name, dialect)?;
                 (Some(Self::If(l)), i)
             }
             Some(token::Token::Key(token::Keyword::While)) => {
-                let (l, i) = WhileStatement::new(tokens, index, class_name);
+                let (l, i) = WhileStatement::new(tokens, index, class_name, dialect)?;
                 (Some(Self::While(l)), i)
             }
             Some(token::Token::Key(token::Keyword::Do)) => {
-                let (l, i) = DoStatement::new(tokens, index, class_name);
+                let (l, i) = DoStatement::new(tokens, index, class_name, dialect)?;
                 (Some(Self::Do(l)), i)
             }
             Some(token::Token::Key(token::Keyword::Return)) => {
-                let (l, i) = ReturnStatement::new(tokens, index, class_name);
+                let (l, i) = ReturnStatement::new(tokens, index, class_name, dialect)?;
                 (Some(Self::Return(l)), i)
             }
+            Some(token::Token::Key(token::Keyword::For)) => {
+                let (l, i) = ForStatement::new(tokens, index, class_name, dialect)?;
+                (Some(Self::For(l)), i)
+            }
+            Some(token::Token::Key(token::Keyword::Break)) => {
+                (Some(Self::Break(TokenIndex(index))), jump_statement_end(tokens, index)?)
+            }
+            Some(token::Token::Key(token::Keyword::Continue)) => {
+                (Some(Self::Continue(TokenIndex(index))), jump_statement_end(tokens, index)?)
+            }
             _ => (None, index),
-        }
+        })
     }
     fn to_string(&self, symbol_tables: &SymbolTables) -> Vec<String> {
         match self {
@@ -914,6 +1211,11 @@ impl Statement {
             Statement::While(s) => s.to_string(symbol_tables),
             Statement::Do(s) => s.to_string(symbol_tables),
             Statement::Return(s) => s.to_string(symbol_tables),
+            Statement::For(s) => s.to_string(symbol_tables),
+            // ループの外のbreak, continueは意味解析でエラーにしている
+            Statement::Break(_) => vec![format!("goto {}", symbol_tables.loop_labels.borrow().last().unwrap().1)],
+            Statement::Continue(_) => vec![format!("goto {}", symbol_tables.loop_labels.borrow().last().unwrap().0)],
+            Statement::Error(_) => vec![],
         }
     }
 }
@@ -922,56 +1224,107 @@ impl Statement {
 struct LetStatement {
     var_name: VarName,
     array_index: Option<Expression>,
+    // Jack+の複合代入(`let x += 1;`など)の演算子とその位置
+    compound_op: Option<(Op, TokenIndex)>,
     right_hand_side: Expression,
 }
 impl LetStatement {
-    fn new(tokens: &[token::Token], index: usize, class_name: &ClassName) -> (Self, usize) {
+    fn new(
+        tokens: &[token::Token],
+        index: usize,
+        class_name: &ClassName,
+        dialect: token::Dialect,
+    ) -> Result<(Self, usize), ParseError> {
         let index = match tokens.get(index) {
             Some(token::Token::Key(token::Keyword::Let)) => index + 1,
-            _ => panic!("{}", invalid_token(tokens, index)),
+            _ => return expected(index, "`let`"),
+        };
+        let (let_statement, index) = Self::assignment(tokens, index, class_name, dialect)?;
+
+        let index = match tokens.get(index) {
+            Some(token::Token::Sym(token::Symbol::SemiColon)) => index + 1,
+            _ => return expected(index, "`;` after statement"),
         };
+
+        Ok((let_statement, index))
+    }
+    // `let`と`;`を除いた代入の部分を読む。for文の初期化と更新にも使う
+    fn assignment(
+        tokens: &[token::Token],
+        index: usize,
+        class_name: &ClassName,
+        dialect: token::Dialect,
+    ) -> Result<(Self, usize), ParseError> {
         let (var_name, mut index) = match tokens.get(index) {
-            Some(token::Token::Identifier(i)) => (VarName(i.clone()), index + 1),
-            _ => panic!("{}", invalid_token(tokens, index)),
+            Some(token::Token::Identifier(i)) => (VarName(i.clone(), TokenIndex(index)), index + 1),
+            _ => return expected(index, "variable name after `let`"),
         };
 
         let mut array_index = None;
         if let Some(token::Token::Sym(token::Symbol::LeftBracket)) = tokens.get(index) {
-            if let (Some(e), returned_index) = Expression::new(tokens, index + 1, class_name) {
-                match tokens.get(returned_index) {
+            match Expression::new(tokens, index + 1, class_name, dialect)? {
+                (Some(e), returned_index) => match tokens.get(returned_index) {
                     Some(token::Token::Sym(token::Symbol::RightBracket)) => {
                         array_index = Some(e);
                         index = returned_index + 1;
                     }
-                    _ => panic!("{}", invalid_token(tokens, returned_index)),
-                }
+                    _ => return expected(returned_index, "`]` after array index"),
+                },
+                _ => return expected(index + 1, "expression"),
             }
         }
 
-        let index = match tokens.get(index) {
-            Some(token::Token::Sym(token::Symbol::Equal)) => index + 1,
-            _ => panic!("{}", invalid_token(tokens, index)),
+        let (compound_op, index) = match (tokens.get(index), Op::new(tokens, index)) {
+            (Some(token::Token::Sym(token::Symbol::Equal)), _) => (None, index + 1),
+            (_, (Some(op), returned_index)) if op.is_compound_assignable() => match tokens.get(returned_index) {
+                Some(token::Token::Sym(token::Symbol::Equal)) if dialect == token::Dialect::Standard => {
+                    return jack_plus_only(index, &format!("`{}=`", semantic::op_symbol(&op)))
+                }
+                Some(token::Token::Sym(token::Symbol::Equal)) => (Some((op, TokenIndex(index))), returned_index + 1),
+                _ => return expected(index, "`=` in let statement"),
+            },
+            _ => return expected(index, "`=` in let statement"),
         };
-        let (right_hand_side, index) = match Expression::new(tokens, index, class_name) {
+        let (right_hand_side, index) = match Expression::new(tokens, index, class_name, dialect)? {
             (Some(e), index) => (e, index),
-            _ => panic!("{}", invalid_token(tokens, index)),
+            _ => return expected(index, "expression"),
         };
 
-        let index = match tokens.get(index) {
-            Some(token::Token::Sym(token::Symbol::SemiColon)) => index + 1,
-            _ => panic!("{}", invalid_token(tokens, index)),
-        };
-
-        (
+        Ok((
             Self {
                 var_name,
                 array_index,
+                compound_op,
                 right_hand_side,
             },
             index,
-        )
+        ))
     }
     fn to_string(&self, symbol_tables: &SymbolTables) -> Vec<String> {
+        // 右辺を評価してpush。複合代入のときは左辺の現在の値に演算する
+        let right_hand_side = match &self.compound_op {
+            Some((op, _)) => {
+                let current = match &self.array_index {
+                    // 左辺のアドレスはスタックに積んであるので、temp 1を介して複製してから値を読む。
+                    // 添字は1回だけ評価し、右辺を評価する間もアドレスはスタックに残しておく
+                    Some(_) => vec![
+                        "pop temp 1".to_string(),
+                        "push temp 1".to_string(),
+                        "push temp 1".to_string(),
+                        "pop pointer 1".to_string(),
+                        "push that 0".to_string(),
+                    ],
+                    None => vec![symbol_tables.get(&self.var_name.0 .0).push()],
+                };
+                [
+                    current,
+                    self.right_hand_side.to_string(symbol_tables),
+                    vec![op.to_string()],
+                ]
+                .concat()
+            }
+            None => self.right_hand_side.to_string(symbol_tables),
+        };
         match &self.array_index {
             Some(a) => {
                 // 左辺を評価してpush(var_nameのアドレス + 添字)
@@ -983,7 +1336,7 @@ impl LetStatement {
                 .concat();
 
                 // 右辺を評価してpush
-                result = [result, self.right_hand_side.to_string(symbol_tables)].concat();
+                result = [result, right_hand_side].concat();
 
                 // 右辺をtemp 0にpop
                 result.push("pop temp 0".to_string());
@@ -1001,8 +1354,7 @@ impl LetStatement {
                 result
             }
             None => {
-                let right = self.right_hand_side.to_string(symbol_tables);
-                let mut result = right;
+                let mut result = right_hand_side;
 
                 let left = symbol_tables.get(&self.var_name.0 .0);
                 result.push(left.pop());
@@ -1014,37 +1366,45 @@ impl LetStatement {
 
 #[derive(Debug, PartialEq, Eq, Clone)]
 struct IfStatement {
+    // `if`の位置
+    keyword_index: TokenIndex,
     condition: Expression,
     positive_case_body: Statements,
     negative_case_body: Option<Statements>,
 }
 impl IfStatement {
-    fn new(tokens: &[token::Token], index: usize, class_name: &ClassName) -> (Self, usize) {
+    fn new(
+        tokens: &[token::Token],
+        index: usize,
+        class_name: &ClassName,
+        dialect: token::Dialect,
+    ) -> Result<(Self, usize), ParseError> {
+        let keyword_index = TokenIndex(index);
         let index = match tokens.get(index) {
             Some(token::Token::Key(token::Keyword::If)) => index + 1,
-            _ => panic!("{}", invalid_token(tokens, index)),
+            _ => return expected(index, "`if`"),
         };
         let index = match tokens.get(index) {
             Some(token::Token::Sym(token::Symbol::LeftParen)) => index + 1,
-            _ => panic!("{}", invalid_token(tokens, index)),
+            _ => return expected(index, "`(` after `if`"),
         };
-        let (condition, index) = match Expression::new(tokens, index, class_name) {
+        let (condition, index) = match Expression::new(tokens, index, class_name, dialect)? {
             (Some(e), index) => (e, index),
-            _ => panic!("{}", invalid_token(tokens, index)),
+            _ => return expected(index, "expression"),
         };
         let index = match tokens.get(index) {
             Some(token::Token::Sym(token::Symbol::RightParen)) => index + 1,
-            _ => panic!("{}", invalid_token(tokens, index)),
+            _ => return expected(index, "`)` after condition"),
         };
 
         let index = match tokens.get(index) {
             Some(token::Token::Sym(token::Symbol::LeftBrace)) => index + 1,
-            _ => panic!("{}", invalid_token(tokens, index)),
+            _ => return expected(index, "`{` before if body"),
         };
-        let (positive_case_body, index) = Statements::new(tokens, index, class_name);
+        let (positive_case_body, index) = Statements::new(tokens, index, class_name, dialect);
         let index = match tokens.get(index) {
             Some(token::Token::Sym(token::Symbol::RightBrace)) => index + 1,
-            _ => panic!("{}", invalid_token(tokens, index)),
+            _ => return expected(index, "`}` after if body"),
         };
 
         match tokens.get(index) {
@@ -1052,50 +1412,60 @@ impl IfStatement {
             Some(token::Token::Key(token::Keyword::Else)) => {
                 let index = index + 1; // elseの分を前に進める
 
-                let index = match tokens.get(index) {
-                    Some(token::Token::Sym(token::Symbol::LeftBrace)) => index + 1,
-                    _ => panic!("{}", invalid_token(tokens, index)),
-                };
-                let (negative_case_body, index) = Statements::new(tokens, index, class_name);
-                let index = match tokens.get(index) {
-                    Some(token::Token::Sym(token::Symbol::RightBrace)) => index + 1,
-                    _ => panic!("{}", invalid_token(tokens, index)),
+                let (negative_case_body, index) = match tokens.get(index) {
+                    // Jack+の`else if`。else節にif文が1つだけあるものとして扱う
+                    Some(token::Token::Key(token::Keyword::If)) if dialect == token::Dialect::Standard => {
+                        return jack_plus_only(index - 1, "`else if`")
+                    }
+                    Some(token::Token::Key(token::Keyword::If)) => {
+                        let (s, index) = IfStatement::new(tokens, index, class_name, dialect)?;
+                        (Statements(vec![Statement::If(s)]), index)
+                    }
+                    Some(token::Token::Sym(token::Symbol::LeftBrace)) => {
+                        let (negative_case_body, index) = Statements::new(tokens, index + 1, class_name, dialect);
+                        let index = match tokens.get(index) {
+                            Some(token::Token::Sym(token::Symbol::RightBrace)) => index + 1,
+                            _ => return expected(index, "`}` after else body"),
+                        };
+                        (negative_case_body, index)
+                    }
+                    _ => return expected(index, "`{` after `else`"),
                 };
-                (
+                Ok((
                     Self {
+                        keyword_index,
                         condition,
                         positive_case_body,
                         negative_case_body: Some(negative_case_body),
                     },
                     index,
-                )
+                ))
             }
             // else節がないパターン
-            _ => (
+            _ => Ok((
                 Self {
+                    keyword_index,
                     condition,
                     positive_case_body,
                     negative_case_body: None,
                 },
                 index,
-            ),
+            )),
         }
     }
     fn to_string(&self, symbol_tables: &SymbolTables) -> Vec<String> {
-        let (negative_case_label_name, end_if_statement_label_name) = {
-            let label_base = format!(
-                "{}.{}:{}",
-                symbol_tables.file_name,
-                symbol_tables.current_subroutine_name.clone().unwrap(),
-                gen_random_6_characters_str()
-            );
+        // ネストしたif文より先に番号を取るので、番号は出現順になる
+        let id = symbol_tables.next_if_label_id();
+        let (negative_case_label_name, end_if_statement_label_name) =
+            (format!("IF_FALSE{}", id), format!("IF_END{}", id));
 
-            (format!("{}_if_start", label_base), format!("{}_if_end", label_base))
-        };
+        if let Some(negative_case_body) = &self.negative_case_body {
+            if symbol_tables.opt_level >= 2 && self.condition.is_boolean() && !self.condition.is_negation() {
+                return self.swapped_to_string(negative_case_body, id, symbol_tables);
+            }
+        }
 
-        let mut result = self.condition.to_string(symbol_tables);
-        result.push("not".to_string());
-        result.push(format!("if-goto {}", negative_case_label_name));
+        let mut result = self.condition.jump_if_false(&negative_case_label_name, symbol_tables);
 
         for statement in &self.positive_case_body.0 {
             result = [result, statement.to_string(symbol_tables)].concat();
@@ -1109,6 +1479,31 @@ impl IfStatement {
             }
         }
 
+        result.push(format!("label {}", end_if_statement_label_name));
+        result
+    }
+    // 条件が必ずtrueかfalseになるときは、notせずにtrueのときthen節へ飛び、else節を先に置く
+    fn swapped_to_string(
+        &self,
+        negative_case_body: &Statements,
+        id: usize,
+        symbol_tables: &SymbolTables,
+    ) -> Vec<String> {
+        let (positive_case_label_name, end_if_statement_label_name) =
+            (format!("IF_TRUE{}", id), format!("IF_END{}", id));
+
+        let mut result = self.condition.to_string(symbol_tables);
+        result.push(format!("if-goto {}", positive_case_label_name));
+        for statement in &negative_case_body.0 {
+            result = [result, statement.to_string(symbol_tables)].concat();
+        }
+        result.push(format!("goto {}", end_if_statement_label_name));
+
+        result.push(format!("label {}", positive_case_label_name));
+        for statement in &self.positive_case_body.0 {
+            result = [result, statement.to_string(symbol_tables)].concat();
+        }
+
         result.push(format!("label {}", end_if_statement_label_name));
         result
     }
@@ -1116,61 +1511,183 @@ impl IfStatement {
 
 #[derive(Debug, PartialEq, Eq, Clone)]
 struct WhileStatement {
+    // `while`の位置
+    keyword_index: TokenIndex,
     condition: Expression,
     body: Statements,
 }
 impl WhileStatement {
-    fn new(tokens: &[token::Token], index: usize, class_name: &ClassName) -> (Self, usize) {
+    fn new(
+        tokens: &[token::Token],
+        index: usize,
+        class_name: &ClassName,
+        dialect: token::Dialect,
+    ) -> Result<(Self, usize), ParseError> {
+        let keyword_index = TokenIndex(index);
         let index = match tokens.get(index) {
             Some(token::Token::Key(token::Keyword::While)) => index + 1,
-            _ => panic!("{}", invalid_token(tokens, index)),
+            _ => return expected(index, "`while`"),
         };
         let index = match tokens.get(index) {
             Some(token::Token::Sym(token::Symbol::LeftParen)) => index + 1,
-            _ => panic!("{}", invalid_token(tokens, index)),
+            _ => return expected(index, "`(` after `while`"),
         };
-        let (condition, index) = match Expression::new(tokens, index, class_name) {
+        let (condition, index) = match Expression::new(tokens, index, class_name, dialect)? {
             (Some(e), index) => (e, index),
-            _ => panic!("{}", invalid_token(tokens, index)),
+            _ => return expected(index, "expression"),
         };
         let index = match tokens.get(index) {
             Some(token::Token::Sym(token::Symbol::RightParen)) => index + 1,
-            _ => panic!("{}", invalid_token(tokens, index)),
+            _ => return expected(index, "`)` after condition"),
         };
 
         let index = match tokens.get(index) {
             Some(token::Token::Sym(token::Symbol::LeftBrace)) => index + 1,
-            _ => panic!("{}", invalid_token(tokens, index)),
+            _ => return expected(index, "`{` before while body"),
         };
-        let (body, index) = Statements::new(tokens, index, class_name);
+        let (body, index) = Statements::new(tokens, index, class_name, dialect);
         let index = match tokens.get(index) {
             Some(token::Token::Sym(token::Symbol::RightBrace)) => index + 1,
-            _ => panic!("{}", invalid_token(tokens, index)),
+            _ => return expected(index, "`}` after while body"),
         };
 
-        (Self { condition, body }, index)
+        Ok((
+            Self {
+                keyword_index,
+                condition,
+                body,
+            },
+            index,
+        ))
     }
     fn to_string(&self, symbol_tables: &SymbolTables) -> Vec<String> {
-        let (start_label_name, end_label_name) = {
-            let label_base = format!(
-                "{}.{}:{}",
-                symbol_tables.file_name,
-                symbol_tables.current_subroutine_name.clone().unwrap(),
-                gen_random_6_characters_str()
-            );
+        let id = symbol_tables.next_while_label_id();
+        let (start_label_name, end_label_name) = (format!("WHILE_EXP{}", id), format!("WHILE_END{}", id));
+
+        let mut result = vec![format!("label {}", start_label_name)];
+
+        result = [result, self.condition.jump_if_false(&end_label_name, symbol_tables)].concat();
+
+        symbol_tables
+            .loop_labels
+            .borrow_mut()
+            .push((start_label_name.clone(), end_label_name.clone()));
+        for statement in &self.body.0 {
+            result = [result, statement.to_string(symbol_tables)].concat();
+        }
+        symbol_tables.loop_labels.borrow_mut().pop();
+        result.push(format!("goto {}", start_label_name));
+
+        result.push(format!("label {}", end_label_name));
+        result
+    }
+}
+
+// Jack+のfor文。初期化と更新は`let`と`;`を省いた代入で、どちらも省略できる
+#[derive(Debug, PartialEq, Eq, Clone)]
+struct ForStatement {
+    // `for`の位置
+    keyword_index: TokenIndex,
+    init: Option<LetStatement>,
+    condition: Expression,
+    step: Option<LetStatement>,
+    body: Statements,
+}
+impl ForStatement {
+    fn new(
+        tokens: &[token::Token],
+        index: usize,
+        class_name: &ClassName,
+        dialect: token::Dialect,
+    ) -> Result<(Self, usize), ParseError> {
+        let keyword_index = TokenIndex(index);
+        let index = match tokens.get(index) {
+            Some(token::Token::Key(token::Keyword::For)) => index + 1,
+            _ => return expected(index, "`for`"),
+        };
+        let index = match tokens.get(index) {
+            Some(token::Token::Sym(token::Symbol::LeftParen)) => index + 1,
+            _ => return expected(index, "`(` after `for`"),
+        };
+        let (init, index) = match tokens.get(index) {
+            Some(token::Token::Sym(token::Symbol::SemiColon)) => (None, index),
+            _ => {
+                let (s, index) = LetStatement::assignment(tokens, index, class_name, dialect)?;
+                (Some(s), index)
+            }
+        };
+        let index = match tokens.get(index) {
+            Some(token::Token::Sym(token::Symbol::SemiColon)) => index + 1,
+            _ => return expected(index, "`;` after initialization"),
+        };
+        let (condition, index) = match Expression::new(tokens, index, class_name, dialect)? {
+            (Some(e), index) => (e, index),
+            _ => return expected(index, "expression"),
+        };
+        let index = match tokens.get(index) {
+            Some(token::Token::Sym(token::Symbol::SemiColon)) => index + 1,
+            _ => return expected(index, "`;` after condition"),
+        };
+        let (step, index) = match tokens.get(index) {
+            Some(token::Token::Sym(token::Symbol::RightParen)) => (None, index),
+            _ => {
+                let (s, index) = LetStatement::assignment(tokens, index, class_name, dialect)?;
+                (Some(s), index)
+            }
+        };
+        let index = match tokens.get(index) {
+            Some(token::Token::Sym(token::Symbol::RightParen)) => index + 1,
+            _ => return expected(index, "`)` after step"),
+        };
 
-            (format!("{}_while_start", label_base), format!("{}_while_end", label_base))
+        let index = match tokens.get(index) {
+            Some(token::Token::Sym(token::Symbol::LeftBrace)) => index + 1,
+            _ => return expected(index, "`{` before for body"),
+        };
+        let (body, index) = Statements::new(tokens, index, class_name, dialect);
+        let index = match tokens.get(index) {
+            Some(token::Token::Sym(token::Symbol::RightBrace)) => index + 1,
+            _ => return expected(index, "`}` after for body"),
         };
 
-        let mut result = vec![format!("label {}", start_label_name)];
+        Ok((
+            Self {
+                keyword_index,
+                init,
+                condition,
+                step,
+                body,
+            },
+            index,
+        ))
+    }
+    // while文と同じラベルに、continueの飛び先として更新の直前のWHILE_STEPを加える
+    fn to_string(&self, symbol_tables: &SymbolTables) -> Vec<String> {
+        let mut result = match &self.init {
+            Some(init) => init.to_string(symbol_tables),
+            None => vec![],
+        };
 
-        result = [result, self.condition.to_string(symbol_tables)].concat();
-        result.push("not".to_string());
-        result.push(format!("if-goto {}", end_label_name));
+        let id = symbol_tables.next_while_label_id();
+        let (start_label_name, step_label_name, end_label_name) =
+            (format!("WHILE_EXP{}", id), format!("WHILE_STEP{}", id), format!("WHILE_END{}", id));
+
+        result.push(format!("label {}", start_label_name));
+        result = [result, self.condition.jump_if_false(&end_label_name, symbol_tables)].concat();
 
+        symbol_tables
+            .loop_labels
+            .borrow_mut()
+            .push((step_label_name.clone(), end_label_name.clone()));
         for statement in &self.body.0 {
             result = [result, statement.to_string(symbol_tables)].concat();
         }
+        symbol_tables.loop_labels.borrow_mut().pop();
+
+        result.push(format!("label {}", step_label_name));
+        if let Some(step) = &self.step {
+            result = [result, step.to_string(symbol_tables)].concat();
+        }
         result.push(format!("goto {}", start_label_name));
 
         result.push(format!("label {}", end_label_name));
@@ -1181,18 +1698,26 @@ impl WhileStatement {
 #[derive(Debug, PartialEq, Eq, Clone)]
 struct DoStatement(SubroutineCall);
 impl DoStatement {
-    fn new(tokens: &[token::Token], index: usize, class_name: &ClassName) -> (Self, usize) {
+    fn new(
+        tokens: &[token::Token],
+        index: usize,
+        class_name: &ClassName,
+        dialect: token::Dialect,
+    ) -> Result<(Self, usize), ParseError> {
         let index = match tokens.get(index) {
             Some(token::Token::Key(token::Keyword::Do)) => index + 1,
-            _ => panic!("{}", invalid_token(tokens, index)),
+            _ => return expected(index, "`do`"),
+        };
+        let (subroutine_call, index) = match SubroutineCall::new(tokens, index, class_name, dialect)? {
+            (Some(s), index) => (s, index),
+            _ => return expected(index, "subroutine call after `do`"),
         };
-        let (subroutine_call, index) = SubroutineCall::new(tokens, index, class_name);
         let index = match tokens.get(index) {
             Some(token::Token::Sym(token::Symbol::SemiColon)) => index + 1,
-            _ => panic!("{}", invalid_token(tokens, index)),
+            _ => return expected(index, "`;` after statement"),
         };
 
-        (Self(subroutine_call.unwrap()), index)
+        Ok((Self(subroutine_call), index))
     }
     fn to_string(&self, symbol_tables: &SymbolTables) -> Vec<String> {
         let mut result = self.0.to_string(symbol_tables);
@@ -1202,20 +1727,27 @@ impl DoStatement {
 }
 
 #[derive(Debug, PartialEq, Eq, Clone)]
-struct ReturnStatement(Option<Expression>);
+// 位置は`return`の位置
+struct ReturnStatement(Option<Expression>, TokenIndex);
 impl ReturnStatement {
-    fn new(tokens: &[token::Token], index: usize, class_name: &ClassName) -> (Self, usize) {
+    fn new(
+        tokens: &[token::Token],
+        index: usize,
+        class_name: &ClassName,
+        dialect: token::Dialect,
+    ) -> Result<(Self, usize), ParseError> {
+        let keyword_index = TokenIndex(index);
         let index = match tokens.get(index) {
             Some(token::Token::Key(token::Keyword::Return)) => index + 1,
-            _ => panic!("{}", invalid_token(tokens, index)),
+            _ => return expected(index, "`return`"),
         };
-        let (expression, index) = Expression::new(tokens, index, class_name);
+        let (expression, index) = Expression::new(tokens, index, class_name, dialect)?;
         let index = match tokens.get(index) {
             Some(token::Token::Sym(token::Symbol::SemiColon)) => index + 1,
-            _ => panic!("{}", invalid_token(tokens, index)),
+            _ => return expected(index, "`;` after statement"),
         };
 
-        (Self(expression), index)
+        Ok((Self(expression, keyword_index), index))
     }
     fn to_string(&self, symbol_tables: &SymbolTables) -> Vec<String> {
         match &self.0 {
@@ -1237,62 +1769,250 @@ impl ReturnStatement {
 #[derive(Debug, PartialEq, Eq, Clone)]
 struct Expression {
     term: Box<Term>,
-    op_term: Vec<(Op, Term)>,
+    // 演算子, 演算子の位置, 右側の項
+    op_term: Vec<(Op, TokenIndex, Term)>,
 }
 impl Expression {
-    fn new(tokens: &[token::Token], index: usize, class_name: &ClassName) -> (Option<Self>, usize) {
-        let (term, mut index) = match Term::new(tokens, index, class_name) {
+    fn new(
+        tokens: &[token::Token],
+        index: usize,
+        class_name: &ClassName,
+        dialect: token::Dialect,
+    ) -> Result<(Option<Self>, usize), ParseError> {
+        let (term, mut index) = match Term::new(tokens, index, class_name, dialect)? {
             (Some(t), i) => (t, i),
-            _ => return (None, index),
+            _ => return Ok((None, index)),
         };
 
         let mut op_term = vec![];
         while let (Some(o), op_index) = Op::new(tokens, index) {
-            index = op_index;
-            if let (Some(t), term_index) = Term::new(tokens, op_index, class_name) {
-                index = term_index;
-                op_term.push((o, t));
+            match Term::new(tokens, op_index, class_name, dialect)? {
+                (Some(t), term_index) => {
+                    op_term.push((o, TokenIndex(index), t));
+                    index = term_index;
+                }
+                _ => return expected(op_index, "term after operator"),
             }
         }
 
-        (
+        Ok((
             Some(Expression {
                 term: Box::new(term),
                 op_term,
             }),
             index,
-        )
+        ))
     }
     fn to_string(&self, symbol_tables: &SymbolTables) -> Vec<String> {
-        let mut result = self.term.to_string(symbol_tables);
-        for o in &self.op_term {
-            result = [result, o.1.to_string(symbol_tables)].concat();
-            result.push(o.0.to_string());
+        let mut op_term = self.op_term.iter().peekable();
+        let mut result = match op_term.peek() {
+            // `2 * x`は`x * 2`と同じなので、左辺の定数は右辺を先に積んでから掛ける
+            Some((Op::Multiply, _, t))
+                if symbol_tables.opt_level >= 2 && power_of_two(&self.term, symbol_tables).is_some() =>
+            {
+                op_term.next();
+                double(t.to_string(symbol_tables), power_of_two(&self.term, symbol_tables).unwrap())
+            }
+            _ => self.term.to_string(symbol_tables),
+        };
+        for (op, _, t) in op_term {
+            result = match op {
+                Op::DoubleAmpersand | Op::DoublePipe => short_circuit(op, result, t, symbol_tables),
+                Op::Multiply if symbol_tables.opt_level >= 2 && power_of_two(t, symbol_tables).is_some() => {
+                    double(result, power_of_two(t, symbol_tables).unwrap())
+                }
+                _ => [result, t.to_string(symbol_tables), vec![op.to_string()]].concat(),
+            };
         }
 
         result
     }
+    // `~e`の形の式か
+    fn is_negation(&self) -> bool {
+        self.op_term.is_empty() && matches!(*self.term, Term::UnaryOp(UnaryOp::Tilde, _, _))
+    }
+    // 条件がfalseのときにlabelへ飛ぶ。
+    // 最適化するときは、`~e`の否定と分岐のための否定は打ち消し合うので、どちらのnotも出力せずにeで分岐する
+    fn jump_if_false(&self, label: &str, symbol_tables: &SymbolTables) -> Vec<String> {
+        match self.term.as_ref() {
+            Term::UnaryOp(UnaryOp::Tilde, _, t) if symbol_tables.opt_level >= 2 && self.is_negation() => {
+                [t.to_string(symbol_tables), vec![format!("if-goto {}", label)]].concat()
+            }
+            _ => [
+                self.to_string(symbol_tables),
+                vec!["not".to_string(), format!("if-goto {}", label)],
+            ]
+            .concat(),
+        }
+    }
+    // 値が必ずtrue(-1)かfalse(0)になる式か。比較の結果とその否定だけを対象にする
+    fn is_boolean(&self) -> bool {
+        match self.op_term.last() {
+            Some((op, _, _)) => matches!(op, Op::LessThan | Op::MoreThan | Op::Equal),
+            None => self.term.is_boolean(),
+        }
+    }
+}
+
+impl Term {
+    fn is_boolean(&self) -> bool {
+        match self {
+            Term::KeyWordConstant(KeyWordConstant::True | KeyWordConstant::False) => true,
+            Term::Expression(e) => e.is_boolean(),
+            Term::UnaryOp(UnaryOp::Tilde, _, t) => t.is_boolean(),
+            _ => false,
+        }
+    }
+}
+
+// 2のべき乗の定数ならその指数を返す。1(2の0乗)は掛けても変わらない
+fn power_of_two(term: &Term, symbol_tables: &SymbolTables) -> Option<u32> {
+    match term.const_value(symbol_tables)? {
+        v if v > 0 && v & (v - 1) == 0 => Some(v.trailing_zeros()),
+        _ => None,
+    }
+}
+
+// スタックの先頭の値をk回2倍する。VMに複製の命令はないので、temp 0に退避して2回pushする
+fn double(value: Vec<String>, k: u32) -> Vec<String> {
+    let mut result = value;
+    for _ in 0..k {
+        result = [
+            result,
+            vec![
+                "pop temp 0".to_string(),
+                "push temp 0".to_string(),
+                "push temp 0".to_string(),
+                "add".to_string(),
+            ],
+        ]
+        .concat();
+    }
+    result
+}
+
+impl Expression {
+    // コンパイル時に値が決まる式ならその値を返す。演算はVMと同じく16bitで行い、左から順に畳み込む
+    fn const_value(&self, symbol_tables: &SymbolTables) -> Option<i16> {
+        let mut left = self.term.const_value(symbol_tables)?;
+        for (op, _, t) in &self.op_term {
+            left = op.fold(left, t.const_value(symbol_tables)?)?;
+        }
+        Some(left)
+    }
+}
+
+impl Op {
+    // 定数どうしの演算の結果。0での割り算は実行時のエラーにするので畳み込まない
+    fn fold(&self, left: i16, right: i16) -> Option<i16> {
+        Some(match self {
+            Op::Plus => left.wrapping_add(right),
+            Op::Minus => left.wrapping_sub(right),
+            Op::Multiply => left.wrapping_mul(right),
+            Op::Div if right == 0 => return None,
+            Op::Div => left.wrapping_div(right),
+            Op::Ampersand => left & right,
+            Op::Pipe => left | right,
+            Op::LessThan => -((left < right) as i16),
+            Op::MoreThan => -((left > right) as i16),
+            Op::Equal => -((left == right) as i16),
+            // short_circuitと同じ結果にする。`&&`は左辺をnotしてif-gotoで分岐するので、右辺に進むのは左辺がtrue(-1)のときだけで、
+            // `||`は左辺が0以外なら右辺に進まない
+            Op::DoubleAmpersand if left != -1 => 0,
+            Op::DoublePipe if left != 0 => -1,
+            Op::DoubleAmpersand | Op::DoublePipe => right,
+        })
+    }
+}
+
+impl Term {
+    fn const_value(&self, symbol_tables: &SymbolTables) -> Option<i16> {
+        match self {
+            Term::IntegerConstant(i) => Some(i.0 as u16 as i16),
+            Term::CharConstant(c) => Some(c.0 as i16),
+            Term::KeyWordConstant(KeyWordConstant::True) => Some(-1),
+            Term::KeyWordConstant(KeyWordConstant::False) => Some(0),
+            Term::VarName(v) => symbol_tables.constant(&v.0 .0),
+            // 他のクラスの列挙子はコード生成時にClassTableから値を引く
+            Term::EnumMember(e, m) => symbol_tables.constant(&enum_member_name(&e.0, &m.0 .0)),
+            Term::Expression(e) => e.const_value(symbol_tables),
+            Term::UnaryOp(UnaryOp::Minus, _, t) => Some(t.const_value(symbol_tables)?.wrapping_neg()),
+            Term::UnaryOp(UnaryOp::Tilde, _, t) => Some(!t.const_value(symbol_tables)?),
+            _ => None,
+        }
+    }
+}
+
+// 左辺の値で右辺を評価するかを分岐する。
+// `a && b`は左辺がfalseならfalseをpushし、`a || b`は左辺がtrueならtrueをpushする。それ以外は右辺の値がそのまま結果になる
+fn short_circuit(op: &Op, left: Vec<String>, right: &Term, symbol_tables: &SymbolTables) -> Vec<String> {
+    let id = symbol_tables.next_short_circuit_label_id();
+    let (skip_label_name, end_label_name, skipped_value) = match op {
+        Op::DoubleAmpersand => {
+            (format!("AND_FALSE{}", id), format!("AND_END{}", id), vec!["push constant 0".to_string()])
+        }
+        _ => {
+            (format!("OR_TRUE{}", id), format!("OR_END{}", id), vec!["push constant 0".to_string(), "not".to_string()])
+        }
+    };
+
+    let mut result = left;
+    if *op == Op::DoubleAmpersand {
+        result.push("not".to_string());
+    }
+    result.push(format!("if-goto {}", skip_label_name));
+    result = [result, right.to_string(symbol_tables)].concat();
+    result.push(format!("goto {}", end_label_name));
+    result.push(format!("label {}", skip_label_name));
+    result = [result, skipped_value].concat();
+    result.push(format!("label {}", end_label_name));
+    result
 }
 
 #[derive(Debug, PartialEq, Eq, Clone)]
 enum Term {
     IntegerConstant(token::IntegerConstant),
     StringConstant(token::StringConstant),
+    CharConstant(token::CharConstant),
     KeyWordConstant(KeyWordConstant),
     VarName(VarName),
     ArrayIndexAccess(VarName, Expression),
     Expression(Expression),
-    UnaryOp(UnaryOp, Box<Term>),
+    // 位置は単項演算子の位置
+    UnaryOp(UnaryOp, TokenIndex, Box<Term>),
     SubroutineCall(SubroutineCall),
+    // Jack+の`EnumName.MEMBER`
+    EnumMember(token::Identifier, VarName),
 }
 impl Term {
-    fn new(tokens: &[token::Token], index: usize, class_name: &ClassName) -> (Option<Self>, usize) {
-        if let (Some(s), index) = SubroutineCall::new(tokens, index, class_name) {
-            return (Some(Term::SubroutineCall(s)), index);
+    fn new(
+        tokens: &[token::Token],
+        index: usize,
+        class_name: &ClassName,
+        dialect: token::Dialect,
+    ) -> Result<(Option<Self>, usize), ParseError> {
+        // `Name.name`のあとに`(`が続かなければsubroutine呼び出しではなく列挙子の参照として読む
+        if let (
+            Some(token::Token::Identifier(enum_name)),
+            Some(token::Token::Sym(token::Symbol::Dot)),
+            Some(token::Token::Identifier(member)),
+        ) = (tokens.get(index), tokens.get(index + 1), tokens.get(index + 2))
+        {
+            if !matches!(tokens.get(index + 3), Some(token::Token::Sym(token::Symbol::LeftParen))) {
+                if dialect == token::Dialect::Standard {
+                    return jack_plus_only(index, &format!("enum member `{}.{}`", enum_name.0, member.0));
+                }
+                let member = VarName(member.clone(), TokenIndex(index + 2));
+                return Ok((Some(Term::EnumMember(enum_name.clone(), member)), index + 3));
+            }
         }
-        match tokens.get(index) {
+        if let (Some(s), index) = SubroutineCall::new(tokens, index, class_name, dialect)? {
+            return Ok((Some(Term::SubroutineCall(s)), index));
+        }
+        Ok(match tokens.get(index) {
             Some(token::Token::IntegerConstant(i)) => (Some(Term::IntegerConstant(i.clone())), index + 1),
             Some(token::Token::StringConstant(s)) => (Some(Term::StringConstant(s.clone())), index + 1),
+            Some(token::Token::CharConstant(c)) => (Some(Term::CharConstant(c.clone())), index + 1),
             Some(token::Token::Key(token::Keyword::True)) => {
                 (Some(Term::KeyWordConstant(KeyWordConstant::True)), index + 1)
             }
@@ -1303,68 +2023,93 @@ impl Term {
                 (Some(Term::KeyWordConstant(KeyWordConstant::Null)), index + 1)
             }
             Some(token::Token::Key(token::Keyword::This)) => {
-                (Some(Term::KeyWordConstant(KeyWordConstant::This)), index + 1)
+                (Some(Term::KeyWordConstant(KeyWordConstant::This(TokenIndex(index)))), index + 1)
             }
             Some(token::Token::Identifier(i)) => {
+                let var_name = VarName(i.clone(), TokenIndex(index));
                 // VarName[index]のパターン
                 match tokens.get(index + 1) {
                     Some(token::Token::Sym(token::Symbol::LeftBracket)) => {
-                        match Expression::new(tokens, index + 2, class_name) {
+                        match Expression::new(tokens, index + 2, class_name, dialect)? {
                             (Some(ex), index) => match tokens.get(index) {
                                 Some(token::Token::Sym(token::Symbol::RightBracket)) => {
                                     // index[len-2]みたいなパターンもあるので。Expression::new()
                                     // から返ってきたindexを使う必要があることに注意
-                                    (Some(Term::ArrayIndexAccess(VarName(i.clone()), ex)), index + 1)
+                                    (Some(Term::ArrayIndexAccess(var_name, ex)), index + 1)
                                 }
-                                _ => panic!("{}", invalid_token(tokens, index)),
+                                _ => return expected(index, "`]` after array index"),
                             },
                             // たまたま Identifier, [, なにか, ]という並びになる可能性もある。なのでVarName[index]としてパースできなかったら
                             // VarNameとして扱っておく
-                            _ => (Some(Term::VarName(VarName(i.clone()))), index + 1),
+                            _ => (Some(Term::VarName(var_name)), index + 1),
                         }
                     }
 
                     // VarNameのパターン
-                    _ => (Some(Term::VarName(VarName(i.clone()))), index + 1),
+                    _ => (Some(Term::VarName(var_name)), index + 1),
                 }
             }
             Some(token::Token::Sym(token::Symbol::LeftParen)) => {
-                let index = match tokens.get(index) {
-                    Some(token::Token::Sym(token::Symbol::LeftParen)) => index + 1,
-                    _ => return (None, index), // ExpressionListから見るとtermが空のパターンもあるのでpanicしてはならない
+                let (expression, index) = match Expression::new(tokens, index + 1, class_name, dialect)? {
+                    (Some(e), index) => (e, index),
+                    _ => return expected(index + 1, "expression"),
                 };
-                let (expression, index) = Expression::new(tokens, index, class_name);
-
                 let index = match tokens.get(index) {
                     Some(token::Token::Sym(token::Symbol::RightParen)) => index + 1,
-                    _ => return (None, index), // ExpressionListから見るとtermが空のパターンもあるのでpanicしてはならない
+                    _ => return expected(index, "`)` after expression"),
                 };
-                (Some(Term::Expression(expression.unwrap())), index)
+                (Some(Term::Expression(expression)), index)
             }
 
             // 単に1token読んだだけではわからないパターン
             _ => {
+                let unary_index = TokenIndex(index);
                 if let (Some(u), index) = UnaryOp::new(tokens, index) {
-                    match Term::new(tokens, index, class_name) {
+                    match Term::new(tokens, index, class_name, dialect)? {
                         (Some(t), index) => {
-                            return (Some(Term::UnaryOp(u, Box::new(t))), index);
+                            return Ok((Some(Term::UnaryOp(u, unary_index, Box::new(t))), index));
                         }
-                        _ => panic!("{}", invalid_token(tokens, index)),
+                        _ => return expected(index, "term after unary operator"),
                     }
                 };
                 (None, index)
             }
-        }
+        })
     }
     fn to_string(&self, symbol_tables: &SymbolTables) -> Vec<String> {
         match self {
-            Term::IntegerConstant(s) => vec![s.to_string()],
-            Term::StringConstant(s) => s.to_string(),
+            Term::IntegerConstant(s) => s.to_string(),
+            Term::CharConstant(c) => vec![c.to_string()],
+            Term::StringConstant(s) => match symbol_tables.interned_string_index(&s.0) {
+                // 初回の評価時だけStringを生成してstatic変数に格納する
+                Some(index) => {
+                    let id = symbol_tables.next_string_label_id();
+                    [
+                        vec![format!("push static {}", index), format!("if-goto STRING_READY{}", id)],
+                        s.to_string(),
+                        vec![
+                            format!("pop static {}", index),
+                            format!("label STRING_READY{}", id),
+                            format!("push static {}", index),
+                        ],
+                    ]
+                    .concat()
+                }
+                None => s.to_string(),
+            },
             Term::KeyWordConstant(s) => s.to_string(),
-            Term::VarName(s) => vec![s.to_string(symbol_tables)],
+            Term::VarName(s) => s.to_string(symbol_tables),
+            Term::EnumMember(e, m) => {
+                // 自クラスの列挙子を優先し、なければ他のクラスで宣言された列挙型から探す。存在は意味解析で確認済み
+                let value = symbol_tables
+                    .constant(&enum_member_name(&e.0, &m.0 .0))
+                    .or_else(|| symbol_tables.classes.enum_value(&e.0, &m.0 .0))
+                    .unwrap();
+                vec![format!("push constant {}", value)]
+            }
             Term::ArrayIndexAccess(v, e) => {
                 // vとeのアドレスを足し合わせる
-                let mut result = vec![v.to_string(symbol_tables)];
+                let mut result = v.to_string(symbol_tables);
                 result = [result, e.to_string(symbol_tables)].concat();
                 result.push("add".to_string());
                 result.push("pop pointer 1".to_string()); // 計算したアドレスをポインタにセット
@@ -1373,7 +2118,7 @@ impl Term {
                 result
             }
             Term::Expression(s) => s.to_string(symbol_tables),
-            Term::UnaryOp(u, t) => {
+            Term::UnaryOp(u, _, t) => {
                 let mut result = t.to_string(symbol_tables);
                 result.push(u.to_string());
                 result
@@ -1388,7 +2133,8 @@ enum KeyWordConstant {
     True,
     False,
     Null,
-    This,
+    // functionの中で使うとエラーになるので位置を持つ
+    This(TokenIndex),
 }
 impl KeyWordConstant {
     #[allow(clippy::inherent_to_string)]
@@ -1397,7 +2143,7 @@ impl KeyWordConstant {
             KeyWordConstant::True => vec!["push constant 0", "not"],
             KeyWordConstant::False => vec!["push constant 0"],
             KeyWordConstant::Null => vec!["push constant 0"],
-            KeyWordConstant::This => vec!["push pointer 0"],
+            KeyWordConstant::This(_) => vec!["push pointer 0"],
         }
         .into_iter()
         .map(|e| e.to_string())
@@ -1407,80 +2153,85 @@ impl KeyWordConstant {
 
 #[derive(Debug, PartialEq, Eq, Clone)]
 struct SubroutineCall {
-    // FIXME: VarNameしかこないはずなのでリファクタできそう。
     receiver: Option<Receiver>,
     name: SubroutineName,
+    // subroutine名の位置。レシーバがある場合はその2つ前(`receiver.name`)がレシーバの位置になる
+    name_index: TokenIndex,
     arguments: ExpressionList,
 }
 impl SubroutineCall {
     // NOTE: _class_nameは必要なくなったがあとで必要になるかもなのでいったん残しておく
-    fn new(tokens: &[token::Token], index: usize, class_name: &ClassName) -> (Option<Self>, usize) {
+    fn new(
+        tokens: &[token::Token],
+        index: usize,
+        class_name: &ClassName,
+        dialect: token::Dialect,
+    ) -> Result<(Option<Self>, usize), ParseError> {
         let exist_receiver = matches!(tokens.get(index + 1), Some(token::Token::Sym(token::Symbol::Dot)));
 
         if exist_receiver {
+            // 変数名かクラス名かはここでは決めずにコード生成時にシンボルテーブルから解決する
             let (receiver, index) = match tokens.get(index) {
-                Some(token::Token::Identifier(i)) => {
-                    // 1文字目が大文字ならclass_nameだと判定する
-                    if i.0.chars().next().map_or(false, char::is_uppercase) {
-                        (Some(Receiver::ClassName(ClassName(i.clone()))), index + 1)
-                    } else {
-                        (Some(Receiver::VarName(VarName(i.clone()))), index + 1)
-                    }
-                }
-                _ => (None, index),
+                Some(token::Token::Identifier(i)) => (Receiver::Name(i.clone()), index + 1),
+                Some(token::Token::Key(token::Keyword::This)) => (Receiver::This, index + 1),
+                _ => return Ok((None, index)),
             };
             // index番目に`.`があることは確認済みなのでindex + 1を見る
+            let name_index = TokenIndex(index + 1);
             let (name, index) = match tokens.get(index + 1) {
                 Some(token::Token::Identifier(i)) => (SubroutineName(i.clone()), index + 2),
-                _ => panic!("{}", invalid_token(tokens, index + 1)),
+                _ => return expected(index + 1, "subroutine name after `.`"),
             };
             let index = match tokens.get(index) {
                 Some(token::Token::Sym(token::Symbol::LeftParen)) => index + 1,
-                _ => panic!("{}", invalid_token(tokens, index)),
+                _ => return expected(index, "`(` after subroutine name"),
             };
-            let (arguments, index) = match ExpressionList::new(tokens, index, class_name) {
+            let (arguments, index) = match ExpressionList::new(tokens, index, class_name, dialect)? {
                 (Some(el), returned_index) => (el, returned_index),
                 _ => (ExpressionList(vec![]), index),
             };
             let index = match tokens.get(index) {
                 Some(token::Token::Sym(token::Symbol::RightParen)) => index + 1,
-                _ => panic!("{}", invalid_token(tokens, index)),
+                _ => return expected(index, "`)` after arguments"),
             };
 
-            (
+            Ok((
                 Some(Self {
-                    receiver,
+                    receiver: Some(receiver),
                     name,
+                    name_index,
                     arguments,
                 }),
                 index,
-            )
+            ))
         } else {
+            let name_index = TokenIndex(index);
             let (subroutine_name, index) = match tokens.get(index) {
                 Some(token::Token::Identifier(i)) => (SubroutineName(i.clone()), index + 1),
-                _ => return (None, index),
+                _ => return Ok((None, index)),
             };
             let index = match tokens.get(index) {
                 Some(token::Token::Sym(token::Symbol::LeftParen)) => index + 1,
-                _ => return (None, index),
+                _ => return Ok((None, index)),
             };
-            let (arguments, index) = match ExpressionList::new(tokens, index, class_name) {
+            let (arguments, index) = match ExpressionList::new(tokens, index, class_name, dialect)? {
                 (Some(el), index) => (el, index),
                 _ => (ExpressionList(vec![]), index),
             };
             let index = match tokens.get(index) {
                 Some(token::Token::Sym(token::Symbol::RightParen)) => index + 1,
-                _ => return (None, index),
+                _ => return Ok((None, index)),
             };
 
-            (
+            Ok((
                 Some(Self {
                     receiver: None,
                     name: subroutine_name,
+                    name_index,
                     arguments,
                 }),
                 index,
-            )
+            ))
         }
     }
     /// method: {class型の変数}.foo() || foo()
@@ -1488,86 +2239,45 @@ impl SubroutineCall {
     /// おそらく小文字スタート
     /// constructor: {class名}.new
     /// function:{大文字始まり}.foo()
-    fn which_subroutine_kind(&self) -> SubroutineDecKind {
-        match &self.receiver {
-            Some(r) => {
-                if r.to_string().chars().next().map_or(false, char::is_lowercase) {
-                    SubroutineDecKind::Method
-                } else if self.name.0 .0.as_str() == "new" {
-                    SubroutineDecKind::Constructor
-                } else {
-                    SubroutineDecKind::Function
-                }
-            }
-            None => SubroutineDecKind::Method,
-        }
-    }
     fn to_string(&self, symbol_tables: &SymbolTables) -> Vec<String> {
-        match self.which_subroutine_kind() {
-            SubroutineDecKind::Constructor | SubroutineDecKind::Function => {
-                let mut result = vec![];
-                for a in &self.arguments.0 {
-                    result = [result, a.to_string(symbol_tables)].concat();
-                }
-
-                // e.g. call Foo.Bar 2
-                result.push(format!(
-                    "call {}.{} {}",
-                    self.receiver.clone().unwrap().to_string(),
-                    self.name.0.to_string(),
-                    self.arguments.0.len()
-                ));
-                result
-            }
-            SubroutineDecKind::Method => {
-                let receiver_symbol_name = match &self.receiver {
-                    Some(Receiver::ClassName(_)) => {
-                        panic!("このパターンは存在しないはず")
-                    }
-                    Some(Receiver::VarName(v)) => v.0 .0.clone(),
-                    // メソッド内でのメソッド呼び出しでthisが省略されているケース
-                    None => "this".to_string(),
-                };
-
-                // レシーバをpush
-                let mut result = vec![format!("push {}", symbol_tables.get(&receiver_symbol_name).to_vm())];
-
-                // 残りの引数をすべてpush
-                for a in &self.arguments.0 {
-                    result = [result, a.to_string(symbol_tables)].concat();
-                }
+        let current_class_name = symbol_tables.current_class_name.clone().unwrap();
+        // 呼び出し先のクラス名と、メソッド呼び出しの場合にpushするレシーバを解決する
+        let (class_name, receiver) = match &self.receiver {
+            // 変数を優先して解決し、変数でなければクラス名として扱う
+            Some(Receiver::Name(n)) => match symbol_tables.find(&n.0) {
+                Some(symbol) => (symbol.get_class_instance_type().unwrap().0 .0, Some(symbol.push())),
+                None => (n.0.clone(), None),
+            },
+            Some(Receiver::This) => (current_class_name, Some("push pointer 0".to_string())),
+            // 自クラスのsubroutineの呼び出し。メソッドならthisがレシーバになる
+            None => match symbol_tables.classes.kind(&current_class_name, &self.name.0 .0) {
+                Some(SubroutineDecKind::Constructor | SubroutineDecKind::Function) => (current_class_name, None),
+                _ => (current_class_name, Some("push pointer 0".to_string())),
+            },
+        };
 
-                // call foo.Bar n+1
-                result.push(format!(
-                    "call {}.{} {}",
-                    symbol_tables
-                        .get(&receiver_symbol_name)
-                        .get_class_instance_type()
-                        .unwrap()
-                        .0
-                         .0,
-                    self.name.0.to_string(),
-                    self.arguments.0.len() + 1
-                ));
-                result
-            }
+        let mut result = vec![];
+        let mut argument_count = self.arguments.0.len();
+        if let Some(receiver) = receiver {
+            result.push(receiver);
+            argument_count += 1;
+        }
+        for a in &self.arguments.0 {
+            result = [result, a.to_string(symbol_tables)].concat();
         }
+
+        // e.g. call Foo.Bar 2
+        result.push(format!("call {}.{} {}", class_name, self.name.0 .0, argument_count));
+        result
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Clone)]
-enum Receiver {
-    ClassName(ClassName),
-    VarName(VarName),
-}
-impl Receiver {
-    #[allow(clippy::inherent_to_string)]
-    fn to_string(&self) -> String {
-        match self {
-            Receiver::ClassName(c) => c.0.to_string(),
-            Receiver::VarName(v) => v.0.to_string(),
-        }
-    }
+#[derive(Debug, PartialEq, Eq, Clone)]
+enum Receiver {
+    // 変数名またはクラス名
+    Name(token::Identifier),
+    // `this.foo()`
+    This,
 }
 
 #[derive(Debug, PartialEq, Eq, Clone)]
@@ -1576,26 +2286,31 @@ impl ExpressionList {
     // 無
     // expression
     // expression, expression, ..., expression
-    fn new(tokens: &[token::Token], index: usize, class_name: &ClassName) -> (Option<Self>, usize) {
-        let (expression, index) = match Expression::new(tokens, index, class_name) {
+    fn new(
+        tokens: &[token::Token],
+        index: usize,
+        class_name: &ClassName,
+        dialect: token::Dialect,
+    ) -> Result<(Option<Self>, usize), ParseError> {
+        let (expression, index) = match Expression::new(tokens, index, class_name, dialect)? {
             (Some(expression), returned_index) => (expression, returned_index),
-            _ => return (None, index),
+            _ => return Ok((None, index)),
         };
 
         let mut index = index;
         let mut expression_list = vec![expression];
         while let Some(token::Token::Sym(token::Symbol::Comma)) = tokens.get(index) {
             index += 1;
-            match Expression::new(tokens, index, class_name) {
+            match Expression::new(tokens, index, class_name, dialect)? {
                 (Some(expression), returned_index) => {
                     index = returned_index;
                     expression_list.push(expression);
                 }
-                _ => return (Some(Self(expression_list)), index),
+                _ => return Ok((Some(Self(expression_list)), index)),
             }
         }
 
-        (Some(Self(expression_list)), index)
+        Ok((Some(Self(expression_list)), index))
     }
 }
 
@@ -1610,6 +2325,9 @@ enum Op {
     LessThan,
     MoreThan,
     Equal,
+    // Jack+の短絡評価
+    DoubleAmpersand,
+    DoublePipe,
 }
 impl Op {
     fn new(tokens: &[token::Token], index: usize) -> (Option<Self>, usize) {
@@ -1623,9 +2341,15 @@ impl Op {
             Some(token::Token::Sym(token::Symbol::LessThan)) => (Some(Op::LessThan), index + 1),
             Some(token::Token::Sym(token::Symbol::MoreThan)) => (Some(Op::MoreThan), index + 1),
             Some(token::Token::Sym(token::Symbol::Equal)) => (Some(Op::Equal), index + 1),
+            Some(token::Token::Sym(token::Symbol::DoubleAmpersand)) => (Some(Op::DoubleAmpersand), index + 1),
+            Some(token::Token::Sym(token::Symbol::DoublePipe)) => (Some(Op::DoublePipe), index + 1),
             _ => (None, index),
         }
     }
+    // 複合代入(`+=`など)に使える演算子か。比較演算子は代入と紛らわしいので使えない
+    fn is_compound_assignable(&self) -> bool {
+        matches!(self, Op::Plus | Op::Minus | Op::Multiply | Op::Div | Op::Ampersand | Op::Pipe)
+    }
     #[allow(clippy::inherent_to_string)]
     fn to_string(&self) -> String {
         match self {
@@ -1638,6 +2362,7 @@ impl Op {
             Op::LessThan => "lt".to_string(),
             Op::MoreThan => "gt".to_string(),
             Op::Equal => "eq".to_string(),
+            Op::DoubleAmpersand | Op::DoublePipe => unreachable!("短絡評価はshort_circuitで分岐にする"),
         }
     }
 }
@@ -1665,8 +2390,82 @@ impl UnaryOp {
     }
 }
 
-fn invalid_token(tokens: &[token::Token], index: usize) -> String {
-    format!("invalid token {:?}[@{}]", tokens, index)
+/*
+ * パースエラー
+ */
+#[derive(Debug, PartialEq, Eq, Clone)]
+struct ParseError {
+    // エラーを検出したトークンの位置。トークン列の終端(tokens.len())を指すこともある
+    index: usize,
+    // 診断メッセージ(例: "expected `;` after statement")
+    message: String,
+}
+
+// パースエラーを返す。
+// パーサは`Result<(Self, usize), ParseError>`を返し、エラーは`?`で回復地点(Statements::new, Class::new)まで伝える
+fn expected<T>(index: usize, what: &str) -> Result<T, ParseError> {
+    Err(ParseError {
+        index,
+        message: format!("expected {}", what),
+    })
+}
+
+// 標準のJackで使えないJack+の構文を読んだときのパースエラーを返す
+fn jack_plus_only<T>(index: usize, what: &str) -> Result<T, ParseError> {
+    Err(ParseError {
+        index,
+        message: format!("{} is only available in Jack+ (--jack-plus)", what),
+    })
+}
+
+// エラーが起きた文をスキップして次の文の先頭(または囲んでいるブロックの`}`)の位置を返す。
+// `;`はスキップする文の一部として読み飛ばす。文の中の`{ ... }`は丸ごと読み飛ばす
+fn skip_statement(tokens: &[token::Token], start: usize) -> usize {
+    let mut index = start + 1;
+    let mut depth = 0;
+    while let Some(t) = tokens.get(index) {
+        match t {
+            token::Token::Sym(token::Symbol::LeftBrace) => depth += 1,
+            token::Token::Sym(token::Symbol::RightBrace) if depth == 0 => break,
+            token::Token::Sym(token::Symbol::RightBrace) => depth -= 1,
+            token::Token::Sym(token::Symbol::SemiColon) if depth == 0 => return index + 1,
+            token::Token::Key(
+                token::Keyword::Let
+                | token::Keyword::If
+                | token::Keyword::While
+                | token::Keyword::Do
+                | token::Keyword::Return
+                | token::Keyword::For
+                | token::Keyword::Break
+                | token::Keyword::Continue,
+            ) if depth == 0 => break,
+            _ => {}
+        }
+        index += 1;
+    }
+    index
+}
+
+// `break;`, `continue;`の`;`を読んで文の直後の位置を返す
+fn jump_statement_end(tokens: &[token::Token], index: usize) -> Result<usize, ParseError> {
+    match tokens.get(index + 1) {
+        Some(token::Token::Sym(token::Symbol::SemiColon)) => Ok(index + 2),
+        _ => expected(index + 1, "`;` after statement"),
+    }
+}
+
+// エラーが起きたsubroutine宣言をスキップして次のsubroutine宣言の位置を返す。
+// 次の宣言がなければクラスを閉じる最後の`}`の位置を返す
+fn skip_subroutine(tokens: &[token::Token], start: usize) -> usize {
+    let mut index = start + 1;
+    while let Some(t) = tokens.get(index) {
+        match t {
+            token::Token::Key(token::Keyword::Constructor | token::Keyword::Function | token::Keyword::Method) => break,
+            token::Token::Sym(token::Symbol::RightBrace) if index + 1 == tokens.len() => break,
+            _ => index += 1,
+        }
+    }
+    index
 }
 
 #[cfg(test)]
@@ -1675,6 +2474,16 @@ mod test {
     use pretty_assertions::assert_eq;
     use token::IntegerConstant;
 
+    // 位置(TokenIndex)はASTの比較で区別されないので、テストでは既定値で組み立てる
+    fn var_name(name: &str) -> VarName {
+        VarName(token::Identifier(name.to_string()), TokenIndex::default())
+    }
+
+    // 変数宣言や引数はsubroutineのスコープに登録されるので、subroutineの中にいる状態のシンボルテーブルを使う
+    fn subroutine_symbol_tables() -> SymbolTables {
+        SymbolTables::default("Main".to_string()).add_subroutine_symbol_table("main".to_string())
+    }
+
     #[test]
     fn test_class_new() {
         /*
@@ -1760,23 +2569,29 @@ mod test {
             ],
             "Main".to_string(),
             0,
-        );
+            token::Dialect::Standard,
+        )
+        .ok();
         let expected = Some(Class {
+            name_index: TokenIndex::default(),
             name: ClassName(token::Identifier("SquareGame".to_string())),
             var_dec: vec![
                 ClassVarDec {
                     kind: ClassVarKind::Field,
                     type_: Type::ClassName("Square".to_string()),
-                    var_names: vec![VarName(token::Identifier("square".to_string()))],
+                    var_names: vec![var_name("square")],
+                    values: vec![],
                 },
                 ClassVarDec {
                     kind: ClassVarKind::Field,
                     type_: Type::Int,
-                    var_names: vec![VarName(token::Identifier("direction".to_string()))],
+                    var_names: vec![var_name("direction")],
+                    values: vec![],
                 },
             ],
             subroutine_dec: vec![
                 SubroutineDec {
+                    name_index: TokenIndex::default(),
                     kind: SubroutineDecKind::Constructor,
                     type_: SubroutineDecType::Type_(Type::ClassName("SquareGame".to_string())),
                     subroutine_name: token::Identifier("new".to_string()),
@@ -1785,29 +2600,35 @@ mod test {
                         var_dec: vec![],
                         statements: Statements(vec![
                             Statement::Let(LetStatement {
-                                var_name: VarName(token::Identifier("square".to_string())),
+                                compound_op: None,
+                                var_name: var_name("square"),
                                 array_index: None,
                                 right_hand_side: Expression {
-                                    term: Box::new(Term::VarName(VarName(token::Identifier("square".to_string())))),
+                                    term: Box::new(Term::VarName(var_name("square"))),
                                     op_term: vec![],
                                 },
                             }),
                             Statement::Let(LetStatement {
-                                var_name: VarName(token::Identifier("direction".to_string())),
+                                compound_op: None,
+                                var_name: var_name("direction"),
                                 array_index: None,
                                 right_hand_side: Expression {
-                                    term: Box::new(Term::VarName(VarName(token::Identifier("direction".to_string())))),
+                                    term: Box::new(Term::VarName(var_name("direction"))),
                                     op_term: vec![],
                                 },
                             }),
-                            Statement::Return(ReturnStatement(Some(Expression {
-                                term: Box::new(Term::VarName(VarName(token::Identifier("square".to_string())))),
-                                op_term: vec![],
-                            }))),
+                            Statement::Return(ReturnStatement(
+                                Some(Expression {
+                                    term: Box::new(Term::VarName(var_name("square"))),
+                                    op_term: vec![],
+                                }),
+                                TokenIndex::default(),
+                            )),
                         ]),
                     },
                 },
                 SubroutineDec {
+                    name_index: TokenIndex::default(),
                     kind: SubroutineDecKind::Method,
                     type_: SubroutineDecType::Void,
                     subroutine_name: token::Identifier("dispose".to_string()),
@@ -1816,19 +2637,21 @@ mod test {
                         var_dec: vec![],
                         statements: Statements(vec![
                             Statement::Do(DoStatement(SubroutineCall {
-                                receiver: Some(Receiver::VarName(VarName(token::Identifier("square".to_string())))),
+                                name_index: TokenIndex::default(),
+                                receiver: Some(Receiver::Name(token::Identifier("square".to_string()))),
                                 name: SubroutineName(token::Identifier("dispose".to_string())),
                                 arguments: ExpressionList(vec![]),
                             })),
                             Statement::Do(DoStatement(SubroutineCall {
-                                receiver: Some(Receiver::ClassName(ClassName(token::Identifier("Memory".to_string())))),
+                                name_index: TokenIndex::default(),
+                                receiver: Some(Receiver::Name(token::Identifier("Memory".to_string()))),
                                 name: SubroutineName(token::Identifier("deAlloc".to_string())),
                                 arguments: ExpressionList(vec![Expression {
-                                    term: Box::new(Term::VarName(VarName(token::Identifier("square".to_string())))),
+                                    term: Box::new(Term::VarName(var_name("square"))),
                                     op_term: vec![],
                                 }]),
                             })),
-                            Statement::Return(ReturnStatement(None)),
+                            Statement::Return(ReturnStatement(None, TokenIndex::default())),
                         ]),
                     },
                 },
@@ -1856,10 +2679,41 @@ mod test {
                     );
                     map
                 },
-                subroutine_scopes: std::collections::HashMap::new(),
-                current_subroutine_name: None,
+                subroutine_scopes: {
+                    let mut map = std::collections::HashMap::new();
+                    map.insert("new".to_string(), std::collections::HashMap::new());
+                    // メソッドは第1引数にレシーバを受け取る
+                    let mut dispose = std::collections::HashMap::new();
+                    for (name, symbol_type) in [
+                        ("pseudo_arg_for_receiver", SubroutineSymbolType::Arg),
+                        ("this", SubroutineSymbolType::Pointer),
+                    ] {
+                        dispose.insert(
+                            name.to_string(),
+                            SubroutineSymbol {
+                                name: name.to_string(),
+                                type_: Type::ClassName("SquareGame".to_string()),
+                                symbol_type,
+                                index: 0,
+                            },
+                        );
+                    }
+                    map.insert("dispose".to_string(), dispose);
+                    map
+                },
+                current_subroutine_name: Some("dispose".to_string()),
                 file_name: "Main".to_string(),
+                if_label_count: std::cell::Cell::new(0),
+                while_label_count: std::cell::Cell::new(0),
+                string_label_count: std::cell::Cell::new(0),
+                short_circuit_label_count: std::cell::Cell::new(0),
+                loop_labels: std::cell::RefCell::default(),
+                current_class_name: Some("SquareGame".to_string()),
+                classes: std::rc::Rc::default(),
+                interned_strings: None,
+                opt_level: 0,
             },
+            errors: vec![],
         });
         assert_eq!(input, expected);
     }
@@ -1888,22 +2742,24 @@ mod test {
                 token::Token::Sym(token::Symbol::SemiColon),
             ],
             3,
+            &ClassName(token::Identifier("Main".to_string())),
+            token::Dialect::Standard,
             SymbolTables::default("Main".to_string()),
-        );
+        )
+        .unwrap();
         let expected = (
             vec![
                 ClassVarDec {
                     kind: ClassVarKind::Field,
                     type_: Type::Int,
-                    var_names: vec![VarName(token::Identifier("x".to_string()))],
+                    var_names: vec![var_name("x")],
+                    values: vec![],
                 },
                 ClassVarDec {
                     kind: ClassVarKind::Static,
                     type_: Type::Boolean,
-                    var_names: vec![
-                        VarName(token::Identifier("y".to_string())),
-                        VarName(token::Identifier("z".to_string())),
-                    ],
+                    var_names: vec![var_name("y"), var_name("z")],
+                    values: vec![],
                 },
             ],
             13,
@@ -1957,43 +2813,48 @@ mod test {
             ],
             0,
             &ClassName(token::Identifier("SquareGame".to_string())),
+            token::Dialect::Standard,
             SymbolTables::default("Main".to_string()),
-        );
+        )
+        .unwrap();
         let expected = (
             Some(SubroutineDec {
+                name_index: TokenIndex::default(),
                 kind: SubroutineDecKind::Constructor,
                 type_: SubroutineDecType::Type_(Type::ClassName("SquareGame".to_string())),
                 subroutine_name: token::Identifier("new".to_string()),
-                parameter_list: ParameterList(vec![
-                    (Type::Int, VarName(token::Identifier("x".to_string()))),
-                    (Type::Int, VarName(token::Identifier("y".to_string()))),
-                ]),
+                parameter_list: ParameterList(vec![(Type::Int, var_name("x")), (Type::Int, var_name("y"))]),
                 body: SubroutineBody {
                     var_dec: vec![VarDec {
                         type_: Type::Boolean,
-                        var_name: vec![VarName(token::Identifier("b".to_string()))],
+                        var_name: vec![var_name("b")],
                     }],
                     statements: Statements(vec![
                         Statement::Let(LetStatement {
-                            var_name: VarName(token::Identifier("square".to_string())),
+                            compound_op: None,
+                            var_name: var_name("square"),
                             array_index: None,
                             right_hand_side: Expression {
-                                term: Box::new(Term::VarName(VarName(token::Identifier("square".to_string())))),
+                                term: Box::new(Term::VarName(var_name("square"))),
                                 op_term: vec![],
                             },
                         }),
                         Statement::Let(LetStatement {
-                            var_name: VarName(token::Identifier("direction".to_string())),
+                            compound_op: None,
+                            var_name: var_name("direction"),
                             array_index: None,
                             right_hand_side: Expression {
-                                term: Box::new(Term::VarName(VarName(token::Identifier("direction".to_string())))),
+                                term: Box::new(Term::VarName(var_name("direction"))),
                                 op_term: vec![],
                             },
                         }),
-                        Statement::Return(ReturnStatement(Some(Expression {
-                            term: Box::new(Term::VarName(VarName(token::Identifier("square".to_string())))),
-                            op_term: vec![],
-                        }))),
+                        Statement::Return(ReturnStatement(
+                            Some(Expression {
+                                term: Box::new(Term::VarName(var_name("square"))),
+                                op_term: vec![],
+                            }),
+                            TokenIndex::default(),
+                        )),
                     ]),
                 },
             }),
@@ -2038,10 +2899,13 @@ mod test {
             ],
             0,
             &ClassName(token::Identifier("SquareGame".to_string())),
+            token::Dialect::Standard,
             SymbolTables::default("Main".to_string()),
-        );
+        )
+        .unwrap();
         let expected = (
             Some(SubroutineDec {
+                name_index: TokenIndex::default(),
                 kind: SubroutineDecKind::Function,
                 type_: SubroutineDecType::Void,
                 subroutine_name: token::Identifier("dispose".to_string()),
@@ -2050,19 +2914,21 @@ mod test {
                     var_dec: vec![],
                     statements: Statements(vec![
                         Statement::Do(DoStatement(SubroutineCall {
-                            receiver: Some(Receiver::VarName(VarName(token::Identifier("square".to_string())))),
+                            name_index: TokenIndex::default(),
+                            receiver: Some(Receiver::Name(token::Identifier("square".to_string()))),
                             name: SubroutineName(token::Identifier("dispose".to_string())),
                             arguments: ExpressionList(vec![]),
                         })),
                         Statement::Do(DoStatement(SubroutineCall {
-                            receiver: Some(Receiver::ClassName(ClassName(token::Identifier("Memory".to_string())))),
+                            name_index: TokenIndex::default(),
+                            receiver: Some(Receiver::Name(token::Identifier("Memory".to_string()))),
                             name: SubroutineName(token::Identifier("deAlloc".to_string())),
                             arguments: ExpressionList(vec![Expression {
-                                term: Box::new(Term::VarName(VarName(token::Identifier("square".to_string())))),
+                                term: Box::new(Term::VarName(var_name("square"))),
                                 op_term: vec![],
                             }]),
                         })),
-                        Statement::Return(ReturnStatement(None)),
+                        Statement::Return(ReturnStatement(None, TokenIndex::default())),
                     ]),
                 },
             }),
@@ -2114,10 +2980,13 @@ mod test {
             ],
             0,
             &ClassName(token::Identifier("SquareGame".to_string())),
+            token::Dialect::Standard,
             SymbolTables::default("Main".to_string()),
-        );
+        )
+        .unwrap();
         let expected = (
             Some(SubroutineDec {
+                name_index: TokenIndex::default(),
                 kind: SubroutineDecKind::Method,
                 type_: SubroutineDecType::Void,
                 subroutine_name: token::Identifier("moveSquare".to_string()),
@@ -2126,26 +2995,29 @@ mod test {
                     var_dec: vec![],
                     statements: Statements(vec![
                         Statement::If(IfStatement {
+                            keyword_index: TokenIndex::default(),
                             condition: Expression {
-                                term: Box::new(Term::VarName(VarName(token::Identifier("direction".to_string())))),
+                                term: Box::new(Term::VarName(var_name("direction"))),
                                 op_term: vec![],
                             },
                             positive_case_body: Statements(vec![Statement::Do(DoStatement(SubroutineCall {
-                                receiver: Some(Receiver::VarName(VarName(token::Identifier("square".to_string())))),
+                                name_index: TokenIndex::default(),
+                                receiver: Some(Receiver::Name(token::Identifier("square".to_string()))),
                                 name: SubroutineName(token::Identifier("moveUp".to_string())),
                                 arguments: ExpressionList(vec![]),
                             }))]),
                             negative_case_body: None,
                         }),
                         Statement::Do(DoStatement(SubroutineCall {
-                            receiver: Some(Receiver::ClassName(ClassName(token::Identifier("Sys".to_string())))),
+                            name_index: TokenIndex::default(),
+                            receiver: Some(Receiver::Name(token::Identifier("Sys".to_string()))),
                             name: SubroutineName(token::Identifier("wait".to_string())),
                             arguments: ExpressionList(vec![Expression {
-                                term: Box::new(Term::VarName(VarName(token::Identifier("direction".to_string())))),
+                                term: Box::new(Term::VarName(var_name("direction"))),
                                 op_term: vec![],
                             }]),
                         })),
-                        Statement::Return(ReturnStatement(None)),
+                        Statement::Return(ReturnStatement(None, TokenIndex::default())),
                     ]),
                 },
             }),
@@ -2185,12 +3057,13 @@ mod test {
                 token::Token::Sym(token::Symbol::SemiColon),
             ],
             0,
-            SymbolTables::default("Main".to_string()),
-        );
+            subroutine_symbol_tables(),
+        )
+        .unwrap();
         let expected = (
             Some(VarDec {
                 type_: Type::ClassName("MyType".to_string()),
-                var_name: vec![VarName(token::Identifier("foo".to_string()))],
+                var_name: vec![var_name("foo")],
             }),
             4,
         );
@@ -2199,7 +3072,7 @@ mod test {
 
         // var int x, y, z;
         let input = VarDec::new(
-            &vec![
+            &[
                 token::Token::Key(token::Keyword::Var),
                 token::Token::Key(token::Keyword::Int),
                 token::Token::Identifier(token::Identifier("x".to_string())),
@@ -2210,16 +3083,13 @@ mod test {
                 token::Token::Sym(token::Symbol::SemiColon),
             ],
             0,
-            SymbolTables::default("Main".to_string()),
-        );
+            subroutine_symbol_tables(),
+        )
+        .unwrap();
         let expected = (
             Some(VarDec {
                 type_: Type::Int,
-                var_name: vec![
-                    VarName(token::Identifier("x".to_string())),
-                    VarName(token::Identifier("y".to_string())),
-                    VarName(token::Identifier("z".to_string())),
-                ],
+                var_name: vec![var_name("x"), var_name("y"), var_name("z")],
             }),
             8,
         );
@@ -2241,22 +3111,17 @@ mod test {
                 token::Token::Identifier(token::Identifier("y".to_string())),
             ],
             0,
-            SymbolTables::default("Main".to_string()),
-        );
-        let expected = (
-            ParameterList(vec![
-                (Type::Int, VarName(token::Identifier("x".to_string()))),
-                (Type::Char, VarName(token::Identifier("y".to_string()))),
-            ]),
-            5,
-        );
+            subroutine_symbol_tables(),
+        )
+        .unwrap();
+        let expected = (ParameterList(vec![(Type::Int, var_name("x")), (Type::Char, var_name("y"))]), 5);
         assert_eq!(input.0, expected.0);
         assert_eq!(input.1, expected.1);
 
         /*
             (引数なし)
         */
-        let input = ParameterList::new(&[], 0, SymbolTables::default("Main".to_string()));
+        let input = ParameterList::new(&[], 0, subroutine_symbol_tables()).unwrap();
         let expected = (ParameterList(vec![]), 0);
         assert_eq!(input.0, expected.0);
         assert_eq!(input.1, expected.1);
@@ -2275,7 +3140,9 @@ mod test {
             ],
             0,
             &ClassName(token::Identifier("Main".to_string())),
-        );
+            token::Dialect::Standard,
+        )
+        .unwrap();
         let expected = (
             Some(ExpressionList(vec![
                 Expression {
@@ -2298,7 +3165,9 @@ mod test {
             &[token::Token::IntegerConstant(token::IntegerConstant(1))],
             0,
             &ClassName(token::Identifier("Main".to_string())),
-        );
+            token::Dialect::Standard,
+        )
+        .unwrap();
         let expected = (
             Some(ExpressionList(vec![Expression {
                 term: Box::new(Term::IntegerConstant(IntegerConstant(1))),
@@ -2311,7 +3180,9 @@ mod test {
         /*
             (引数なし)
         */
-        let input = ExpressionList::new(&[], 0, &ClassName(token::Identifier("Main".to_string())));
+        let input =
+            ExpressionList::new(&[], 0, &ClassName(token::Identifier("Main".to_string())), token::Dialect::Standard)
+                .unwrap();
         let expected = (None, 0);
         assert_eq!(input, expected);
     }
@@ -2322,7 +3193,7 @@ mod test {
             Main.show(x, y)(レシーバがclass_name)
         */
         let input = SubroutineCall::new(
-            &vec![
+            &[
                 token::Token::Identifier(token::Identifier("Main".to_string())),
                 token::Token::Sym(token::Symbol::Dot),
                 token::Token::Identifier(token::Identifier("show".to_string())),
@@ -2334,18 +3205,21 @@ mod test {
             ],
             0,
             &ClassName(token::Identifier("Main".to_string())),
-        );
+            token::Dialect::Standard,
+        )
+        .unwrap();
         let expected = (
             Some(SubroutineCall {
-                receiver: Some(Receiver::ClassName(ClassName(token::Identifier("Main".to_string())))),
+                name_index: TokenIndex::default(),
+                receiver: Some(Receiver::Name(token::Identifier("Main".to_string()))),
                 name: SubroutineName(token::Identifier("show".to_string())),
                 arguments: ExpressionList(vec![
                     Expression {
-                        term: Box::new(Term::VarName(VarName(token::Identifier("x".to_string())))),
+                        term: Box::new(Term::VarName(var_name("x"))),
                         op_term: vec![],
                     },
                     Expression {
-                        term: Box::new(Term::VarName(VarName(token::Identifier("y".to_string())))),
+                        term: Box::new(Term::VarName(var_name("y"))),
                         op_term: vec![],
                     },
                 ]),
@@ -2367,10 +3241,13 @@ mod test {
             ],
             0,
             &ClassName(token::Identifier("Main".to_string())),
-        );
+            token::Dialect::Standard,
+        )
+        .unwrap();
         let expected = (
             Some(SubroutineCall {
-                receiver: Some(Receiver::VarName(VarName(token::Identifier("person".to_string())))),
+                name_index: TokenIndex::default(),
+                receiver: Some(Receiver::Name(token::Identifier("person".to_string()))),
                 name: SubroutineName(token::Identifier("show".to_string())),
                 arguments: ExpressionList(vec![]),
             }),
@@ -2392,18 +3269,21 @@ mod test {
             ],
             0,
             &ClassName(token::Identifier("Main".to_string())),
-        );
+            token::Dialect::Standard,
+        )
+        .unwrap();
         let expected = (
             Some(SubroutineCall {
+                name_index: TokenIndex::default(),
                 receiver: None,
                 name: SubroutineName(token::Identifier("show".to_string())),
                 arguments: ExpressionList(vec![
                     Expression {
-                        term: Box::new(Term::VarName(VarName(token::Identifier("x".to_string())))),
+                        term: Box::new(Term::VarName(var_name("x"))),
                         op_term: vec![],
                     },
                     Expression {
-                        term: Box::new(Term::VarName(VarName(token::Identifier("y".to_string())))),
+                        term: Box::new(Term::VarName(var_name("y"))),
                         op_term: vec![],
                     },
                 ]),
@@ -2423,9 +3303,12 @@ mod test {
             ],
             0,
             &ClassName(token::Identifier("Main".to_string())),
-        );
+            token::Dialect::Standard,
+        )
+        .unwrap();
         let expected = (
             Some(SubroutineCall {
+                name_index: TokenIndex::default(),
                 receiver: None,
                 name: SubroutineName(token::Identifier("show".to_string())),
                 arguments: ExpressionList(vec![]),
@@ -2450,10 +3333,13 @@ mod test {
             ],
             0,
             &ClassName(token::Identifier("Main".to_string())),
-        );
+            token::Dialect::Standard,
+        )
+        .unwrap();
         let expected = (
             LetStatement {
-                var_name: VarName(token::Identifier("foo".to_string())),
+                compound_op: None,
+                var_name: var_name("foo"),
                 array_index: None,
                 right_hand_side: Expression {
                     term: Box::new(Term::IntegerConstant(IntegerConstant(1))),
@@ -2480,14 +3366,18 @@ mod test {
             ],
             0,
             &ClassName(token::Identifier("Main".to_string())),
-        );
+            token::Dialect::Standard,
+        )
+        .unwrap();
         let expected = (
             LetStatement {
-                var_name: VarName(token::Identifier("length".to_string())),
+                compound_op: None,
+                var_name: var_name("length"),
                 array_index: None,
                 right_hand_side: Expression {
                     term: Box::new(Term::SubroutineCall(SubroutineCall {
-                        receiver: Some(Receiver::ClassName(ClassName(token::Identifier("Keyboard".to_string())))),
+                        name_index: TokenIndex::default(),
+                        receiver: Some(Receiver::Name(token::Identifier("Keyboard".to_string()))),
                         name: SubroutineName(token::Identifier("readInt".to_string())),
                         arguments: ExpressionList(vec![Expression {
                             term: Box::new(Term::StringConstant(token::StringConstant(
@@ -2522,17 +3412,21 @@ mod test {
             ],
             0,
             &ClassName(token::Identifier("Main".to_string())),
-        );
+            token::Dialect::Standard,
+        )
+        .unwrap();
         let expected = (
             LetStatement {
-                var_name: VarName(token::Identifier("a".to_string())),
+                compound_op: None,
+                var_name: var_name("a"),
                 array_index: Some(Expression {
-                    term: Box::new(Term::VarName(VarName(token::Identifier("i".to_string())))),
+                    term: Box::new(Term::VarName(var_name("i"))),
                     op_term: vec![],
                 }),
                 right_hand_side: Expression {
                     term: Box::new(Term::SubroutineCall(SubroutineCall {
-                        receiver: Some(Receiver::ClassName(ClassName(token::Identifier("Keyboard".to_string())))),
+                        name_index: TokenIndex::default(),
+                        receiver: Some(Receiver::Name(token::Identifier("Keyboard".to_string()))),
                         name: SubroutineName(token::Identifier("readInt".to_string())),
                         arguments: ExpressionList(vec![Expression {
                             term: Box::new(Term::StringConstant(token::StringConstant(
@@ -2564,19 +3458,23 @@ mod test {
             ],
             0,
             &ClassName(token::Identifier("Main".to_string())),
-        );
+            token::Dialect::Standard,
+        )
+        .unwrap();
         let expected = (
             LetStatement {
-                var_name: VarName(token::Identifier("sum".to_string())),
+                compound_op: None,
+                var_name: var_name("sum"),
                 array_index: None,
                 right_hand_side: Expression {
-                    term: Box::new(Term::VarName(VarName(token::Identifier("sum".to_string())))),
+                    term: Box::new(Term::VarName(var_name("sum"))),
                     op_term: vec![(
                         Op::Plus,
+                        TokenIndex::default(),
                         Term::ArrayIndexAccess(
-                            VarName(token::Identifier("a".to_string())),
+                            var_name("a"),
                             Expression {
-                                term: Box::new(Term::VarName(VarName(token::Identifier("i".to_string())))),
+                                term: Box::new(Term::VarName(var_name("i"))),
                                 op_term: vec![],
                             },
                         ),
@@ -2603,18 +3501,23 @@ mod test {
             ],
             0,
             &ClassName(token::Identifier("Main".to_string())),
-        );
+            token::Dialect::Standard,
+        )
+        .unwrap();
         let expected = (
             LetStatement {
-                var_name: VarName(token::Identifier("j".to_string())),
+                compound_op: None,
+                var_name: var_name("j"),
                 array_index: None,
                 right_hand_side: Expression {
-                    term: Box::new(Term::VarName(VarName(token::Identifier("j".to_string())))),
+                    term: Box::new(Term::VarName(var_name("j"))),
                     op_term: vec![(
                         Op::Div,
+                        TokenIndex::default(),
                         Term::Expression(Expression {
                             term: Box::new(Term::UnaryOp(
                                 UnaryOp::Minus,
+                                TokenIndex::default(),
                                 Box::new(Term::IntegerConstant(token::IntegerConstant(1))),
                             )),
                             op_term: vec![],
@@ -2653,16 +3556,20 @@ mod test {
             ],
             0,
             &ClassName(token::Identifier("Main".to_string())),
-        );
+            token::Dialect::Standard,
+        )
+        .unwrap();
         let expected = (
             IfStatement {
+                keyword_index: TokenIndex::default(),
                 condition: Expression {
                     term: Box::new(Term::KeyWordConstant(KeyWordConstant::True)),
                     op_term: vec![],
                 },
                 positive_case_body: Statements(vec![
                     Statement::Let(LetStatement {
-                        var_name: VarName(token::Identifier("foo".to_string())),
+                        compound_op: None,
+                        var_name: var_name("foo"),
                         array_index: None,
                         right_hand_side: Expression {
                             term: Box::new(Term::IntegerConstant(IntegerConstant(1))),
@@ -2670,7 +3577,8 @@ mod test {
                         },
                     }),
                     Statement::Let(LetStatement {
-                        var_name: VarName(token::Identifier("bar".to_string())),
+                        compound_op: None,
+                        var_name: var_name("bar"),
                         array_index: None,
                         right_hand_side: Expression {
                             term: Box::new(Term::KeyWordConstant(KeyWordConstant::True)),
@@ -2721,16 +3629,20 @@ mod test {
             ],
             0,
             &ClassName(token::Identifier("Main".to_string())),
-        );
+            token::Dialect::Standard,
+        )
+        .unwrap();
         let expected = (
             IfStatement {
+                keyword_index: TokenIndex::default(),
                 condition: Expression {
                     term: Box::new(Term::KeyWordConstant(KeyWordConstant::True)),
                     op_term: vec![],
                 },
                 positive_case_body: Statements(vec![
                     Statement::Let(LetStatement {
-                        var_name: VarName(token::Identifier("foo".to_string())),
+                        compound_op: None,
+                        var_name: var_name("foo"),
                         array_index: None,
                         right_hand_side: Expression {
                             term: Box::new(Term::IntegerConstant(IntegerConstant(1))),
@@ -2738,7 +3650,8 @@ mod test {
                         },
                     }),
                     Statement::Let(LetStatement {
-                        var_name: VarName(token::Identifier("bar".to_string())),
+                        compound_op: None,
+                        var_name: var_name("bar"),
                         array_index: None,
                         right_hand_side: Expression {
                             term: Box::new(Term::KeyWordConstant(KeyWordConstant::True)),
@@ -2748,7 +3661,8 @@ mod test {
                 ]),
                 negative_case_body: Some(Statements(vec![
                     Statement::Let(LetStatement {
-                        var_name: VarName(token::Identifier("baz".to_string())),
+                        compound_op: None,
+                        var_name: var_name("baz"),
                         array_index: None,
                         right_hand_side: Expression {
                             term: Box::new(Term::IntegerConstant(IntegerConstant(1))),
@@ -2756,7 +3670,8 @@ mod test {
                         },
                     }),
                     Statement::Let(LetStatement {
-                        var_name: VarName(token::Identifier("qux".to_string())),
+                        compound_op: None,
+                        var_name: var_name("qux"),
                         array_index: None,
                         right_hand_side: Expression {
                             term: Box::new(Term::KeyWordConstant(KeyWordConstant::Null)),
@@ -2773,7 +3688,7 @@ mod test {
             if (true) {} else {}
         */
         let input = IfStatement::new(
-            &vec![
+            &[
                 token::Token::Key(token::Keyword::If),
                 token::Token::Sym(token::Symbol::LeftParen),
                 token::Token::Key(token::Keyword::True),
@@ -2786,9 +3701,12 @@ mod test {
             ],
             0,
             &ClassName(token::Identifier("Main".to_string())),
-        );
+            token::Dialect::Standard,
+        )
+        .unwrap();
         let expected = (
             IfStatement {
+                keyword_index: TokenIndex::default(),
                 condition: Expression {
                     term: Box::new(Term::KeyWordConstant(KeyWordConstant::True)),
                     op_term: vec![],
@@ -2827,16 +3745,20 @@ mod test {
             ],
             0,
             &ClassName(token::Identifier("Main".to_string())),
-        );
+            token::Dialect::Standard,
+        )
+        .unwrap();
         let expected = (
             WhileStatement {
+                keyword_index: TokenIndex::default(),
                 condition: Expression {
                     term: Box::new(Term::KeyWordConstant(KeyWordConstant::True)),
                     op_term: vec![],
                 },
                 body: Statements(vec![
                     Statement::Let(LetStatement {
-                        var_name: VarName(token::Identifier("foo".to_string())),
+                        compound_op: None,
+                        var_name: var_name("foo"),
                         array_index: None,
                         right_hand_side: Expression {
                             term: Box::new(Term::IntegerConstant(IntegerConstant(1))),
@@ -2844,7 +3766,8 @@ mod test {
                         },
                     }),
                     Statement::Let(LetStatement {
-                        var_name: VarName(token::Identifier("bar".to_string())),
+                        compound_op: None,
+                        var_name: var_name("bar"),
                         array_index: None,
                         right_hand_side: Expression {
                             term: Box::new(Term::KeyWordConstant(KeyWordConstant::True)),
@@ -2864,7 +3787,7 @@ mod test {
             do game.run();
         */
         let input = DoStatement::new(
-            &vec![
+            &[
                 token::Token::Key(token::Keyword::Do),
                 token::Token::Identifier(token::Identifier("game".to_string())),
                 token::Token::Sym(token::Symbol::Dot),
@@ -2875,10 +3798,13 @@ mod test {
             ],
             0,
             &ClassName(token::Identifier("Main".to_string())),
-        );
+            token::Dialect::Standard,
+        )
+        .unwrap();
         let expected = (
             DoStatement(SubroutineCall {
-                receiver: Some(Receiver::VarName(VarName(token::Identifier("game".to_string())))),
+                name_index: TokenIndex::default(),
+                receiver: Some(Receiver::Name(token::Identifier("game".to_string()))),
                 name: SubroutineName(token::Identifier("run".to_string())),
                 arguments: ExpressionList(vec![]),
             }),
@@ -2899,8 +3825,10 @@ mod test {
             ],
             0,
             &ClassName(token::Identifier("Main".to_string())),
-        );
-        let expected = (ReturnStatement(None), 2);
+            token::Dialect::Standard,
+        )
+        .unwrap();
+        let expected = (ReturnStatement(None, TokenIndex::default()), 2);
         assert_eq!(input, expected);
 
         /*
@@ -2914,12 +3842,17 @@ mod test {
             ],
             0,
             &ClassName(token::Identifier("Main".to_string())),
-        );
+            token::Dialect::Standard,
+        )
+        .unwrap();
         let expected = (
-            ReturnStatement(Some(Expression {
-                term: Box::new(Term::KeyWordConstant(KeyWordConstant::True)),
-                op_term: vec![],
-            })),
+            ReturnStatement(
+                Some(Expression {
+                    term: Box::new(Term::KeyWordConstant(KeyWordConstant::True)),
+                    op_term: vec![],
+                }),
+                TokenIndex::default(),
+            ),
             3,
         );
         assert_eq!(input, expected);
@@ -2955,30 +3888,33 @@ mod test {
             ],
             0,
             &ClassName(token::Identifier("Main".to_string())),
-            SymbolTables::default("Main".to_string()),
-        );
+            token::Dialect::Standard,
+            subroutine_symbol_tables(),
+        )
+        .unwrap();
         let expected = (
             SubroutineBody {
                 var_dec: vec![VarDec {
                     type_: Type::Int,
-                    var_name: vec![
-                        VarName(token::Identifier("x".to_string())),
-                        VarName(token::Identifier("y".to_string())),
-                    ],
+                    var_name: vec![var_name("x"), var_name("y")],
                 }],
                 statements: Statements(vec![
                     Statement::Let(LetStatement {
-                        var_name: VarName(token::Identifier("foo".to_string())),
+                        compound_op: None,
+                        var_name: var_name("foo"),
                         array_index: None,
                         right_hand_side: Expression {
                             term: Box::new(Term::KeyWordConstant(KeyWordConstant::True)),
                             op_term: vec![],
                         },
                     }),
-                    Statement::Return(ReturnStatement(Some(Expression {
-                        term: Box::new(Term::VarName(VarName(token::Identifier("x".to_string())))),
-                        op_term: vec![],
-                    }))),
+                    Statement::Return(ReturnStatement(
+                        Some(Expression {
+                            term: Box::new(Term::VarName(var_name("x"))),
+                            op_term: vec![],
+                        }),
+                        TokenIndex::default(),
+                    )),
                 ]),
             },
             16,
@@ -2996,8 +3932,10 @@ mod test {
             ],
             0,
             &ClassName(token::Identifier("Main".to_string())),
-            SymbolTables::default("Main".to_string()),
-        );
+            token::Dialect::Standard,
+            subroutine_symbol_tables(),
+        )
+        .unwrap();
         let expected = (
             SubroutineBody {
                 var_dec: vec![],
@@ -3022,10 +3960,13 @@ mod test {
             ],
             0,
             &ClassName(token::Identifier("Main".to_string())),
-        );
+            token::Dialect::Standard,
+        )
+        .unwrap();
         let expected = (
             Some(Statement::Let(LetStatement {
-                var_name: VarName(token::Identifier("foo".to_string())),
+                compound_op: None,
+                var_name: var_name("foo"),
                 array_index: None,
                 right_hand_side: Expression {
                     term: Box::new(Term::IntegerConstant(IntegerConstant(1))),
@@ -3038,7 +3979,7 @@ mod test {
 
         // if (true) { let foo = 1; }
         let input = Statement::new(
-            &vec![
+            &[
                 token::Token::Key(token::Keyword::If),
                 token::Token::Sym(token::Symbol::LeftParen),
                 token::Token::Key(token::Keyword::True),
@@ -3053,15 +3994,19 @@ mod test {
             ],
             0,
             &ClassName(token::Identifier("Main".to_string())),
-        );
+            token::Dialect::Standard,
+        )
+        .unwrap();
         let expected = (
             Some(Statement::If(IfStatement {
+                keyword_index: TokenIndex::default(),
                 condition: Expression {
                     term: Box::new(Term::KeyWordConstant(KeyWordConstant::True)),
                     op_term: vec![],
                 },
                 positive_case_body: Statements(vec![Statement::Let(LetStatement {
-                    var_name: VarName(token::Identifier("foo".to_string())),
+                    compound_op: None,
+                    var_name: var_name("foo"),
                     array_index: None,
                     right_hand_side: Expression {
                         term: Box::new(Term::IntegerConstant(IntegerConstant(1))),
@@ -3076,7 +4021,7 @@ mod test {
 
         // while (true) { let foo = 1; }
         let input = Statement::new(
-            &vec![
+            &[
                 token::Token::Key(token::Keyword::While),
                 token::Token::Sym(token::Symbol::LeftParen),
                 token::Token::Key(token::Keyword::True),
@@ -3091,15 +4036,19 @@ mod test {
             ],
             0,
             &ClassName(token::Identifier("Main".to_string())),
-        );
+            token::Dialect::Standard,
+        )
+        .unwrap();
         let expected = (
             Some(Statement::While(WhileStatement {
+                keyword_index: TokenIndex::default(),
                 condition: Expression {
                     term: Box::new(Term::KeyWordConstant(KeyWordConstant::True)),
                     op_term: vec![],
                 },
                 body: Statements(vec![Statement::Let(LetStatement {
-                    var_name: VarName(token::Identifier("foo".to_string())),
+                    compound_op: None,
+                    var_name: var_name("foo"),
                     array_index: None,
                     right_hand_side: Expression {
                         term: Box::new(Term::IntegerConstant(IntegerConstant(1))),
@@ -3113,7 +4062,7 @@ mod test {
 
         // do game.run();
         let input = Statement::new(
-            &vec![
+            &[
                 token::Token::Key(token::Keyword::Do),
                 token::Token::Identifier(token::Identifier("game".to_string())),
                 token::Token::Sym(token::Symbol::Dot),
@@ -3124,10 +4073,13 @@ mod test {
             ],
             0,
             &ClassName(token::Identifier("Main".to_string())),
-        );
+            token::Dialect::Standard,
+        )
+        .unwrap();
         let expected = (
             Some(Statement::Do(DoStatement(SubroutineCall {
-                receiver: Some(Receiver::VarName(VarName(token::Identifier("game".to_string())))),
+                name_index: TokenIndex::default(),
+                receiver: Some(Receiver::Name(token::Identifier("game".to_string()))),
                 name: SubroutineName(token::Identifier("run".to_string())),
                 arguments: ExpressionList(vec![]),
             }))),
@@ -3144,12 +4096,17 @@ mod test {
             ],
             0,
             &ClassName(token::Identifier("Main".to_string())),
-        );
+            token::Dialect::Standard,
+        )
+        .unwrap();
         let expected = (
-            Some(Statement::Return(ReturnStatement(Some(Expression {
-                term: Box::new(Term::KeyWordConstant(KeyWordConstant::True)),
-                op_term: vec![],
-            })))),
+            Some(Statement::Return(ReturnStatement(
+                Some(Expression {
+                    term: Box::new(Term::KeyWordConstant(KeyWordConstant::True)),
+                    op_term: vec![],
+                }),
+                TokenIndex::default(),
+            ))),
             3,
         );
         assert_eq!(input, expected);
@@ -3164,7 +4121,9 @@ mod test {
             &[token::Token::IntegerConstant(token::IntegerConstant(1))],
             0,
             &ClassName(token::Identifier("Main".to_string())),
-        );
+            token::Dialect::Standard,
+        )
+        .unwrap();
         let expected = (
             Some(Expression {
                 term: Box::new(Term::IntegerConstant(token::IntegerConstant(1))),
@@ -3185,11 +4144,13 @@ mod test {
             ],
             0,
             &ClassName(token::Identifier("Main".to_string())),
-        );
+            token::Dialect::Standard,
+        )
+        .unwrap();
         let expected = (
             Some(Expression {
                 term: Box::new(Term::IntegerConstant(token::IntegerConstant(1))),
-                op_term: vec![(Op::Plus, Term::IntegerConstant(token::IntegerConstant(1)))],
+                op_term: vec![(Op::Plus, TokenIndex::default(), Term::IntegerConstant(token::IntegerConstant(1)))],
             }),
             3,
         );
@@ -3208,13 +4169,15 @@ mod test {
             ],
             0,
             &ClassName(token::Identifier("Main".to_string())),
-        );
+            token::Dialect::Standard,
+        )
+        .unwrap();
         let expected = (
             Some(Expression {
                 term: Box::new(Term::IntegerConstant(token::IntegerConstant(1))),
                 op_term: vec![
-                    (Op::Plus, Term::IntegerConstant(token::IntegerConstant(1))),
-                    (Op::Minus, Term::IntegerConstant(token::IntegerConstant(1))),
+                    (Op::Plus, TokenIndex::default(), Term::IntegerConstant(token::IntegerConstant(1))),
+                    (Op::Minus, TokenIndex::default(), Term::IntegerConstant(token::IntegerConstant(1))),
                 ],
             }),
             5,
@@ -3234,13 +4197,15 @@ mod test {
             ],
             0,
             &ClassName(token::Identifier("Main".to_string())),
-        );
+            token::Dialect::Standard,
+        )
+        .unwrap();
         let expected = (
             Some(Expression {
                 term: Box::new(Term::IntegerConstant(token::IntegerConstant(1))),
                 op_term: vec![
-                    (Op::Plus, Term::IntegerConstant(token::IntegerConstant(1))),
-                    (Op::Minus, Term::IntegerConstant(token::IntegerConstant(1))),
+                    (Op::Plus, TokenIndex::default(), Term::IntegerConstant(token::IntegerConstant(1))),
+                    (Op::Minus, TokenIndex::default(), Term::IntegerConstant(token::IntegerConstant(1))),
                 ],
             }),
             5,
@@ -3257,12 +4222,15 @@ mod test {
             ],
             0,
             &ClassName(token::Identifier("Main".to_string())),
-        );
+            token::Dialect::Standard,
+        )
+        .unwrap();
         let expected = (
             Some(Expression {
                 term: Box::new(Term::Expression(Expression {
                     term: Box::new(Term::UnaryOp(
                         UnaryOp::Minus,
+                        TokenIndex::default(),
                         Box::new(Term::IntegerConstant(token::IntegerConstant(2))),
                     )),
                     op_term: vec![],
@@ -3273,4 +4241,519 @@ mod test {
         );
         assert_eq!(input, expected);
     }
+
+    #[test]
+    fn test_to_vm_labels() {
+        let source = r#"
+class Main {
+    function void main() {
+        var int i;
+        while (i < 10) {
+            if (i = 3) {
+                let i = i + 2;
+            } else {
+                if (i = 5) {
+                    let i = i + 1;
+                }
+            }
+            let i = i + 1;
+        }
+        return;
+    }
+    function void f() {
+        if (true) {
+            return;
+        }
+        return;
+    }
+}
+"#;
+        let compile = || {
+            let tokens = token::Tokens::new(source.to_string());
+            let ast = Ast::new(tokens, "Main".to_string()).unwrap();
+            ast.to_vm(&ClassTable::new(&[&ast]), &CodegenOptions::default())
+        };
+        // 何度コンパイルしても同じ結果になる
+        assert_eq!(compile(), compile());
+        // ラベルはsubroutineごとに出現順の通し番号になる
+        let labels = compile()
+            .lines()
+            .filter(|l| l.starts_with("label "))
+            .map(|l| l.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            labels,
+            vec![
+                "label WHILE_EXP0",
+                "label IF_FALSE0",
+                "label IF_FALSE1",
+                "label IF_END1",
+                "label IF_END0",
+                "label WHILE_END0",
+                "label IF_FALSE0",
+                "label IF_END0",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_to_vm_jack_plus() {
+        let source = r#"
+class Main {
+    function void main() {
+        var int i;
+        var Array a;
+        for (i = 0; i < 3; i += 1) {
+            if (i = 1) {
+                continue;
+            } else if (i = 2) {
+                break;
+            }
+            let a[i] -= 2;
+        }
+        return;
+    }
+}
+"#;
+        let tokens = token::Tokens::with_dialect(source.to_string(), token::Dialect::JackPlus);
+        let ast = Ast::new(tokens, "Main".to_string()).unwrap();
+        // for文はwhile文のラベルで、continueは更新の直前のWHILE_STEPに飛ぶ。else ifはelse節の中のif文になる
+        assert_eq!(
+            ast.to_vm(&ClassTable::new(&[&ast]), &CodegenOptions::default())
+                .lines()
+                .collect::<Vec<_>>(),
+            vec![
+                "function Main.main 2",
+                "push constant 0",
+                "pop local 0",
+                "label WHILE_EXP0",
+                "push local 0",
+                "push constant 3",
+                "lt",
+                "not",
+                "if-goto WHILE_END0",
+                "push local 0",
+                "push constant 1",
+                "eq",
+                "not",
+                "if-goto IF_FALSE0",
+                "goto WHILE_STEP0",
+                "goto IF_END0",
+                "label IF_FALSE0",
+                "push local 0",
+                "push constant 2",
+                "eq",
+                "not",
+                "if-goto IF_FALSE1",
+                "goto WHILE_END0",
+                "goto IF_END1",
+                "label IF_FALSE1",
+                "label IF_END1",
+                "label IF_END0",
+                "push local 1",
+                "push local 0",
+                "add",
+                "pop temp 1",
+                "push temp 1",
+                "push temp 1",
+                "pop pointer 1",
+                "push that 0",
+                "push constant 2",
+                "sub",
+                "pop temp 0",
+                "pop pointer 1",
+                "push temp 0",
+                "pop that 0",
+                "label WHILE_STEP0",
+                "push local 0",
+                "push constant 1",
+                "add",
+                "pop local 0",
+                "goto WHILE_EXP0",
+                "label WHILE_END0",
+                "push constant 0",
+                "return",
+            ]
+        );
+
+        // 標準のJackではJack+の構文をパースした位置でエラーにする。`for`などはただの識別子になる
+        let diagnostics = |source: &str| match Ast::new(token::Tokens::new(source.to_string()), "Main".to_string()) {
+            Ok(_) => vec![],
+            Err(d) => d
+                .into_iter()
+                .map(|d| format!("{}:{}: {}", d.span.line, d.span.column, d.message))
+                .collect(),
+        };
+        let standard = r#"
+class Main {
+    function void main() {
+        var int i;
+        var Array a;
+        let i += 1;
+        if (i = 1) {
+            let i = 0;
+        } else if (i = 2) {
+            let i = 1;
+        }
+        let a[i] -= 2;
+        return;
+    }
+}
+"#;
+        assert_eq!(
+            diagnostics(standard),
+            vec![
+                "6:15: `+=` is only available in Jack+ (--jack-plus)",
+                "9:11: `else if` is only available in Jack+ (--jack-plus)",
+                "12:18: `-=` is only available in Jack+ (--jack-plus)",
+            ]
+        );
+        assert_eq!(diagnostics(source)[0], "6:9: expected statement or `}`");
+        assert_eq!(
+            diagnostics("class Main { function void main() { var int for; let for = 1; return; } }"),
+            Vec::<String>::new()
+        );
+    }
+
+    // OSはエミュレータのネイティブ実装を使ってMain.mainを実行し、出力された文字列を返す
+    pub(super) fn run(ast: &Ast, options: &CodegenOptions) -> String {
+        use vm_translator::emulator::{StopReason, VMEmulator};
+        use vm_translator::translator::{Bootstrap, VMProgram};
+
+        let vm = VMProgram::new("Main".to_string(), ast.to_vm(&ClassTable::new(&[ast]), options)).unwrap();
+        let bootstrap = Bootstrap {
+            entry_point: "Main.main".to_string(),
+            ..Default::default()
+        };
+        let mut emulator = VMEmulator::new(&[vm], Some(bootstrap), true).unwrap();
+        assert_eq!(emulator.run(1_000_000), Ok(StopReason::Halted));
+        emulator.native_os().unwrap().printed_text().to_string()
+    }
+
+    #[test]
+    fn test_compound_assignment_evaluates_index_once() {
+        let source = r#"
+class Main {
+    static int calls;
+    function int index() {
+        let calls = calls + 1;
+        return calls;
+    }
+    function void main() {
+        var Array a;
+        let a = Array.new(3);
+        let a[1] = 10;
+        let a[2] = 20;
+        let a[Main.index()] += Main.index();
+        do Output.printInt(a[1]);
+        do Output.printChar(32);
+        do Output.printInt(a[2]);
+        do Output.printChar(32);
+        do Output.printInt(calls);
+        return;
+    }
+}
+"#;
+        let tokens = token::Tokens::with_dialect(source.to_string(), token::Dialect::JackPlus);
+        let ast = Ast::new(tokens, "Main".to_string()).unwrap();
+        // 添字は1回だけ評価し、右辺で書き換わった値ではなく読み出したときと同じ要素に書き込む
+        assert_eq!(run(&ast, &CodegenOptions::default()), "12 20 2");
+    }
+
+    #[test]
+    fn test_to_vm_short_circuit() {
+        let source = r#"
+class Main {
+    function boolean f(int a, Array b) {
+        return (a > 0) && (b[a] = 1) || (a = 0);
+    }
+}
+"#;
+        let tokens = token::Tokens::with_dialect(source.to_string(), token::Dialect::JackPlus);
+        let ast = Ast::new(tokens, "Main".to_string()).unwrap();
+        // 左辺だけで結果が決まるときは右辺を評価しない
+        assert_eq!(
+            ast.to_vm(&ClassTable::new(&[&ast]), &CodegenOptions::default())
+                .lines()
+                .collect::<Vec<_>>(),
+            vec![
+                "function Main.f 0",
+                "push argument 0",
+                "push constant 0",
+                "gt",
+                "not",
+                "if-goto AND_FALSE0",
+                "push argument 1",
+                "push argument 0",
+                "add",
+                "pop pointer 1",
+                "push that 0",
+                "push constant 1",
+                "eq",
+                "goto AND_END0",
+                "label AND_FALSE0",
+                "push constant 0",
+                "label AND_END0",
+                "if-goto OR_TRUE1",
+                "push argument 0",
+                "push constant 0",
+                "eq",
+                "goto OR_END1",
+                "label OR_TRUE1",
+                "push constant 0",
+                "not",
+                "label OR_END1",
+                "return",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_to_vm_constants() {
+        let source = r#"
+class Main {
+    const int MAX = 511, HALF = MAX / 2 + 1;
+    const int MASK = 0x8000;
+    enum Direction { UP, DOWN }
+    enum Key { LEFT, DOWN, UP }
+    static int counter;
+    function int main(int UP) {
+        let counter = MAX + HALF;
+        return MASK | UP | Direction.DOWN | Key.UP;
+    }
+}
+"#;
+        let tokens = token::Tokens::with_dialect(source.to_string(), token::Dialect::JackPlus);
+        let ast = Ast::new(tokens, "Main".to_string()).unwrap();
+        // 定数はstaticを使わずに値を埋め込む。同名の引数があればそちらを参照する
+        assert_eq!(
+            ast.to_vm(&ClassTable::new(&[&ast]), &CodegenOptions::default())
+                .lines()
+                .collect::<Vec<_>>(),
+            vec![
+                "function Main.main 0",
+                "push constant 511",
+                "push constant 256",
+                "add",
+                "pop static 0",
+                "push constant 32767",
+                "not",
+                "push argument 0",
+                "or",
+                "push constant 1",
+                "or",
+                "push constant 2",
+                "or",
+                "return",
+            ]
+        );
+
+        // 他のクラスの列挙型も`EnumName.MEMBER`で参照できる
+        let game = token::Tokens::with_dialect(
+            "class Game { enum State { READY, OVER } }".to_string(),
+            token::Dialect::JackPlus,
+        );
+        let game = Ast::new(game, "Game".to_string()).unwrap();
+        let tokens = token::Tokens::with_dialect(
+            "class Main { function int main() { return State.OVER; } }".to_string(),
+            token::Dialect::JackPlus,
+        );
+        let ast = Ast::new(tokens, "Main".to_string()).unwrap();
+        assert_eq!(
+            ast.to_vm(&ClassTable::new(&[&ast, &game]), &CodegenOptions::default())
+                .lines()
+                .collect::<Vec<_>>(),
+            vec!["function Main.main 0", "push constant 1", "return"]
+        );
+
+        // 標準のJackでは列挙子を参照できない
+        let diagnostics = Ast::new(
+            token::Tokens::new("class Main { function int main() { return State.OVER; } }".to_string()),
+            "Main".to_string(),
+        )
+        .err()
+        .unwrap();
+        assert_eq!(diagnostics[0].message, "enum member `State.OVER` is only available in Jack+ (--jack-plus)");
+
+        // 定数式でない初期値はエラーにする
+        let tokens = token::Tokens::with_dialect(
+            "class Main { static int s; const int C = s + 1; }".to_string(),
+            token::Dialect::JackPlus,
+        );
+        let diagnostics = Ast::new(tokens, "Main".to_string()).err().unwrap();
+        assert_eq!(diagnostics[0].message, "expected constant expression");
+    }
+
+    #[test]
+    fn test_to_vm_subroutine_call() {
+        // 大文字で始まる変数、小文字で始まるクラス、`this`レシーバ、`new`以外のコンストラクタ
+        let main = r#"
+class Main {
+    field game Game;
+    constructor Main make() {
+        let Game = game.create();
+        do this.run();
+        do helper();
+        return this;
+    }
+    method void run() {
+        do Game.start();
+        do run();
+        return;
+    }
+    function void helper() {
+        return;
+    }
+}
+"#;
+        let game = r#"
+class game {
+    constructor game create() {
+        return this;
+    }
+    method void start() {
+        return;
+    }
+}
+"#;
+        let main = Ast::new(token::Tokens::new(main.to_string()), "Main".to_string()).unwrap();
+        let game = Ast::new(token::Tokens::new(game.to_string()), "game".to_string()).unwrap();
+        let classes = ClassTable::new(&[&main, &game]);
+        assert_eq!(
+            main.to_vm(&classes, &CodegenOptions::default()),
+            [
+                "function Main.make 0",
+                "push constant 1",
+                "call Memory.alloc 1",
+                "pop pointer 0",
+                "call game.create 0",
+                "pop this 0",
+                "push pointer 0",
+                "call Main.run 1",
+                "pop temp 0",
+                "call Main.helper 0",
+                "pop temp 0",
+                "push pointer 0",
+                "return",
+                "function Main.run 0",
+                "push argument 0",
+                "pop pointer 0",
+                "push this 0",
+                "call game.start 1",
+                "pop temp 0",
+                "push pointer 0",
+                "call Main.run 1",
+                "pop temp 0",
+                "push constant 0",
+                "return",
+                "function Main.helper 0",
+                "push constant 0",
+                "return",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn test_to_vm_intern_strings() {
+        let source = r#"
+class Main {
+    static int count;
+    function void main() {
+        do Output.printString("ab");
+        do Output.printString("c");
+        do Output.printString("ab");
+        return;
+    }
+}
+"#;
+        let ast = Ast::new(token::Tokens::new(source.to_string()), "Main".to_string()).unwrap();
+        let classes = ClassTable::new(&[&ast]);
+        let options = CodegenOptions {
+            intern_strings: true,
+            ..Default::default()
+        };
+        // 文字列ごとにクラス変数の後ろのstatic変数を割り当て、初回の評価時だけStringを生成する
+        let intern = |index: usize, label: usize, chars: &[u32]| {
+            let mut result = vec![
+                format!("push static {}", index),
+                format!("if-goto STRING_READY{}", label),
+                format!("push constant {}", chars.len()),
+                "call String.new 1".to_string(),
+            ];
+            for c in chars {
+                result.push(format!("push constant {}", c));
+                result.push("call String.appendChar 2".to_string());
+            }
+            result.push(format!("pop static {}", index));
+            result.push(format!("label STRING_READY{}", label));
+            result.push(format!("push static {}", index));
+            result.push("call Output.printString 1".to_string());
+            result.push("pop temp 0".to_string());
+            result
+        };
+        assert_eq!(
+            ast.to_vm(&classes, &options),
+            [
+                vec!["function Main.main 0".to_string()],
+                intern(1, 0, &[97, 98]),
+                intern(2, 1, &[99]),
+                intern(1, 2, &[97, 98]),
+                vec!["push constant 0".to_string(), "return".to_string()],
+            ]
+            .concat()
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn test_ast_new_diagnostics() {
+        let diagnostics = |source: &str| {
+            let tokens = token::Tokens::new(source.to_string());
+            match Ast::new(tokens, "Main".to_string()) {
+                Ok(_) => vec![],
+                Err(d) => d
+                    .into_iter()
+                    .map(|d| format!("{}:{}: {}", d.span.line, d.span.column, d.message))
+                    .collect(),
+            }
+        };
+
+        // エラーのあった文をスキップして後続の文, subroutineのエラーも報告する
+        assert_eq!(
+            diagnostics(
+                r#"class Main {
+  function void main() {
+    var int x;
+    let x = 1
+    let x = ;
+    if (x { let x = 2; }
+    do Output.printInt(x);
+    return;
+  }
+  function int f() {
+    return x +;
+  }
+}"#
+            ),
+            vec![
+                "5:5: expected `;` after statement",
+                "5:13: expected expression",
+                "6:11: expected `)` after condition",
+                "11:15: expected term after operator",
+            ]
+        );
+
+        // トークン列の終端でのエラーは最後のトークンの直後を指す
+        assert_eq!(
+            diagnostics("class Main {\n  function void main() {\n    return;"),
+            vec!["3:12: expected `}` after subroutine body"]
+        );
+
+        assert_eq!(diagnostics("function void main"), vec!["1:1: expected `class`"]);
+        assert_eq!(
+            diagnostics("class Main {\n  function void main() {\n    var int x;\n    return;\n    var int y;\n  }\n}"),
+            vec!["5:5: expected statement or `}`"]
+        );
+    }
 }
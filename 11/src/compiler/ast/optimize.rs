@@ -0,0 +1,310 @@
+use super::*;
+
+// --opt-levelが1以上のときに、意味解析のあとでコード生成の前に行う書き換え。
+// - 整数の定数式を1つの定数に畳み込む。Jackの二項演算は左から順に評価するので、先頭から定数が続く部分だけをまとめる
+// - 条件が定数のif文, while文, for文で実行されない節を取り除く
+// 型検査は済んでいるので、trueを-1にするような型の変わる書き換えをしてもよい
+
+impl Ast {
+    pub fn optimize(&mut self) {
+        let class = &mut self.class;
+        for subroutine_dec in &mut class.subroutine_dec {
+            // 引数やローカル変数が同名の定数を隠すので、subroutineのスコープで定数を探す
+            let symbol_tables = class
+                .symbol_tables
+                .update_current_subroutine_name(subroutine_dec.subroutine_name.0.clone());
+            statements(&mut subroutine_dec.body.statements, &symbol_tables);
+        }
+    }
+}
+
+fn statements(statements: &mut Statements, symbol_tables: &SymbolTables) {
+    let mut result = vec![];
+    for mut statement in std::mem::take(&mut statements.0) {
+        match &mut statement {
+            Statement::Let(s) => let_statement(s, symbol_tables),
+            Statement::If(s) => {
+                expression(&mut s.condition, symbol_tables);
+                self::statements(&mut s.positive_case_body, symbol_tables);
+                if let Some(negative_case_body) = &mut s.negative_case_body {
+                    self::statements(negative_case_body, symbol_tables);
+                }
+                // notした条件で分岐するので、then節に進むのは条件がtrue(-1)のときだけで、それ以外の定数ならelse節に進む
+                match s.condition.const_value(symbol_tables) {
+                    Some(-1) => {
+                        result.append(&mut s.positive_case_body.0);
+                        continue;
+                    }
+                    Some(_) => {
+                        if let Some(negative_case_body) = &mut s.negative_case_body {
+                            result.append(&mut negative_case_body.0);
+                        }
+                        continue;
+                    }
+                    None => {}
+                }
+            }
+            Statement::While(s) => {
+                expression(&mut s.condition, symbol_tables);
+                self::statements(&mut s.body, symbol_tables);
+                if s.condition.const_value(symbol_tables).is_some_and(|v| v != -1) {
+                    continue;
+                }
+            }
+            Statement::For(s) => {
+                if let Some(init) = &mut s.init {
+                    let_statement(init, symbol_tables);
+                }
+                expression(&mut s.condition, symbol_tables);
+                if let Some(step) = &mut s.step {
+                    let_statement(step, symbol_tables);
+                }
+                self::statements(&mut s.body, symbol_tables);
+                // 一度も回らないfor文は初期化だけを残す
+                if s.condition.const_value(symbol_tables).is_some_and(|v| v != -1) {
+                    result.extend(s.init.take().map(Statement::Let));
+                    continue;
+                }
+            }
+            Statement::Do(s) => subroutine_call(&mut s.0, symbol_tables),
+            Statement::Return(s) => {
+                if let Some(e) = &mut s.0 {
+                    expression(e, symbol_tables);
+                }
+            }
+            Statement::Break(_) | Statement::Continue(_) | Statement::Error(_) => {}
+        }
+        result.push(statement);
+    }
+    statements.0 = result;
+}
+
+fn let_statement(s: &mut LetStatement, symbol_tables: &SymbolTables) {
+    if let Some(a) = &mut s.array_index {
+        expression(a, symbol_tables);
+    }
+    expression(&mut s.right_hand_side, symbol_tables);
+}
+
+fn subroutine_call(s: &mut SubroutineCall, symbol_tables: &SymbolTables) {
+    for a in &mut s.arguments.0 {
+        expression(a, symbol_tables);
+    }
+}
+
+fn term(t: &mut Term, symbol_tables: &SymbolTables) {
+    match t {
+        Term::ArrayIndexAccess(_, e) => expression(e, symbol_tables),
+        Term::Expression(e) => expression(e, symbol_tables),
+        Term::UnaryOp(_, _, t) => term(t, symbol_tables),
+        Term::SubroutineCall(s) => subroutine_call(s, symbol_tables),
+        Term::IntegerConstant(_)
+        | Term::StringConstant(_)
+        | Term::CharConstant(_)
+        | Term::KeyWordConstant(_)
+        | Term::VarName(_)
+        | Term::EnumMember(..) => {}
+    }
+    // 演算を含む項だけを置き換える。定数1つの項はそのままの方が短いか同じ長さになる
+    if matches!(t, Term::Expression(_) | Term::UnaryOp(..)) {
+        if let Some(v) = t.const_value(symbol_tables) {
+            *t = constant(v);
+        }
+    }
+}
+
+fn expression(e: &mut Expression, symbol_tables: &SymbolTables) {
+    term(&mut e.term, symbol_tables);
+    for (_, _, t) in &mut e.op_term {
+        term(t, symbol_tables);
+    }
+
+    let Some(mut value) = e.term.const_value(symbol_tables) else {
+        return;
+    };
+    let mut folded = 0;
+    for (op, _, t) in &e.op_term {
+        match t.const_value(symbol_tables).and_then(|right| op.fold(value, right)) {
+            Some(v) => value = v,
+            None => break,
+        }
+        folded += 1;
+    }
+    if folded > 0 {
+        *e.term = constant(value);
+        e.op_term.drain(..folded);
+    }
+}
+
+// 負の値は0x8000以上の整数定数として持つ。コード生成でnotを使ってpushする
+fn constant(value: i16) -> Term {
+    Term::IntegerConstant(token::IntegerConstant(value as u16 as u32))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn to_vm(source: &str, opt_level: u8) -> Vec<String> {
+        let tokens = token::Tokens::with_dialect(source.to_string(), token::Dialect::JackPlus);
+        let mut ast = Ast::new(tokens, "Main".to_string()).unwrap();
+        if opt_level >= 1 {
+            ast.optimize();
+        }
+        let options = CodegenOptions {
+            opt_level,
+            ..Default::default()
+        };
+        ast.to_vm(&ClassTable::new(&[&ast]), &options)
+            .lines()
+            .map(str::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn test_optimize() {
+        let source = r#"
+class Main {
+    const int N = 4;
+    function int f(int x) {
+        var int y;
+        let y = (1 + 2) * 3 + x - -(N * 2);
+        if (false) {
+            do Main.f(1);
+        }
+        while (N < 0) {
+            let y = 0;
+        }
+        if (N = 4) {
+            let y = y * N;
+        } else {
+            let y = 8 * y;
+        }
+        return y;
+    }
+}
+"#;
+        assert_eq!(
+            to_vm(source, 1),
+            vec![
+                "function Main.f 1",
+                "push constant 9",
+                "push argument 0",
+                "add",
+                "push constant 7",
+                "not",
+                "sub",
+                "pop local 0",
+                "push local 0",
+                "push constant 4",
+                "call Math.multiply 2",
+                "pop local 0",
+                "push local 0",
+                "return",
+            ]
+        );
+        assert_eq!(
+            to_vm(source, 2)[8..15],
+            [
+                "push local 0",
+                "pop temp 0",
+                "push temp 0",
+                "push temp 0",
+                "add",
+                "pop temp 0",
+                "push temp 0",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_optimize_keeps_runtime_result() {
+        let source = r#"
+class Main {
+    const int ONE = 1;
+    function void main() {
+        do Output.printInt(1 && true);
+        do Output.printChar(32);
+        do Output.printInt(ONE || false);
+        do Output.printChar(32);
+        do Output.printInt(-1 && 5);
+        do Output.printChar(32);
+        if (1 && true) {
+            do Output.printInt(1);
+        } else {
+            do Output.printInt(2);
+        }
+        return;
+    }
+}
+"#;
+        let run = |opt_level| {
+            let tokens = token::Tokens::with_dialect(source.to_string(), token::Dialect::JackPlus);
+            let mut ast = Ast::new(tokens, "Main".to_string()).unwrap();
+            if opt_level >= 1 {
+                ast.optimize();
+            }
+            let options = CodegenOptions {
+                opt_level,
+                ..Default::default()
+            };
+            super::super::test::run(&ast, &options)
+        };
+        // `&&`の左辺が-1以外の0でない値なら、実行時と同じくfalseに畳み込む
+        assert_eq!(run(0), "0 -1 5 2");
+        assert_eq!(run(1), run(0));
+        assert_eq!(run(2), run(0));
+    }
+
+    #[test]
+    fn test_optimize_branches() {
+        let source = r#"
+class Main {
+    function void f(int x) {
+        if (x < 1) {
+            let x = 1;
+        } else {
+            let x = 2;
+        }
+        while (~(x = 0)) {
+            let x = x - 1;
+        }
+        return;
+    }
+}
+"#;
+        // レベル0は比較用の出力と同じ形のまま
+        assert_eq!(to_vm(source, 0)[1..5], ["push argument 0", "push constant 1", "lt", "not"]);
+        assert_eq!(
+            to_vm(source, 2),
+            vec![
+                "function Main.f 0",
+                "push argument 0",
+                "push constant 1",
+                "lt",
+                "if-goto IF_TRUE0",
+                "push constant 2",
+                "pop argument 0",
+                "goto IF_END0",
+                "label IF_TRUE0",
+                "push constant 1",
+                "pop argument 0",
+                "label IF_END0",
+                "label WHILE_EXP0",
+                "push argument 0",
+                "push constant 0",
+                "eq",
+                "if-goto WHILE_END0",
+                "push argument 0",
+                "push constant 1",
+                "sub",
+                "pop argument 0",
+                "goto WHILE_EXP0",
+                "label WHILE_END0",
+                "push constant 0",
+                "return",
+            ]
+        );
+    }
+}
@@ -0,0 +1,235 @@
+use super::*;
+
+// Jackの二項演算は左から順に評価するが、--precedenceを指定したときは一般的な優先順位でまとめ直す。
+// 優先順位の高い演算を`( )`で囲んだ式(Term::Expression)に組み替えるだけなので、意味解析とコード生成はそのまま使える
+
+impl Ast {
+    pub fn apply_precedence(&mut self) {
+        for var_dec in &mut self.class.var_dec {
+            for value in &mut var_dec.values {
+                expression(value);
+            }
+        }
+        for subroutine_dec in &mut self.class.subroutine_dec {
+            statements(&mut subroutine_dec.body.statements);
+        }
+        self.class.evaluate_constants();
+    }
+}
+
+impl Class {
+    // 組み替えた式でconstの値を計算し直す。先に宣言した定数から順に決める
+    fn evaluate_constants(&mut self) {
+        for var_dec in &self.var_dec {
+            for (var_name, value) in var_dec.var_names.iter().zip(&var_dec.values) {
+                // パースしたときに定数式であることは確かめているので、組み替えても値は決まる
+                let v = value.const_value(&self.symbol_tables).unwrap();
+                if let Some(symbol) = self.symbol_tables.class_scope.get_mut(&var_name.0 .0) {
+                    symbol.symbol_type = ClassSymbolType::Constant(v);
+                }
+            }
+        }
+    }
+}
+
+// 優先順位の段数。precedence()の値はこれより小さい
+const LEVELS: usize = 7;
+
+// 値が大きいほど強く結びつく。単項演算子はTermの一部なので二項演算子より常に強い
+fn precedence(op: &Op) -> usize {
+    match op {
+        Op::DoublePipe => 0,
+        Op::DoubleAmpersand => 1,
+        Op::Pipe => 2,
+        Op::Ampersand => 3,
+        Op::LessThan | Op::MoreThan | Op::Equal => 4,
+        Op::Plus | Op::Minus => 5,
+        Op::Multiply | Op::Div => 6,
+    }
+}
+
+fn statements(statements: &mut Statements) {
+    for statement in &mut statements.0 {
+        match statement {
+            Statement::Let(s) => let_statement(s),
+            Statement::If(s) => {
+                expression(&mut s.condition);
+                self::statements(&mut s.positive_case_body);
+                if let Some(negative_case_body) = &mut s.negative_case_body {
+                    self::statements(negative_case_body);
+                }
+            }
+            Statement::While(s) => {
+                expression(&mut s.condition);
+                self::statements(&mut s.body);
+            }
+            Statement::For(s) => {
+                if let Some(init) = &mut s.init {
+                    let_statement(init);
+                }
+                expression(&mut s.condition);
+                if let Some(step) = &mut s.step {
+                    let_statement(step);
+                }
+                self::statements(&mut s.body);
+            }
+            Statement::Do(s) => subroutine_call(&mut s.0),
+            Statement::Return(s) => {
+                if let Some(e) = &mut s.0 {
+                    expression(e);
+                }
+            }
+            Statement::Break(_) | Statement::Continue(_) | Statement::Error(_) => {}
+        }
+    }
+}
+
+fn let_statement(s: &mut LetStatement) {
+    if let Some(a) = &mut s.array_index {
+        expression(a);
+    }
+    expression(&mut s.right_hand_side);
+}
+
+fn subroutine_call(s: &mut SubroutineCall) {
+    for a in &mut s.arguments.0 {
+        expression(a);
+    }
+}
+
+fn term(t: &mut Term) {
+    match t {
+        Term::ArrayIndexAccess(_, e) | Term::Expression(e) => expression(e),
+        Term::UnaryOp(_, _, t) => term(t),
+        Term::SubroutineCall(s) => subroutine_call(s),
+        Term::IntegerConstant(_)
+        | Term::StringConstant(_)
+        | Term::CharConstant(_)
+        | Term::KeyWordConstant(_)
+        | Term::VarName(_)
+        | Term::EnumMember(..) => {}
+    }
+}
+
+fn expression(e: &mut Expression) {
+    term(&mut e.term);
+    for (_, _, t) in &mut e.op_term {
+        term(t);
+    }
+    if e.op_term.is_empty() {
+        return;
+    }
+
+    let first = std::mem::replace(e.term.as_mut(), Term::KeyWordConstant(KeyWordConstant::Null));
+    let mut rest = std::mem::take(&mut e.op_term).into_iter().peekable();
+    *e = group(first, &mut rest, 0);
+}
+
+// firstから始めて、優先順位がlevel以上の演算子が続く間を1つの式にまとめる。
+// 同じ優先順位の演算子は左から順に評価するので、そのまま1つの式に並べる
+fn group(
+    first: Term,
+    rest: &mut std::iter::Peekable<impl Iterator<Item = (Op, TokenIndex, Term)>>,
+    level: usize,
+) -> Expression {
+    let operand = |t: Term, rest: &mut std::iter::Peekable<_>| {
+        if level + 1 == LEVELS {
+            Expression {
+                term: Box::new(t),
+                op_term: vec![],
+            }
+        } else {
+            group(t, rest, level + 1)
+        }
+    };
+
+    let left = operand(first, rest);
+    let mut op_term = vec![];
+    while let Some((op, _, _)) = rest.peek() {
+        if precedence(op) != level {
+            break;
+        }
+        let (op, index, t) = rest.next().unwrap();
+        op_term.push((op, index, into_term(operand(t, rest))));
+    }
+
+    if op_term.is_empty() {
+        left
+    } else {
+        Expression {
+            term: Box::new(into_term(left)),
+            op_term,
+        }
+    }
+}
+
+// 演算を含む式はかっこで囲んだ項にする。項が1つだけの式はその項を取り出す
+fn into_term(e: Expression) -> Term {
+    if e.op_term.is_empty() {
+        *e.term
+    } else {
+        Term::Expression(e)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_apply_precedence() {
+        let source = r#"
+class Main {
+    function int f(int a, int b, int c) {
+        if ((a < b + c * 2) & ~(a = 1) | (b > c)) {
+            return Main.f(a - b / c, -a * b, c);
+        }
+        return a + b * c - a;
+    }
+}
+"#;
+        // 組み替えた式を`( )`を補ったソースとして書き出して比べる
+        fn show_term(t: &Term) -> String {
+            match t {
+                Term::IntegerConstant(i) => i.0.to_string(),
+                Term::VarName(v) => v.0 .0.clone(),
+                Term::Expression(e) => format!("({})", show(e)),
+                Term::UnaryOp(UnaryOp::Minus, _, t) => format!("-{}", show_term(t)),
+                Term::UnaryOp(UnaryOp::Tilde, _, t) => format!("~{}", show_term(t)),
+                Term::SubroutineCall(s) => {
+                    format!("{}({})", s.name.0 .0, s.arguments.0.iter().map(show).collect::<Vec<_>>().join(", "))
+                }
+                _ => unreachable!(),
+            }
+        }
+        fn show(e: &Expression) -> String {
+            let mut result = show_term(&e.term);
+            for (op, _, t) in &e.op_term {
+                result = format!("{} {} {}", result, semantic::op_symbol(op), show_term(t));
+            }
+            result
+        }
+
+        let mut ast = Ast::new(token::Tokens::new(source.to_string()), "Main".to_string()).unwrap();
+        ast.apply_precedence();
+        let statements = &ast.class.subroutine_dec[0].body.statements.0;
+        let Statement::If(if_statement) = &statements[0] else {
+            unreachable!()
+        };
+        let Statement::Return(ReturnStatement(Some(inner), _)) = &if_statement.positive_case_body.0[0] else {
+            unreachable!()
+        };
+        let Statement::Return(ReturnStatement(Some(last), _)) = &statements[1] else {
+            unreachable!()
+        };
+        assert_eq!(
+            [&if_statement.condition, inner, last].map(show),
+            [
+                "((a < (b + (c * 2))) & ~(a = 1)) | (b > c)",
+                "f(a - (b / c), -a * b, c)",
+                "a + (b * c) - a",
+            ]
+        );
+    }
+}
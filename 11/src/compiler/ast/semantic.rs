@@ -0,0 +1,978 @@
+use super::*;
+
+// 入力ディレクトリの全クラスをまとめて意味解析する。エラーはastsと同じ並びでファイルごとに返す
+// declarationsはクラスの参照先としてだけ使い、検査はしない。strictのときは型検査も行う
+pub(crate) fn check(asts: &[&Ast], declarations: &[&Ast], strict: bool) -> Vec<Vec<Diagnostic>> {
+    // 同名のクラスがあれば入力ファイル側を優先する
+    let mut classes = std::collections::HashMap::new();
+    for ast in declarations.iter().chain(asts.iter()) {
+        classes.insert(ast.class.name.0 .0.clone(), &ast.class);
+    }
+
+    let mut results: Vec<_> = asts
+        .iter()
+        .map(|ast| {
+            let mut checker = Checker {
+                classes: &classes,
+                class: &ast.class,
+                subroutine: None,
+                tokens: &ast.tokens,
+                strict,
+                references: std::collections::HashSet::new(),
+                loop_depth: 0,
+                diagnostics: vec![],
+            };
+            checker.check_class();
+            (checker.diagnostics, checker.references, ast.tokens.span_at(ast.class.name_index.0))
+        })
+        .collect();
+
+    // Mainを含む全クラスが入力されていれば、Mainから辿れないクラスを未使用として報告する
+    let names: Vec<&str> = asts.iter().map(|ast| ast.class.name.0 .0.as_str()).collect();
+    if names.contains(&"Main") {
+        let mut used = std::collections::HashSet::from(["Main"]);
+        let mut stack = vec!["Main"];
+        while let Some(name) = stack.pop() {
+            let index = names.iter().position(|n| *n == name).unwrap();
+            for reference in &results[index].1 {
+                if let Some(&n) = names.iter().find(|n| **n == reference) {
+                    if used.insert(n) {
+                        stack.push(n);
+                    }
+                }
+            }
+        }
+        for (name, (diagnostics, _, span)) in names.iter().zip(results.iter_mut()) {
+            if !used.contains(name) {
+                diagnostics.push(Diagnostic::warning(*span, format!("class `{}` is never used", name)));
+            }
+        }
+    }
+
+    results
+        .into_iter()
+        .map(|(mut diagnostics, _, _)| {
+            // missing returnはsubroutineを辿り終えてから報告するので位置順に並べ直す
+            diagnostics.sort_by_key(|d| (d.span.line, d.span.column));
+            diagnostics
+        })
+        .collect()
+}
+
+// 診断の位置はパース時にASTのノードに記録したトークンの位置(TokenIndex)から求める
+struct Checker<'a> {
+    classes: &'a std::collections::HashMap<String, &'a Class>,
+    class: &'a Class,
+    subroutine: Option<&'a SubroutineDec>,
+    tokens: &'a token::Tokens,
+    // trueのときは型検査も行う
+    strict: bool,
+    // このクラスから型やsubroutine呼び出しで参照しているクラス
+    references: std::collections::HashSet<String>,
+    // 処理中の文を囲んでいるwhile文, for文の数。break, continueの検査に使う
+    loop_depth: usize,
+    diagnostics: Vec<Diagnostic>,
+}
+
+// 式の型。配列の要素など型がわからないものはUnknownとして検査しない
+#[derive(Debug, PartialEq, Eq, Clone)]
+enum Inferred {
+    Type(Type),
+    Null,
+    Void,
+    Unknown,
+}
+impl Inferred {
+    fn name(&self) -> &str {
+        match self {
+            Inferred::Type(t) => type_name(t),
+            Inferred::Null => "null",
+            Inferred::Void => "void",
+            Inferred::Unknown => "unknown",
+        }
+    }
+}
+
+impl<'a> Checker<'a> {
+    fn error(&mut self, index: usize, message: String) {
+        self.diagnostics
+            .push(Diagnostic::new(self.tokens.span_at(index), message));
+    }
+
+    fn class_name(&self) -> &'a str {
+        &self.class.name.0 .0
+    }
+
+    fn subroutine_name(&self) -> String {
+        let name = self.subroutine.map_or("", |s| s.subroutine_name.0.as_str());
+        format!("{}.{}", self.class_name(), name)
+    }
+
+    fn in_function(&self) -> bool {
+        matches!(self.subroutine, Some(s) if s.kind == SubroutineDecKind::Function)
+    }
+
+    fn check_class(&mut self) {
+        for var_dec in &self.class.var_dec {
+            if !matches!(var_dec.kind, ClassVarKind::Enum(_)) {
+                self.type_(&var_dec.type_, type_index(&var_dec.var_names[0]));
+            }
+            for (i, var_name) in var_dec.var_names.iter().enumerate() {
+                let index = var_name.1 .0;
+                // constの値の式
+                if let Some(value) = var_dec.values.get(i) {
+                    let actual = self.expression(value);
+                    self.assign(&var_dec.type_, &actual, index, format!("constant `{}`", var_name.0 .0));
+                }
+            }
+        }
+        for subroutine in &self.class.subroutine_dec {
+            self.check_subroutine(subroutine);
+        }
+    }
+
+    fn check_subroutine(&mut self, subroutine: &'a SubroutineDec) {
+        self.subroutine = Some(subroutine);
+        let name_index = subroutine.name_index.0;
+        // 戻り値の型はsubroutine名の直前の1トークン
+        if let SubroutineDecType::Type_(t) = &subroutine.type_ {
+            self.type_(t, name_index - 1);
+        }
+        for (type_, var_name) in &subroutine.parameter_list.0 {
+            self.type_(type_, type_index(var_name));
+        }
+        for var_dec in &subroutine.body.var_dec {
+            self.type_(&var_dec.type_, type_index(&var_dec.var_name[0]));
+        }
+        self.statements(&subroutine.body.statements);
+
+        if !always_returns(&subroutine.body.statements) {
+            let message = format!("missing `return` at the end of `{}`", self.subroutine_name());
+            self.error(name_index, message);
+        }
+    }
+
+    fn type_(&mut self, type_: &Type, index: usize) {
+        if let Type::ClassName(c) = type_ {
+            self.references.insert(c.clone());
+            if !self.classes.contains_key(c) {
+                self.error(index, format!("unknown class `{}`", c));
+            }
+        }
+    }
+
+    // 変数の型を返す。functionの中でfieldを参照していたらエラーにする
+    fn resolve_variable(&mut self, name: &str, index: usize) -> Option<Type> {
+        let symbol_tables = &self.class.symbol_tables;
+        if let Some(s) = self
+            .subroutine
+            .and_then(|s| symbol_tables.subroutine_scopes.get(&s.subroutine_name.0))
+            .and_then(|scope| scope.get(name))
+        {
+            return Some(s.type_.clone());
+        }
+        let s = symbol_tables.class_scope.get(name)?;
+        if s.symbol_type == ClassSymbolType::Field && self.in_function() {
+            let message = format!("field `{}` cannot be used in function `{}`", name, self.subroutine_name());
+            self.error(index, message);
+        }
+        Some(s.type_.clone())
+    }
+
+    // subroutineのスコープに同名の変数がなく、クラスの定数として宣言されていればtrue
+    fn is_constant(&self, name: &str) -> bool {
+        let symbol_tables = &self.class.symbol_tables;
+        let local = self
+            .subroutine
+            .and_then(|s| symbol_tables.subroutine_scopes.get(&s.subroutine_name.0))
+            .is_some_and(|scope| scope.contains_key(name));
+        !local
+            && matches!(
+                symbol_tables.class_scope.get(name),
+                Some(ClassSymbol {
+                    symbol_type: ClassSymbolType::Constant(_),
+                    ..
+                })
+            )
+    }
+
+    // `EnumName.MEMBER`を検査する。列挙型は自クラスのものを優先し、なければ他のクラスから探す
+    fn enum_member(&mut self, enum_name: &str, member: &VarName) {
+        let member_index = member.1 .0;
+        // 列挙型の名前は`EnumName.MEMBER`の2つ前のトークン
+        let enum_index = member_index - 2;
+        let member = member.0 .0.as_str();
+        let declared_in = |class: &'a Class| {
+            class.var_dec.iter().find_map(|v| match &v.kind {
+                ClassVarKind::Enum(name) if name.0 == enum_name => Some((class, v)),
+                _ => None,
+            })
+        };
+        let (class, var_dec) = match declared_in(self.class) {
+            Some(found) => found,
+            None => {
+                let mut found: Vec<_> = self.classes.values().filter_map(|c| declared_in(c)).collect();
+                match found.len() {
+                    0 => return self.error(enum_index, format!("unknown enum `{}`", enum_name)),
+                    1 => found.remove(0),
+                    _ => {
+                        let mut names: Vec<_> = found.iter().map(|(c, _)| c.name.0 .0.as_str()).collect();
+                        names.sort();
+                        let message =
+                            format!("enum `{}` is declared in more than one class: {}", enum_name, names.join(", "));
+                        return self.error(enum_index, message);
+                    }
+                }
+            }
+        };
+        self.references.insert(class.name.0 .0.clone());
+        if !var_dec.var_names.iter().any(|v| v.0 .0 == member) {
+            self.error(member_index, format!("unknown enumerator `{}.{}`", enum_name, member));
+        }
+    }
+
+    fn variable_type(&mut self, var_name: &VarName, index: usize) -> Option<Type> {
+        let type_ = self.resolve_variable(&var_name.0 .0, index);
+        if type_.is_none() {
+            self.error(index, format!("undeclared variable `{}`", var_name.0 .0));
+        }
+        type_
+    }
+
+    fn statements(&mut self, statements: &'a Statements) {
+        for statement in &statements.0 {
+            match statement {
+                Statement::Let(s) => self.assignment(s),
+                Statement::If(s) => {
+                    let index = s.keyword_index.0;
+                    let condition = self.expression(&s.condition);
+                    self.assign(&Type::Boolean, &condition, index, "condition of `if`".to_string());
+                    self.statements(&s.positive_case_body);
+                    if let Some(negative_case_body) = &s.negative_case_body {
+                        self.statements(negative_case_body);
+                    }
+                }
+                Statement::While(s) => {
+                    let index = s.keyword_index.0;
+                    let condition = self.expression(&s.condition);
+                    self.assign(&Type::Boolean, &condition, index, "condition of `while`".to_string());
+                    self.loop_body(&s.body);
+                }
+                Statement::For(s) => {
+                    let index = s.keyword_index.0;
+                    if let Some(init) = &s.init {
+                        self.assignment(init);
+                    }
+                    let condition = self.expression(&s.condition);
+                    self.assign(&Type::Boolean, &condition, index, "condition of `for`".to_string());
+                    if let Some(step) = &s.step {
+                        self.assignment(step);
+                    }
+                    self.loop_body(&s.body);
+                }
+                Statement::Break(index) => self.jump(index.0, "break"),
+                Statement::Continue(index) => self.jump(index.0, "continue"),
+                Statement::Do(s) => {
+                    self.subroutine_call(&s.0);
+                }
+                Statement::Return(s) => {
+                    let actual = s.0.as_ref().map(|e| self.expression(e));
+                    self.return_value(actual, s.1 .0);
+                }
+                // パースエラーがあった場合は意味解析まで進まない
+                Statement::Error(_) => {}
+            }
+        }
+    }
+
+    // `let`を除いた代入の部分。for文の初期化と更新もここで検査する
+    fn assignment(&mut self, s: &LetStatement) {
+        let index = s.var_name.1 .0;
+        let type_ = self.variable_type(&s.var_name, index);
+        if self.is_constant(&s.var_name.0 .0) {
+            self.error(index, format!("cannot assign to constant `{}`", s.var_name.0 .0));
+        }
+        let expected = match &s.array_index {
+            // 配列の要素は型を持たない
+            Some(e) => {
+                let index_type = self.expression(e);
+                self.assign(&Type::Int, &index_type, index, "array index".to_string());
+                None
+            }
+            None => type_,
+        };
+        let actual = match &s.compound_op {
+            // 複合代入は左辺の現在の値との二項演算として検査する
+            Some((op, op_index)) => {
+                let left = expected.clone().map_or(Inferred::Unknown, Inferred::Type);
+                let right = self.expression(&s.right_hand_side);
+                self.binary_op(op, left, right, op_index.0)
+            }
+            None => self.expression(&s.right_hand_side),
+        };
+        if let Some(expected) = expected {
+            self.assign(&expected, &actual, index, format!("assignment to `{}`", s.var_name.0 .0));
+        }
+    }
+
+    fn loop_body(&mut self, body: &'a Statements) {
+        self.loop_depth += 1;
+        self.statements(body);
+        self.loop_depth -= 1;
+    }
+
+    fn jump(&mut self, index: usize, name: &str) {
+        if self.loop_depth == 0 {
+            self.error(index, format!("`{}` outside of a loop", name));
+        }
+    }
+
+    fn return_value(&mut self, actual: Option<Inferred>, index: usize) {
+        let Some(subroutine) = self.subroutine else {
+            return;
+        };
+        let name = self.subroutine_name();
+        match (&subroutine.type_, actual) {
+            (SubroutineDecType::Void, Some(_)) => {
+                self.type_error(index, format!("void subroutine `{}` returns a value", name));
+            }
+            (SubroutineDecType::Type_(t), None) => {
+                let message = format!("`{}` must return a value of type `{}`", name, type_name(t));
+                self.type_error(index, message);
+            }
+            (SubroutineDecType::Type_(t), Some(actual)) => {
+                self.assign(t, &actual, index, format!("return value of `{}`", name));
+            }
+            (SubroutineDecType::Void, None) => {}
+        }
+    }
+
+    fn expression(&mut self, expression: &Expression) -> Inferred {
+        let mut left = self.term(&expression.term);
+        for (op, index, t) in &expression.op_term {
+            let right = self.term(t);
+            left = self.binary_op(op, left, right, index.0);
+        }
+        left
+    }
+
+    fn term(&mut self, term: &Term) -> Inferred {
+        match term {
+            Term::IntegerConstant(_) => Inferred::Type(Type::Int),
+            Term::StringConstant(_) => Inferred::Type(Type::ClassName("String".to_string())),
+            Term::CharConstant(_) => Inferred::Type(Type::Char),
+            Term::KeyWordConstant(KeyWordConstant::True | KeyWordConstant::False) => Inferred::Type(Type::Boolean),
+            Term::KeyWordConstant(KeyWordConstant::Null) => Inferred::Null,
+            Term::KeyWordConstant(KeyWordConstant::This(index)) => {
+                let index = index.0;
+                if self.in_function() {
+                    let message = format!("`this` cannot be used in function `{}`", self.subroutine_name());
+                    self.error(index, message);
+                }
+                Inferred::Type(Type::ClassName(self.class_name().to_string()))
+            }
+            Term::VarName(v) => self.variable_type(v, v.1 .0).map_or(Inferred::Unknown, Inferred::Type),
+            Term::EnumMember(e, m) => {
+                self.enum_member(&e.0, m);
+                Inferred::Type(Type::Int)
+            }
+            Term::ArrayIndexAccess(v, e) => {
+                let index = v.1 .0;
+                self.variable_type(v, index);
+                let index_type = self.expression(e);
+                self.assign(&Type::Int, &index_type, index, "array index".to_string());
+                Inferred::Unknown
+            }
+            Term::Expression(e) => self.expression(e),
+            Term::UnaryOp(u, index, t) => {
+                let index = index.0;
+                let operand = self.term(t);
+                match (u, operand) {
+                    (UnaryOp::Tilde, Inferred::Type(Type::Boolean)) => Inferred::Type(Type::Boolean),
+                    (_, operand) => {
+                        self.operand(&operand, index, "unary operator");
+                        Inferred::Type(Type::Int)
+                    }
+                }
+            }
+            Term::SubroutineCall(s) => self.subroutine_call(s),
+        }
+    }
+
+    // 二項演算の結果の型を返す。Jackには演算子の優先順位がないので左から順に畳み込む
+    fn binary_op(&mut self, op: &Op, left: Inferred, right: Inferred, index: usize) -> Inferred {
+        let symbol = op_symbol(op);
+        match op {
+            Op::Plus | Op::Minus | Op::Multiply | Op::Div | Op::LessThan | Op::MoreThan => {
+                self.operand(&left, index, symbol);
+                self.operand(&right, index, symbol);
+                match op {
+                    Op::LessThan | Op::MoreThan => Inferred::Type(Type::Boolean),
+                    _ => Inferred::Type(Type::Int),
+                }
+            }
+            Op::Ampersand | Op::Pipe => match (&left, &right) {
+                (Inferred::Type(Type::Boolean), Inferred::Type(Type::Boolean)) => Inferred::Type(Type::Boolean),
+                _ => {
+                    self.mixing(&left, &right, index, symbol);
+                    self.operand(&left, index, symbol);
+                    self.operand(&right, index, symbol);
+                    Inferred::Type(Type::Int)
+                }
+            },
+            Op::Equal => {
+                self.mixing(&left, &right, index, symbol);
+                Inferred::Type(Type::Boolean)
+            }
+            Op::DoubleAmpersand | Op::DoublePipe => {
+                self.boolean_operand(&left, index, symbol);
+                self.boolean_operand(&right, index, symbol);
+                Inferred::Type(Type::Boolean)
+            }
+        }
+    }
+
+    // `&&`, `||`の被演算子はbooleanであることを期待する
+    fn boolean_operand(&mut self, operand: &Inferred, index: usize, operator: &str) {
+        match operand {
+            Inferred::Type(Type::Boolean) | Inferred::Unknown => {}
+            Inferred::Void => self.type_error(index, format!("void value used as operand of `{}`", operator)),
+            _ => {
+                let message = format!("`{}` expects `boolean` operands, found `{}`", operator, operand.name());
+                self.type_warning(index, message);
+            }
+        }
+    }
+
+    // 算術演算(とビット演算)の被演算子はintであることを期待する
+    fn operand(&mut self, operand: &Inferred, index: usize, operator: &str) {
+        match operand {
+            Inferred::Type(Type::Int) | Inferred::Unknown => {}
+            Inferred::Void => self.type_error(index, format!("void value used as operand of `{}`", operator)),
+            _ => {
+                let message = format!("`{}` expects `int` operands, found `{}`", operator, operand.name());
+                self.type_warning(index, message);
+            }
+        }
+    }
+
+    // 被演算子の型が食い違っていたら警告する。オブジェクト同士の比較はnullとの比較のみ許す
+    fn mixing(&mut self, left: &Inferred, right: &Inferred, index: usize, operator: &str) {
+        match (left, right) {
+            (Inferred::Void, _) | (_, Inferred::Void) => {
+                self.type_error(index, format!("void value used as operand of `{}`", operator));
+            }
+            (Inferred::Unknown, _) | (_, Inferred::Unknown) => {}
+            (Inferred::Null, Inferred::Null | Inferred::Type(Type::ClassName(_)))
+            | (Inferred::Type(Type::ClassName(_)), Inferred::Null) => {}
+            (l, r) if l == r => {}
+            (l, r) => {
+                let message = format!("mixing `{}` and `{}` in `{}`", l.name(), r.name(), operator);
+                self.type_warning(index, message);
+            }
+        }
+    }
+
+    // 代入(引数の受け渡し, 戻り値を含む)の型を検査する。
+    // 異なるクラスのオブジェクトの代入はエラー、プリミティブ型同士やオブジェクトとプリミティブ型の混在は警告にする。
+    // Arrayは型のないポインタとして使われる(`Memory.deAlloc(this)`など)ので、どのクラスとも相互に代入できる
+    fn assign(&mut self, expected: &Type, actual: &Inferred, index: usize, context: String) {
+        match (expected, actual) {
+            (_, Inferred::Unknown) | (Type::ClassName(_), Inferred::Null) => {}
+            (_, Inferred::Void) => self.type_error(index, format!("{}: void value used", context)),
+            (Type::ClassName(e), Inferred::Type(Type::ClassName(a))) if e == "Array" || a == "Array" => {}
+            (Type::ClassName(e), Inferred::Type(Type::ClassName(a))) if e != a => {
+                let message = format!("{}: expected `{}`, found `{}`", context, e, a);
+                self.type_error(index, message);
+            }
+            (e, a) if Inferred::Type(e.clone()) != *a => {
+                let message = format!("{}: expected `{}`, found `{}`", context, type_name(e), a.name());
+                self.type_warning(index, message);
+            }
+            _ => {}
+        }
+    }
+
+    fn type_error(&mut self, index: usize, message: String) {
+        if self.strict {
+            self.error(index, message);
+        }
+    }
+
+    fn type_warning(&mut self, index: usize, message: String) {
+        if self.strict {
+            self.diagnostics
+                .push(Diagnostic::warning(self.tokens.span_at(index), message));
+        }
+    }
+
+    fn subroutine_call(&mut self, call: &SubroutineCall) -> Inferred {
+        let name = &call.name.0 .0;
+        let index = call.name_index.0;
+        let callee = match &call.receiver {
+            // 自クラスのsubroutineの呼び出し
+            None => {
+                let callee = find_subroutine(self.class, name);
+                match callee {
+                    Some(s) => {
+                        if s.kind == SubroutineDecKind::Method && self.in_function() {
+                            let message = format!(
+                                "method `{}.{}` cannot be called from function `{}` without a receiver",
+                                self.class_name(),
+                                name,
+                                self.subroutine_name()
+                            );
+                            self.error(index, message);
+                        }
+                    }
+                    None => self.error(index, format!("unknown subroutine `{}.{}`", self.class_name(), name)),
+                }
+                callee.map(|s| (self.class, s))
+            }
+            Some(Receiver::This) => {
+                if self.in_function() {
+                    let message = format!("`this` cannot be used in function `{}`", self.subroutine_name());
+                    // レシーバは`receiver.name`の2つ前のトークン
+                    self.error(index - 2, message);
+                }
+                self.callee(self.class, call, index, true)
+            }
+            Some(Receiver::Name(receiver_name)) => {
+                let receiver_name = &receiver_name.0;
+                let receiver_index = index - 2;
+                // 変数を優先して解決し、変数でなければクラス名として扱う
+                match self.resolve_variable(receiver_name, receiver_index) {
+                    // 未知のクラスは宣言の時点でエラーにしている
+                    Some(Type::ClassName(class_name)) => match self.classes.get(&class_name).copied() {
+                        Some(class) => self.callee(class, call, index, true),
+                        None => None,
+                    },
+                    Some(type_) => {
+                        let message =
+                            format!("cannot call `{}` on `{}` of type `{}`", name, receiver_name, type_name(&type_));
+                        self.error(receiver_index, message);
+                        None
+                    }
+                    None => match self.classes.get(receiver_name).copied() {
+                        Some(class) => {
+                            self.references.insert(receiver_name.clone());
+                            self.callee(class, call, index, false)
+                        }
+                        None => {
+                            let message = format!("undeclared variable or unknown class `{}`", receiver_name);
+                            self.error(receiver_index, message);
+                            None
+                        }
+                    },
+                }
+            }
+        };
+
+        if let Some((class, s)) = callee {
+            self.arity(&class.name.0 .0, s, call, index);
+        }
+        for (i, argument) in call.arguments.0.iter().enumerate() {
+            let actual = self.expression(argument);
+            if let Some((class, s)) = callee {
+                if let Some((expected, _)) = s.parameter_list.0.get(i) {
+                    let context = format!("argument {} of `{}.{}`", i + 1, class.name.0 .0, name);
+                    self.assign(expected, &actual, index, context);
+                }
+            }
+        }
+
+        match callee {
+            Some((_, s)) => match &s.type_ {
+                SubroutineDecType::Void => Inferred::Void,
+                SubroutineDecType::Type_(t) => Inferred::Type(t.clone()),
+            },
+            None => Inferred::Unknown,
+        }
+    }
+
+    // `receiver.name(...)`形式の呼び出し先を解決して検査する。on_instanceはレシーバが変数かどうか
+    fn callee(
+        &mut self,
+        class: &'a Class,
+        call: &SubroutineCall,
+        index: usize,
+        on_instance: bool,
+    ) -> Option<(&'a Class, &'a SubroutineDec)> {
+        let class_name = &class.name.0 .0;
+        let name = &call.name.0 .0;
+        let Some(s) = find_subroutine(class, name) else {
+            self.error(index, format!("unknown subroutine `{}.{}`", class_name, name));
+            return None;
+        };
+        match (&s.kind, on_instance) {
+            (SubroutineDecKind::Method, false) => {
+                let message = format!("method `{}.{}` must be called on an instance", class_name, name);
+                self.error(index, message);
+            }
+            (SubroutineDecKind::Constructor | SubroutineDecKind::Function, true) => {
+                let message =
+                    format!("{} `{}.{}` cannot be called on an instance", kind_name(&s.kind), class_name, name);
+                self.error(index, message);
+            }
+            _ => {}
+        }
+        Some((class, s))
+    }
+
+    fn arity(&mut self, class_name: &str, s: &SubroutineDec, call: &SubroutineCall, index: usize) {
+        let (expected, given) = (s.parameter_list.0.len(), call.arguments.0.len());
+        if expected != given {
+            let message = format!(
+                "`{}.{}` expects {} argument(s), but {} given",
+                class_name, s.subroutine_name.0, expected, given
+            );
+            self.error(index, message);
+        }
+    }
+}
+
+// 変数宣言と引数の型は(最初の)変数名の直前の1トークン
+fn type_index(var_name: &VarName) -> usize {
+    var_name.1 .0 - 1
+}
+
+fn find_subroutine<'a>(class: &'a Class, name: &str) -> Option<&'a SubroutineDec> {
+    class.subroutine_dec.iter().find(|s| s.subroutine_name.0 == name)
+}
+
+// 最後の文がreturnであるか、if/elseの両方の節がreturnで終わっていればtrue
+fn always_returns(statements: &Statements) -> bool {
+    match statements.0.last() {
+        Some(Statement::Return(_)) => true,
+        Some(Statement::If(IfStatement {
+            positive_case_body,
+            negative_case_body: Some(negative_case_body),
+            ..
+        })) => always_returns(positive_case_body) && always_returns(negative_case_body),
+        _ => false,
+    }
+}
+
+fn kind_name(kind: &SubroutineDecKind) -> &'static str {
+    match kind {
+        SubroutineDecKind::Constructor => "constructor",
+        SubroutineDecKind::Function => "function",
+        SubroutineDecKind::Method => "method",
+    }
+}
+
+pub(super) fn op_token(op: &Op) -> token::Symbol {
+    match op {
+        Op::Plus => token::Symbol::Plus,
+        Op::Minus => token::Symbol::Minus,
+        Op::Multiply => token::Symbol::Asterisk,
+        Op::Div => token::Symbol::Slash,
+        Op::Ampersand => token::Symbol::Ampersand,
+        Op::Pipe => token::Symbol::Pipe,
+        Op::LessThan => token::Symbol::LessThan,
+        Op::MoreThan => token::Symbol::MoreThan,
+        Op::Equal => token::Symbol::Equal,
+        Op::DoubleAmpersand => token::Symbol::DoubleAmpersand,
+        Op::DoublePipe => token::Symbol::DoublePipe,
+    }
+}
+
+pub(super) fn op_symbol(op: &Op) -> &'static str {
+    match op {
+        Op::Plus => "+",
+        Op::Minus => "-",
+        Op::Multiply => "*",
+        Op::Div => "/",
+        Op::Ampersand => "&",
+        Op::Pipe => "|",
+        Op::LessThan => "<",
+        Op::MoreThan => ">",
+        Op::Equal => "=",
+        Op::DoubleAmpersand => "&&",
+        Op::DoublePipe => "||",
+    }
+}
+
+fn type_name(type_: &Type) -> &str {
+    match type_ {
+        Type::Int => "int",
+        Type::Char => "char",
+        Type::Boolean => "boolean",
+        Type::ClassName(c) => c,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::compiler::diagnostics::Severity;
+    use pretty_assertions::assert_eq;
+
+    fn diagnostics(sources: &[&str], strict: bool) -> Vec<Vec<String>> {
+        let asts: Vec<Ast> = sources
+            .iter()
+            .map(|source| Ast::new(token::Tokens::new(source.to_string()), String::new()).unwrap())
+            .collect();
+        let os = crate::compiler::program::os_declarations();
+        check(&asts.iter().collect::<Vec<_>>(), &os.iter().collect::<Vec<_>>(), strict)
+            .into_iter()
+            .map(|d| {
+                d.into_iter()
+                    .map(|d| match (strict, d.severity) {
+                        (false, _) => format!("{}:{}: {}", d.span.line, d.span.column, d.message),
+                        (true, Severity::Error) => format!("{}:{}: error: {}", d.span.line, d.span.column, d.message),
+                        (true, Severity::Warning) => {
+                            format!("{}:{}: warning: {}", d.span.line, d.span.column, d.message)
+                        }
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_check() {
+        let main = r#"class Main {
+    field int count;
+    function void main() {
+        var Game game;
+        let game = Game.new(1);
+        do game.run();
+        do game.run(1);
+        do Game.run();
+        do game.create();
+        do Output.printInt(count);
+        do draw();
+        let y = Math.multiply(2);
+        do Unknown.foo();
+        let game = this;
+        do this.draw();
+        return;
+    }
+    method void draw() {
+        do draw();
+        let count = count + 1;
+        do count.foo();
+        if (count) {
+            return;
+        }
+    }
+    method int size() {
+        if (count) {
+            return 1;
+        } else {
+            return 2;
+        }
+    }
+}"#;
+        let game = r#"class Game {
+    constructor Game new(int n) {
+        return this;
+    }
+    function Game create() {
+        return Game.new(0);
+    }
+    method void run() {
+        do Main.size();
+        return;
+    }
+}"#;
+
+        assert_eq!(
+            diagnostics(&[main, game], false),
+            vec![
+                vec![
+                    "7:17: `Game.run` expects 0 argument(s), but 1 given",
+                    "8:17: method `Game.run` must be called on an instance",
+                    "9:17: function `Game.create` cannot be called on an instance",
+                    "10:28: field `count` cannot be used in function `Main.main`",
+                    "11:12: method `Main.draw` cannot be called from function `Main.main` without a receiver",
+                    "12:13: undeclared variable `y`",
+                    "12:22: `Math.multiply` expects 2 argument(s), but 1 given",
+                    "13:12: undeclared variable or unknown class `Unknown`",
+                    "14:20: `this` cannot be used in function `Main.main`",
+                    "15:12: `this` cannot be used in function `Main.main`",
+                    "18:17: missing `return` at the end of `Main.draw`",
+                    "21:12: cannot call `foo` on `count` of type `int`",
+                ],
+                vec!["9:17: method `Main.size` must be called on an instance"],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_check_jack_plus() {
+        let main = r#"class Main {
+    const int MAX = 3;
+    const boolean FLAG = 1;
+    function void main() {
+        var int i;
+        var boolean b;
+        break;
+        for (i = 0; i < 3; i += 1) {
+            while (b) { continue; }
+            break;
+        }
+        if (b) { continue; } else if (i) { let i -= 1; }
+        let b += 1;
+        let i |= b;
+        let MAX = i;
+        return;
+    }
+}"#;
+        let tokens = token::Tokens::with_dialect(main.to_string(), token::Dialect::JackPlus);
+        let ast = Ast::new(tokens, String::new()).unwrap();
+        let os = crate::compiler::program::os_declarations();
+        let diagnostics: Vec<String> = check(&[&ast], &os.iter().collect::<Vec<_>>(), true)
+            .remove(0)
+            .into_iter()
+            .map(|d| format!("{}:{}: {}", d.span.line, d.span.column, d.message))
+            .collect();
+
+        // 複合代入は左辺の現在の値との二項演算として型を検査する
+        assert_eq!(
+            diagnostics,
+            vec![
+                "3:19: constant `FLAG`: expected `boolean`, found `int`",
+                "7:9: `break` outside of a loop",
+                "12:18: `continue` outside of a loop",
+                "12:35: condition of `if`: expected `boolean`, found `int`",
+                "13:13: assignment to `b`: expected `boolean`, found `int`",
+                "13:15: `+` expects `int` operands, found `boolean`",
+                "14:15: mixing `int` and `boolean` in `|`",
+                "14:15: `|` expects `int` operands, found `boolean`",
+                "15:13: cannot assign to constant `MAX`",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_check_enum_members() {
+        let main = r#"class Main {
+    enum Direction { UP, DOWN }
+    function int main() {
+        var int i;
+        let i = Direction.DOWN + State.OVER;
+        let i = Direction.LEFT + UP;
+        let i = Shape.CIRCLE + Mode.ON;
+        return i;
+    }
+}"#;
+        let game = "class Game { enum State { READY, OVER } enum Mode { OFF, ON } }";
+        let screen = "class Screen2 { enum Mode { ON, OFF } }";
+        let asts: Vec<Ast> = [main, game, screen]
+            .iter()
+            .map(|source| {
+                let tokens = token::Tokens::with_dialect(source.to_string(), token::Dialect::JackPlus);
+                Ast::new(tokens, String::new()).unwrap()
+            })
+            .collect();
+        let diagnostics: Vec<String> = check(&asts.iter().collect::<Vec<_>>(), &[], false)
+            .remove(0)
+            .into_iter()
+            .map(|d| format!("{}:{}: {}", d.span.line, d.span.column, d.message))
+            .collect();
+
+        // 列挙子は列挙型の名前を付けないと参照できない
+        assert_eq!(
+            diagnostics,
+            vec![
+                "6:27: unknown enumerator `Direction.LEFT`",
+                "6:34: undeclared variable `UP`",
+                "7:17: unknown enum `Shape`",
+                "7:32: enum `Mode` is declared in more than one class: Game, Screen2",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_check_strict() {
+        let main = r#"class Main {
+    function void main() {
+        var int i;
+        var char c;
+        var boolean b;
+        var Point p;
+        var Line l;
+        var Array a;
+        let i = c;
+        let b = i < 3;
+        let i = b + 1;
+        let p = l;
+        let p = Point.new(1, true);
+        let p = null;
+        let a = p;
+        let l = a[i];
+        let i = p.getX() + Main.f(p);
+        let i = Main.g();
+        if (i) {
+            return 1;
+        }
+        return;
+    }
+    function int f(Line l) {
+        return;
+    }
+    function void g() {
+        return;
+    }
+}"#;
+        let point = r#"class Point {
+    field int x;
+    constructor Point new(int ax, int ay) {
+        let x = ax;
+        return this;
+    }
+    method int getX() {
+        return x;
+    }
+}"#;
+        let line = "class Line { function void f() { return; } }";
+
+        assert_eq!(
+            diagnostics(&[main, point, line], true),
+            vec![
+                vec![
+                    "9:13: warning: assignment to `i`: expected `int`, found `char`",
+                    "11:19: warning: `+` expects `int` operands, found `boolean`",
+                    "12:13: error: assignment to `p`: expected `Point`, found `Line`",
+                    "13:23: warning: argument 2 of `Point.new`: expected `int`, found `boolean`",
+                    "17:33: error: argument 1 of `Main.f`: expected `Line`, found `Point`",
+                    "18:13: error: assignment to `i`: void value used",
+                    "19:9: warning: condition of `if`: expected `boolean`, found `int`",
+                    "20:13: error: void subroutine `Main.main` returns a value",
+                    "25:9: error: `Main.f` must return a value of type `int`",
+                ],
+                vec![],
+                vec![],
+            ]
+        );
+        // strictでなければ型検査はしない
+        assert_eq!(diagnostics(&[main, point, line], false), vec![vec![], vec![], vec![]] as Vec<Vec<String>>);
+    }
+
+    #[test]
+    fn test_check_unused_classes() {
+        let main = "class Main { function void main() { do Game.run(); return; } }";
+        let game = "class Game { function void run() { var Board b; return; } }";
+        let board = "class Board { }";
+        // Mainから参照していないクラスから参照されていても未使用になる
+        let unused = "class Unused { function void f() { do Helper.g(); return; } }";
+        let helper = "class Helper { function void g() { return; } }";
+        assert_eq!(
+            diagnostics(&[main, game, board, unused, helper], false),
+            vec![
+                vec![],
+                vec![],
+                vec![],
+                vec!["1:7: class `Unused` is never used"],
+                vec!["1:7: class `Helper` is never used"],
+            ]
+        );
+        // Mainがなければ全体が揃っていないので報告しない
+        assert_eq!(
+            diagnostics(&[game, board, unused, helper], false),
+            vec![vec![], vec![], vec![], vec![]] as Vec<Vec<String>>
+        );
+    }
+}
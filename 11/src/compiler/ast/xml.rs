@@ -0,0 +1,528 @@
+use super::*;
+
+// 10章の構文解析器の出力形式(`<class>`から始まるXML)でASTを出力する
+
+impl Ast {
+    pub fn to_xml(&self) -> String {
+        self.class.to_xml().join("\n")
+    }
+}
+
+impl Class {
+    fn to_xml(&self) -> Vec<String> {
+        let mut result = vec![];
+        let (class_open, class_close) = get_xml_tag("class".to_string());
+        result.push(class_open);
+        result.push(to_xml_tag(token::Keyword::Class));
+        result.push(self.name.0.to_xml());
+        result.push(to_xml_tag(token::Symbol::LeftBrace));
+        for var_dec in &self.var_dec {
+            result = [result, var_dec.to_xml()].concat();
+        }
+        for subroutine in &self.subroutine_dec {
+            result = [result, subroutine.to_xml()].concat();
+        }
+
+        result.push(to_xml_tag(token::Symbol::RightBrace));
+        result.push(class_close);
+        result
+    }
+}
+
+impl ClassVarDec {
+    fn to_xml(&self) -> Vec<String> {
+        let mut result = vec![];
+        let (open, close) = get_xml_tag("classVarDec".to_string());
+        result.push(open);
+        if let ClassVarKind::Enum(name) = &self.kind {
+            result.push(to_xml_tag(token::Keyword::Enum));
+            result.push(name.to_xml());
+            result.push(to_xml_tag(token::Symbol::LeftBrace));
+            for (index, n) in self.var_names.iter().enumerate() {
+                if index != 0 {
+                    result.push(to_xml_tag(token::Symbol::Comma));
+                }
+                result.push(n.to_xml());
+            }
+            result.push(to_xml_tag(token::Symbol::RightBrace));
+            result.push(close);
+            return result;
+        }
+        result.push(self.kind.to_xml());
+        result.push(self.type_.to_xml());
+        for (index, n) in self.var_names.iter().enumerate() {
+            if index != 0 {
+                result.push(to_xml_tag(token::Symbol::Comma));
+            }
+            result.push(n.to_xml());
+            // constの値
+            if let Some(v) = self.values.get(index) {
+                result.push(to_xml_tag(token::Symbol::Equal));
+                result = [result, v.to_xml()].concat();
+            }
+        }
+        if !&self.var_names.is_empty() {
+            result.push(to_xml_tag(token::Symbol::SemiColon));
+        }
+
+        result.push(close);
+        result
+    }
+}
+
+impl ClassVarKind {
+    fn to_xml(&self) -> String {
+        let (open, close) = get_xml_tag("keyword".to_string());
+        format!("{} {} {}", open, format!("{:?}", self).to_lowercase(), close)
+    }
+}
+
+impl Type {
+    fn to_xml(&self) -> String {
+        match self {
+            Type::ClassName(c) => token::Identifier(c.clone()).to_xml(),
+            _ => {
+                let (open, close) = get_xml_tag("keyword".to_string());
+                format!("{} {} {}", open, format!("{:?}", self).to_lowercase(), close)
+            }
+        }
+    }
+}
+
+impl SubroutineDec {
+    fn to_xml(&self) -> Vec<String> {
+        let mut result = vec![];
+        let (open, close) = get_xml_tag("subroutineDec".to_string());
+        result.push(open);
+        result.push(self.kind.to_xml());
+        result.push(self.type_.to_xml());
+        result.push(self.subroutine_name.to_xml());
+        result.push(to_xml_tag(token::Symbol::LeftParen));
+        result = [result, self.parameter_list.to_xml()].concat();
+        result.push(to_xml_tag(token::Symbol::RightParen));
+        result = [result, self.body.to_xml()].concat();
+        result.push(close);
+        result
+    }
+}
+
+impl SubroutineDecKind {
+    fn to_xml(&self) -> String {
+        let (open, close) = get_xml_tag("keyword".to_string());
+        format!("{} {} {}", open, format!("{:?}", self).to_lowercase(), close)
+    }
+}
+
+impl SubroutineDecType {
+    fn to_xml(&self) -> String {
+        match self {
+            SubroutineDecType::Void => to_xml_tag(token::Keyword::Void),
+            SubroutineDecType::Type_(t) => t.to_xml(),
+        }
+    }
+}
+
+impl ParameterList {
+    fn to_xml(&self) -> Vec<String> {
+        let mut result = vec![];
+        let (open, close) = get_xml_tag("parameterList".to_string());
+        result.push(open);
+        for (index, p) in self.0.iter().enumerate() {
+            if index != 0 {
+                result.push(to_xml_tag(token::Symbol::Comma));
+            }
+            result.push(p.0.to_xml());
+            result.push(p.1.to_xml());
+        }
+        result.push(close);
+        result
+    }
+}
+
+impl SubroutineBody {
+    fn to_xml(&self) -> Vec<String> {
+        let mut result = vec![];
+        let (open, close) = get_xml_tag("subroutineBody".to_string());
+        result.push(open);
+        result.push(to_xml_tag(token::Symbol::LeftBrace));
+        for v in &self.var_dec {
+            result = [result, v.to_xml()].concat();
+        }
+        result = [result, self.statements.to_xml()].concat();
+        result.push(to_xml_tag(token::Symbol::RightBrace));
+        result.push(close);
+        result
+    }
+}
+
+impl VarDec {
+    fn to_xml(&self) -> Vec<String> {
+        let mut result = vec![];
+        let (open, close) = get_xml_tag("varDec".to_string());
+        result.push(open);
+        result.push(to_xml_tag(token::Keyword::Var));
+        result.push(self.type_.to_xml());
+        for (index, n) in self.var_name.iter().enumerate() {
+            if index != 0 {
+                result.push(to_xml_tag(token::Symbol::Comma));
+            }
+            result.push(n.to_xml());
+        }
+        if !&self.var_name.is_empty() {
+            result.push(to_xml_tag(token::Symbol::SemiColon));
+        }
+
+        result.push(close);
+        result
+    }
+}
+
+impl VarName {
+    fn to_xml(&self) -> String {
+        self.0.to_xml()
+    }
+}
+
+impl Statements {
+    fn to_xml(&self) -> Vec<String> {
+        let (open, close) = get_xml_tag("statements".to_string());
+        let mut result = vec![open];
+        for s in &self.0 {
+            result = [result, s.to_xml()].concat();
+        }
+        result.push(close);
+        result
+    }
+}
+
+impl Statement {
+    fn to_xml(&self) -> Vec<String> {
+        match self {
+            Statement::Let(s) => s.to_xml(),
+            Statement::If(s) => s.to_xml(),
+            Statement::While(s) => s.to_xml(),
+            Statement::Do(s) => s.to_xml(),
+            Statement::Return(s) => s.to_xml(),
+            Statement::For(s) => s.to_xml(),
+            Statement::Break(_) => jump_statement_to_xml("breakStatement", token::Keyword::Break),
+            Statement::Continue(_) => jump_statement_to_xml("continueStatement", token::Keyword::Continue),
+            Statement::Error(_) => unreachable!("パースエラーのあるASTは出力しない"),
+        }
+    }
+}
+
+impl LetStatement {
+    fn to_xml(&self) -> Vec<String> {
+        let (open, close) = get_xml_tag("letStatement".to_string());
+        let mut result = vec![open];
+        result.push(to_xml_tag(token::Keyword::Let));
+        result = [result, self.assignment_to_xml()].concat();
+        result.push(to_xml_tag(token::Symbol::SemiColon));
+        result.push(close);
+        result
+    }
+
+    // `let`と`;`を除いた部分。for文の初期化と更新にも使う
+    fn assignment_to_xml(&self) -> Vec<String> {
+        let mut result = vec![self.var_name.to_xml()];
+
+        // index
+        if let Some(a) = &self.array_index {
+            result.push(to_xml_tag(token::Symbol::LeftBracket));
+            result = [result, a.to_xml()].concat();
+            result.push(to_xml_tag(token::Symbol::RightBracket));
+        }
+
+        if let Some((op, _)) = &self.compound_op {
+            result.push(to_xml_tag(semantic::op_token(op)));
+        }
+        result.push(to_xml_tag(token::Symbol::Equal));
+        result = [result, self.right_hand_side.to_xml()].concat();
+        result
+    }
+}
+
+impl IfStatement {
+    fn to_xml(&self) -> Vec<String> {
+        let (open, close) = get_xml_tag("ifStatement".to_string());
+        let mut result = vec![open];
+        result.push(to_xml_tag(token::Keyword::If));
+        result.push(to_xml_tag(token::Symbol::LeftParen));
+        result = [result, self.condition.to_xml()].concat();
+        result.push(to_xml_tag(token::Symbol::RightParen));
+        result.push(to_xml_tag(token::Symbol::LeftBrace));
+        result = [result, self.positive_case_body.to_xml()].concat();
+        result.push(to_xml_tag(token::Symbol::RightBrace));
+
+        if let Some(n) = &self.negative_case_body {
+            result.push(to_xml_tag(token::Keyword::Else));
+            result.push(to_xml_tag(token::Symbol::LeftBrace));
+            result = [result, n.to_xml()].concat();
+            result.push(to_xml_tag(token::Symbol::RightBrace));
+        }
+
+        result.push(close);
+        result
+    }
+}
+
+impl WhileStatement {
+    fn to_xml(&self) -> Vec<String> {
+        let (open, close) = get_xml_tag("whileStatement".to_string());
+        let mut result = vec![open];
+        result.push(to_xml_tag(token::Keyword::While));
+        result.push(to_xml_tag(token::Symbol::LeftParen));
+        result = [result, self.condition.to_xml()].concat();
+        result.push(to_xml_tag(token::Symbol::RightParen));
+        result.push(to_xml_tag(token::Symbol::LeftBrace));
+        result = [result, self.body.to_xml()].concat();
+        result.push(to_xml_tag(token::Symbol::RightBrace));
+        result.push(close);
+        result
+    }
+}
+
+impl ForStatement {
+    fn to_xml(&self) -> Vec<String> {
+        let (open, close) = get_xml_tag("forStatement".to_string());
+        let mut result = vec![open];
+        result.push(to_xml_tag(token::Keyword::For));
+        result.push(to_xml_tag(token::Symbol::LeftParen));
+        if let Some(init) = &self.init {
+            result = [result, init.assignment_to_xml()].concat();
+        }
+        result.push(to_xml_tag(token::Symbol::SemiColon));
+        result = [result, self.condition.to_xml()].concat();
+        result.push(to_xml_tag(token::Symbol::SemiColon));
+        if let Some(step) = &self.step {
+            result = [result, step.assignment_to_xml()].concat();
+        }
+        result.push(to_xml_tag(token::Symbol::RightParen));
+        result.push(to_xml_tag(token::Symbol::LeftBrace));
+        result = [result, self.body.to_xml()].concat();
+        result.push(to_xml_tag(token::Symbol::RightBrace));
+        result.push(close);
+        result
+    }
+}
+
+fn jump_statement_to_xml(tag: &str, keyword: token::Keyword) -> Vec<String> {
+    let (open, close) = get_xml_tag(tag.to_string());
+    vec![open, to_xml_tag(keyword), to_xml_tag(token::Symbol::SemiColon), close]
+}
+
+impl DoStatement {
+    fn to_xml(&self) -> Vec<String> {
+        let (open, close) = get_xml_tag("doStatement".to_string());
+        let mut result = vec![open];
+        result.push(to_xml_tag(token::Keyword::Do));
+        result = [result, self.0.to_xml()].concat();
+        result.push(to_xml_tag(token::Symbol::SemiColon));
+        result.push(close);
+        result
+    }
+}
+
+impl ReturnStatement {
+    fn to_xml(&self) -> Vec<String> {
+        let (open, close) = get_xml_tag("returnStatement".to_string());
+        let mut result = vec![open];
+        result.push(to_xml_tag(token::Keyword::Return));
+        if let Some(e) = &self.0 {
+            result = [result, e.to_xml()].concat()
+        }
+        result.push(to_xml_tag(token::Symbol::SemiColon));
+        result.push(close);
+        result
+    }
+}
+
+impl Expression {
+    fn to_xml(&self) -> Vec<String> {
+        let mut result = vec![];
+        let (open, close) = get_xml_tag("expression".to_string());
+        result.push(open);
+        result = [result, self.term.to_xml()].concat();
+        for (op, _, t) in &self.op_term {
+            result.push(op.to_xml());
+            result = [result, t.to_xml()].concat();
+        }
+
+        result.push(close);
+        result
+    }
+}
+
+impl Term {
+    fn to_xml(&self) -> Vec<String> {
+        let mut result = vec![];
+        let (open, close) = get_xml_tag("term".to_string());
+        result.push(open);
+
+        let content = match self {
+            Term::IntegerConstant(s) => vec![s.to_xml()],
+            Term::StringConstant(s) => vec![s.to_xml()],
+            Term::CharConstant(c) => vec![c.to_xml()],
+            Term::KeyWordConstant(s) => vec![s.to_xml()],
+            Term::VarName(s) => vec![s.to_xml()],
+            Term::EnumMember(e, m) => vec![e.to_xml(), to_xml_tag(token::Symbol::Dot), m.to_xml()],
+            Term::ArrayIndexAccess(v, e) => {
+                let mut result = vec![v.to_xml()];
+                result.push(to_xml_tag(token::Symbol::LeftBracket));
+                result = [result, e.to_xml()].concat();
+                result.push(to_xml_tag(token::Symbol::RightBracket));
+                result
+            }
+            Term::Expression(s) => {
+                let mut result = vec![to_xml_tag(token::Symbol::LeftParen)];
+                result = [result, s.to_xml()].concat();
+                result.push(to_xml_tag(token::Symbol::RightParen));
+                result
+            }
+            Term::UnaryOp(u, _, t) => [vec![u.to_xml()], t.to_xml()].concat(),
+            Term::SubroutineCall(s) => s.to_xml(),
+        };
+        result = [result, content].concat();
+
+        result.push(close);
+        result
+    }
+}
+
+impl KeyWordConstant {
+    fn to_xml(&self) -> String {
+        let (open, close) = get_xml_tag("keyword".to_string());
+        let keyword = match self {
+            KeyWordConstant::True => "true",
+            KeyWordConstant::False => "false",
+            KeyWordConstant::Null => "null",
+            KeyWordConstant::This(_) => "this",
+        };
+        format!("{} {} {}", open, keyword, close)
+    }
+}
+
+impl SubroutineCall {
+    fn to_xml(&self) -> Vec<String> {
+        let mut result = vec![];
+        match &self.receiver {
+            Some(Receiver::Name(n)) => result.push(n.to_xml()),
+            Some(Receiver::This) => result.push(to_xml_tag(token::Keyword::This)),
+            None => {}
+        }
+        if self.receiver.is_some() {
+            result.push(to_xml_tag(token::Symbol::Dot));
+        }
+        result.push(self.name.0.to_xml());
+        result.push(to_xml_tag(token::Symbol::LeftParen));
+
+        let (open, close) = get_xml_tag("expressionList".to_string());
+        result.push(open);
+        for (index, a) in self.arguments.0.iter().enumerate() {
+            if index != 0 {
+                result.push(to_xml_tag(token::Symbol::Comma));
+            }
+            result = [result, a.to_xml()].concat();
+        }
+        result.push(close);
+
+        result.push(to_xml_tag(token::Symbol::RightParen));
+        result
+    }
+}
+
+impl Op {
+    fn to_xml(&self) -> String {
+        let content = match self {
+            Op::Plus => "+",
+            Op::Minus => "-",
+            Op::Multiply => "*",
+            Op::Div => "/",
+            Op::Ampersand => "&amp;",
+            Op::Pipe => "|",
+            Op::LessThan => "&lt;",
+            Op::MoreThan => "&gt;",
+            Op::Equal => "=",
+            Op::DoubleAmpersand => "&amp;&amp;",
+            Op::DoublePipe => "||",
+        };
+        let (open, close) = get_xml_tag("symbol".to_string());
+        format!("{} {} {}", open, content, close)
+    }
+}
+
+impl UnaryOp {
+    fn to_xml(&self) -> String {
+        let content = match self {
+            UnaryOp::Minus => "-",
+            UnaryOp::Tilde => "~",
+        };
+        let (open, close) = get_xml_tag("symbol".to_string());
+        format!("{} {} {}", open, content, close)
+    }
+}
+
+fn get_xml_tag(tag_name: String) -> (String, String) {
+    (format!("<{}>", tag_name), format!("</{}>", tag_name))
+}
+
+// キーワードと記号を`<keyword> class </keyword>`のようなタグにする。タグ名は型名から求める
+fn to_xml_tag<T: std::fmt::Debug>(value: T) -> String {
+    let type_name_slice = std::any::type_name_of_val(&value).split("::").collect::<Vec<&str>>();
+    let tag_name = to_lowercase_at_1(type_name_slice[type_name_slice.len() - 1]);
+    format!("<{}> {} </{}>", &tag_name, format!("{:?}", value).to_lowercase(), &tag_name)
+}
+
+fn to_lowercase_at_1(s: &str) -> String {
+    let mut result = String::with_capacity(s.len());
+    for (i, c) in s.chars().enumerate() {
+        if i == 0 {
+            result.extend(c.to_lowercase());
+        } else {
+            result.push(c);
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    // `diff -w -B`と同じく空白と空行を無視して比較する
+    fn normalize(xml: &str) -> Vec<String> {
+        xml.lines()
+            .map(|l| l.split_whitespace().collect::<String>())
+            .filter(|l| !l.is_empty())
+            .collect()
+    }
+
+    #[test]
+    fn test_to_xml() {
+        // 10章で提供されている構文解析器の出力例と比較する
+        let root = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("../10/test_data");
+        for (dir, class) in [
+            ("ArrayTest", "Main"),
+            ("ExpressionLessSquare", "Main"),
+            ("ExpressionLessSquare", "Square"),
+            ("ExpressionLessSquare", "SquareGame"),
+            ("Square", "Main"),
+            ("Square", "Square"),
+            ("Square", "SquareGame"),
+        ] {
+            let source = std::fs::read_to_string(root.join(dir).join(format!("{}.jack", class))).unwrap();
+            let expected = std::fs::read_to_string(root.join(dir).join(format!("{}.xml", class))).unwrap();
+            let tokens = token::Tokens::new(source.clone());
+            assert_eq!(
+                normalize(&tokens.to_xml()),
+                normalize(&std::fs::read_to_string(root.join(dir).join(format!("{}T.xml", class))).unwrap()),
+                "{}/{}T.xml",
+                dir,
+                class
+            );
+            let ast = Ast::new(tokens, class.to_string()).unwrap();
+            assert_eq!(normalize(&ast.to_xml()), normalize(&expected), "{}/{}.xml", dir, class);
+        }
+    }
+}
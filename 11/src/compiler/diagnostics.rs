@@ -0,0 +1,119 @@
+use crate::analyzer::token::Span;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Severity {
+    /// コンパイルを失敗させる
+    Error,
+    /// 出力はするが報告だけする
+    Warning,
+}
+
+/// コンパイル時の診断メッセージ。ソース上の位置とメッセージを持つ
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub span: Span,
+    pub message: String,
+}
+
+impl Diagnostic {
+    pub fn new(span: Span, message: String) -> Self {
+        Self {
+            severity: Severity::Error,
+            span,
+            message,
+        }
+    }
+
+    pub fn warning(span: Span, message: String) -> Self {
+        Self {
+            severity: Severity::Warning,
+            span,
+            message,
+        }
+    }
+
+    /// エラー位置とソースの該当行を`^`で指した形式で出力する
+    ///
+    /// ```text
+    /// error: expected `;` after statement at Main.jack:3:5
+    ///   |
+    /// 3 |     return;
+    ///   |     ^^^^^^
+    /// ```
+    pub fn render(&self, file_name: &str, source: &str) -> String {
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        let mut result = vec![format!(
+            "{}: {} at {}:{}:{}",
+            severity, self.message, file_name, self.span.line, self.span.column
+        )];
+
+        if let Some(line) = source.lines().nth(self.span.line - 1) {
+            let line_number = self.span.line.to_string();
+            let gutter = " ".repeat(line_number.len());
+            // タブはそのまま残してキャレットの位置がずれないようにする
+            let padding: String = line
+                .chars()
+                .take(self.span.column - 1)
+                .map(|c| if c == '\t' { '\t' } else { ' ' })
+                .collect();
+            result.push(format!("{} |", gutter));
+            result.push(format!("{} | {}", line_number, line));
+            result.push(format!("{} | {}{}", gutter, padding, "^".repeat(self.span.length.max(1))));
+        }
+
+        result.join("\n")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_render() {
+        let source = "class Main {\n  function void main() {\n    let x = 1\n    return;\n  }\n}\n";
+        let diagnostic = Diagnostic::new(
+            Span {
+                line: 4,
+                column: 5,
+                length: 6,
+            },
+            "expected `;` after statement".to_string(),
+        );
+        assert_eq!(
+            diagnostic.render("Main.jack", source),
+            [
+                "error: expected `;` after statement at Main.jack:4:5",
+                "  |",
+                "4 |     return;",
+                "  |     ^^^^^^",
+            ]
+            .join("\n")
+        );
+
+        // タブインデントの行でもキャレットの位置を合わせる
+        let diagnostic = Diagnostic::warning(
+            Span {
+                line: 2,
+                column: 3,
+                length: 1,
+            },
+            "expected expression".to_string(),
+        );
+        assert_eq!(
+            diagnostic.render("Main.jack", "class Main {\n\t\tlet x = ;\n}"),
+            [
+                "warning: expected expression at Main.jack:2:3",
+                "  |",
+                "2 | \t\tlet x = ;",
+                "  | \t\t^",
+            ]
+            .join("\n")
+        );
+    }
+}
@@ -0,0 +1,98 @@
+// 08のVMトランスレータの拡張命令(`--extended`)を使うように生成したVMコードを書き換える
+// 標準の命令列と同じ結果になる置き換えだけを行うので、置き換え後も元のコードと同じように動作する
+// Math.divideは0除算のときにOSがSys.error(3)を呼ぶが、divは何も報告せずに停止するので置き換えない
+const REWRITE_RULES: &[(&[&str], &str)] = &[
+    // 乗算はOSの関数を呼び出さずに計算する
+    (&["call Math.multiply 2"], "mul"),
+    // 2倍は左シフトにする(mulに置き換えた後に適用する)
+    (&["push constant 2", "mul"], "shl"),
+    // 配列への代入 `a[i] = x`
+    (&["pop temp 0", "pop pointer 1", "push temp 0", "pop that 0"], "pop that *"),
+    // 配列の参照 `a[i]`
+    (&["pop pointer 1", "push that 0"], "push that *"),
+];
+
+// rewrite_mathがfalseの場合は、自作のMathクラスを呼び出すようにMathの関数呼び出しを置き換えない
+pub fn use_extended_commands(vm: &str, rewrite_math: bool) -> String {
+    let mut lines = vm.lines().map(|l| l.to_string()).collect::<Vec<_>>();
+    for (pattern, replacement) in REWRITE_RULES {
+        if !rewrite_math && pattern.iter().any(|p| p.starts_with("call Math.")) {
+            continue;
+        }
+        let mut result = vec![];
+        let mut i = 0;
+        while i < lines.len() {
+            if lines[i..].starts_with(&pattern.iter().map(|p| p.to_string()).collect::<Vec<_>>()) {
+                result.push(replacement.to_string());
+                i += pattern.len();
+            } else {
+                result.push(lines[i].clone());
+                i += 1;
+            }
+        }
+        lines = result;
+    }
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_use_extended_commands() {
+        // let a[i] = (x * y) / 2; let y = x * 2; let x = a[i];
+        let vm = r#"push local 0
+push local 1
+add
+push local 2
+push local 3
+call Math.multiply 2
+push constant 2
+call Math.divide 2
+pop temp 0
+pop pointer 1
+push temp 0
+pop that 0
+push local 2
+push constant 2
+call Math.multiply 2
+pop local 3
+push local 0
+push local 1
+add
+pop pointer 1
+push that 0
+pop local 2"#;
+        assert_eq!(
+            use_extended_commands(vm, true),
+            r#"push local 0
+push local 1
+add
+push local 2
+push local 3
+mul
+push constant 2
+call Math.divide 2
+pop that *
+push local 2
+shl
+pop local 3
+push local 0
+push local 1
+add
+push that *
+pop local 2"#
+        );
+
+        // 自作のMathクラスがある場合はその実装を呼び出す
+        assert_eq!(
+            use_extended_commands(
+                "push local 0\npush constant 2\ncall Math.multiply 2\npush local 1\npop pointer 1\npush that 0",
+                false
+            ),
+            "push local 0\npush constant 2\ncall Math.multiply 2\npush local 1\npush that *"
+        );
+    }
+}
@@ -1 +1,4 @@
 pub(crate) mod ast;
+pub(crate) mod diagnostics;
+pub(crate) mod extended;
+pub(crate) mod program;
@@ -0,0 +1,136 @@
+use crate::analyzer::token;
+use crate::compiler::ast::{semantic, Ast, ClassTable, CodegenOptions};
+use crate::compiler::diagnostics::Diagnostic;
+
+// 12の標準OSクラス。本体は使わず、クラスとsubroutineの宣言だけを参照する
+const OS_SOURCES: [&str; 8] = [
+    include_str!("../../../12/Array.jack"),
+    include_str!("../../../12/Keyboard.jack"),
+    include_str!("../../../12/Math.jack"),
+    include_str!("../../../12/Memory.jack"),
+    include_str!("../../../12/Output.jack"),
+    include_str!("../../../12/Screen.jack"),
+    include_str!("../../../12/String.jack"),
+    include_str!("../../../12/Sys.jack"),
+];
+
+pub fn os_declarations() -> Vec<Ast> {
+    OS_SOURCES
+        .iter()
+        .map(|source| {
+            let tokens = token::Tokens::new(source.to_string());
+            match Ast::new(tokens, String::new()) {
+                Ok(ast) => ast,
+                Err(_) => unreachable!("OSクラスの宣言はパースできるはず"),
+            }
+        })
+        .collect()
+}
+
+/// 入力された全クラスをまとめてコンパイルする単位。
+/// 全ファイルを先にパースしておき、宣言と合わせたクラス表を作ってから意味解析とコード生成を行う
+pub struct Program {
+    sources: Vec<Ast>,
+    // 参照先としてだけ使うクラス。同名のクラスがあればsources側を優先する
+    declarations: Vec<Ast>,
+    classes: ClassTable,
+}
+
+impl Program {
+    pub fn new(sources: Vec<Ast>, declarations: Vec<Ast>) -> Self {
+        let classes = ClassTable::new(&declarations.iter().chain(sources.iter()).collect::<Vec<_>>());
+        Self {
+            sources,
+            declarations,
+            classes,
+        }
+    }
+
+    // sourcesと同じ並びでファイルごとの診断を返す
+    pub fn check(&self, strict: bool) -> Vec<Vec<Diagnostic>> {
+        semantic::check(&self.sources.iter().collect::<Vec<_>>(), &self.declarations.iter().collect::<Vec<_>>(), strict)
+    }
+
+    // 意味解析のあとで、コード生成の前にASTを最適化する
+    pub fn optimize(&mut self) {
+        for source in &mut self.sources {
+            source.optimize();
+        }
+    }
+
+    // 意味解析でエラーがなかったsourcesのindex番目のクラスのVMコードを返す
+    pub fn to_vm(&self, index: usize, options: &CodegenOptions) -> String {
+        self.sources[index].to_vm(&self.classes, options)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use vm_translator::emulator::{StopReason, VMEmulator};
+    use vm_translator::translator::{Bootstrap, VMProgram};
+
+    #[test]
+    fn test_run_compiled_program() {
+        let source = std::fs::read_to_string("test_data/ComplexArrays/Main.jack").unwrap();
+        let ast = Ast::new(token::Tokens::new(source), "Main".to_string()).unwrap();
+        let program = Program::new(vec![ast], os_declarations());
+        assert_eq!(program.check(false), vec![vec![]]);
+
+        // OSはエミュレータのネイティブ実装を使い、Sys.initの代わりにMain.mainから実行する
+        let vm = VMProgram::new("Main".to_string(), program.to_vm(0, &CodegenOptions::default())).unwrap();
+        let bootstrap = Bootstrap {
+            entry_point: "Main.main".to_string(),
+            ..Default::default()
+        };
+        let mut emulator = VMEmulator::new(&[vm], Some(bootstrap), true).unwrap();
+        assert_eq!(emulator.run(1_000_000), Ok(StopReason::Halted));
+        assert_eq!(
+            emulator.native_os().unwrap().printed_text(),
+            [
+                "Test 1: expected result: 5; actual result: 5",
+                "Test 2: expected result: 40; actual result: 40",
+                "Test 3: expected result: 0; actual result: 0",
+                "Test 4: expected result: 77; actual result: 77",
+                "Test 5: expected result: 110; actual result: 110",
+                "",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn test_run_os_class_with_native_os() {
+        // 12/MathTestを自作のMath.jackと組み合わせ、それ以外のOSクラスはネイティブ実装で実行する
+        let asts = ["Main", "Math"]
+            .map(|name| {
+                let source = std::fs::read_to_string(format!("../12/MathTest/{}.jack", name)).unwrap();
+                Ast::new(token::Tokens::new(source), name.to_string()).unwrap()
+            })
+            .into_iter()
+            .collect();
+        let program = Program::new(asts, os_declarations());
+        assert_eq!(program.check(false), vec![vec![], vec![]]);
+        let vms = ["Main", "Math"]
+            .iter()
+            .enumerate()
+            .map(|(i, name)| VMProgram::new(name.to_string(), program.to_vm(i, &CodegenOptions::default())).unwrap())
+            .collect::<Vec<_>>();
+
+        // 本家のテストと同様にSys.initから実行する。.vmで定義したMath.initも呼び出される
+        let run = |vms: &[VMProgram]| {
+            let mut emulator = VMEmulator::new(vms, Some(Bootstrap::default()), true).unwrap();
+            assert_eq!(emulator.run(10_000_000), Ok(StopReason::Halted));
+            ((8000..8014).map(|a| emulator.ram(a)).collect::<Vec<_>>(), emulator.steps())
+        };
+        let (with_math, with_math_steps) = run(&vms);
+        let (native, native_steps) = run(&vms[..1]);
+        // MathTest.cmpの期待値
+        let expected = vec![6, -180, -18000, -18000, 0, 3, -3000, 0, 3, 181, 123, 123, 27, 32767];
+        assert_eq!(with_math, expected);
+        assert_eq!(native, expected);
+        // Math.jackの関数が実際に呼び出されている
+        assert!(native_steps < with_math_steps);
+    }
+}
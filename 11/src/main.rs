@@ -1,31 +1,165 @@
 use compiler::ast;
+use compiler::diagnostics::{Diagnostic, Severity};
+use compiler::program;
 
 mod analyzer;
 mod compiler;
 
 fn main() {
     let command_line_args: Vec<String> = std::env::args().collect();
-    if command_line_args.len() != 2 {
-        println!("Usage: jack_analyzer <filepath>");
-        return;
+    // --extended-vmを指定した場合は08のVMトランスレータの拡張命令(mul, shl等)を使ったコードを出力する
+    // --strictを指定した場合は型検査も行う
+    // --emitで出力形式を選ぶ。tokensとxmlは10章の形式で、意味解析は行わない
+    // --intern-stringsを指定した場合は同じ文字列定数を一度だけ生成して使い回す
+    // --jack-plusを指定した場合はfor文などを加えたJack+としてコンパイルする
+    // --precedenceを指定した場合は二項演算を左から順ではなく一般的な優先順位で評価する
+    // --opt-levelで最適化のレベル(0から2)を選ぶ。既定の0は提供されている比較用の出力と同じコードになる
+    let mut extended = false;
+    let mut strict = false;
+    let mut precedence = false;
+    let mut options = ast::CodegenOptions::default();
+    let mut dialect = analyzer::token::Dialect::Standard;
+    let mut emit = Emit::Vm;
+    let mut path = None;
+    let mut valid = true;
+    for arg in &command_line_args[1..] {
+        match arg.as_str() {
+            "--extended-vm" => extended = true,
+            "--strict" => strict = true,
+            "--precedence" => precedence = true,
+            "--intern-strings" => options.intern_strings = true,
+            "--jack-plus" => dialect = analyzer::token::Dialect::JackPlus,
+            "--opt-level=0" => options.opt_level = 0,
+            "--opt-level=1" => options.opt_level = 1,
+            "--opt-level=2" => options.opt_level = 2,
+            "--emit=tokens" => emit = Emit::Tokens,
+            "--emit=xml" => emit = Emit::Xml,
+            "--emit=vm" => emit = Emit::Vm,
+            _ if path.is_none() && !arg.starts_with("--") => path = Some(arg),
+            _ => valid = false,
+        }
     }
-    let source_file_path = std::path::PathBuf::from(&command_line_args[1]);
+    let (true, Some(path)) = (valid, path) else {
+        println!(
+            "Usage: jack_compiler [--emit=<tokens|xml|vm>] [--extended-vm] [--strict] [--intern-strings] [--jack-plus] [--precedence] [--opt-level=<0|1|2>] <filepath>"
+        );
+        return;
+    };
+    let source_file_path = std::path::PathBuf::from(path);
     let target_files = get_target_files(&source_file_path).unwrap();
 
+    // パースエラーがあったファイルはスキップして残りのファイルのパースを続ける
+    let mut has_error = false;
+    let mut sources = vec![];
+    let mut asts = vec![];
     for target in target_files {
         let content = std::fs::read_to_string(target.clone()).unwrap();
-        let tokens = analyzer::token::Tokens::new(content);
-        let output_file_path = target.with_extension("vm");
-        let ast = ast::Ast::new(tokens.tokens, target.file_stem().unwrap().to_string_lossy().to_string());
-        let vm = ast.to_vm();
-        let _ = std::fs::write(output_file_path, vm);
+        let tokens = analyzer::token::Tokens::with_dialect(content.clone(), dialect);
+        let stem = target.file_stem().unwrap().to_string_lossy().to_string();
+        // 提供されている比較用の.xmlを上書きしないように.gen.xmlとして出力する
+        if emit == Emit::Tokens {
+            report(&target, &content, &tokens.errors);
+            has_error |= !tokens.errors.is_empty();
+            let _ = std::fs::write(target.with_file_name(format!("{}T.gen.xml", stem)), tokens.to_xml());
+            continue;
+        }
+        match ast::Ast::new(tokens, stem) {
+            Ok(mut ast) => {
+                if precedence {
+                    ast.apply_precedence();
+                }
+                if emit == Emit::Xml {
+                    let _ = std::fs::write(target.with_extension("gen.xml"), ast.to_xml());
+                }
+                sources.push((target, content));
+                asts.push(ast);
+            }
+            Err(diagnostics) => {
+                report(&target, &content, &diagnostics);
+                has_error = true;
+            }
+        }
+    }
+    // 一部のファイルしかパースできていない状態で意味解析すると未知のクラスのエラーが連鎖するので、ここで終了する
+    if has_error {
+        std::process::exit(1);
+    }
+    if emit != Emit::Vm {
+        return;
+    }
+
+    // 標準OSクラスの宣言に加えて、ファイルを1つだけ指定した場合は同じディレクトリのクラスも宣言として参照できるようにする
+    let mut declarations = program::os_declarations();
+    if source_file_path.is_file() {
+        declarations.extend(get_sibling_classes(&source_file_path, dialect));
+    }
+    let mut program = program::Program::new(asts, declarations);
+    let semantic_errors = program.check(strict);
+    if options.opt_level >= 1 {
+        program.optimize();
+    }
+
+    // 入力か同じディレクトリに自作のMathクラスがあれば、拡張命令に置き換えずにその実装を呼び出す
+    let has_user_math = sources.iter().any(|(t, _)| t.file_stem().is_some_and(|s| s == "Math"))
+        || (source_file_path.is_file() && source_file_path.with_file_name("Math.jack").is_file());
+
+    // 警告だけのファイルはvmを出力する
+    for (index, ((target, content), diagnostics)) in sources.iter().zip(semantic_errors).enumerate() {
+        report(target, content, &diagnostics);
+        if diagnostics.iter().any(|d| d.severity == Severity::Error) {
+            has_error = true;
+            continue;
+        }
+        let vm = program.to_vm(index, &options);
+        let vm = if extended {
+            compiler::extended::use_extended_commands(&vm, !has_user_math)
+        } else {
+            vm
+        };
+        let _ = std::fs::write(target.with_extension("vm"), vm);
+    }
+
+    if has_error {
+        std::process::exit(1);
     }
 }
 
+#[derive(PartialEq, Eq)]
+enum Emit {
+    // トークン列のXML(`MainT.xml`の形式)
+    Tokens,
+    // 構文木のXML(`Main.xml`の形式)
+    Xml,
+    Vm,
+}
+
+fn report(target: &std::path::Path, content: &str, diagnostics: &[Diagnostic]) {
+    let file_name = target.file_name().unwrap().to_string_lossy();
+    for d in diagnostics {
+        eprintln!("{}\n", d.render(&file_name, content));
+    }
+}
+
+// 指定したファイル以外の同じディレクトリの.jackファイルをパースする。パースできなかったものは無視する
+fn get_sibling_classes(file_path: &std::path::Path, dialect: analyzer::token::Dialect) -> Vec<ast::Ast> {
+    let Some(siblings) = file_path.parent().and_then(get_target_files) else {
+        return vec![];
+    };
+    siblings
+        .into_iter()
+        .filter(|p| p.file_name() != file_path.file_name())
+        .filter_map(|p| {
+            let content = std::fs::read_to_string(&p).ok()?;
+            let stem = p.file_stem().unwrap().to_string_lossy().to_string();
+            ast::Ast::new(analyzer::token::Tokens::with_dialect(content, dialect), stem).ok()
+        })
+        .collect()
+}
+
 fn get_target_files(input_path: &std::path::Path) -> Option<Vec<std::path::PathBuf>> {
     // inputがファイルだったら.jackかどうか判定して(target_files, output_file_path)を返す
     if input_path.is_file() {
-        if input_path.extension().unwrap() != "jack" {
+        if input_path.extension().is_none_or(|e| e != "jack") {
             return None;
         }
         return Some(vec![input_path.to_path_buf()]);
@@ -38,10 +172,11 @@ fn get_target_files(input_path: &std::path::Path) -> Option<Vec<std::path::PathB
     let mut result = vec![];
     for e in std::fs::read_dir(input_path).unwrap() {
         let e_path = e.unwrap().path();
-        if e_path.extension().unwrap() == "jack" {
+        if e_path.extension().is_some_and(|e| e == "jack") {
             result.push(e_path);
         }
     }
+    result.sort();
 
     Some(result)
 }
@@ -68,6 +203,11 @@ mod test {
             let file_path = test_target_dir.join("foo.md");
             std::fs::File::create(&file_path).unwrap();
             assert_eq!(get_target_files(&file_path), None);
+
+            // 拡張子がないファイル
+            let file_path = test_target_dir.join("Makefile");
+            std::fs::File::create(&file_path).unwrap();
+            assert_eq!(get_target_files(&file_path), None);
         }
 
         // fileのパスを渡す(拡張子がjack)